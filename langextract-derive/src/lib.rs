@@ -0,0 +1,261 @@
+//! Derive macro for `langextract_rust::Extractable`.
+//!
+//! A struct with named fields maps to one extraction class; each variant of an
+//! enum maps to its own class. Class names are the snake_case type or variant
+//! name unless overridden, and doc comments become descriptions in the prompt.
+//!
+//! ```rust,ignore
+//! use langextract_rust::Extractable;
+//!
+//! /// A drug mentioned in the text
+//! #[derive(Extractable)]
+//! struct Medication {
+//!     #[extract(text)]
+//!     name: String,
+//!     /// Dose in milligrams
+//!     dose_mg: Option<f64>,
+//! }
+//! ```
+//!
+//! Supported attributes:
+//!
+//! - `#[extract(class = "name")]` on a struct or variant sets the class name
+//! - `#[extract(text)]` on a field fills it from the extraction text
+//! - `#[extract(rename = "key")]` on a field sets the attribute key
+//! - `#[extract(skip)]` on a field leaves it out and fills it with `Default::default()`
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{
+    parse_macro_input, spanned::Spanned, Attribute, Data, DeriveInput, Error, Expr, ExprLit, Fields,
+    FieldsNamed, Lit, LitStr, Meta, Result,
+};
+
+#[proc_macro_derive(Extractable, attributes(extract))]
+pub fn derive_extractable(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(&input).unwrap_or_else(Error::into_compile_error).into()
+}
+
+/// `#[extract(...)]` options on a struct, variant or field
+#[derive(Default)]
+struct Options {
+    class: Option<LitStr>,
+    rename: Option<LitStr>,
+    text: bool,
+    skip: bool,
+}
+
+impl Options {
+    fn parse(attrs: &[Attribute]) -> Result<Self> {
+        let mut options = Self::default();
+        for attr in attrs.iter().filter(|a| a.path().is_ident("extract")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("class") {
+                    options.class = Some(meta.value()?.parse()?);
+                } else if meta.path.is_ident("rename") {
+                    options.rename = Some(meta.value()?.parse()?);
+                } else if meta.path.is_ident("text") {
+                    options.text = true;
+                } else if meta.path.is_ident("skip") {
+                    options.skip = true;
+                } else {
+                    return Err(meta.error("expected `class`, `rename`, `text` or `skip`"));
+                }
+                Ok(())
+            })?;
+        }
+        Ok(options)
+    }
+}
+
+/// One extraction class: its name, description and how to build the value
+struct Class {
+    name: String,
+    description: Option<String>,
+    attributes: Vec<TokenStream2>,
+    construct: TokenStream2,
+}
+
+fn expand(input: &DeriveInput) -> Result<TokenStream2> {
+    let ident = &input.ident;
+    let classes = match &input.data {
+        Data::Struct(data) => {
+            let Fields::Named(fields) = &data.fields else {
+                return Err(Error::new(
+                    input.span(),
+                    "Extractable can only be derived for structs with named fields",
+                ));
+            };
+            let options = Options::parse(&input.attrs)?;
+            let name = class_name(&options, &ident.to_string());
+            vec![named_class(name, doc_comment(&input.attrs), quote!(Self), fields)?]
+        }
+        Data::Enum(data) => {
+            let mut classes = Vec::new();
+            for variant in &data.variants {
+                let options = Options::parse(&variant.attrs)?;
+                let name = class_name(&options, &variant.ident.to_string());
+                let description = doc_comment(&variant.attrs);
+                let variant_ident = &variant.ident;
+                classes.push(match &variant.fields {
+                    Fields::Unit => Class {
+                        name,
+                        description,
+                        attributes: Vec::new(),
+                        construct: quote!(Self::#variant_ident),
+                    },
+                    Fields::Named(fields) => {
+                        named_class(name, description, quote!(Self::#variant_ident), fields)?
+                    }
+                    Fields::Unnamed(_) => {
+                        return Err(Error::new(
+                            variant.span(),
+                            "Extractable variants must be unit or have named fields",
+                        ))
+                    }
+                });
+            }
+            classes
+        }
+        Data::Union(_) => {
+            return Err(Error::new(input.span(), "Extractable cannot be derived for unions"));
+        }
+    };
+
+    let class_defs = classes.iter().map(|class| {
+        let name = &class.name;
+        let description = option_tokens(class.description.as_deref());
+        let attributes = &class.attributes;
+        quote! {
+            ::langextract_rust::schema::ExtractionClass {
+                name: #name.to_string(),
+                description: #description,
+                attributes: vec![#(#attributes),*],
+            }
+        }
+    });
+    let arms = classes.iter().map(|class| {
+        let name = &class.name;
+        let construct = &class.construct;
+        quote!(#name => Ok(#construct))
+    });
+    let names = classes.iter().map(|class| &class.name);
+
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::langextract_rust::typed::Extractable for #ident #ty_generics #where_clause {
+            fn extraction_classes() -> Vec<::langextract_rust::schema::ExtractionClass> {
+                vec![#(#class_defs),*]
+            }
+
+            fn from_extraction(
+                extraction: &::langextract_rust::data::Extraction,
+            ) -> ::langextract_rust::exceptions::LangExtractResult<Self> {
+                match extraction.extraction_class.as_str() {
+                    #(#arms,)*
+                    _ => Err(::langextract_rust::typed::unknown_class(extraction, &[#(#names),*])),
+                }
+            }
+        }
+    })
+}
+
+/// Build the class for a struct or struct-like variant
+fn named_class(
+    name: String,
+    description: Option<String>,
+    path: TokenStream2,
+    fields: &FieldsNamed,
+) -> Result<Class> {
+    let mut attributes = Vec::new();
+    let mut inits = Vec::new();
+    let mut has_text = false;
+
+    for field in &fields.named {
+        let options = Options::parse(&field.attrs)?;
+        let ident = field.ident.as_ref().expect("named field");
+        let ty = &field.ty;
+
+        if options.skip {
+            inits.push(quote!(#ident: ::std::default::Default::default()));
+        } else if options.text {
+            if has_text {
+                return Err(Error::new(field.span(), "only one field can be marked `#[extract(text)]`"));
+            }
+            has_text = true;
+            inits.push(quote!(#ident: ::langextract_rust::typed::text::<#ty>(extraction)?));
+        } else {
+            let key = options
+                .rename
+                .map(|lit| lit.value())
+                .unwrap_or_else(|| ident.to_string().trim_start_matches("r#").to_string());
+            let field_description = option_tokens(doc_comment(&field.attrs).as_deref());
+            attributes.push(quote! {
+                ::langextract_rust::typed::attribute_spec::<#ty>(#key, #field_description)
+            });
+            inits.push(quote!(#ident: ::langextract_rust::typed::attribute::<#ty>(extraction, #key)?));
+        }
+    }
+
+    Ok(Class {
+        name,
+        description,
+        attributes,
+        construct: quote!(#path { #(#inits),* }),
+    })
+}
+
+/// The class name from `#[extract(class = "...")]`, or the snake_case identifier
+fn class_name(options: &Options, ident: &str) -> String {
+    match &options.class {
+        Some(lit) => lit.value(),
+        None => to_snake_case(ident),
+    }
+}
+
+/// Join `///` doc comment lines into one description
+fn doc_comment(attrs: &[Attribute]) -> Option<String> {
+    let lines: Vec<String> = attrs
+        .iter()
+        .filter(|a| a.path().is_ident("doc"))
+        .filter_map(|a| match &a.meta {
+            Meta::NameValue(nv) => match &nv.value {
+                Expr::Lit(ExprLit { lit: Lit::Str(s), .. }) => Some(s.value().trim().to_string()),
+                _ => None,
+            },
+            _ => None,
+        })
+        .filter(|line| !line.is_empty())
+        .collect();
+    (!lines.is_empty()).then(|| lines.join(" "))
+}
+
+fn option_tokens(value: Option<&str>) -> TokenStream2 {
+    match value {
+        Some(value) => quote!(Some(#value.to_string())),
+        None => quote!(None),
+    }
+}
+
+/// `MedicationDose` -> `medication_dose`, `HTTPServer` -> `http_server`
+fn to_snake_case(ident: &str) -> String {
+    let chars: Vec<char> = ident.trim_start_matches("r#").chars().collect();
+    let mut out = String::new();
+    for (i, &c) in chars.iter().enumerate() {
+        if c.is_uppercase() {
+            let prev = i.checked_sub(1).map(|j| chars[j]);
+            let next = chars.get(i + 1);
+            let boundary = prev.is_some_and(|p| p.is_lowercase() || p.is_ascii_digit())
+                || (prev.is_some_and(char::is_uppercase) && next.is_some_and(|n| n.is_lowercase()));
+            if boundary {
+                out.push('_');
+            }
+            out.extend(c.to_lowercase());
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
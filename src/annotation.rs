@@ -2,18 +2,32 @@
 
 use crate::{
     alignment::TextAligner,
-    chunking::{ChunkResult, ResultAggregator, TextChunk, TokenChunk, ChunkIterator},
-    data::{AnnotatedDocument, Extraction, Document},
-    exceptions::LangExtractResult,
-    inference::BaseLanguageModel,
+    cancellation::StopSignal,
+    checkpoint::{self, CheckpointConfig, ChunkCheckpoints},
+    chunking::{token_text_chunks, ChunkResult, ResultAggregator, TextChunk},
+    data::{AnnotatedDocument, ChunkFailure, CoverageReport, Extraction},
+    exceptions::{LangExtractError, LangExtractResult},
+    inference::{BaseLanguageModel, ScoredOutput},
     logging::{report_progress, ProgressEvent},
     prompting::PromptTemplateStructured,
-    resolver::Resolver,
-    tokenizer::Tokenizer,
+    resolver::{IncrementalJsonParser, Resolver},
+    usage::{UsageSummary, UsageTracker},
 };
 use futures::stream::{self, StreamExt};
 use std::collections::HashMap;
-use std::time::Instant;
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Extraction attribute naming the provider that produced it, set when a
+/// fallback chain picked between several providers
+pub const ANSWERED_BY_ATTRIBUTE: &str = "answered_by";
+
+/// Inference parameter carrying a consensus sample's index
+///
+/// Providers ignore it, but the response cache and fixtures key on it, so
+/// samples that send the same prompt don't all get one stored completion.
+pub const SAMPLE_KWARG: &str = "consensus_sample";
 
 /// Main annotator for processing text through language models
 pub struct Annotator {
@@ -25,6 +39,20 @@ pub struct Annotator {
     max_output_tokens: usize,
     /// Cached expected fields derived from prompt_template examples
     expected_fields: Vec<String>,
+    /// Stream model output and report extractions as they are parsed
+    streaming: bool,
+    /// Running token usage, checked against the budget before each call
+    usage_tracker: Arc<UsageTracker>,
+    /// Where chunk results are checkpointed, if anywhere
+    checkpoints: Option<CheckpointConfig>,
+    /// Keep failed chunks in the result instead of dropping them or aborting
+    partial_results: bool,
+    /// Fires when the run is cancelled or times out
+    stop: StopSignal,
+    /// Time limit for each model call
+    request_timeout: Option<Duration>,
+    /// Consensus sample this annotator draws, if any
+    sample: Option<usize>,
 }
 
 impl Annotator {
@@ -51,6 +79,13 @@ impl Annotator {
             temperature: 0.5,
             max_output_tokens: estimated_max_tokens,
             expected_fields,
+            streaming: false,
+            usage_tracker: Arc::new(UsageTracker::default()),
+            checkpoints: None,
+            partial_results: false,
+            stop: StopSignal::default(),
+            request_timeout: None,
+            sample: None,
         }
     }
 
@@ -80,9 +115,85 @@ impl Annotator {
             temperature,
             max_output_tokens: computed_max_tokens,
             expected_fields,
+            streaming: false,
+            usage_tracker: Arc::new(UsageTracker::default()),
+            checkpoints: None,
+            partial_results: false,
+            stop: StopSignal::default(),
+            request_timeout: None,
+            sample: None,
         }
     }
 
+    /// Stream model output, reporting `ProgressEvent::ExtractionFound` for
+    /// each extraction as soon as it is parsed
+    pub fn with_streaming(mut self, streaming: bool) -> Self {
+        self.streaming = streaming;
+        self
+    }
+
+    /// Record token usage in a shared tracker, which aborts the run once its
+    /// budget is exceeded
+    pub fn with_usage_tracker(mut self, usage_tracker: Arc<UsageTracker>) -> Self {
+        self.usage_tracker = usage_tracker;
+        self
+    }
+
+    /// Persist each chunk result as it completes, and with `resume` set skip
+    /// the chunks an earlier run completed
+    pub fn with_checkpoints(mut self, checkpoints: CheckpointConfig) -> Self {
+        self.checkpoints = Some(checkpoints);
+        self
+    }
+
+    /// Keep every failed chunk (including configuration and budget errors) in
+    /// the result and attach a [`CoverageReport`], instead of dropping failed
+    /// chunks or aborting the run
+    pub fn with_partial_results(mut self, partial_results: bool) -> Self {
+        self.partial_results = partial_results;
+        self
+    }
+
+    /// Stop when `stop` fires, returning the chunks finished so far, and fail
+    /// any model call that takes longer than `request_timeout`
+    pub fn with_stop_signal(mut self, stop: StopSignal, request_timeout: Option<Duration>) -> Self {
+        self.stop = stop;
+        self.request_timeout = request_timeout;
+        self
+    }
+
+    /// Draw consensus sample number `sample`, sent as [`SAMPLE_KWARG`]
+    pub fn with_sample(mut self, sample: usize) -> Self {
+        self.sample = Some(sample);
+        self
+    }
+
+    /// Whether failed chunks are kept in a partial result
+    pub(crate) fn partial_results(&self) -> bool {
+        self.partial_results
+    }
+
+    /// The run's stop signal
+    pub(crate) fn stop_signal(&self) -> &StopSignal {
+        &self.stop
+    }
+
+    /// Open the checkpoints for `text` split into `chunks`, if checkpointing
+    /// is enabled
+    pub(crate) fn open_checkpoints(
+        &self,
+        text: &str,
+        chunks: &[TextChunk],
+        additional_context: Option<&str>,
+    ) -> LangExtractResult<Option<ChunkCheckpoints>> {
+        let Some(config) = &self.checkpoints else {
+            return Ok(None);
+        };
+        let prompt = self.build_prompt("", additional_context)?;
+        let key = checkpoint::document_key(self.language_model.model_id(), &prompt, text, chunks);
+        Ok(Some(ChunkCheckpoints::open(config, &key)))
+    }
+
     /// Annotate text and return annotated document
     #[tracing::instrument(skip_all, fields(text_len = text.len(), max_char_buffer, max_workers))]
     pub async fn annotate_text(
@@ -98,7 +209,12 @@ impl Annotator {
         // Check if we need to chunk the text
         if text.len() <= max_char_buffer {
             // Text is small enough, process directly
-            return self.process_single_text(text, resolver, additional_context, debug).await;
+            return match self.process_single_text(text, resolver, additional_context, debug).await {
+                Err(e) if self.partial_results || e.is_cancelled() => {
+                    Ok(failed_document(text, e.to_string(), None))
+                }
+                result => result,
+            };
         }
 
         // Text is too large, use token-based chunking
@@ -160,9 +276,25 @@ impl Annotator {
         let mut kwargs = HashMap::new();
         kwargs.insert("temperature".to_string(), serde_json::json!(self.temperature));
         kwargs.insert("max_completion_tokens".to_string(), serde_json::json!(self.max_output_tokens));
+        if let Some(sample) = self.sample {
+            kwargs.insert(SAMPLE_KWARG.to_string(), serde_json::json!(sample));
+        }
+
+        // Call the language model, unless the budget is already spent
+        self.usage_tracker.check()?;
+        let results = if self.streaming {
+            let output = self.stop.call(self.request_timeout, self.stream_completion(&prompt, &kwargs)).await?;
+            vec![vec![output]]
+        } else {
+            let call = self.language_model.infer(std::slice::from_ref(&prompt), &kwargs);
+            self.stop.call(self.request_timeout, call).await?
+        };
 
-        // Call the language model
-        let results = self.language_model.infer(&[prompt], &kwargs).await?;
+        // Record token usage, estimating it when the provider reports none
+        let usage = match results.first().and_then(|batch| batch.first()) {
+            Some(output) => self.usage_tracker.record_call(self.language_model.model_id(), &prompt, output)?,
+            None => UsageSummary::default(),
+        };
         
         report_progress(ProgressEvent::ModelResponse {
             success: true,
@@ -180,6 +312,7 @@ impl Annotator {
 
         // Extract the response
         let mut annotated_doc = AnnotatedDocument::with_extractions(Vec::new(), text.to_string());
+        annotated_doc.usage = Some(usage);
         
         if let Some(batch) = results.first() {
             if let Some(output) = batch.first() {
@@ -200,8 +333,9 @@ impl Annotator {
                     raw_output_length: response_text.len(),
                 });
 
-                match resolver.validate_and_parse(response_text, &expected_fields) {
-                    Ok((mut extractions, validation_result)) => {
+                let (parse_result, validation_result) = resolver.validate_response(response_text, expected_fields);
+                match parse_result {
+                    Ok(mut extractions) => {
                         // Report validation results
                         report_progress(ProgressEvent::ValidationCompleted {
                             extractions_found: extractions.len(),
@@ -247,6 +381,14 @@ impl Annotator {
                             warnings: validation_result.warnings.len(),
                         });
                     }
+                    Err(e) if self.partial_results => {
+                        let raw_output_file = validation_result
+                            .raw_output_file
+                            .or_else(|| resolver.save_failed_output(response_text).ok());
+                        let mut failed = failed_document(text, format!("Failed to parse model response: {}", e), raw_output_file);
+                        failed.usage = annotated_doc.usage.take();
+                        annotated_doc = failed;
+                    }
                     Err(e) => {
                         if debug {
                             report_progress(ProgressEvent::Debug {
@@ -259,9 +401,23 @@ impl Annotator {
                         annotated_doc.extractions = Some(vec![extraction]);
                     }
                 }
+
+                // Record which provider answered when a fallback chain chose one
+                if let (Some(provider), Some(extractions)) = (&output.provider, annotated_doc.extractions.as_mut()) {
+                    for extraction in extractions {
+                        extraction
+                            .attributes
+                            .get_or_insert_with(HashMap::new)
+                            .insert(ANSWERED_BY_ATTRIBUTE.to_string(), serde_json::json!(provider));
+                    }
+                }
             }
         }
 
+        if self.partial_results && annotated_doc.coverage.is_none() {
+            annotated_doc.coverage = Some(CoverageReport::new(text.len(), 1, Vec::new()));
+        }
+
         Ok(annotated_doc)
     }
 
@@ -278,42 +434,7 @@ impl Annotator {
         debug: bool,
         max_workers: usize,
     ) -> LangExtractResult<AnnotatedDocument> {
-        // Create tokenizer and tokenize the text
-        let tokenizer = Tokenizer::new()?;
-        let tokenized_text = tokenizer.tokenize(text)?;
-        
-        // Create document for chunking
-        let document = Document {
-            document_id: None,
-            text: text.to_string(),
-            additional_context: None,
-        };
-        
-        // Create token-based chunk iterator
-        let chunk_iter = ChunkIterator::new(&tokenized_text, &tokenizer, max_char_buffer, Some(&document))?;
-        
-        // Collect chunks from iterator
-        let token_chunks: Result<Vec<TokenChunk>, _> = chunk_iter.collect();
-        let token_chunks = token_chunks?;
-        
-        // Convert TokenChunks to TextChunks for compatibility with existing pipeline
-        let mut text_chunks = Vec::new();
-        for (i, token_chunk) in token_chunks.iter().enumerate() {
-            let chunk_text = token_chunk.chunk_text(&tokenizer)?;
-            let char_interval = token_chunk.char_interval(&tokenizer)?;
-            let chunk_len = chunk_text.len();
-            
-            let text_chunk = TextChunk {
-                id: i,
-                text: chunk_text,
-                char_offset: char_interval.start_pos.unwrap_or(0),
-                char_length: chunk_len,
-                document_id: None,
-                has_overlap: false,
-                overlap_info: None,
-            };
-            text_chunks.push(text_chunk);
-        }
+        let text_chunks = token_text_chunks(text, max_char_buffer)?;
         
         // Report chunking started
         report_progress(ProgressEvent::ChunkingStarted {
@@ -364,17 +485,39 @@ impl Annotator {
             total_chunks,
         });
 
+        // Chunks completed by an earlier run are restored instead of re-sent
+        let checkpoints = self.open_checkpoints(original_text, &chunks, additional_context)?;
+        let restored = match &checkpoints {
+            Some(checkpoints) => checkpoints.completed()?,
+            None => Vec::new(),
+        };
+        let restored_ids = ChunkCheckpoints::completed_ids(&restored);
+        if !restored.is_empty() {
+            report_progress(ProgressEvent::Debug {
+                operation: "checkpoint".to_string(),
+                details: format!("Resuming: {}/{} chunks restored from checkpoint", restored.len(), total_chunks),
+            });
+        }
+
         // Use buffer_unordered to process ALL chunks with bounded concurrency.
         // This replaces the previous batch-loop-with-take pattern that silently
-        // dropped chunks when batch_length > max_workers.
-        let chunk_results: Vec<LangExtractResult<ChunkResult>> = stream::iter(chunks.iter())
-            .map(|chunk| self.process_chunk(chunk, resolver, additional_context, debug))
+        // dropped chunks when batch_length > max_workers. Once the run stops,
+        // pending chunks are dropped and recorded as failures below.
+        // The futures are built up front (they do nothing until polled) so the
+        // stream's type holds no closure, keeping it Send for spawned tasks
+        let pending: Vec<_> = chunks
+            .iter()
+            .filter(|chunk| !restored_ids.contains(&chunk.id))
+            .map(|chunk| self.process_and_checkpoint(chunk, resolver, additional_context, debug, checkpoints.as_ref()))
+            .collect();
+        let chunk_results: Vec<LangExtractResult<ChunkResult>> = stream::iter(pending)
             .buffer_unordered(max_workers)
+            .take_until(self.stop.stopped())
             .collect()
             .await;
 
         // Collect results, propagating any errors
-        let mut collected_results = Vec::with_capacity(chunk_results.len());
+        let mut collected_results = restored;
         for (i, result) in chunk_results.into_iter().enumerate() {
             collected_results.push(result?);
             if debug && (i + 1) % max_workers == 0 {
@@ -385,6 +528,14 @@ impl Annotator {
             }
         }
 
+        if self.stop.is_stopped() {
+            let finished: HashSet<usize> = collected_results.iter().map(|r| r.chunk_id).collect();
+            let error = self.stop.error().to_string();
+            collected_results.extend(chunks.iter().filter(|chunk| !finished.contains(&chunk.id)).map(|chunk| {
+                ChunkResult::failure(chunk.id, chunk.char_offset, chunk.char_length, error.clone())
+            }));
+        }
+
         if debug {
             report_progress(ProgressEvent::Debug {
                 operation: "batch_processing".to_string(),
@@ -396,12 +547,20 @@ impl Annotator {
         report_progress(ProgressEvent::AggregationStarted {
             chunk_count: chunks.len(),
         });
+        let failures: Vec<ChunkFailure> = collected_results.iter().filter_map(ChunkResult::to_failure).collect();
         let aggregator = ResultAggregator::new();
-        let final_result = aggregator.aggregate_chunk_results(
+        let mut final_result = aggregator.aggregate_chunk_results(
             collected_results,
             original_text.to_string(),
             None,
         )?;
+        if self.partial_results || self.stop.is_stopped() {
+            let coverage = CoverageReport::new(original_text.len(), total_chunks, failures);
+            if !coverage.is_complete() {
+                log::warn!("Partial result: {}", coverage.describe());
+            }
+            final_result.coverage = Some(coverage);
+        }
 
         report_progress(ProgressEvent::ProcessingCompleted {
             total_extractions: final_result.extraction_count(),
@@ -421,6 +580,22 @@ impl Annotator {
 
 
 
+    /// Process a chunk and checkpoint its result
+    async fn process_and_checkpoint(
+        &self,
+        chunk: &TextChunk,
+        resolver: &Resolver,
+        additional_context: Option<&str>,
+        debug: bool,
+        checkpoints: Option<&ChunkCheckpoints>,
+    ) -> LangExtractResult<ChunkResult> {
+        let result = self.process_chunk(chunk, resolver, additional_context, debug).await?;
+        if let Some(checkpoints) = checkpoints {
+            checkpoints.save(&result)?;
+        }
+        Ok(result)
+    }
+
     /// Process a single chunk
     #[tracing::instrument(skip_all, fields(chunk_id = chunk.id, chunk_len = chunk.text.len()))]
     async fn process_chunk(
@@ -434,6 +609,19 @@ impl Annotator {
 
         match self.process_single_text(&chunk.text, resolver, additional_context, false).await {
             Ok(annotated_doc) => {
+                // An unparseable response in partial mode
+                if let Some(failure) = annotated_doc.coverage.as_ref().and_then(|c| c.failed_chunks.first()) {
+                    return Ok(ChunkResult::failure(
+                        chunk.id,
+                        chunk.char_offset,
+                        chunk.char_length,
+                        failure.error.clone(),
+                    )
+                    .with_raw_output_file(failure.raw_output_file.clone())
+                    .with_processing_time(start_time.elapsed())
+                    .with_usage(annotated_doc.usage));
+                }
+
                 let mut extractions = annotated_doc.extractions.unwrap_or_default();
                 
                 // Align extractions with the chunk text
@@ -457,8 +645,15 @@ impl Annotator {
                     extractions,
                     chunk.char_offset,
                     chunk.char_length,
-                ).with_processing_time(start_time.elapsed()))
+                )
+                .with_processing_time(start_time.elapsed())
+                .with_usage(annotated_doc.usage))
             }
+            // Configuration problems (e.g. a replay fixture miss) affect every
+            // chunk, and an exceeded budget must stop the run, so fail instead
+            // of dropping the chunk, unless a partial result was asked for
+            Err(e @ (LangExtractError::ConfigurationError(_) | LangExtractError::BudgetExceeded(_)))
+                if !self.partial_results => Err(e),
             Err(e) => {
                 if debug {
                     report_progress(ProgressEvent::Debug {
@@ -477,6 +672,28 @@ impl Annotator {
         }
     }
 
+    /// Collect a streamed completion, reporting extractions as their JSON
+    /// objects close
+    async fn stream_completion(
+        &self,
+        prompt: &str,
+        kwargs: &HashMap<String, serde_json::Value>,
+    ) -> LangExtractResult<ScoredOutput> {
+        let mut stream = self.language_model.infer_stream(prompt, kwargs).await?;
+        let mut parser = IncrementalJsonParser::new();
+
+        while let Some(delta) = stream.next().await {
+            for extraction in parser.feed(&delta?) {
+                report_progress(ProgressEvent::ExtractionFound {
+                    extraction_class: extraction.extraction_class,
+                    extraction_text: extraction.extraction_text,
+                });
+            }
+        }
+
+        Ok(ScoredOutput::from_text(parser.text().to_string()))
+    }
+
     /// Build the prompt using the new template system
     fn build_prompt(&self, text: &str, additional_context: Option<&str>) -> LangExtractResult<String> {
         // Use the new template system for better prompt generation
@@ -484,3 +701,20 @@ impl Annotator {
     }
 
 }
+
+/// A document whose only chunk failed, with no extractions
+fn failed_document(text: &str, error: String, raw_output_file: Option<String>) -> AnnotatedDocument {
+    let mut document = AnnotatedDocument::with_extractions(Vec::new(), text.to_string());
+    document.coverage = Some(CoverageReport::new(
+        text.len(),
+        1,
+        vec![ChunkFailure {
+            chunk_id: 0,
+            char_offset: 0,
+            char_length: text.len(),
+            error,
+            raw_output_file,
+        }],
+    ));
+    document
+}
@@ -0,0 +1,157 @@
+//! Cancellation and timeouts for extraction runs.
+//!
+//! A run stops when the [`CancellationToken`] in its configuration is
+//! cancelled or when its overall timeout passes. Model calls still in flight
+//! are dropped, chunks that never finished are recorded as failures in the
+//! result's [`CoverageReport`](crate::CoverageReport), and whatever finished
+//! is returned. A separate request timeout bounds each model call; a call
+//! that runs past it fails like any other model error.
+
+use crate::exceptions::{LangExtractError, LangExtractResult};
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio_util::sync::DropGuard;
+
+pub use tokio_util::sync::CancellationToken;
+
+/// Stop signal of one run: fires when the caller's token is cancelled or the
+/// run's timeout passes
+#[derive(Debug, Clone, Default)]
+pub struct StopSignal {
+    token: CancellationToken,
+    timed_out: Arc<AtomicBool>,
+}
+
+impl StopSignal {
+    /// A signal that follows `parent` and fires on its own after `timeout`
+    ///
+    /// Starting a timeout spawns a timer task, so this must be called inside
+    /// a Tokio runtime when `timeout` is set.
+    pub fn new(parent: Option<&CancellationToken>, timeout: Option<Duration>) -> Self {
+        let token = parent.map(CancellationToken::child_token).unwrap_or_default();
+        let timed_out = Arc::new(AtomicBool::new(false));
+        if let Some(timeout) = timeout {
+            let (token, timed_out) = (token.clone(), timed_out.clone());
+            tokio::spawn(async move {
+                tokio::select! {
+                    _ = tokio::time::sleep(timeout) => {
+                        timed_out.store(true, Ordering::SeqCst);
+                        token.cancel();
+                    }
+                    _ = token.cancelled() => {}
+                }
+            });
+        }
+        Self { token, timed_out }
+    }
+
+    /// Guard to hold for the length of the run; dropping it stops the timer
+    /// without cancelling the caller's token
+    pub fn guard(&self) -> DropGuard {
+        self.token.clone().drop_guard()
+    }
+
+    /// Token cancelled when this signal fires, for nested runs
+    pub fn token(&self) -> CancellationToken {
+        self.token.clone()
+    }
+
+    /// Whether the run has been cancelled or timed out
+    pub fn is_stopped(&self) -> bool {
+        self.token.is_cancelled()
+    }
+
+    /// Wait until the run is cancelled or times out
+    pub async fn stopped(&self) {
+        self.token.cancelled().await
+    }
+
+    /// Why the run stopped, for messages
+    pub fn reason(&self) -> &'static str {
+        if self.timed_out.load(Ordering::SeqCst) {
+            "timed out"
+        } else {
+            "cancelled"
+        }
+    }
+
+    /// Error for work dropped because the run stopped
+    pub fn error(&self) -> LangExtractError {
+        LangExtractError::cancelled(format!("Run {} before this work finished", self.reason()))
+    }
+
+    /// Await a model call, giving up after `request_timeout` or as soon as the
+    /// run stops
+    pub async fn call<T, F>(&self, request_timeout: Option<Duration>, call: F) -> LangExtractResult<T>
+    where
+        F: Future<Output = LangExtractResult<T>>,
+    {
+        if self.is_stopped() {
+            return Err(self.error());
+        }
+        let bounded = async {
+            match request_timeout {
+                Some(limit) => tokio::time::timeout(limit, call).await.unwrap_or_else(|_| {
+                    Err(LangExtractError::inference_simple(format!(
+                        "Model call timed out after {}ms",
+                        limit.as_millis()
+                    )))
+                }),
+                None => call.await,
+            }
+        };
+        tokio::select! {
+            result = bounded => result,
+            _ = self.stopped() => Err(self.error()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_timeout_stops_pending_calls() {
+        let signal = StopSignal::new(None, Some(Duration::from_millis(20)));
+        let _guard = signal.guard();
+
+        let result = signal
+            .call(None, async {
+                tokio::time::sleep(Duration::from_secs(5)).await;
+                Ok(())
+            })
+            .await;
+
+        let err = result.unwrap_err();
+        assert!(err.is_cancelled());
+        assert_eq!(signal.reason(), "timed out");
+    }
+
+    #[tokio::test]
+    async fn test_request_timeout_fails_the_call_only() {
+        let parent = CancellationToken::new();
+        let signal = StopSignal::new(Some(&parent), None);
+
+        let slow = signal
+            .call(Some(Duration::from_millis(10)), async {
+                tokio::time::sleep(Duration::from_secs(5)).await;
+                Ok(())
+            })
+            .await;
+        assert!(slow.unwrap_err().is_inference_error());
+        assert!(!signal.is_stopped());
+
+        parent.cancel();
+        assert!(signal.is_stopped());
+        assert_eq!(signal.reason(), "cancelled");
+        assert!(signal.call(None, async { Ok(()) }).await.unwrap_err().is_cancelled());
+
+        // Ending a nested run never cancels its parent
+        let parent = CancellationToken::new();
+        drop(StopSignal::new(Some(&parent), None).guard());
+        assert!(!parent.is_cancelled());
+    }
+}
@@ -0,0 +1,184 @@
+//! Checkpoints for long extraction runs.
+//!
+//! With a [`CheckpointConfig`] in [`ExtractConfig`](crate::ExtractConfig),
+//! every chunk result is written to disk as soon as it completes. Results are
+//! stored one file per chunk under a directory named by a SHA-256 hash of the
+//! model, the prompt, the document text and its chunk boundaries, so a changed
+//! document or prompt never picks up stale results. A run that fails part way
+//! keeps its finished chunks; a rerun with `resume` set skips them and rebuilds
+//! the final document from the stored results.
+
+use crate::{
+    chunking::{ChunkResult, TextChunk},
+    exceptions::{LangExtractError, LangExtractResult},
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+/// Where chunk results are checkpointed and whether to reuse them
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CheckpointConfig {
+    /// Directory holding one subdirectory per document
+    pub dir: PathBuf,
+    /// Skip chunks that already have a checkpoint
+    #[serde(default)]
+    pub resume: bool,
+}
+
+impl CheckpointConfig {
+    /// Checkpoint into `dir` without reusing earlier results
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            resume: false,
+        }
+    }
+
+    /// Skip chunks completed by an earlier run
+    pub fn with_resume(mut self, resume: bool) -> Self {
+        self.resume = resume;
+        self
+    }
+}
+
+/// Checkpointed chunk results of one document
+#[derive(Debug, Clone)]
+pub struct ChunkCheckpoints {
+    dir: PathBuf,
+    resume: bool,
+}
+
+impl ChunkCheckpoints {
+    /// Open the checkpoints for a document, identified by [`document_key`]
+    pub fn open(config: &CheckpointConfig, key: &str) -> Self {
+        Self {
+            dir: config.dir.join(key),
+            resume: config.resume,
+        }
+    }
+
+    /// Directory holding this document's chunk results
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Results of the chunks completed by earlier runs, empty unless resuming
+    ///
+    /// Restored results carry no usage, since their tokens were paid for by
+    /// the run that produced them. Unreadable files (e.g. from a crash
+    /// mid-write) are ignored, so those chunks are processed again.
+    pub fn completed(&self) -> LangExtractResult<Vec<ChunkResult>> {
+        if !self.resume || !self.dir.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut results = Vec::new();
+        for entry in std::fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if path.extension().and_then(|e| e.to_str()) != Some("json") {
+                continue;
+            }
+            match std::fs::read_to_string(&path)
+                .ok()
+                .and_then(|content| serde_json::from_str::<ChunkResult>(&content).ok())
+            {
+                Some(mut result) if result.success => {
+                    result.usage = None;
+                    results.push(result);
+                }
+                _ => log::warn!("Ignoring unreadable checkpoint {}", path.display()),
+            }
+        }
+        results.sort_by_key(|r| r.chunk_id);
+        Ok(results)
+    }
+
+    /// IDs of the chunks in `completed`
+    pub fn completed_ids(completed: &[ChunkResult]) -> HashSet<usize> {
+        completed.iter().map(|r| r.chunk_id).collect()
+    }
+
+    /// Persist a chunk result; failed chunks are not saved so a resumed run
+    /// retries them
+    pub fn save(&self, result: &ChunkResult) -> LangExtractResult<()> {
+        if !result.success {
+            return Ok(());
+        }
+        std::fs::create_dir_all(&self.dir)?;
+        let path = self.dir.join(format!("chunk_{:06}.json", result.chunk_id));
+        let content = serde_json::to_vec(result)
+            .map_err(|e| LangExtractError::serialization(format!("Failed to serialize checkpoint: {}", e)))?;
+        // Write then rename so an interrupted run never leaves a partial file
+        let tmp_path = path.with_extension(format!("tmp-{}", uuid::Uuid::new_v4()));
+        std::fs::write(&tmp_path, content)?;
+        std::fs::rename(&tmp_path, &path)?;
+        Ok(())
+    }
+}
+
+/// Checkpoint key for a document: a hash of the model, the prompt without
+/// the chunk text, the document text and its chunk boundaries
+pub fn document_key(model_id: &str, prompt: &str, text: &str, chunks: &[TextChunk]) -> String {
+    let mut hasher = Sha256::new();
+    for part in [model_id.as_bytes(), prompt.as_bytes(), text.as_bytes()] {
+        hasher.update(part);
+        hasher.update([0u8]);
+    }
+    for chunk in chunks {
+        hasher.update(format!("{}:{}:{};", chunk.id, chunk.char_offset, chunk.char_length));
+    }
+    hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::Extraction;
+
+    fn chunks() -> Vec<TextChunk> {
+        vec![
+            TextChunk::new(0, "Ada wrote notes.".to_string(), 0, None),
+            TextChunk::new(1, "Grace wrote compilers.".to_string(), 17, None),
+        ]
+    }
+
+    #[test]
+    fn test_resume_restores_saved_chunks() {
+        let dir = tempfile::tempdir().unwrap();
+        let key = document_key("mistral", "Extract people", "Ada wrote notes. Grace wrote compilers.", &chunks());
+        let config = CheckpointConfig::new(dir.path());
+
+        let checkpoints = ChunkCheckpoints::open(&config, &key);
+        let done = ChunkResult::success(1, vec![Extraction::new("person".to_string(), "Grace".to_string())], 17, 22)
+            .with_usage(Some(Default::default()));
+        checkpoints.save(&done).unwrap();
+        checkpoints.save(&ChunkResult::failure(0, 0, 16, "timeout".to_string())).unwrap();
+
+        // Without resume, earlier results are not reused
+        assert!(checkpoints.completed().unwrap().is_empty());
+
+        let resumed = ChunkCheckpoints::open(&config.clone().with_resume(true), &key);
+        let completed = resumed.completed().unwrap();
+        assert_eq!(completed.len(), 1);
+        assert_eq!(completed[0].chunk_id, 1);
+        assert_eq!(completed[0].extractions.as_ref().unwrap()[0].extraction_text, "Grace");
+        assert!(completed[0].usage.is_none());
+        assert_eq!(ChunkCheckpoints::completed_ids(&completed), HashSet::from([1]));
+    }
+
+    #[test]
+    fn test_document_key_changes_with_prompt_and_chunks() {
+        let text = "Ada wrote notes. Grace wrote compilers.";
+        let key = document_key("mistral", "Extract people", text, &chunks());
+
+        assert_eq!(key, document_key("mistral", "Extract people", text, &chunks()));
+        assert_ne!(key, document_key("mistral", "Extract places", text, &chunks()));
+        assert_ne!(key, document_key("mistral", "Extract people", text, &chunks()[..1]));
+    }
+}
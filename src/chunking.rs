@@ -6,12 +6,14 @@
 //! is lost during processing.
 
 use crate::{
-    data::{AnnotatedDocument, Document, Extraction, CharInterval},
+    data::{AnnotatedDocument, ChunkFailure, Document, Extraction, CharInterval},
     exceptions::LangExtractResult,
     tokenizer::{TokenInterval, TokenizedText, Tokenizer, SentenceIterator},
+    usage::UsageSummary,
 };
 use regex::Regex;
 use semchunk_rs::Chunker;
+use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 use once_cell::sync::Lazy;
 
@@ -840,6 +842,37 @@ impl<'a> Iterator for ChunkIterator<'a> {
     }
 }
 
+/// Split text into sentence-aligned chunks of at most `max_char_buffer` characters
+///
+/// Runs a [`ChunkIterator`] over the tokenized text and numbers the chunks in
+/// document order; this is how the annotator splits text that is too long for
+/// a single prompt.
+pub fn token_text_chunks(text: &str, max_char_buffer: usize) -> LangExtractResult<Vec<TextChunk>> {
+    let tokenizer = Tokenizer::new()?;
+    let tokenized_text = tokenizer.tokenize(text)?;
+    let document = Document::new(text.to_string());
+
+    let chunk_iter = ChunkIterator::new(&tokenized_text, &tokenizer, max_char_buffer, Some(&document))?;
+    let mut text_chunks = Vec::new();
+    for (i, token_chunk) in chunk_iter.enumerate() {
+        let token_chunk = token_chunk?;
+        let chunk_text = token_chunk.chunk_text(&tokenizer)?;
+        let char_interval = token_chunk.char_interval(&tokenizer)?;
+        let chunk_len = chunk_text.len();
+
+        text_chunks.push(TextChunk {
+            id: i,
+            text: chunk_text,
+            char_offset: char_interval.start_pos.unwrap_or(0),
+            char_length: chunk_len,
+            document_id: None,
+            has_overlap: false,
+            overlap_info: None,
+        });
+    }
+    Ok(text_chunks)
+}
+
 /// Result aggregator for combining extractions from multiple chunks
 pub struct ResultAggregator {
     /// Similarity threshold for duplicate detection
@@ -873,9 +906,13 @@ impl ResultAggregator {
         document_id: Option<String>,
     ) -> LangExtractResult<AnnotatedDocument> {
         let mut all_extractions = Vec::new();
+        let mut usage: Option<UsageSummary> = None;
 
         // Collect all extractions from chunks
         for chunk_result in chunk_results {
+            if let Some(chunk_usage) = &chunk_result.usage {
+                usage.get_or_insert_with(UsageSummary::default).merge(chunk_usage);
+            }
             if let Some(extractions) = chunk_result.extractions {
                 // Character positions should already be adjusted by the alignment process
                 // during chunk processing, so we don't need to add the offset again here
@@ -893,6 +930,7 @@ impl ResultAggregator {
         // Create the aggregated document
         let mut annotated_doc = AnnotatedDocument::with_extractions(deduplicated_extractions, original_text);
         annotated_doc.document_id = document_id;
+        annotated_doc.usage = usage;
 
         Ok(annotated_doc)
     }
@@ -970,7 +1008,7 @@ impl Default for ResultAggregator {
 }
 
 /// Result from processing a single chunk
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ChunkResult {
     /// ID of the chunk that was processed
     pub chunk_id: usize,
@@ -986,6 +1024,11 @@ pub struct ChunkResult {
     pub error: Option<String>,
     /// Processing time for this chunk
     pub processing_time: Option<std::time::Duration>,
+    /// Token usage of the model calls for this chunk
+    pub usage: Option<UsageSummary>,
+    /// Saved model output for a chunk whose response could not be parsed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub raw_output_file: Option<String>,
 }
 
 impl ChunkResult {
@@ -1004,6 +1047,8 @@ impl ChunkResult {
             success: true,
             error: None,
             processing_time: None,
+            usage: None,
+            raw_output_file: None,
         }
     }
 
@@ -1022,6 +1067,8 @@ impl ChunkResult {
             success: false,
             error: Some(error),
             processing_time: None,
+            usage: None,
+            raw_output_file: None,
         }
     }
 
@@ -1030,6 +1077,32 @@ impl ChunkResult {
         self.processing_time = Some(duration);
         self
     }
+
+    /// Set token usage
+    pub fn with_usage(mut self, usage: Option<UsageSummary>) -> Self {
+        self.usage = usage;
+        self
+    }
+
+    /// Set the saved raw output of an unparseable response
+    pub fn with_raw_output_file(mut self, raw_output_file: Option<String>) -> Self {
+        self.raw_output_file = raw_output_file;
+        self
+    }
+
+    /// The failure this result records, if it failed
+    pub fn to_failure(&self) -> Option<ChunkFailure> {
+        if self.success {
+            return None;
+        }
+        Some(ChunkFailure {
+            chunk_id: self.chunk_id,
+            char_offset: self.char_offset,
+            char_length: self.char_length,
+            error: self.error.clone().unwrap_or_default(),
+            raw_output_file: self.raw_output_file.clone(),
+        })
+    }
 }
 
 #[cfg(test)]
@@ -533,6 +533,18 @@ impl From<LangExtractConfig> for crate::ExtractConfig {
             multipass_max_passes: config.multipass.max_passes,
             multipass_min_extractions: config.multipass.min_extractions_per_chunk,
             multipass_quality_threshold: config.multipass.quality_threshold,
+            consensus: None,
+            verification: None,
+            streaming: false,
+            extraction_classes: None,
+            budget: Default::default(),
+            price_table: None,
+            allow_partial: false,
+            checkpoint: None,
+            request_timeout_ms: None,
+            timeout_ms: None,
+            cancellation: None,
+            usage_tracker: None,
             progress_handler: config.progress.handler,
         }
     }
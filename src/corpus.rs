@@ -0,0 +1,301 @@
+//! Extraction over a corpus of documents with shared resources.
+//!
+//! [`extract_documents`] builds the provider and resolver once and runs every
+//! document through them. Model calls from all documents draw on one pool of
+//! `max_workers` permits, so a large corpus can't multiply the concurrency of
+//! a single run, and all documents share one usage budget.
+
+use crate::{
+    annotate_with_resolver,
+    data::{AnnotatedDocument, Document, ExampleData, FormatType},
+    exceptions::{LangExtractError, LangExtractResult},
+    factory,
+    inference::{BaseLanguageModel, ScoredOutput, TextStream},
+    prompting::PromptTemplateStructured,
+    resolver::Resolver,
+    usage::UsageTracker,
+    ExtractConfig,
+};
+use async_trait::async_trait;
+use futures::stream::{Stream, StreamExt};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::Semaphore;
+
+/// A document that could not be extracted
+#[derive(Debug)]
+pub struct DocumentError {
+    /// ID of the failed document
+    pub document_id: String,
+    /// Why it failed
+    pub error: LangExtractError,
+}
+
+impl std::fmt::Display for DocumentError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Document {}: {}", self.document_id, self.error)
+    }
+}
+
+impl std::error::Error for DocumentError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+/// Extract from every document in `documents`, yielding results as they
+/// complete
+///
+/// The provider, verifier model, resolver and usage tracker are created
+/// once. Up to `config.max_workers` documents are processed at a time, and
+/// model calls across all of them, verification included, are limited to
+/// `config.max_workers` in flight. Each result carries the document's
+/// `document_id` and `metadata`; documents without an ID get a generated one.
+/// A document's own `additional_context` replaces the one in `config`.
+///
+/// Setup problems (no examples, no provider configuration) are returned
+/// before any document is read. An exceeded budget fails every remaining
+/// document. `config.timeout_ms` applies to each document; once
+/// `config.cancellation` is cancelled, documents in progress return what they
+/// finished and no further documents start.
+pub async fn extract_documents<S>(
+    documents: S,
+    prompt_description: Option<&str>,
+    examples: &[ExampleData],
+    config: ExtractConfig,
+) -> LangExtractResult<impl Stream<Item = Result<AnnotatedDocument, DocumentError>>>
+where
+    S: Stream<Item = Document>,
+{
+    if examples.is_empty() {
+        return Err(LangExtractError::InvalidInput(
+            "Examples are required for reliable extraction. Please provide at least one ExampleData object with sample extractions.".to_string()
+        ));
+    }
+
+    dotenvy::dotenv().ok();
+    crate::init_progress_handler(&config);
+
+    let mut prompt_template = PromptTemplateStructured::new(prompt_description);
+    prompt_template.examples.extend(examples.iter().cloned());
+
+    let model: Arc<dyn BaseLanguageModel> =
+        factory::create_model(&config, Some(&prompt_template.examples)).await?.into();
+    let resolver = Resolver::new(&config, model.requires_fence_output())?;
+    let workers = config.max_workers.max(1);
+    let permits = Arc::new(Semaphore::new(workers));
+    // The verifier is built once too, and its calls take the same permits
+    let verifier_model = crate::create_verifier_model(&config, &prompt_template.examples)
+        .await?
+        .map(|model| PermitModel { inner: model.into(), permits: permits.clone() });
+    let cancellation = config.cancellation.clone().unwrap_or_default();
+
+    let mut config = config;
+    if config.usage_tracker.is_none() {
+        config.usage_tracker = Some(Arc::new(UsageTracker::new(
+            config.price_table.clone().unwrap_or_default(),
+            config.budget,
+        )));
+    }
+
+    let shared = Arc::new(SharedResources {
+        model: PermitModel { inner: model, permits },
+        verifier_model,
+        resolver,
+        prompt_template,
+        config,
+    });
+
+    Ok(documents
+        .take_until(cancellation.cancelled_owned())
+        .map(move |document| {
+            let shared = shared.clone();
+            async move { shared.extract(document).await }
+        })
+        .buffer_unordered(workers))
+}
+
+/// Everything built once and used by every document
+struct SharedResources {
+    model: PermitModel,
+    verifier_model: Option<PermitModel>,
+    resolver: Resolver,
+    prompt_template: PromptTemplateStructured,
+    config: ExtractConfig,
+}
+
+impl SharedResources {
+    async fn extract(&self, mut document: Document) -> Result<AnnotatedDocument, DocumentError> {
+        let document_id = document.get_document_id();
+
+        let mut config = self.config.clone();
+        if document.additional_context.is_some() {
+            config.additional_context = document.additional_context.take();
+        }
+
+        let model = Box::new(self.model.clone());
+        let verifier_model = self.verifier_model.clone().map(|m| Box::new(m) as Box<dyn BaseLanguageModel>);
+        let prompt_template = self.prompt_template.clone();
+        match annotate_with_resolver(&document.text, prompt_template, model, verifier_model, &self.resolver, &config).await {
+            Ok(mut result) => {
+                result.document_id = Some(document_id);
+                result.metadata = document.metadata;
+                Ok(result)
+            }
+            Err(error) => Err(DocumentError { document_id, error }),
+        }
+    }
+}
+
+/// A handle on a model shared across documents that holds one of the run's
+/// concurrency permits for each call
+///
+/// Unlike [`crate::inference::SharedModel`], which only shares the model, this
+/// also bounds how many calls are in flight across every document.
+#[derive(Clone)]
+struct PermitModel {
+    inner: Arc<dyn BaseLanguageModel>,
+    permits: Arc<Semaphore>,
+}
+
+impl PermitModel {
+    async fn permit(&self) -> LangExtractResult<tokio::sync::OwnedSemaphorePermit> {
+        self.permits
+            .clone()
+            .acquire_owned()
+            .await
+            .map_err(|e| LangExtractError::configuration(format!("Concurrency limiter closed: {}", e)))
+    }
+}
+
+#[async_trait]
+impl BaseLanguageModel for PermitModel {
+    fn applied_schema_config(&self) -> Option<HashMap<String, serde_json::Value>> {
+        self.inner.applied_schema_config()
+    }
+
+    fn requires_fence_output(&self) -> bool {
+        self.inner.requires_fence_output()
+    }
+
+    async fn infer(
+        &self,
+        batch_prompts: &[String],
+        kwargs: &HashMap<String, serde_json::Value>,
+    ) -> LangExtractResult<Vec<Vec<ScoredOutput>>> {
+        let _permit = self.permit().await?;
+        self.inner.infer(batch_prompts, kwargs).await
+    }
+
+    fn supports_streaming(&self) -> bool {
+        self.inner.supports_streaming()
+    }
+
+    async fn infer_stream(
+        &self,
+        prompt: &str,
+        kwargs: &HashMap<String, serde_json::Value>,
+    ) -> LangExtractResult<TextStream> {
+        // Hold the permit until the stream is dropped
+        let permit = self.permit().await?;
+        let stream = self.inner.infer_stream(prompt, kwargs).await?;
+        Ok(Box::pin(stream.map(move |delta| {
+            let _ = &permit;
+            delta
+        })))
+    }
+
+    fn format_type(&self) -> FormatType {
+        self.inner.format_type()
+    }
+
+    fn model_id(&self) -> &str {
+        self.inner.model_id()
+    }
+
+    fn provider_name(&self) -> &str {
+        self.inner.provider_name()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{ollama, ollama_config, person_examples};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    /// Records the highest number of calls in flight at once
+    struct SlowModel {
+        in_flight: AtomicUsize,
+        peak: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl BaseLanguageModel for SlowModel {
+        async fn infer(
+            &self,
+            batch_prompts: &[String],
+            _kwargs: &HashMap<String, serde_json::Value>,
+        ) -> LangExtractResult<Vec<Vec<ScoredOutput>>> {
+            let now = self.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+            self.peak.fetch_max(now, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            self.in_flight.fetch_sub(1, Ordering::SeqCst);
+            Ok(batch_prompts.iter().map(|_| vec![ScoredOutput::from_text("{}".to_string())]).collect())
+        }
+
+        fn model_id(&self) -> &str {
+            "slow"
+        }
+
+        fn provider_name(&self) -> &str {
+            "test"
+        }
+    }
+
+    #[tokio::test]
+    async fn test_shared_model_bounds_concurrency() {
+        let inner = Arc::new(SlowModel { in_flight: AtomicUsize::new(0), peak: AtomicUsize::new(0) });
+        let model = PermitModel { inner: inner.clone(), permits: Arc::new(Semaphore::new(2)) };
+
+        let calls = (0..8).map(|_| {
+            let model = model.clone();
+            async move { model.infer(&["prompt".to_string()], &HashMap::new()).await }
+        });
+        for result in futures::future::join_all(calls).await {
+            result.unwrap();
+        }
+
+        assert_eq!(inner.peak.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_extract_documents_yields_each_document() {
+        let server = ollama().await;
+        let config = ExtractConfig { max_workers: 2, ..ollama_config(&server) };
+
+        let documents = (0..3).map(|i| {
+            let mut document = Document::new(format!("Note {}: Ada Lovelace wrote notes.", i));
+            document.set_document_id(format!("note-{}", i));
+            document
+        });
+        let results: Vec<_> = extract_documents(futures::stream::iter(documents), None, &person_examples(), config)
+            .await
+            .unwrap()
+            .collect()
+            .await;
+
+        let mut ids: Vec<String> = results
+            .into_iter()
+            .map(|result| {
+                let document = result.unwrap();
+                assert_eq!(document.extractions.as_ref().unwrap()[0].extraction_text, "Ada Lovelace");
+                document.document_id.unwrap()
+            })
+            .collect();
+        ids.sort();
+        assert_eq!(ids, vec!["note-0", "note-1", "note-2"]);
+        assert_eq!(server.received_requests().await.unwrap().len(), 3);
+    }
+}
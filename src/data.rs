@@ -3,12 +3,14 @@
 //! This module defines the fundamental data structures used throughout the langextract
 //! library, including documents, extractions, and configuration types.
 
+use crate::usage::UsageSummary;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use uuid::Uuid;
 
 /// Status indicating how well an extraction aligns with the source text
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "mcp", derive(schemars::JsonSchema))]
 #[serde(rename_all = "snake_case")]
 pub enum AlignmentStatus {
     /// Extraction matches the source text exactly
@@ -23,6 +25,7 @@ pub enum AlignmentStatus {
 
 /// Represents a character interval in text
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "mcp", derive(schemars::JsonSchema))]
 pub struct CharInterval {
     /// Starting position of the interval (inclusive)
     pub start_pos: Option<usize>,
@@ -81,6 +84,7 @@ impl TokenInterval {
 /// within the source text. It can represent diverse information for NLP
 /// information extraction tasks.
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "mcp", derive(schemars::JsonSchema))]
 pub struct Extraction {
     /// The class or type of the extraction
     pub extraction_class: String,
@@ -197,6 +201,10 @@ pub struct Document {
     /// Unique identifier for the document
     #[serde(skip_serializing_if = "Option::is_none")]
     pub document_id: Option<String>,
+    /// Source fields carried through to the annotated document, e.g. the
+    /// other columns of a CSV row
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub metadata: HashMap<String, serde_json::Value>,
 }
 
 impl Document {
@@ -206,6 +214,7 @@ impl Document {
             text,
             additional_context: None,
             document_id: None,
+            metadata: HashMap::new(),
         }
     }
 
@@ -215,6 +224,7 @@ impl Document {
             text,
             additional_context: Some(additional_context),
             document_id: None,
+            metadata: HashMap::new(),
         }
     }
 
@@ -247,6 +257,15 @@ pub struct AnnotatedDocument {
     pub extractions: Option<Vec<Extraction>>,
     /// Original text content
     pub text: Option<String>,
+    /// Token usage and cost of the model calls that produced this document
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usage: Option<UsageSummary>,
+    /// Metadata of the source document
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub metadata: HashMap<String, serde_json::Value>,
+    /// Which parts of the text were processed, when partial results are allowed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub coverage: Option<CoverageReport>,
 }
 
 impl AnnotatedDocument {
@@ -256,6 +275,9 @@ impl AnnotatedDocument {
             document_id: None,
             extractions: None,
             text: None,
+            usage: None,
+            metadata: HashMap::new(),
+            coverage: None,
         }
     }
 
@@ -265,6 +287,9 @@ impl AnnotatedDocument {
             document_id: None,
             extractions: Some(extractions),
             text: Some(text),
+            usage: None,
+            metadata: HashMap::new(),
+            coverage: None,
         }
     }
 
@@ -318,6 +343,89 @@ impl Default for AnnotatedDocument {
     }
 }
 
+/// A chunk that could not be processed, kept in a partial result
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChunkFailure {
+    /// ID of the failed chunk
+    pub chunk_id: usize,
+    /// Character offset of the chunk in the document
+    pub char_offset: usize,
+    /// Length of the chunk in characters
+    pub char_length: usize,
+    /// Why the chunk failed
+    pub error: String,
+    /// Saved model output that could not be parsed, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub raw_output_file: Option<String>,
+}
+
+/// Which spans of a document were processed successfully
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CoverageReport {
+    /// Length of the document in characters
+    pub total_chars: usize,
+    /// Number of chunks the document was split into
+    pub total_chunks: usize,
+    /// Chunks that never produced a result
+    pub failed_chunks: Vec<ChunkFailure>,
+}
+
+impl CoverageReport {
+    /// Coverage of a document of `total_chars` split into `total_chunks`
+    pub fn new(total_chars: usize, total_chunks: usize, mut failed_chunks: Vec<ChunkFailure>) -> Self {
+        failed_chunks.sort_by_key(|f| f.chunk_id);
+        Self {
+            total_chars,
+            total_chunks,
+            failed_chunks,
+        }
+    }
+
+    /// Whether every chunk was processed
+    pub fn is_complete(&self) -> bool {
+        self.failed_chunks.is_empty()
+    }
+
+    /// Character ranges `[start, end)` of the failed chunks, merged and in order
+    pub fn unprocessed_spans(&self) -> Vec<(usize, usize)> {
+        let mut spans: Vec<(usize, usize)> = self
+            .failed_chunks
+            .iter()
+            .map(|f| (f.char_offset, (f.char_offset + f.char_length).min(self.total_chars)))
+            .collect();
+        spans.sort();
+
+        let mut merged: Vec<(usize, usize)> = Vec::with_capacity(spans.len());
+        for (start, end) in spans {
+            match merged.last_mut() {
+                Some(last) if start <= last.1 => last.1 = last.1.max(end),
+                _ => merged.push((start, end)),
+            }
+        }
+        merged
+    }
+
+    /// Fraction of characters processed, from 0.0 to 1.0
+    pub fn fraction(&self) -> f64 {
+        if self.total_chars == 0 {
+            return 1.0;
+        }
+        let unprocessed: usize = self.unprocessed_spans().iter().map(|(start, end)| end - start).sum();
+        1.0 - unprocessed as f64 / self.total_chars as f64
+    }
+
+    /// One-line summary, e.g. "92.5% of 12000 chars processed, 1 of 12 chunks failed"
+    pub fn describe(&self) -> String {
+        format!(
+            "{:.1}% of {} chars processed, {} of {} chunks failed",
+            self.fraction() * 100.0,
+            self.total_chars,
+            self.failed_chunks.len(),
+            self.total_chunks
+        )
+    }
+}
+
 /// Enumeration of supported output formats
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
@@ -354,6 +462,7 @@ impl std::str::FromStr for FormatType {
 /// Represents a single training example that shows the model how to extract
 /// information from text.
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "mcp", derive(schemars::JsonSchema))]
 pub struct ExampleData {
     /// The raw input text (sentence, paragraph, etc.)
     pub text: String,
@@ -509,4 +618,25 @@ mod tests {
         let deserialized: Document = serde_json::from_str(&json_str).unwrap();
         assert_eq!(doc, deserialized);
     }
+
+    #[test]
+    fn test_coverage_report_spans() {
+        let failure = |chunk_id, char_offset, char_length| ChunkFailure {
+            chunk_id,
+            char_offset,
+            char_length,
+            error: "timeout".to_string(),
+            raw_output_file: None,
+        };
+        let report = CoverageReport::new(100, 5, vec![failure(3, 60, 20), failure(1, 20, 20), failure(2, 40, 10)]);
+
+        assert!(!report.is_complete());
+        assert_eq!(report.failed_chunks[0].chunk_id, 1);
+        assert_eq!(report.unprocessed_spans(), vec![(20, 50), (60, 80)]);
+        assert!((report.fraction() - 0.5).abs() < 1e-9);
+        assert_eq!(report.describe(), "50.0% of 100 chars processed, 3 of 5 chunks failed");
+
+        assert_eq!(CoverageReport::new(0, 0, Vec::new()).fraction(), 1.0);
+    }
 }
+
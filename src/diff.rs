@@ -0,0 +1,378 @@
+//! Comparison of two saved extraction runs.
+//!
+//! [`diff_runs`] lines up the extractions of two runs over the same input,
+//! such as before and after a prompt or model change, and reports what was
+//! added, removed and changed. Runs are saved annotated documents (JSON,
+//! JSONL or YAML) or pipeline results; documents are paired by ID or text and
+//! pipeline steps by step ID. Within a pair, extractions are aligned by class
+//! and overlapping span (by text when either has no span). An aligned pair
+//! whose text, span or attributes differ is a change, with one
+//! [`AttributeDelta`] per differing attribute.
+//!
+//! [`export_diff_html`](crate::visualization::export_diff_html) renders a
+//! diff as a side-by-side HTML page.
+
+use crate::{
+    data::{AnnotatedDocument, Extraction},
+    evaluation::{self, has_span, match_pairs, normalize, spans_overlap},
+    exceptions::{LangExtractError, LangExtractResult},
+    pipeline::StepResult,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::path::Path;
+
+/// A saved run
+#[derive(Debug, Clone)]
+pub enum SavedRun {
+    /// Annotated documents from `extract`
+    Documents(Vec<AnnotatedDocument>),
+    /// Step results of a pipeline run
+    Pipeline(Vec<StepResult>),
+}
+
+/// Fields of a saved pipeline result (or server response) needed for a diff
+#[derive(Deserialize)]
+struct SavedPipelineRun {
+    step_results: Vec<StepResult>,
+}
+
+impl SavedRun {
+    /// Load a run: a pipeline result if the file has `step_results`,
+    /// otherwise annotated documents
+    pub fn load(path: &Path) -> LangExtractResult<Self> {
+        let is_json = !matches!(
+            path.extension().and_then(|e| e.to_str()),
+            Some("jsonl" | "ndjson" | "yaml" | "yml")
+        );
+        if is_json {
+            let content = std::fs::read_to_string(path)?;
+            let value: serde_json::Value = serde_json::from_str(&content)
+                .map_err(|e| LangExtractError::invalid_input(format!("{}: {}", path.display(), e)))?;
+            if value.get("step_results").is_some() {
+                let run: SavedPipelineRun = serde_json::from_value(value)
+                    .map_err(|e| LangExtractError::invalid_input(format!("{}: {}", path.display(), e)))?;
+                return Ok(Self::Pipeline(run.step_results));
+            }
+        }
+        evaluation::load_annotated_documents(path).map(Self::Documents)
+    }
+
+    fn kind(&self) -> &'static str {
+        match self {
+            SavedRun::Documents(_) => "annotated documents",
+            SavedRun::Pipeline(_) => "a pipeline result",
+        }
+    }
+}
+
+/// A change to one attribute of an aligned extraction
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AttributeDelta {
+    /// Attribute name
+    pub key: String,
+    /// Value in the first run, if set
+    pub before: Option<serde_json::Value>,
+    /// Value in the second run, if set
+    pub after: Option<serde_json::Value>,
+}
+
+/// An extraction present in both runs with different text, span or
+/// attributes
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExtractionChange {
+    /// The extraction in the first run
+    pub before: Extraction,
+    /// The extraction in the second run
+    pub after: Extraction,
+    /// Whether the extracted text differs
+    pub text_changed: bool,
+    /// Whether the character span differs
+    pub span_changed: bool,
+    /// Attributes that differ, by name
+    pub attributes: Vec<AttributeDelta>,
+}
+
+/// Differences within one document or pipeline step
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SectionDiff {
+    /// Document ID, `document N`, or pipeline step ID
+    pub name: String,
+    /// Document text from the first run (or the second, if only it has one)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    /// Only in the second run
+    pub added: Vec<Extraction>,
+    /// Only in the first run
+    pub removed: Vec<Extraction>,
+    /// In both runs, but different
+    pub changed: Vec<ExtractionChange>,
+    /// In both runs and identical
+    pub unchanged: Vec<Extraction>,
+}
+
+impl SectionDiff {
+    /// Whether anything was added, removed or changed
+    pub fn has_changes(&self) -> bool {
+        !(self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty())
+    }
+}
+
+/// Result of [`diff_runs`]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RunDiff {
+    /// One entry per document or pipeline step, in first-run order
+    pub sections: Vec<SectionDiff>,
+}
+
+impl RunDiff {
+    /// Whether the runs differ anywhere
+    pub fn has_changes(&self) -> bool {
+        self.sections.iter().any(SectionDiff::has_changes)
+    }
+
+    /// Added, removed, changed and unchanged extraction counts
+    pub fn counts(&self) -> (usize, usize, usize, usize) {
+        self.sections.iter().fold((0, 0, 0, 0), |(a, r, c, u), s| {
+            (a + s.added.len(), r + s.removed.len(), c + s.changed.len(), u + s.unchanged.len())
+        })
+    }
+}
+
+/// Compare two runs of the same kind
+pub fn diff_runs(before: &SavedRun, after: &SavedRun) -> LangExtractResult<RunDiff> {
+    match (before, after) {
+        (SavedRun::Documents(a), SavedRun::Documents(b)) => Ok(diff_documents(a, b)),
+        (SavedRun::Pipeline(a), SavedRun::Pipeline(b)) => Ok(diff_steps(a, b)),
+        _ => Err(LangExtractError::invalid_input(format!(
+            "Cannot compare {} with {}",
+            before.kind(),
+            after.kind()
+        ))),
+    }
+}
+
+/// Compare two sets of annotated documents, paired by ID, then text, then
+/// position
+pub fn diff_documents(before: &[AnnotatedDocument], after: &[AnnotatedDocument]) -> RunDiff {
+    let mut claimed = BTreeSet::new();
+    let mut sections = Vec::new();
+    for (i, a) in before.iter().enumerate() {
+        let partner = find_document(a, i, after, &claimed);
+        if let Some(j) = partner {
+            claimed.insert(j);
+        }
+        let b = partner.map(|j| &after[j]);
+        sections.push(diff_section(
+            document_name(a, i),
+            a.text.clone().or_else(|| b.and_then(|b| b.text.clone())),
+            a.extractions.as_deref().unwrap_or_default(),
+            b.and_then(|b| b.extractions.as_deref()).unwrap_or_default(),
+        ));
+    }
+    for (j, b) in after.iter().enumerate().filter(|(j, _)| !claimed.contains(j)) {
+        sections.push(diff_section(
+            document_name(b, j),
+            b.text.clone(),
+            &[],
+            b.extractions.as_deref().unwrap_or_default(),
+        ));
+    }
+    RunDiff { sections }
+}
+
+/// Compare the steps of two pipeline runs, paired by step ID
+pub fn diff_steps(before: &[StepResult], after: &[StepResult]) -> RunDiff {
+    let mut sections: Vec<SectionDiff> = before
+        .iter()
+        .map(|a| {
+            let b = after.iter().find(|b| b.step_id == a.step_id);
+            diff_section(
+                a.step_id.clone(),
+                None,
+                &a.extractions,
+                b.map(|b| b.extractions.as_slice()).unwrap_or_default(),
+            )
+        })
+        .collect();
+    for b in after.iter().filter(|b| !before.iter().any(|a| a.step_id == b.step_id)) {
+        sections.push(diff_section(b.step_id.clone(), None, &[], &b.extractions));
+    }
+    RunDiff { sections }
+}
+
+fn document_name(doc: &AnnotatedDocument, index: usize) -> String {
+    doc.document_id.clone().unwrap_or_else(|| format!("document {}", index + 1))
+}
+
+fn find_document(
+    doc: &AnnotatedDocument,
+    index: usize,
+    candidates: &[AnnotatedDocument],
+    claimed: &BTreeSet<usize>,
+) -> Option<usize> {
+    let free = |j: &usize| !claimed.contains(j);
+    let by_id = doc.document_id.as_ref().and_then(|id| {
+        (0..candidates.len()).filter(free).find(|&j| candidates[j].document_id.as_ref() == Some(id))
+    });
+    let by_text = || {
+        doc.text.as_ref().and_then(|text| {
+            (0..candidates.len()).filter(free).find(|&j| candidates[j].text.as_ref() == Some(text))
+        })
+    };
+    by_id
+        .or_else(by_text)
+        .or_else(|| Some(index).filter(|j| *j < candidates.len() && free(j)))
+}
+
+/// Whether two extractions are the same item across runs
+pub(crate) fn same_item(a: &Extraction, b: &Extraction) -> bool {
+    if a.extraction_class != b.extraction_class {
+        return false;
+    }
+    if has_span(a) && has_span(b) {
+        spans_overlap(a, b)
+    } else {
+        normalize(&a.extraction_text) == normalize(&b.extraction_text)
+    }
+}
+
+fn diff_section(name: String, text: Option<String>, before: &[Extraction], after: &[Extraction]) -> SectionDiff {
+    let pairs = match_pairs(before, after, same_item);
+    let mut section = SectionDiff {
+        name,
+        text,
+        added: Vec::new(),
+        removed: Vec::new(),
+        changed: Vec::new(),
+        unchanged: Vec::new(),
+    };
+
+    for (a, partner) in before.iter().zip(&pairs) {
+        let Some(j) = partner else {
+            section.removed.push(a.clone());
+            continue;
+        };
+        let b = &after[*j];
+        let change = ExtractionChange {
+            before: a.clone(),
+            after: b.clone(),
+            text_changed: a.extraction_text != b.extraction_text,
+            span_changed: a.char_interval != b.char_interval,
+            attributes: attribute_deltas(a, b),
+        };
+        if change.text_changed || change.span_changed || !change.attributes.is_empty() {
+            section.changed.push(change);
+        } else {
+            section.unchanged.push(b.clone());
+        }
+    }
+    let matched: BTreeSet<usize> = pairs.iter().flatten().copied().collect();
+    section.added = (0..after.len())
+        .filter(|j| !matched.contains(j))
+        .map(|j| after[j].clone())
+        .collect();
+    section
+}
+
+fn attribute_deltas(a: &Extraction, b: &Extraction) -> Vec<AttributeDelta> {
+    let keys: BTreeSet<&String> = a
+        .attributes
+        .iter()
+        .chain(b.attributes.iter())
+        .flat_map(|attributes| attributes.keys())
+        .collect();
+    keys.into_iter()
+        .filter_map(|key| {
+            let before = a.get_attribute(key).cloned();
+            let after = b.get_attribute(key).cloned();
+            (before != after).then(|| AttributeDelta {
+                key: key.clone(),
+                before,
+                after,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::CharInterval;
+
+    fn extraction(class: &str, text: &str, start: usize) -> Extraction {
+        Extraction::with_char_interval(
+            class.to_string(),
+            text.to_string(),
+            CharInterval::new(Some(start), Some(start + text.len())),
+        )
+    }
+
+    #[test]
+    fn test_diff_documents_reports_each_kind_of_change() {
+        let text = "Ada Lovelace met Charles Babbage in London.";
+        let mut role = extraction("person", "Ada Lovelace", 0);
+        role.set_attribute("role".to_string(), serde_json::json!("writer"));
+        let before = vec![AnnotatedDocument::with_extractions(
+            vec![role.clone(), extraction("person", "Babbage", 25), extraction("place", "London", 36)],
+            text.to_string(),
+        )];
+
+        let mut new_role = role.clone();
+        new_role.set_attribute("role".to_string(), serde_json::json!("mathematician"));
+        new_role.set_attribute("era".to_string(), serde_json::json!("1840s"));
+        let after = vec![AnnotatedDocument::with_extractions(
+            vec![new_role, extraction("person", "Charles Babbage", 17), extraction("event", "met", 13)],
+            text.to_string(),
+        )];
+
+        let diff = diff_documents(&before, &after);
+        assert_eq!(diff.sections.len(), 1);
+        let section = &diff.sections[0];
+        assert_eq!(section.name, "document 1");
+        assert_eq!(section.removed[0].extraction_text, "London");
+        assert_eq!(section.added[0].extraction_class, "event");
+
+        let babbage = section.changed.iter().find(|c| c.before.extraction_text == "Babbage").unwrap();
+        assert!(babbage.text_changed && babbage.span_changed);
+        let ada = section.changed.iter().find(|c| c.before.extraction_text == "Ada Lovelace").unwrap();
+        assert!(!ada.text_changed && !ada.span_changed);
+        let keys: Vec<_> = ada.attributes.iter().map(|d| d.key.as_str()).collect();
+        assert_eq!(keys, vec!["era", "role"]);
+        assert_eq!(ada.attributes[0].before, None);
+        assert_eq!(diff.counts(), (1, 1, 2, 0));
+
+        // A run compared with itself has no changes
+        let same = diff_documents(&before, &before);
+        assert!(!same.has_changes());
+        assert_eq!(same.counts(), (0, 0, 0, 3));
+    }
+
+    #[test]
+    fn test_diff_pipeline_steps_and_mismatched_runs() {
+        let step = |id: &str, extractions: Vec<Extraction>| StepResult {
+            step_id: id.to_string(),
+            step_name: id.to_string(),
+            extractions,
+            processing_time_ms: 0,
+            input_count: 1,
+            success: true,
+            error_message: None,
+            usage: Default::default(),
+        };
+        let before = SavedRun::Pipeline(vec![step("requirements", vec![extraction("requirement", "respond in 2s", 0)])]);
+        let after = SavedRun::Pipeline(vec![
+            step("requirements", vec![extraction("requirement", "respond in 2s", 0)]),
+            step("values", vec![extraction("value", "2", 11)]),
+        ]);
+
+        let diff = diff_runs(&before, &after).unwrap();
+        assert_eq!(diff.sections.len(), 2);
+        assert!(!diff.sections[0].has_changes());
+        assert_eq!(diff.sections[1].name, "values");
+        assert_eq!(diff.sections[1].added.len(), 1);
+
+        let documents = SavedRun::Documents(Vec::new());
+        assert!(diff_runs(&before, &documents).is_err());
+    }
+}
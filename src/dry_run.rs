@@ -0,0 +1,270 @@
+//! Dry runs: render the prompts an extraction would send without calling a model.
+//!
+//! [`dry_run`] splits the text the way the annotator does, renders the prompt
+//! for every chunk and counts its tokens with the tiktoken encoding of the
+//! configured model. No provider is created, so no credentials or network
+//! access are needed. [`PipelineExecutor::dry_run`](crate::pipeline::PipelineExecutor::dry_run)
+//! does the same for every pipeline step.
+
+use crate::{
+    chunking::{token_text_chunks, TextChunk},
+    data::ExampleData,
+    exceptions::{LangExtractError, LangExtractResult},
+    prompting::PromptTemplateStructured,
+    ExtractConfig,
+};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use tiktoken_rs::CoreBPE;
+
+/// One prompt that would be sent to the model
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PromptPreview {
+    /// Pipeline step the prompt belongs to, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub step_id: Option<String>,
+    /// Chunk index within the step's input
+    pub chunk_id: usize,
+    /// Character offset of the chunk in the input text
+    pub char_offset: usize,
+    /// Length of the chunk in characters
+    pub char_length: usize,
+    /// Rendered prompt
+    pub prompt: String,
+    /// Tokens in the rendered prompt
+    pub prompt_tokens: usize,
+    /// The input comes from an earlier step's model output, so the prompt
+    /// shows its shape around a placeholder
+    #[serde(default)]
+    pub placeholder: bool,
+}
+
+/// Prompts and token estimates for a run that was not sent to the model
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DryRunReport {
+    /// Every prompt of the first pass, in order
+    pub prompts: Vec<PromptPreview>,
+    /// Maximum number of passes; later multi-pass passes only re-send
+    /// low-yield chunks
+    pub passes: usize,
+    /// Tokenizer used for the estimates
+    pub encoding: String,
+}
+
+impl DryRunReport {
+    /// Number of chunks rendered from known input
+    pub fn chunk_count(&self) -> usize {
+        self.prompts.iter().filter(|p| !p.placeholder).count()
+    }
+
+    /// Tokens across all first-pass prompts
+    pub fn prompt_tokens(&self) -> usize {
+        self.prompts.iter().map(|p| p.prompt_tokens).sum()
+    }
+
+    /// Upper bound on prompt tokens if every pass re-sent every chunk
+    pub fn max_prompt_tokens(&self) -> usize {
+        self.prompt_tokens() * self.passes.max(1)
+    }
+
+    /// Whether some prompts depend on model output and only show their shape
+    pub fn has_placeholders(&self) -> bool {
+        self.prompts.iter().any(|p| p.placeholder)
+    }
+
+    /// Write each prompt to its own file under `dir`, in a subdirectory per
+    /// pipeline step, and return the paths written
+    pub fn write_prompts(&self, dir: &Path) -> LangExtractResult<Vec<PathBuf>> {
+        let mut paths = Vec::with_capacity(self.prompts.len());
+        for preview in &self.prompts {
+            let parent = match &preview.step_id {
+                Some(step_id) => dir.join(step_id),
+                None => dir.to_path_buf(),
+            };
+            std::fs::create_dir_all(&parent)?;
+            let path = parent.join(format!("chunk_{:04}.txt", preview.chunk_id));
+            std::fs::write(&path, &preview.prompt)?;
+            paths.push(path);
+        }
+        Ok(paths)
+    }
+
+    /// One-line summary, e.g. "3 chunks, 4512 prompt tokens (cl100k_base)"
+    pub fn describe(&self) -> String {
+        let mut summary = format!(
+            "{} chunks, {} prompt tokens ({})",
+            self.chunk_count(),
+            self.prompt_tokens(),
+            self.encoding
+        );
+        if self.passes > 1 {
+            summary.push_str(&format!(
+                ", up to {} over {} passes",
+                self.max_prompt_tokens(),
+                self.passes
+            ));
+        }
+        if self.has_placeholders() {
+            summary.push_str("; steps fed by earlier output are counted once");
+        }
+        summary
+    }
+}
+
+/// Render the prompts [`extract`](crate::extract) would send for `text`,
+/// without creating a provider
+pub fn dry_run(
+    text: &str,
+    prompt_description: Option<&str>,
+    examples: &[ExampleData],
+    config: &ExtractConfig,
+) -> LangExtractResult<DryRunReport> {
+    let renderer = PromptRenderer::new(prompt_description, examples, config)?;
+    let prompts = renderer.render_chunks(text, None)?;
+    Ok(renderer.report(prompts))
+}
+
+/// Renders and counts prompts for one prompt description and example set
+pub(crate) struct PromptRenderer<'a> {
+    template: PromptTemplateStructured,
+    config: &'a ExtractConfig,
+    bpe: CoreBPE,
+    encoding: String,
+}
+
+impl<'a> PromptRenderer<'a> {
+    pub(crate) fn new(
+        prompt_description: Option<&str>,
+        examples: &[ExampleData],
+        config: &'a ExtractConfig,
+    ) -> LangExtractResult<Self> {
+        if examples.is_empty() {
+            return Err(LangExtractError::invalid_input(
+                "Examples are required for reliable extraction. Please provide at least one ExampleData object with sample extractions.",
+            ));
+        }
+
+        let mut template = PromptTemplateStructured::new(prompt_description);
+        template.examples.extend(examples.iter().cloned());
+
+        // Models tiktoken doesn't know (Ollama, Gemini, ...) are estimated with cl100k_base
+        let (bpe, encoding) = match tiktoken_rs::get_bpe_from_model(&config.model_id) {
+            Ok(bpe) => (bpe, format!("tiktoken/{}", config.model_id)),
+            Err(_) => (
+                tiktoken_rs::cl100k_base().map_err(|e| {
+                    LangExtractError::invalid_input(format!("Failed to initialize tiktoken tokenizer: {}", e))
+                })?,
+                "cl100k_base".to_string(),
+            ),
+        };
+
+        Ok(Self {
+            template,
+            config,
+            bpe,
+            encoding,
+        })
+    }
+
+    /// Split `text` like the annotator and render a prompt per chunk
+    pub(crate) fn render_chunks(
+        &self,
+        text: &str,
+        step_id: Option<&str>,
+    ) -> LangExtractResult<Vec<PromptPreview>> {
+        let chunks = if text.len() <= self.config.max_char_buffer {
+            vec![TextChunk::new(0, text.to_string(), 0, None)]
+        } else {
+            token_text_chunks(text, self.config.max_char_buffer)?
+        };
+
+        chunks
+            .into_iter()
+            .map(|chunk| {
+                let mut preview = self.render(&chunk.text, step_id)?;
+                preview.chunk_id = chunk.id;
+                preview.char_offset = chunk.char_offset;
+                preview.char_length = chunk.char_length;
+                Ok(preview)
+            })
+            .collect()
+    }
+
+    /// Render a single prompt around `text`
+    pub(crate) fn render(&self, text: &str, step_id: Option<&str>) -> LangExtractResult<PromptPreview> {
+        let prompt = self
+            .template
+            .render(text, self.config.additional_context.as_deref())?;
+        Ok(PromptPreview {
+            step_id: step_id.map(String::from),
+            chunk_id: 0,
+            char_offset: 0,
+            char_length: text.len(),
+            prompt_tokens: self.bpe.encode_with_special_tokens(&prompt).len(),
+            prompt,
+            placeholder: false,
+        })
+    }
+
+    pub(crate) fn report(&self, prompts: Vec<PromptPreview>) -> DryRunReport {
+        DryRunReport {
+            prompts,
+            passes: if self.config.enable_multipass {
+                self.config.multipass_max_passes
+            } else {
+                1
+            },
+            encoding: self.encoding.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::person_examples as examples;
+
+    #[test]
+    fn test_dry_run_renders_a_prompt_per_chunk() {
+        let text = "Ada Lovelace wrote notes. Charles Babbage built engines. Grace Hopper wrote compilers.";
+        let config = ExtractConfig {
+            max_char_buffer: 30,
+            ..Default::default()
+        };
+
+        let report = dry_run(text, Some("Extract people"), &examples(), &config).unwrap();
+
+        assert_eq!(report.chunk_count(), 3);
+        assert_eq!(report.passes, 1);
+        for preview in &report.prompts {
+            let chunk = &text[preview.char_offset..preview.char_offset + preview.char_length];
+            assert!(preview.prompt.contains(chunk));
+            assert!(preview.prompt.contains("Extract people"));
+            assert!(preview.prompt_tokens > 0);
+        }
+        assert_eq!(
+            report.prompt_tokens(),
+            report.prompts.iter().map(|p| p.prompt_tokens).sum::<usize>()
+        );
+    }
+
+    #[test]
+    fn test_write_prompts_per_step() {
+        let config = ExtractConfig::default();
+        let renderer = PromptRenderer::new(None, &examples(), &config).unwrap();
+        let mut prompts = renderer.render_chunks("Ada Lovelace wrote notes.", None).unwrap();
+        prompts.extend(renderer.render_chunks("Grace Hopper", Some("people")).unwrap());
+        let report = renderer.report(prompts);
+
+        let dir = tempfile::tempdir().unwrap();
+        let paths = report.write_prompts(dir.path()).unwrap();
+
+        assert_eq!(paths, vec![dir.path().join("chunk_0000.txt"), dir.path().join("people/chunk_0000.txt")]);
+        assert_eq!(std::fs::read_to_string(&paths[1]).unwrap(), report.prompts[1].prompt);
+    }
+
+    #[test]
+    fn test_dry_run_requires_examples() {
+        assert!(dry_run("text", None, &[], &ExtractConfig::default()).is_err());
+    }
+}
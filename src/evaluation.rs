@@ -0,0 +1,556 @@
+//! Evaluation of extractions against gold annotations.
+//!
+//! [`evaluate`] compares predicted [`AnnotatedDocument`]s with gold ones and
+//! reports precision, recall and F1 per extraction class under three ways of
+//! matching a prediction to a gold extraction:
+//!
+//! - [`MatchMode::Exact`]: same class and the same character span
+//! - [`MatchMode::Overlap`]: same class and overlapping spans
+//! - [`MatchMode::Text`]: same class and the same text, wherever it is
+//!
+//! Each gold extraction is matched at most once. A confusion matrix of
+//! classes over overlapping spans shows which classes the model mixes up.
+//! Gold files can also be in the [`ExampleData`](crate::ExampleData) format;
+//! extractions without a character interval are located in the document text
+//! before matching.
+
+use crate::{
+    alignment::TextAligner,
+    data::{AnnotatedDocument, CharInterval, Extraction},
+    exceptions::{LangExtractError, LangExtractResult},
+};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::path::Path;
+
+/// Row and column label for extractions with no counterpart in the
+/// confusion matrix
+pub const NO_MATCH: &str = "(none)";
+
+/// How a predicted extraction is matched to a gold one
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MatchMode {
+    /// Same class and the same character span
+    Exact,
+    /// Same class and overlapping character spans
+    Overlap,
+    /// Same class and the same text (ignoring case and spacing), wherever it is
+    Text,
+}
+
+impl MatchMode {
+    /// All modes, in report order
+    pub const ALL: [MatchMode; 3] = [MatchMode::Exact, MatchMode::Overlap, MatchMode::Text];
+
+    fn matches(self, gold: &Extraction, predicted: &Extraction) -> bool {
+        if gold.extraction_class != predicted.extraction_class {
+            return false;
+        }
+        match self {
+            MatchMode::Exact => match (&gold.char_interval, &predicted.char_interval) {
+                (Some(a), Some(b)) => a.start_pos.is_some() && a.end_pos.is_some() && a == b,
+                _ => false,
+            },
+            MatchMode::Overlap => spans_overlap(gold, predicted),
+            MatchMode::Text => normalize(&gold.extraction_text) == normalize(&predicted.extraction_text),
+        }
+    }
+}
+
+impl fmt::Display for MatchMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MatchMode::Exact => write!(f, "Exact span"),
+            MatchMode::Overlap => write!(f, "Overlapping span"),
+            MatchMode::Text => write!(f, "Text only"),
+        }
+    }
+}
+
+/// Counts and scores for one class, or for all classes together
+///
+/// Scores with a zero denominator (no predictions, or no gold extractions)
+/// are 0.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct ClassMetrics {
+    /// Gold extractions
+    pub gold: usize,
+    /// Predicted extractions
+    pub predicted: usize,
+    /// Predictions matched to a gold extraction
+    pub true_positives: usize,
+    /// Matched predictions over all predictions
+    pub precision: f64,
+    /// Matched gold extractions over all gold extractions
+    pub recall: f64,
+    /// Harmonic mean of precision and recall
+    pub f1: f64,
+}
+
+impl ClassMetrics {
+    /// Scores from counts
+    pub fn from_counts(gold: usize, predicted: usize, true_positives: usize) -> Self {
+        let ratio = |n: usize, d: usize| if d == 0 { 0.0 } else { n as f64 / d as f64 };
+        let precision = ratio(true_positives, predicted);
+        let recall = ratio(true_positives, gold);
+        let f1 = if precision + recall == 0.0 {
+            0.0
+        } else {
+            2.0 * precision * recall / (precision + recall)
+        };
+        Self {
+            gold,
+            predicted,
+            true_positives,
+            precision,
+            recall,
+            f1,
+        }
+    }
+
+    /// Predictions with no gold counterpart
+    pub fn false_positives(&self) -> usize {
+        self.predicted - self.true_positives
+    }
+
+    /// Gold extractions the predictions missed
+    pub fn false_negatives(&self) -> usize {
+        self.gold - self.true_positives
+    }
+}
+
+/// Scores under one match mode
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ModeReport {
+    /// How predictions were matched
+    pub mode: MatchMode,
+    /// Scores per class
+    pub classes: BTreeMap<String, ClassMetrics>,
+    /// Scores over all extractions (micro average)
+    pub overall: ClassMetrics,
+    /// Mean F1 over classes (macro average)
+    pub macro_f1: f64,
+}
+
+/// Counts of gold classes (rows) against predicted classes (columns) for
+/// extractions paired by overlapping spans
+///
+/// Gold extractions with no overlapping prediction fall in the [`NO_MATCH`]
+/// column; predictions overlapping no gold extraction fall in the
+/// [`NO_MATCH`] row.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ConfusionMatrix {
+    /// Class labels, sorted, followed by [`NO_MATCH`]
+    pub labels: Vec<String>,
+    /// Counts by gold class, then predicted class
+    pub counts: BTreeMap<String, BTreeMap<String, usize>>,
+}
+
+impl ConfusionMatrix {
+    /// How often a gold `gold` extraction was predicted as `predicted`
+    pub fn count(&self, gold: &str, predicted: &str) -> usize {
+        self.counts.get(gold).and_then(|row| row.get(predicted)).copied().unwrap_or(0)
+    }
+
+    fn add(&mut self, gold: &str, predicted: &str) {
+        *self
+            .counts
+            .entry(gold.to_string())
+            .or_default()
+            .entry(predicted.to_string())
+            .or_default() += 1;
+    }
+}
+
+/// Result of [`evaluate`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EvaluationReport {
+    /// Gold documents evaluated
+    pub documents: usize,
+    /// Gold documents with no matching prediction; their extractions all
+    /// count as missed
+    pub documents_without_predictions: usize,
+    /// Gold extractions that could not be located in their text
+    pub unaligned_gold: usize,
+    /// Predicted extractions that could not be located in their text
+    pub unaligned_predicted: usize,
+    /// Scores under each match mode
+    pub modes: Vec<ModeReport>,
+    /// Class confusion over overlapping spans
+    pub confusion: ConfusionMatrix,
+}
+
+impl EvaluationReport {
+    /// Scores under `mode`
+    pub fn mode(&self, mode: MatchMode) -> Option<&ModeReport> {
+        self.modes.iter().find(|m| m.mode == mode)
+    }
+
+    /// The report as Markdown tables
+    pub fn to_markdown(&self) -> String {
+        let mut out = String::from("# Evaluation Report\n\n");
+        out.push_str(&format!("- Documents: {}\n", self.documents));
+        if self.documents_without_predictions > 0 {
+            out.push_str(&format!("- Documents without predictions: {}\n", self.documents_without_predictions));
+        }
+        out.push_str(&format!("- Unaligned extractions: {} gold, {} predicted\n", self.unaligned_gold, self.unaligned_predicted));
+
+        for mode in &self.modes {
+            out.push_str(&format!("\n## {}\n\n", mode.mode));
+            out.push_str("| Class | Gold | Predicted | Matched | Precision | Recall | F1 |\n");
+            out.push_str("|---|---:|---:|---:|---:|---:|---:|\n");
+            for (class, metrics) in &mode.classes {
+                out.push_str(&metrics_row(class, metrics));
+            }
+            out.push_str(&metrics_row("**All**", &mode.overall));
+            out.push_str(&format!("\nMacro F1: {:.3}\n", mode.macro_f1));
+        }
+
+        out.push_str("\n## Confusion Matrix (overlapping spans)\n\n");
+        out.push_str("| Gold \\ Predicted |");
+        for label in &self.confusion.labels {
+            out.push_str(&format!(" {} |", label));
+        }
+        out.push_str("\n|---|");
+        out.push_str(&"---:|".repeat(self.confusion.labels.len()));
+        out.push('\n');
+        for gold in &self.confusion.labels {
+            out.push_str(&format!("| {} |", gold));
+            for predicted in &self.confusion.labels {
+                out.push_str(&format!(" {} |", self.confusion.count(gold, predicted)));
+            }
+            out.push('\n');
+        }
+        out
+    }
+}
+
+fn metrics_row(label: &str, m: &ClassMetrics) -> String {
+    format!(
+        "| {} | {} | {} | {} | {:.3} | {:.3} | {:.3} |\n",
+        label, m.gold, m.predicted, m.true_positives, m.precision, m.recall, m.f1
+    )
+}
+
+/// Score `predicted` documents against `gold` ones
+///
+/// Documents are paired by `document_id`, or by text when either has no ID.
+/// Extractions without a character interval are aligned to their document's
+/// text first, so gold written as [`ExampleData`](crate::ExampleData) works
+/// with span matching.
+pub fn evaluate(gold: &[AnnotatedDocument], predicted: &[AnnotatedDocument]) -> EvaluationReport {
+    let aligner = TextAligner::new();
+    let mut unaligned_gold = 0;
+    let mut unaligned_predicted = 0;
+    let mut documents_without_predictions = 0;
+    let mut pairs = Vec::with_capacity(gold.len());
+
+    for gold_doc in gold {
+        let gold_extractions = aligned_extractions(&aligner, gold_doc, &mut unaligned_gold);
+        let predicted_extractions = match find_prediction(gold_doc, predicted) {
+            Some(doc) => aligned_extractions(&aligner, doc, &mut unaligned_predicted),
+            None => {
+                documents_without_predictions += 1;
+                Vec::new()
+            }
+        };
+        pairs.push((gold_extractions, predicted_extractions));
+    }
+
+    let modes = MatchMode::ALL.iter().map(|&mode| score(mode, &pairs)).collect();
+
+    let mut confusion = ConfusionMatrix::default();
+    let mut labels = BTreeSet::new();
+    for (gold_extractions, predicted_extractions) in &pairs {
+        labels.extend(gold_extractions.iter().map(|e| e.extraction_class.clone()));
+        labels.extend(predicted_extractions.iter().map(|e| e.extraction_class.clone()));
+        confuse(gold_extractions, predicted_extractions, &mut confusion);
+    }
+    confusion.labels = labels.into_iter().chain([NO_MATCH.to_string()]).collect();
+
+    EvaluationReport {
+        documents: gold.len(),
+        documents_without_predictions,
+        unaligned_gold,
+        unaligned_predicted,
+        modes,
+        confusion,
+    }
+}
+
+/// Load annotated documents from a JSON array or object, a JSONL file (one
+/// document per line) or YAML, as written by `lx-rs extract` or in the
+/// [`ExampleData`](crate::ExampleData) format
+pub fn load_annotated_documents(path: &Path) -> LangExtractResult<Vec<AnnotatedDocument>> {
+    let content = std::fs::read_to_string(path)?;
+    let invalid = |e: String| LangExtractError::invalid_input(format!("{}: {}", path.display(), e));
+
+    match path.extension().and_then(|e| e.to_str()) {
+        Some("jsonl") | Some("ndjson") => content
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| serde_json::from_str(line).map_err(|e| invalid(e.to_string())))
+            .collect(),
+        Some("yaml") | Some("yml") => {
+            let value: serde_yaml::Value = serde_yaml::from_str(&content).map_err(|e| invalid(e.to_string()))?;
+            match value {
+                serde_yaml::Value::Sequence(_) => serde_yaml::from_value(value),
+                _ => serde_yaml::from_value(value).map(|doc| vec![doc]),
+            }
+            .map_err(|e| invalid(e.to_string()))
+        }
+        _ => {
+            let value: serde_json::Value = serde_json::from_str(&content).map_err(|e| invalid(e.to_string()))?;
+            match value {
+                serde_json::Value::Array(_) => serde_json::from_value(value),
+                _ => serde_json::from_value(value).map(|doc| vec![doc]),
+            }
+            .map_err(|e| invalid(e.to_string()))
+        }
+    }
+}
+
+fn find_prediction<'a>(gold: &AnnotatedDocument, predicted: &'a [AnnotatedDocument]) -> Option<&'a AnnotatedDocument> {
+    if let Some(id) = &gold.document_id {
+        if let Some(doc) = predicted.iter().find(|p| p.document_id.as_ref() == Some(id)) {
+            return Some(doc);
+        }
+    }
+    gold.text
+        .as_ref()
+        .and_then(|text| predicted.iter().find(|p| p.text.as_ref() == Some(text)))
+}
+
+/// The document's extractions, aligning those without a span to its text
+fn aligned_extractions(aligner: &TextAligner, doc: &AnnotatedDocument, unaligned: &mut usize) -> Vec<Extraction> {
+    let mut extractions = doc.extractions.clone().unwrap_or_default();
+    for extraction in &mut extractions {
+        if has_span(extraction) {
+            continue;
+        }
+        if let Some(text) = &doc.text {
+            if let Ok(Some(interval)) = aligner.align_single_extraction(extraction, text, 0) {
+                extraction.char_interval = Some(interval);
+                continue;
+            }
+        }
+        *unaligned += 1;
+    }
+    extractions
+}
+
+pub(crate) fn has_span(extraction: &Extraction) -> bool {
+    matches!(
+        extraction.char_interval,
+        Some(CharInterval { start_pos: Some(_), end_pos: Some(_) })
+    )
+}
+
+pub(crate) fn spans_overlap(a: &Extraction, b: &Extraction) -> bool {
+    match (&a.char_interval, &b.char_interval) {
+        (Some(a), Some(b)) => a.overlaps_with(b),
+        _ => false,
+    }
+}
+
+/// Characters shared by two extractions' spans
+fn overlap_len(a: &Extraction, b: &Extraction) -> usize {
+    match (&a.char_interval, &b.char_interval) {
+        (
+            Some(CharInterval { start_pos: Some(s1), end_pos: Some(e1) }),
+            Some(CharInterval { start_pos: Some(s2), end_pos: Some(e2) }),
+        ) => (*e1).min(*e2).saturating_sub((*s1).max(*s2)),
+        _ => 0,
+    }
+}
+
+/// Lowercase with runs of whitespace collapsed
+pub(crate) fn normalize(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase()
+}
+
+/// Pair each gold extraction with at most one prediction, preferring the one
+/// sharing the most characters
+pub(crate) fn match_pairs(gold: &[Extraction], predicted: &[Extraction], accept: impl Fn(&Extraction, &Extraction) -> bool) -> Vec<Option<usize>> {
+    let mut used = vec![false; predicted.len()];
+    gold.iter()
+        .map(|g| {
+            let best = predicted
+                .iter()
+                .enumerate()
+                .filter(|(i, p)| !used[*i] && accept(g, p))
+                .max_by_key(|(i, p)| (overlap_len(g, p), std::cmp::Reverse(*i)))
+                .map(|(i, _)| i);
+            if let Some(i) = best {
+                used[i] = true;
+            }
+            best
+        })
+        .collect()
+}
+
+fn score(mode: MatchMode, pairs: &[(Vec<Extraction>, Vec<Extraction>)]) -> ModeReport {
+    // (gold, predicted, matched) per class
+    let mut counts: BTreeMap<String, (usize, usize, usize)> = BTreeMap::new();
+    for (gold, predicted) in pairs {
+        for g in gold {
+            counts.entry(g.extraction_class.clone()).or_default().0 += 1;
+        }
+        for p in predicted {
+            counts.entry(p.extraction_class.clone()).or_default().1 += 1;
+        }
+        for (g, matched) in gold.iter().zip(match_pairs(gold, predicted, |g, p| mode.matches(g, p))) {
+            if matched.is_some() {
+                counts.entry(g.extraction_class.clone()).or_default().2 += 1;
+            }
+        }
+    }
+
+    let classes: BTreeMap<String, ClassMetrics> = counts
+        .into_iter()
+        .map(|(class, (g, p, tp))| (class, ClassMetrics::from_counts(g, p, tp)))
+        .collect();
+    let overall = classes.values().fold((0, 0, 0), |(g, p, tp), m| (g + m.gold, p + m.predicted, tp + m.true_positives));
+    let macro_f1 = if classes.is_empty() {
+        0.0
+    } else {
+        classes.values().map(|m| m.f1).sum::<f64>() / classes.len() as f64
+    };
+    ModeReport {
+        mode,
+        classes,
+        overall: ClassMetrics::from_counts(overall.0, overall.1, overall.2),
+        macro_f1,
+    }
+}
+
+fn confuse(gold: &[Extraction], predicted: &[Extraction], confusion: &mut ConfusionMatrix) {
+    // Same-class matches first, so a correct prediction isn't taken by a
+    // neighbouring gold extraction of another class
+    let same_class = match_pairs(gold, predicted, |g, p| MatchMode::Overlap.matches(g, p));
+    let mut used: BTreeSet<usize> = same_class.iter().flatten().copied().collect();
+    let remaining: Vec<usize> = (0..predicted.len()).filter(|i| !used.contains(i)).collect();
+    let others: Vec<Extraction> = remaining.iter().map(|&i| predicted[i].clone()).collect();
+    let unmatched_gold: Vec<usize> = (0..gold.len()).filter(|&i| same_class[i].is_none()).collect();
+    let unmatched: Vec<Extraction> = unmatched_gold.iter().map(|&i| gold[i].clone()).collect();
+    let cross_class = match_pairs(&unmatched, &others, spans_overlap);
+
+    for (g, matched) in gold.iter().zip(&same_class) {
+        if matched.is_some() {
+            confusion.add(&g.extraction_class, &g.extraction_class);
+        }
+    }
+    for (g, matched) in unmatched.iter().zip(&cross_class) {
+        match matched {
+            Some(j) => {
+                used.insert(remaining[*j]);
+                confusion.add(&g.extraction_class, &others[*j].extraction_class);
+            }
+            None => confusion.add(&g.extraction_class, NO_MATCH),
+        }
+    }
+    for (i, p) in predicted.iter().enumerate() {
+        if !used.contains(&i) {
+            confusion.add(NO_MATCH, &p.extraction_class);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn extraction(class: &str, text: &str, start: usize) -> Extraction {
+        Extraction::with_char_interval(
+            class.to_string(),
+            text.to_string(),
+            CharInterval::new(Some(start), Some(start + text.len())),
+        )
+    }
+
+    fn document(text: &str, extractions: Vec<Extraction>) -> AnnotatedDocument {
+        AnnotatedDocument::with_extractions(extractions, text.to_string())
+    }
+
+    #[test]
+    fn test_scores_under_each_match_mode() {
+        let text = "Ada Lovelace met Charles Babbage in London.";
+        let gold = vec![document(text, vec![
+            extraction("person", "Ada Lovelace", 0),
+            extraction("person", "Charles Babbage", 17),
+            extraction("place", "London", 36),
+        ])];
+        let predicted = vec![document(text, vec![
+            extraction("person", "Ada Lovelace", 0),
+            // Partial span: overlaps but isn't exact
+            extraction("person", "Babbage", 25),
+            // Right text, wrong place
+            Extraction::with_char_interval("place".to_string(), "london".to_string(), CharInterval::new(Some(0), Some(3))),
+        ])];
+
+        let report = evaluate(&gold, &predicted);
+        let exact = report.mode(MatchMode::Exact).unwrap();
+        assert_eq!(exact.overall.true_positives, 1);
+        assert_eq!(exact.classes["person"].recall, 0.5);
+        assert_eq!(exact.classes["place"].f1, 0.0);
+
+        let overlap = report.mode(MatchMode::Overlap).unwrap();
+        assert_eq!(overlap.classes["person"].f1, 1.0);
+        assert_eq!(overlap.overall.false_positives(), 1);
+
+        let text_only = report.mode(MatchMode::Text).unwrap();
+        assert_eq!(text_only.classes["place"].true_positives, 1);
+        assert_eq!(text_only.classes["person"].true_positives, 1);
+        assert!((text_only.macro_f1 - 0.75).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_confusion_matrix_and_example_format() {
+        // Gold in the examples format, without spans
+        let gold: Vec<AnnotatedDocument> = serde_json::from_value(serde_json::json!([{
+            "text": "Aspirin 100 mg daily for pain.",
+            "extractions": [
+                { "extraction_class": "medication", "extraction_text": "Aspirin" },
+                { "extraction_class": "dosage", "extraction_text": "100 mg" },
+                { "extraction_class": "condition", "extraction_text": "pain" },
+            ],
+        }]))
+        .unwrap();
+        let predicted = vec![document("Aspirin 100 mg daily for pain.", vec![
+            extraction("medication", "Aspirin", 0),
+            extraction("frequency", "100 mg", 8),
+            extraction("frequency", "daily", 15),
+        ])];
+
+        let report = evaluate(&gold, &predicted);
+        assert_eq!(report.unaligned_gold, 0);
+        assert_eq!(report.mode(MatchMode::Exact).unwrap().classes["medication"].f1, 1.0);
+
+        let confusion = &report.confusion;
+        assert_eq!(confusion.labels, vec!["condition", "dosage", "frequency", "medication", NO_MATCH]);
+        assert_eq!(confusion.count("medication", "medication"), 1);
+        assert_eq!(confusion.count("dosage", "frequency"), 1);
+        assert_eq!(confusion.count("condition", NO_MATCH), 1);
+        assert_eq!(confusion.count(NO_MATCH, "frequency"), 1);
+
+        let markdown = report.to_markdown();
+        assert!(markdown.contains("## Exact span"));
+        assert!(markdown.contains("| dosage | 0 | 0 | 1 | 0 | 0 |"));
+    }
+
+    #[test]
+    fn test_missing_prediction_counts_as_missed() {
+        let gold = vec![
+            document("one", vec![extraction("x", "one", 0)]),
+            document("two", vec![extraction("x", "two", 0)]),
+        ];
+        let predicted = vec![document("two", vec![extraction("x", "two", 0)])];
+
+        let report = evaluate(&gold, &predicted);
+        assert_eq!(report.documents_without_predictions, 1);
+        let overall = report.mode(MatchMode::Exact).unwrap().overall;
+        assert_eq!((overall.precision, overall.recall), (1.0, 0.5));
+    }
+}
@@ -63,6 +63,14 @@ pub enum LangExtractError {
     #[error("Visualization error: {0}")]
     VisualizationError(String),
 
+    /// A token or cost budget was exceeded, aborting the run
+    #[error("Budget exceeded: {0}")]
+    BudgetExceeded(String),
+
+    /// The run was cancelled or ran past its timeout
+    #[error("Cancelled: {0}")]
+    Cancelled(String),
+
     /// Generic error for unexpected conditions
     #[error("Unexpected error: {0}")]
     UnexpectedError(String),
@@ -96,6 +104,28 @@ impl LangExtractError {
         }
     }
 
+    /// Create an inference error for a non-success HTTP response
+    ///
+    /// The status and any `Retry-After` delay are kept as the error source so
+    /// retry policies can classify the failure.
+    pub fn http_status<S: Into<String>>(
+        provider: &str,
+        status: u16,
+        retry_after: Option<std::time::Duration>,
+        body: S,
+    ) -> Self {
+        let status_error = HttpStatusError {
+            status,
+            retry_after,
+            body: body.into(),
+        };
+        Self::InferenceError {
+            message: format!("{} API error: {}", provider, status_error),
+            provider: Some(provider.to_string()),
+            source: Some(Box::new(status_error)),
+        }
+    }
+
     /// Create a new invalid input error
     pub fn invalid_input<S: Into<String>>(message: S) -> Self {
         Self::InvalidInput(message.into())
@@ -131,6 +161,16 @@ impl LangExtractError {
         Self::VisualizationError(message.into())
     }
 
+    /// Create a new budget exceeded error
+    pub fn budget_exceeded<S: Into<String>>(message: S) -> Self {
+        Self::BudgetExceeded(message.into())
+    }
+
+    /// Create a new cancellation error
+    pub fn cancelled<S: Into<String>>(message: S) -> Self {
+        Self::Cancelled(message.into())
+    }
+
     /// Create a new unexpected error
     pub fn unexpected<S: Into<String>>(message: S) -> Self {
         Self::UnexpectedError(message.into())
@@ -144,6 +184,14 @@ impl LangExtractError {
         }
     }
 
+    /// Get the HTTP status details if this error came from a non-success response
+    pub fn status_error(&self) -> Option<&HttpStatusError> {
+        match self {
+            Self::InferenceError { source: Some(source), .. } => source.downcast_ref::<HttpStatusError>(),
+            _ => None,
+        }
+    }
+
     /// Check if this error is related to configuration
     pub fn is_configuration_error(&self) -> bool {
         matches!(self, Self::ConfigurationError(_))
@@ -163,6 +211,11 @@ impl LangExtractError {
     pub fn is_parsing_error(&self) -> bool {
         matches!(self, Self::ParsingError(_))
     }
+
+    /// Check if the run was cancelled or timed out
+    pub fn is_cancelled(&self) -> bool {
+        matches!(self, Self::Cancelled(_))
+    }
 }
 
 // Convert from serde JSON errors
@@ -186,6 +239,18 @@ impl From<dotenvy::Error> for LangExtractError {
     }
 }
 
+/// Non-success HTTP response from a provider
+#[derive(Error, Debug, Clone)]
+#[error("HTTP {status}: {body}")]
+pub struct HttpStatusError {
+    /// HTTP status code
+    pub status: u16,
+    /// Delay requested by a `Retry-After` header
+    pub retry_after: Option<std::time::Duration>,
+    /// Response body, for diagnostics
+    pub body: String,
+}
+
 /// Specialized error for inference operations
 #[derive(Error, Debug)]
 pub enum InferenceError {
@@ -305,6 +370,19 @@ mod tests {
         
         let lang_err: LangExtractError = inference_err.into();
         assert!(lang_err.is_inference_error());
+        assert!(lang_err.status_error().is_none());
+    }
+
+    #[test]
+    fn test_http_status_error() {
+        let err = LangExtractError::http_status("gemini", 429, Some(std::time::Duration::from_secs(3)), "slow down");
+        assert!(err.is_inference_error());
+        assert_eq!(err.provider(), Some("gemini"));
+        assert_eq!(err.to_string(), "Inference error: gemini API error: HTTP 429: slow down");
+
+        let status = err.status_error().unwrap();
+        assert_eq!(status.status, 429);
+        assert_eq!(status.retry_after, Some(std::time::Duration::from_secs(3)));
     }
 
     #[test]
@@ -4,9 +4,15 @@ use crate::{
     data::ExampleData,
     exceptions::{LangExtractError, LangExtractResult},
     inference::BaseLanguageModel,
-    providers::{create_provider, ProviderConfig},
+    providers::{
+        create_provider, CachedModel, FallbackModel, FixtureMode, ProviderConfig, RecordReplayModel,
+        UniversalProvider,
+    },
+    resolver::Resolver,
+    schema::ExtractionClass,
     ExtractConfig,
 };
+use std::collections::BTreeSet;
 
 #[cfg(test)]
 use crate::providers::ProviderType;
@@ -18,28 +24,111 @@ pub async fn create_model(
 ) -> LangExtractResult<Box<dyn BaseLanguageModel>> {
     // Determine provider type and configuration from the ExtractConfig
     let provider_config = create_provider_config(config)?;
+    let fixture = provider_config.fixture.clone();
+    let cache = provider_config.cache.clone();
+    let base_url = provider_config.base_url.clone();
+
+    // Replay never touches the real provider, so no credentials are needed
+    if let Some(fixture) = fixture.as_ref().filter(|f| f.mode == FixtureMode::Replay) {
+        let mut model = RecordReplayModel::replay(&fixture.path)?;
+        model.set_fence_output(config.fence_output);
+        return Ok(Box::new(model));
+    }
     
-    // Create the provider
-    let mut provider = create_provider(provider_config)?;
+    let fallbacks = provider_config.fallbacks.clone();
+    let fallback_policy = provider_config.fallback_policy.clone();
+
+    let mut model = build_provider(provider_config, config, examples)?;
+
+    // Chain the fallback providers behind the primary; caching and recording
+    // wrap the whole chain
+    if !fallbacks.is_empty() {
+        let mut models = vec![model];
+        for fallback in fallbacks {
+            models.push(build_provider(fallback, config, examples)?);
+        }
+
+        let resolver = Resolver::new(config, models[0].requires_fence_output())?;
+        let expected_fields: Vec<String> = examples
+            .unwrap_or_default()
+            .iter()
+            .flat_map(|example| example.extractions.iter())
+            .map(|extraction| extraction.extraction_class.clone())
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect();
+
+        model = Box::new(
+            FallbackModel::new(models, fallback_policy)?.with_resolver(resolver, expected_fields),
+        );
+    }
+
+    if let Some(cache) = cache {
+        model = Box::new(CachedModel::new(model, cache)?.with_base_url(base_url));
+    }
+
+    if let Some(fixture) = fixture.filter(|f| f.mode == FixtureMode::Record) {
+        model = Box::new(RecordReplayModel::record(model, fixture.path)?);
+    }
     
-    // Apply schema if examples are provided
-    if let Some(example_data) = examples {
-        if config.use_schema_constraints && !example_data.is_empty() {
-            if let Some(schema_class) = provider.get_schema_class() {
-                // For now, we'll use a basic schema
-                provider.apply_schema(Some(schema_class));
+    Ok(model)
+}
+
+/// Create a model for `provider_config` with the rest of `config`'s settings,
+/// e.g. for a consensus sample or verification pass on another provider
+pub(crate) async fn create_model_for_provider(
+    provider_config: &ProviderConfig,
+    config: &ExtractConfig,
+    examples: Option<&[ExampleData]>,
+) -> LangExtractResult<Box<dyn BaseLanguageModel>> {
+    let mut provider_specific = config.clone();
+    provider_specific.language_model_params.insert(
+        "provider_config".to_string(),
+        serde_json::to_value(provider_config)?,
+    );
+    create_model(&provider_specific, examples).await
+}
+
+/// Create a single provider with schema and fence settings applied
+fn build_provider(
+    provider_config: ProviderConfig,
+    config: &ExtractConfig,
+    examples: Option<&[ExampleData]>,
+) -> LangExtractResult<Box<dyn BaseLanguageModel>> {
+    Ok(Box::new(build_universal_provider(provider_config, config, examples)?))
+}
+
+/// Create the concrete provider behind [`build_provider`], for callers that
+/// need provider-specific request building
+pub(crate) fn build_universal_provider(
+    provider_config: ProviderConfig,
+    config: &ExtractConfig,
+    examples: Option<&[ExampleData]>,
+) -> LangExtractResult<UniversalProvider> {
+    let mut provider = create_provider(provider_config)?;
+
+    // Constrain output to the configured classes, or those in the examples
+    if config.use_schema_constraints {
+        let classes = match (&config.extraction_classes, examples) {
+            (Some(classes), _) => classes.clone(),
+            (None, Some(example_data)) if !example_data.is_empty() => ExtractionClass::from_examples(example_data),
+            _ => Vec::new(),
+        };
+        if !classes.is_empty() {
+            if let Some(schema) = provider.schema_for_classes(&classes)? {
+                provider.apply_schema(Some(schema));
             }
         }
     }
-    
+
     // Set fence output preference
     provider.set_fence_output(config.fence_output);
-    
-    Ok(Box::new(provider))
+
+    Ok(provider)
 }
 
 /// Create provider configuration from ExtractConfig
-fn create_provider_config(config: &ExtractConfig) -> LangExtractResult<ProviderConfig> {
+pub(crate) fn create_provider_config(config: &ExtractConfig) -> LangExtractResult<ProviderConfig> {
     // Check if provider configuration is already specified in language_model_params
     if let Some(provider_config_value) = config.language_model_params.get("provider_config") {
         if let Ok(provider_config) = serde_json::from_value::<ProviderConfig>(provider_config_value.clone()) {
@@ -97,4 +186,49 @@ mod tests {
         assert_eq!(result_config.model, "mistral");
         assert_eq!(result_config.base_url, "http://localhost:11434");
     }
+
+    #[tokio::test]
+    async fn test_replay_fixture_needs_no_credentials() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("fixture.json");
+        crate::providers::replay::Fixture::default().save(&path).unwrap();
+
+        let provider_config = ProviderConfig::openai("gpt-4o", None).with_replay(&path);
+        let config = crate::test_support::with_provider(ExtractConfig::default(), &provider_config);
+
+        let model = create_model(&config, None).await.unwrap();
+        assert!(model.infer(&["prompt".to_string()], &Default::default()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_fallback_chain_records_answering_provider() {
+        use crate::annotation::ANSWERED_BY_ATTRIBUTE;
+        use crate::providers::RetryPolicy;
+        use crate::test_support::{ollama, person_examples, with_provider};
+        use wiremock::matchers::{method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let primary = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/api/generate"))
+            .respond_with(ResponseTemplate::new(503))
+            .expect(1)
+            .mount(&primary)
+            .await;
+        let backup = ollama().await;
+
+        let provider_config = ProviderConfig::ollama("mistral", Some(primary.uri()))
+            .with_retry_policy(RetryPolicy::none())
+            .with_fallback(ProviderConfig::ollama("llama3", Some(backup.uri())));
+        let config = with_provider(ExtractConfig::default(), &provider_config);
+
+        let result = crate::extract("Ada Lovelace wrote notes.", None, &person_examples(), config).await.unwrap();
+
+        let extractions = result.extractions.unwrap();
+        assert_eq!(extractions[0].extraction_text, "Ada Lovelace");
+        assert_eq!(
+            extractions[0].attributes.as_ref().unwrap()[ANSWERED_BY_ATTRIBUTE],
+            "ollama/llama3"
+        );
+    }
 }
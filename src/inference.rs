@@ -3,11 +3,20 @@
 //! This module provides the core abstraction for language model inference,
 //! including the base trait that all providers must implement.
 
-use crate::{data::FormatType, exceptions::LangExtractResult, schema::BaseSchema};
+use crate::{
+    data::FormatType,
+    exceptions::LangExtractResult,
+    schema::{BaseSchema, ExtractionClass},
+    usage::TokenUsage,
+};
 use async_trait::async_trait;
+use futures::stream::BoxStream;
 use serde::{Deserialize, Serialize};
 use std::fmt;
 
+/// Stream of text deltas produced by [`BaseLanguageModel::infer_stream`]
+pub type TextStream = BoxStream<'static, LangExtractResult<String>>;
+
 /// A scored output from a language model
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ScoredOutput {
@@ -15,6 +24,12 @@ pub struct ScoredOutput {
     pub score: Option<f32>,
     /// The generated text output
     pub output: Option<String>,
+    /// Provider that produced this output, when a model chooses between several
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provider: Option<String>,
+    /// Token counts reported by the provider for this call
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usage: Option<TokenUsage>,
 }
 
 impl ScoredOutput {
@@ -23,6 +38,8 @@ impl ScoredOutput {
         Self {
             output: Some(output),
             score,
+            provider: None,
+            usage: None,
         }
     }
 
@@ -31,9 +48,23 @@ impl ScoredOutput {
         Self {
             output: Some(output),
             score: None,
+            provider: None,
+            usage: None,
         }
     }
 
+    /// Record which provider produced this output
+    pub fn with_provider(mut self, provider: impl Into<String>) -> Self {
+        self.provider = Some(provider.into());
+        self
+    }
+
+    /// Record the token counts for the call that produced this output
+    pub fn with_usage(mut self, usage: TokenUsage) -> Self {
+        self.usage = Some(usage);
+        self
+    }
+
     /// Get the output text, returning empty string if None
     pub fn text(&self) -> &str {
         self.output.as_deref().unwrap_or("")
@@ -77,11 +108,26 @@ pub trait BaseLanguageModel: Send + Sync {
         None
     }
 
+    /// Build the schema to constrain output to the given extraction classes
+    ///
+    /// Defaults to [`get_schema_class`](Self::get_schema_class), which ignores
+    /// the classes.
+    fn schema_for_classes(&self, classes: &[ExtractionClass]) -> LangExtractResult<Option<Box<dyn BaseSchema>>> {
+        let _ = classes;
+        Ok(self.get_schema_class())
+    }
+
     /// Apply a schema instance to this provider
     fn apply_schema(&mut self, _schema: Option<Box<dyn BaseSchema>>) {
         // Default implementation does nothing
     }
 
+    /// Provider configuration of the schema passed to
+    /// [`apply_schema`](Self::apply_schema), if one is in effect
+    fn applied_schema_config(&self) -> Option<std::collections::HashMap<String, serde_json::Value>> {
+        None
+    }
+
     /// Set explicit fence output preference
     fn set_fence_output(&mut self, _fence_output: Option<bool>) {
         // Default implementation does nothing
@@ -118,6 +164,25 @@ pub trait BaseLanguageModel: Send + Sync {
         Ok(results.into_iter().next().unwrap_or_default())
     }
 
+    /// Whether [`infer_stream`](Self::infer_stream) yields output incrementally
+    fn supports_streaming(&self) -> bool {
+        false
+    }
+
+    /// Stream the completion for a single prompt as text deltas
+    ///
+    /// The default implementation waits for [`infer_single`](Self::infer_single)
+    /// and yields the first output as one delta.
+    async fn infer_stream(
+        &self,
+        prompt: &str,
+        kwargs: &std::collections::HashMap<String, serde_json::Value>,
+    ) -> LangExtractResult<TextStream> {
+        let outputs = self.infer_single(prompt, kwargs).await?;
+        let text = outputs.first().map(|o| o.text().to_string()).unwrap_or_default();
+        Ok(Box::pin(futures::stream::once(async move { Ok(text) })))
+    }
+
     /// Parse model output as JSON or YAML
     ///
     /// This expects raw JSON/YAML without code fences.
@@ -173,6 +238,53 @@ pub trait BaseLanguageModel: Send + Sync {
     }
 }
 
+/// A handle on a model shared by several annotators, e.g. consensus samples
+/// or a verification pass
+pub(crate) struct SharedModel(pub(crate) std::sync::Arc<dyn BaseLanguageModel>);
+
+#[async_trait]
+impl BaseLanguageModel for SharedModel {
+    fn applied_schema_config(&self) -> Option<std::collections::HashMap<String, serde_json::Value>> {
+        self.0.applied_schema_config()
+    }
+
+    fn requires_fence_output(&self) -> bool {
+        self.0.requires_fence_output()
+    }
+
+    async fn infer(
+        &self,
+        batch_prompts: &[String],
+        kwargs: &std::collections::HashMap<String, serde_json::Value>,
+    ) -> LangExtractResult<Vec<Vec<ScoredOutput>>> {
+        self.0.infer(batch_prompts, kwargs).await
+    }
+
+    fn supports_streaming(&self) -> bool {
+        self.0.supports_streaming()
+    }
+
+    async fn infer_stream(
+        &self,
+        prompt: &str,
+        kwargs: &std::collections::HashMap<String, serde_json::Value>,
+    ) -> LangExtractResult<TextStream> {
+        self.0.infer_stream(prompt, kwargs).await
+    }
+
+    fn format_type(&self) -> FormatType {
+        self.0.format_type()
+    }
+
+    fn model_id(&self) -> &str {
+        self.0.model_id()
+    }
+
+    fn provider_name(&self) -> &str {
+        self.0.provider_name()
+    }
+}
+
 /// Error type for inference operations that don't produce any outputs
 #[derive(Debug, thiserror::Error)]
 #[error("No scored outputs available from the language model: {message}")]
@@ -1,7 +1,11 @@
 //! I/O utilities for loading text from various sources.
 
+use crate::data::Document;
 use crate::exceptions::{LangExtractError, LangExtractResult};
+use globset::{GlobBuilder, GlobMatcher};
 use regex::Regex;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 
 /// Check if a string is a URL (starts with http:// or https://)
 pub fn is_url(text: &str) -> bool {
@@ -97,6 +101,193 @@ pub fn detect_content_type(content: &str) -> ContentType {
     ContentType::PlainText
 }
 
+/// How a file is split into documents
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputFormat {
+    /// The whole file is one document
+    Text,
+    /// One JSON object per line
+    Jsonl,
+    /// One document per row, with a header row
+    Csv,
+}
+
+impl InputFormat {
+    /// Guess the format from a file extension; anything unknown is text
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|e| e.to_str()).map(str::to_ascii_lowercase).as_deref() {
+            Some("jsonl") | Some("ndjson") => Self::Jsonl,
+            Some("csv") => Self::Csv,
+            _ => Self::Text,
+        }
+    }
+}
+
+/// Which fields of a JSONL object or CSV row hold the text and the ID
+///
+/// All other fields become document metadata. Rows without an ID are keyed
+/// by `<source>:<n>`, the line number in JSONL or the record number in CSV
+/// (the header is record 1).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecordFields {
+    /// Field holding the document text
+    pub text_field: String,
+    /// Field holding the document ID
+    pub id_field: String,
+}
+
+impl Default for RecordFields {
+    fn default() -> Self {
+        Self {
+            text_field: "text".to_string(),
+            id_field: "id".to_string(),
+        }
+    }
+}
+
+/// Whether an input names a glob pattern rather than a path
+pub fn is_glob(input: &str) -> bool {
+    input.contains(['*', '?', '['])
+}
+
+/// Expand a directory or glob pattern into the files it names, sorted
+///
+/// Directories are walked recursively, skipping hidden entries. Globs
+/// support `*` and `?` within a path component, `[...]` character classes
+/// and `**` for any number of directories.
+pub fn expand_input_paths(input: &str) -> LangExtractResult<Vec<PathBuf>> {
+    let (root, pattern) = if is_glob(input) {
+        let root: PathBuf = Path::new(input)
+            .components()
+            .take_while(|c| !is_glob(&c.as_os_str().to_string_lossy()))
+            .collect();
+        (root, Some(glob_matcher(input)?))
+    } else {
+        (PathBuf::from(input), None)
+    };
+    let walk_root = if root.as_os_str().is_empty() { Path::new(".") } else { root.as_path() };
+
+    let mut paths = Vec::new();
+    let entries = walkdir::WalkDir::new(walk_root)
+        .into_iter()
+        .filter_entry(|e| e.depth() == 0 || !e.file_name().to_string_lossy().starts_with('.'));
+    for entry in entries {
+        let entry = entry.map_err(|e| LangExtractError::invalid_input(format!("Cannot read {}: {}", input, e)))?;
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        // Match against the path as the pattern spells it, without a "./" prefix
+        let path = match entry.path().strip_prefix(".") {
+            Ok(relative) if root.as_os_str().is_empty() => relative,
+            _ => entry.path(),
+        };
+        if pattern.as_ref().is_none_or(|glob| glob.is_match(path)) {
+            paths.push(path.to_path_buf());
+        }
+    }
+    paths.sort();
+    Ok(paths)
+}
+
+/// Compile a glob pattern; wildcards other than `**` stop at `/`
+fn glob_matcher(pattern: &str) -> LangExtractResult<GlobMatcher> {
+    GlobBuilder::new(pattern)
+        .literal_separator(true)
+        .build()
+        .map(|glob| glob.compile_matcher())
+        .map_err(|e| LangExtractError::invalid_input(format!("Invalid glob pattern '{}': {}", pattern, e)))
+}
+
+/// Read the documents in a file: the whole file for text, one per line or
+/// row for JSONL and CSV
+///
+/// Text documents are keyed by their path.
+pub fn load_documents(path: &Path, format: InputFormat, fields: &RecordFields) -> LangExtractResult<Vec<Document>> {
+    let content = std::fs::read_to_string(path)?;
+    let source = path.display().to_string();
+    match format {
+        InputFormat::Text => {
+            let mut document = Document::new(content);
+            document.set_document_id(source);
+            Ok(vec![document])
+        }
+        InputFormat::Jsonl => documents_from_jsonl(&content, &source, fields),
+        InputFormat::Csv => documents_from_csv(&content, &source, fields),
+    }
+}
+
+/// Parse JSONL content into documents
+pub fn documents_from_jsonl(content: &str, source: &str, fields: &RecordFields) -> LangExtractResult<Vec<Document>> {
+    content
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| {
+            let record: HashMap<String, serde_json::Value> = serde_json::from_str(line).map_err(|e| {
+                LangExtractError::invalid_input(format!("{}:{}: not a JSON object: {}", source, i + 1, e))
+            })?;
+            record_document(record, source, i + 1, fields)
+        })
+        .collect()
+}
+
+/// Parse CSV content with a header row into documents
+///
+/// Every row must have as many cells as the header.
+pub fn documents_from_csv(content: &str, source: &str, fields: &RecordFields) -> LangExtractResult<Vec<Document>> {
+    let invalid = |e: csv::Error| LangExtractError::invalid_input(format!("{}: invalid CSV: {}", source, e));
+    let mut reader = csv::Reader::from_reader(content.as_bytes());
+    let header = reader.headers().map_err(invalid)?.clone();
+    if header.is_empty() {
+        return Err(LangExtractError::invalid_input(format!("{}: CSV file has no header row", source)));
+    }
+
+    let mut documents = Vec::new();
+    for row in reader.records() {
+        let row = row.map_err(invalid)?;
+        if row.iter().all(str::is_empty) {
+            continue;
+        }
+        let line = row.position().map_or(0, |p| p.line() as usize);
+        let record = header
+            .iter()
+            .zip(row.iter())
+            .map(|(name, cell)| (name.to_string(), serde_json::Value::String(cell.to_string())))
+            .collect();
+        documents.push(record_document(record, source, line, fields)?);
+    }
+    Ok(documents)
+}
+
+fn record_document(
+    mut record: HashMap<String, serde_json::Value>,
+    source: &str,
+    line: usize,
+    fields: &RecordFields,
+) -> LangExtractResult<Document> {
+    let text = match record.remove(&fields.text_field) {
+        Some(serde_json::Value::String(text)) => text,
+        Some(other) => other.to_string(),
+        None => {
+            return Err(LangExtractError::invalid_input(format!(
+                "{}:{}: no '{}' field",
+                source, line, fields.text_field
+            )))
+        }
+    };
+    let id = match record.remove(&fields.id_field) {
+        Some(serde_json::Value::String(id)) if !id.is_empty() => id,
+        Some(serde_json::Value::Null) | None => format!("{}:{}", source, line),
+        Some(serde_json::Value::String(_)) => format!("{}:{}", source, line),
+        Some(other) => other.to_string(),
+    };
+
+    let mut document = Document::new(text);
+    document.set_document_id(id);
+    document.metadata = record;
+    Ok(document)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -173,4 +364,69 @@ mod tests {
             ContentType::PlainText
         );
     }
+
+    #[test]
+    fn test_documents_from_jsonl() {
+        let content = "{\"id\": \"a\", \"text\": \"Ada wrote notes.\", \"year\": 1843}\n\n{\"text\": \"Grace wrote compilers.\"}\n";
+        let documents = documents_from_jsonl(content, "notes.jsonl", &RecordFields::default()).unwrap();
+
+        assert_eq!(documents.len(), 2);
+        assert_eq!(documents[0].document_id.as_deref(), Some("a"));
+        assert_eq!(documents[0].text, "Ada wrote notes.");
+        assert_eq!(documents[0].metadata["year"], serde_json::json!(1843));
+        assert_eq!(documents[1].document_id.as_deref(), Some("notes.jsonl:3"));
+
+        assert!(documents_from_jsonl("{\"body\": \"x\"}", "bad.jsonl", &RecordFields::default()).is_err());
+    }
+
+    #[test]
+    fn test_documents_from_csv() {
+        let content = "row,body,author\r\n7,\"Ada wrote \"\"notes\"\",\nthen more.\",Ada\r\n,Grace wrote compilers.,Grace\n";
+        let fields = RecordFields {
+            text_field: "body".to_string(),
+            id_field: "row".to_string(),
+        };
+        let documents = documents_from_csv(content, "notes.csv", &fields).unwrap();
+
+        assert_eq!(documents.len(), 2);
+        assert_eq!(documents[0].document_id.as_deref(), Some("7"));
+        assert_eq!(documents[0].text, "Ada wrote \"notes\",\nthen more.");
+        assert_eq!(documents[0].metadata["author"], serde_json::json!("Ada"));
+        assert_eq!(documents[1].document_id.as_deref(), Some("notes.csv:3"));
+
+        let err = documents_from_csv("id,text\n1,short\n2,extra,cell\n", "ragged.csv", &fields).unwrap_err();
+        assert!(err.to_string().contains("ragged.csv"));
+        assert!(documents_from_csv("", "empty.csv", &fields).is_err());
+    }
+
+    #[test]
+    fn test_glob_matcher() {
+        let re = glob_matcher("docs/**/*.txt").unwrap();
+        assert!(re.is_match("docs/a.txt"));
+        assert!(re.is_match("docs/x/y/a.txt"));
+        assert!(!re.is_match("docs/a.md"));
+        assert!(!re.is_match("other/a.txt"));
+
+        let re = glob_matcher("notes_?.[!c]sv").unwrap();
+        assert!(re.is_match("notes_1.tsv"));
+        assert!(!re.is_match("notes_1.csv"));
+        assert!(!re.is_match("notes_12.tsv"));
+    }
+
+    #[test]
+    fn test_expand_input_paths() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("sub")).unwrap();
+        std::fs::create_dir_all(dir.path().join(".hidden")).unwrap();
+        for name in ["b.txt", "a.md", "sub/c.txt", ".hidden/d.txt"] {
+            std::fs::write(dir.path().join(name), "text").unwrap();
+        }
+        let root = dir.path().display().to_string();
+
+        let all = expand_input_paths(&root).unwrap();
+        assert_eq!(all, vec![dir.path().join("a.md"), dir.path().join("b.txt"), dir.path().join("sub/c.txt")]);
+
+        let txt = expand_input_paths(&format!("{}/**/*.txt", root)).unwrap();
+        assert_eq!(txt, vec![dir.path().join("b.txt"), dir.path().join("sub/c.txt")]);
+    }
 }
@@ -43,6 +43,9 @@
 //! }
 //! ```
 
+// Lets `#[derive(Extractable)]` refer to this crate as `::langextract_rust` from inside it
+extern crate self as langextract_rust;
+
 // Core modules
 pub mod config;
 pub mod data;
@@ -52,25 +55,41 @@ pub mod schema;
 // Processing modules
 pub mod alignment;
 pub mod annotation;
+pub mod cancellation;
+pub mod checkpoint;
 pub mod chunking;
+pub mod corpus;
 pub mod inference;
 pub mod multipass;
 pub mod tokenizer;
+pub mod typed;
+pub mod verification;
 
 // Provider modules
 pub mod providers;
 pub mod factory;
 
 // Utility modules
+pub mod diff;
+pub mod dry_run;
+pub mod evaluation;
 pub mod io;
 pub mod logging;
+#[cfg(feature = "mcp")]
+pub mod mcp;
 pub mod pipeline;
 pub mod progress;
 pub mod prompting;
 pub mod resolver;
+#[cfg(feature = "server")]
+pub mod server;
 pub mod templates;
+pub mod usage;
 pub mod visualization;
 
+#[cfg(test)]
+pub(crate) mod test_support;
+
 // Re-export key types for convenience
 pub use config::{
     LangExtractConfig, ProcessingConfig, ValidationConfig as NewValidationConfig, 
@@ -78,20 +97,33 @@ pub use config::{
     VisualizationConfig, InferenceConfig as NewInferenceConfig, ProgressConfig, 
     ChunkingStrategy, ExportFormat as NewExportFormat
 };
+pub use cancellation::CancellationToken;
+pub use checkpoint::CheckpointConfig;
+pub use corpus::{extract_documents, DocumentError};
+pub use dry_run::{dry_run, DryRunReport, PromptPreview};
 pub use data::{
-    AlignmentStatus, AnnotatedDocument, CharInterval, Document, ExampleData, Extraction,
-    FormatType,
+    AlignmentStatus, AnnotatedDocument, CharInterval, ChunkFailure, CoverageReport, Document,
+    ExampleData, Extraction, FormatType,
 };
+pub use evaluation::{evaluate, EvaluationReport, MatchMode};
 pub use exceptions::{LangExtractError, LangExtractResult};
 pub use inference::{BaseLanguageModel, ScoredOutput};
+pub use multipass::ConsensusConfig;
+pub use verification::{RejectAction, Verdict, VerificationConfig, VerificationSummary, Verifier};
 pub use logging::{ProgressHandler, ProgressEvent, ConsoleProgressHandler, SilentProgressHandler, LogProgressHandler};
-pub use providers::{ProviderConfig, ProviderType, UniversalProvider};
-pub use resolver::{ValidationConfig, ValidationResult, ValidationError, ValidationWarning, CoercionSummary, CoercionDetail, CoercionTargetType};
-pub use visualization::{ExportFormat, ExportConfig, export_document};
+pub use providers::{CustomHttpMapping, ProviderConfig, ProviderType, RecordReplayModel, UniversalProvider};
+pub use resolver::{IncrementalJsonParser, ValidationConfig, ValidationResult, ValidationError, ValidationWarning, CoercionSummary, CoercionDetail, CoercionTargetType};
+pub use visualization::{ExportFormat, ExportConfig, export_diff_html, export_document};
 pub use pipeline::{PipelineConfig, PipelineStep, PipelineResult, PipelineExecutor};
+pub use schema::{AttributeSpec, AttributeType, ExtractionClass};
+pub use typed::{extract_typed, AttributeValue, Extractable, Grounded};
+pub use usage::{PriceTable, TokenUsage, UsageBudget, UsageSummary, UsageTracker};
+#[cfg(feature = "derive")]
+pub use langextract_derive::Extractable;
 
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::time::Duration;
 
 /// Configuration for the extract function
 #[derive(Clone, Serialize, Deserialize)]
@@ -132,6 +164,49 @@ pub struct ExtractConfig {
     pub multipass_min_extractions: usize,
     /// Quality threshold for keeping extractions (0.0 to 1.0)
     pub multipass_quality_threshold: f32,
+    /// Sample each chunk several times and keep the extractions enough
+    /// samples agree on, instead of running refinement passes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub consensus: Option<multipass::ConsensusConfig>,
+    /// Check each extraction against its source text with the model after
+    /// results are aggregated
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub verification: Option<verification::VerificationConfig>,
+    /// Stream model output and report each extraction as soon as it is parsed
+    #[serde(default)]
+    pub streaming: bool,
+    /// Extraction classes and attribute types to constrain output to; derived
+    /// from the examples when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub extraction_classes: Option<Vec<schema::ExtractionClass>>,
+    /// Token and cost limits that abort the run once exceeded
+    #[serde(default)]
+    pub budget: usage::UsageBudget,
+    /// Per-model prices for the usage summary and cost budget
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub price_table: Option<usage::PriceTable>,
+    /// Keep failed chunks in the result with a coverage report instead of
+    /// dropping them or aborting the run
+    #[serde(default)]
+    pub allow_partial: bool,
+    /// Persist chunk results as they complete so a failed run can resume
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub checkpoint: Option<checkpoint::CheckpointConfig>,
+    /// Time limit for each model call in milliseconds; a call that runs
+    /// longer fails its chunk
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_timeout_ms: Option<u64>,
+    /// Time limit for the whole run in milliseconds; chunks unfinished when it
+    /// passes are dropped and the finished ones returned
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout_ms: Option<u64>,
+    /// Token that stops the run when cancelled, returning what finished (not
+    /// serialized)
+    #[serde(skip)]
+    pub cancellation: Option<CancellationToken>,
+    /// Usage tracker shared with other runs, e.g. pipeline steps (not serialized)
+    #[serde(skip)]
+    pub usage_tracker: Option<std::sync::Arc<usage::UsageTracker>>,
     /// Progress handler for reporting extraction progress (not serialized)
     #[serde(skip)]
     pub progress_handler: Option<std::sync::Arc<dyn ProgressHandler>>,
@@ -158,6 +233,18 @@ impl Default for ExtractConfig {
             multipass_max_passes: 2,
             multipass_min_extractions: 1,
             multipass_quality_threshold: 0.3,
+            consensus: None,
+            verification: None,
+            streaming: false,
+            extraction_classes: None,
+            budget: usage::UsageBudget::default(),
+            price_table: None,
+            allow_partial: false,
+            checkpoint: None,
+            request_timeout_ms: None,
+            timeout_ms: None,
+            cancellation: None,
+            usage_tracker: None,
             progress_handler: None,
         }
     }
@@ -184,6 +271,17 @@ impl std::fmt::Debug for ExtractConfig {
             .field("multipass_max_passes", &self.multipass_max_passes)
             .field("multipass_min_extractions", &self.multipass_min_extractions)
             .field("multipass_quality_threshold", &self.multipass_quality_threshold)
+            .field("consensus", &self.consensus)
+            .field("verification", &self.verification)
+            .field("streaming", &self.streaming)
+            .field("extraction_classes", &self.extraction_classes)
+            .field("budget", &self.budget)
+            .field("price_table", &self.price_table)
+            .field("allow_partial", &self.allow_partial)
+            .field("checkpoint", &self.checkpoint)
+            .field("request_timeout_ms", &self.request_timeout_ms)
+            .field("timeout_ms", &self.timeout_ms)
+            .field("cancellation", &self.cancellation)
             .field("progress_handler", &"<ProgressHandler>")
             .finish()
     }
@@ -196,6 +294,56 @@ impl ExtractConfig {
         self
     }
 
+    /// Record token usage in a tracker shared with other runs
+    pub fn with_usage_tracker(mut self, tracker: std::sync::Arc<usage::UsageTracker>) -> Self {
+        self.usage_tracker = Some(tracker);
+        self
+    }
+
+    /// Return partial results with a coverage report when chunks fail
+    pub fn with_partial_results(mut self, allow_partial: bool) -> Self {
+        self.allow_partial = allow_partial;
+        self
+    }
+
+    /// Checkpoint chunk results under `checkpoint.dir`
+    pub fn with_checkpoint(mut self, checkpoint: checkpoint::CheckpointConfig) -> Self {
+        self.checkpoint = Some(checkpoint);
+        self
+    }
+
+    /// Vote across repeated samples of each chunk, keeping what enough of
+    /// them agree on
+    pub fn with_consensus(mut self, consensus: multipass::ConsensusConfig) -> Self {
+        self.consensus = Some(consensus);
+        self
+    }
+
+    /// Check each extraction against its source text once results are
+    /// aggregated
+    pub fn with_verification(mut self, verification: verification::VerificationConfig) -> Self {
+        self.verification = Some(verification);
+        self
+    }
+
+    /// Stop the run when `token` is cancelled
+    pub fn with_cancellation(mut self, token: CancellationToken) -> Self {
+        self.cancellation = Some(token);
+        self
+    }
+
+    /// Fail any model call that takes longer than `timeout`
+    pub fn with_request_timeout(mut self, timeout: std::time::Duration) -> Self {
+        self.request_timeout_ms = Some(timeout.as_millis() as u64);
+        self
+    }
+
+    /// Stop the run after `timeout`, returning what finished
+    pub fn with_timeout(mut self, timeout: std::time::Duration) -> Self {
+        self.timeout_ms = Some(timeout.as_millis() as u64);
+        self
+    }
+
     /// Enable console progress output with default settings
     pub fn with_console_progress(mut self) -> Self {
         self.progress_handler = Some(std::sync::Arc::new(ConsoleProgressHandler::new()));
@@ -277,17 +425,7 @@ pub async fn extract(
     dotenvy::dotenv().ok();
 
     // Initialize progress handler
-    if let Some(handler) = &config.progress_handler {
-        logging::init_progress_handler(handler.clone());
-    } else {
-        // Default to console handler if debug is enabled, otherwise silent
-        let default_handler: std::sync::Arc<dyn ProgressHandler> = if config.debug {
-            std::sync::Arc::new(ConsoleProgressHandler::new())
-        } else {
-            std::sync::Arc::new(SilentProgressHandler)
-        };
-        logging::init_progress_handler(default_handler);
-    }
+    init_progress_handler(&config);
 
     // Handle URL input
     let text = if io::is_url(text_or_documents) {
@@ -303,43 +441,191 @@ pub async fn extract(
     // Create language model
     let language_model = factory::create_model(&config, Some(&prompt_template.examples)).await?;
 
-    // Create resolver
-    let resolver = resolver::Resolver::new(&config, language_model.requires_fence_output())?;
-
-    // Create annotator with config-driven inference parameters
-    let annotator = annotation::Annotator::with_config(
-        language_model,
-        prompt_template,
-        config.temperature,
-        config.language_model_params.get("max_output_tokens")
-            .and_then(|v| v.as_u64())
-            .map(|v| v as usize),
+    annotate_with_model(&text, prompt_template, language_model, &config).await
+}
+
+/// Install the configured progress handler, or a default one
+pub(crate) fn init_progress_handler(config: &ExtractConfig) {
+    if let Some(handler) = &config.progress_handler {
+        logging::init_progress_handler(handler.clone());
+    } else {
+        // Default to console handler if debug is enabled, otherwise silent
+        let default_handler: std::sync::Arc<dyn ProgressHandler> = if config.debug {
+            std::sync::Arc::new(ConsoleProgressHandler::new())
+        } else {
+            std::sync::Arc::new(SilentProgressHandler)
+        };
+        logging::init_progress_handler(default_handler);
+    }
+}
+
+/// Annotate text with an already-built model: resolve, align and aggregate
+/// its responses as configured
+pub(crate) async fn annotate_with_model(
+    text: &str,
+    prompt_template: prompting::PromptTemplateStructured,
+    language_model: Box<dyn BaseLanguageModel>,
+    config: &ExtractConfig,
+) -> LangExtractResult<AnnotatedDocument> {
+    let resolver = resolver::Resolver::new(config, language_model.requires_fence_output())?;
+    let verifier_model = create_verifier_model(config, &prompt_template.examples).await?;
+    annotate_with_resolver(text, prompt_template, language_model, verifier_model, &resolver, config).await
+}
+
+/// Build the model for the verification pass, or `None` when verification
+/// is off or should share the run's model
+///
+/// The verifier is built without the extraction schema so the provider can
+/// return verdicts. The run's model is only shared when the caller supplied
+/// it and there is no provider config to build another from.
+pub(crate) async fn create_verifier_model(
+    config: &ExtractConfig,
+    examples: &[ExampleData],
+) -> LangExtractResult<Option<Box<dyn BaseLanguageModel>>> {
+    let Some(verification) = &config.verification else {
+        return Ok(None);
+    };
+    let mut unconstrained = config.clone();
+    unconstrained.use_schema_constraints = false;
+    match &verification.model {
+        Some(provider_config) => {
+            factory::create_model_for_provider(provider_config, &unconstrained, Some(examples)).await.map(Some)
+        }
+        None if factory::create_provider_config(config).is_ok() => {
+            factory::create_model(&unconstrained, Some(examples)).await.map(Some)
+        }
+        None => Ok(None),
+    }
+}
+
+/// [`annotate_with_model`] with a resolver shared across calls
+///
+/// `verifier_model` comes from [`create_verifier_model`]; when it is `None`
+/// and verification is on, the verifier shares `language_model`.
+pub(crate) async fn annotate_with_resolver(
+    text: &str,
+    prompt_template: prompting::PromptTemplateStructured,
+    language_model: Box<dyn BaseLanguageModel>,
+    verifier_model: Option<Box<dyn BaseLanguageModel>>,
+    resolver: &resolver::Resolver,
+    config: &ExtractConfig,
+) -> LangExtractResult<AnnotatedDocument> {
+    // Stop on the caller's token or once the run's timeout passes
+    let stop = cancellation::StopSignal::new(
+        config.cancellation.as_ref(),
+        config.timeout_ms.map(Duration::from_millis),
     );
+    let _stop_guard = stop.guard();
+
+    // Share the caller's usage tracker, or start one for this run
+    let usage_tracker = config.usage_tracker.clone().unwrap_or_else(|| {
+        std::sync::Arc::new(usage::UsageTracker::new(
+            config.price_table.clone().unwrap_or_default(),
+            config.budget,
+        ))
+    });
+
+    // The verification pass uses its own model when one was built, otherwise
+    // a handle on the run's
+    let (language_model, verifier) = match &config.verification {
+        Some(verification) => {
+            let (language_model, verifier_model): (Box<dyn BaseLanguageModel>, Box<dyn BaseLanguageModel>) =
+                match verifier_model {
+                    Some(verifier_model) => (language_model, verifier_model),
+                    None => {
+                        let shared: std::sync::Arc<dyn BaseLanguageModel> = std::sync::Arc::from(language_model);
+                        (Box::new(inference::SharedModel(shared.clone())), Box::new(inference::SharedModel(shared)))
+                    }
+                };
+            let classes = match &config.extraction_classes {
+                Some(classes) => classes.iter().map(|class| class.name.clone()).collect(),
+                None => schema::ExtractionClass::from_examples(&prompt_template.examples)
+                    .into_iter()
+                    .map(|class| class.name)
+                    .collect(),
+            };
+            let verifier = verification::Verifier::new(verifier_model, verification.clone(), classes)
+                .with_usage_tracker(usage_tracker.clone())
+                .with_stop_signal(stop.clone(), config.request_timeout_ms.map(Duration::from_millis))
+                .with_max_workers(config.max_workers);
+            (language_model, Some(verifier))
+        }
+        None => (language_model, None),
+    };
+
+    // Create annotators with config-driven inference parameters
+    let max_output_tokens = config.language_model_params.get("max_output_tokens")
+        .and_then(|v| v.as_u64())
+        .map(|v| v as usize);
+    let build_annotator = |language_model: Box<dyn BaseLanguageModel>,
+                           prompt_template: prompting::PromptTemplateStructured,
+                           temperature: f32| {
+        annotation::Annotator::with_config(language_model, prompt_template, temperature, max_output_tokens)
+            .with_streaming(config.streaming)
+            .with_usage_tracker(usage_tracker.clone())
+            .with_partial_results(config.allow_partial)
+            .with_stop_signal(stop.clone(), config.request_timeout_ms.map(Duration::from_millis))
+    };
 
     // Perform annotation - use multi-pass if enabled
-    if config.enable_multipass {
-        // Use multi-pass extraction
-        let multipass_config = multipass::MultiPassConfig {
-            max_passes: config.multipass_max_passes,
-            min_extractions_per_chunk: config.multipass_min_extractions,
-            enable_targeted_reprocessing: true,
-            enable_refinement_passes: true,
-            quality_threshold: config.multipass_quality_threshold,
-            max_reprocess_chunks: 10,
-            temperature_decay: 0.9,
-            max_char_buffer: config.max_char_buffer,
-            batch_length: config.batch_length,
-            max_workers: config.max_workers,
-        };
+    let mut result = if let Some(consensus) = &config.consensus {
+        consensus.validate()?;
+        if config.checkpoint.is_some() {
+            return Err(LangExtractError::configuration(
+                "Consensus extraction can't be checkpointed: samples are not saved per chunk, so a resumed run would start over",
+            ));
+        }
+
+        // One annotator per sample, each with its configured model (or a
+        // handle on the run's) and temperature
+        let shared: std::sync::Arc<dyn BaseLanguageModel> = std::sync::Arc::from(language_model);
+        let mut annotators = Vec::with_capacity(consensus.samples);
+        for sample in 0..consensus.samples {
+            let model = match consensus.model_for(sample) {
+                Some(provider_config) => {
+                    factory::create_model_for_provider(provider_config, config, Some(&prompt_template.examples)).await?
+                }
+                None => Box::new(inference::SharedModel(shared.clone())),
+            };
+            let temperature = consensus.temperature_for(sample).unwrap_or(config.temperature);
+            annotators.push(build_annotator(model, prompt_template.clone(), temperature).with_sample(sample));
+        }
+        let annotator = annotators.remove(0);
 
         let processor = multipass::MultiPassProcessor::new(
-            multipass_config,
+            multipass_config(config),
             annotator,
-            resolver,
+            resolver.clone(),
+        )
+        .with_samplers(annotators);
+
+        let (result, _stats) = processor.extract_multipass(
+            text,
+            config.additional_context.as_deref(),
+            config.debug,
+        ).await?;
+
+        if config.debug {
+            log::info!("Consensus extraction completed with {} total extractions",
+                result.extraction_count());
+        }
+
+        result
+    } else if config.enable_multipass {
+        let mut annotator = build_annotator(language_model, prompt_template, config.temperature);
+        if let Some(checkpoint) = &config.checkpoint {
+            annotator = annotator.with_checkpoints(checkpoint.clone());
+        }
+
+        // Use multi-pass extraction
+        let processor = multipass::MultiPassProcessor::new(
+            multipass_config(config),
+            annotator,
+            resolver.clone(),
         );
 
         let (result, _stats) = processor.extract_multipass(
-            &text,
+            text,
             config.additional_context.as_deref(),
             config.debug,
         ).await?;
@@ -349,20 +635,54 @@ pub async fn extract(
                 result.extraction_count());
         }
 
-        Ok(result)
+        result
     } else {
+        let mut annotator = build_annotator(language_model, prompt_template, config.temperature);
+        if let Some(checkpoint) = &config.checkpoint {
+            annotator = annotator.with_checkpoints(checkpoint.clone());
+        }
+
         // Use single-pass extraction
         annotator
             .annotate_text(
-                &text,
-                &resolver,
+                text,
+                resolver,
                 config.max_char_buffer,
                 config.batch_length,
                 config.additional_context.as_deref(),
                 config.debug,
                 config.max_workers,
             )
-            .await
+            .await?
+    };
+
+    // Check each extraction against its source once results are aggregated
+    if let Some(verifier) = &verifier {
+        let summary = verifier.verify(&mut result).await?;
+        if config.debug {
+            log::info!("Verification: {} confirmed, {} corrected, {} rejected, {} unverified",
+                summary.confirmed, summary.corrected, summary.rejected, summary.unverified);
+        }
+    }
+
+    result.usage = result.usage.map(|usage| usage.priced(usage_tracker.prices()));
+    Ok(result)
+}
+
+/// Multi-pass settings taken from the extraction config
+fn multipass_config(config: &ExtractConfig) -> multipass::MultiPassConfig {
+    multipass::MultiPassConfig {
+        max_passes: config.multipass_max_passes,
+        min_extractions_per_chunk: config.multipass_min_extractions,
+        enable_targeted_reprocessing: true,
+        enable_refinement_passes: true,
+        quality_threshold: config.multipass_quality_threshold,
+        max_reprocess_chunks: 10,
+        temperature_decay: 0.9,
+        max_char_buffer: config.max_char_buffer,
+        batch_length: config.batch_length,
+        max_workers: config.max_workers,
+        consensus: config.consensus.clone(),
     }
 }
 
@@ -377,6 +697,7 @@ pub fn visualize(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_support::{ollama_config, ollama_with, person_examples};
 
     #[test]
     fn test_extract_config_default() {
@@ -403,4 +724,225 @@ mod tests {
             }
         });
     }
+
+    #[tokio::test]
+    async fn test_streaming_extract_matches_full_response() {
+        use wiremock::matchers::{body_partial_json, method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        let body = [
+            r#"{"response": "{\"person\": \"Ada", "done": false}"#,
+            r#"{"response": " Lovelace\"}", "done": true}"#,
+        ]
+        .join("\n");
+        Mock::given(method("POST"))
+            .and(path("/api/generate"))
+            .and(body_partial_json(serde_json::json!({ "stream": true })))
+            .respond_with(ResponseTemplate::new(200).set_body_string(body))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let config = ExtractConfig {
+            streaming: true,
+            ..ollama_config(&server)
+        };
+        let result = extract("Ada Lovelace wrote notes.", None, &person_examples(), config).await.unwrap();
+
+        let extractions = result.extractions.unwrap();
+        assert_eq!(extractions.len(), 1);
+        assert_eq!(extractions[0].extraction_text, "Ada Lovelace");
+        assert!(extractions[0].char_interval.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_extract_reports_usage_and_enforces_budget() {
+        let server = ollama_with(serde_json::json!({
+            "response": crate::test_support::PERSON_REPLY,
+            "prompt_eval_count": 120,
+            "eval_count": 30
+        }))
+        .await;
+
+        let mut config = ExtractConfig {
+            price_table: Some(usage::PriceTable::new().with_price("mistral", 1.0, 2.0)),
+            ..ollama_config(&server)
+        };
+        let examples = person_examples();
+
+        let result = extract("Ada Lovelace wrote notes.", None, &examples, config.clone()).await.unwrap();
+        let usage = result.usage.unwrap();
+        assert_eq!((usage.prompt_tokens, usage.completion_tokens), (120, 30));
+        assert_eq!((usage.calls, usage.estimated_calls), (1, 0));
+        let cost = usage.cost_usd.unwrap();
+        assert!((cost - 0.00018).abs() < 1e-12);
+
+        config.budget.max_tokens = Some(100);
+        let err = extract("Ada Lovelace wrote notes.", None, &examples, config).await.unwrap_err();
+        assert!(matches!(err, LangExtractError::BudgetExceeded(_)));
+    }
+
+    #[tokio::test]
+    async fn test_resume_sends_only_unfinished_chunks() {
+        use wiremock::matchers::{body_string_contains, method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let text = "Ada Lovelace wrote notes. Charles Babbage built engines. Grace Hopper wrote compilers.";
+        let examples = person_examples();
+        let dir = tempfile::tempdir().unwrap();
+
+        async fn run(text: &str, examples: &[ExampleData], checkpoint: CheckpointConfig, expected_calls: u64) -> AnnotatedDocument {
+            let server = MockServer::start().await;
+            for name in ["Ada Lovelace", "Charles Babbage", "Grace Hopper"] {
+                Mock::given(method("POST"))
+                    .and(path("/api/generate"))
+                    .and(body_string_contains(format!("{} ", name)))
+                    .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                        "response": format!("{{\"person\": \"{}\"}}", name)
+                    })))
+                    .mount(&server)
+                    .await;
+            }
+            let config = ExtractConfig {
+                max_char_buffer: 30,
+                ..ollama_config(&server)
+            }
+            .with_checkpoint(checkpoint);
+
+            let result = extract(text, None, examples, config).await.unwrap();
+            assert_eq!(server.received_requests().await.unwrap().len() as u64, expected_calls);
+            result
+        }
+
+        let first = run(text, &examples, CheckpointConfig::new(dir.path()), 3).await;
+        assert_eq!(first.extraction_count(), 3);
+
+        // Lose one chunk, as if the run had failed before it finished
+        let document_dir = std::fs::read_dir(dir.path()).unwrap().next().unwrap().unwrap().path();
+        std::fs::remove_file(document_dir.join("chunk_000001.json")).unwrap();
+
+        let resumed = run(text, &examples, CheckpointConfig::new(dir.path()).with_resume(true), 1).await;
+        let mut names: Vec<_> = resumed.extractions.unwrap().into_iter().map(|e| e.extraction_text).collect();
+        names.sort();
+        assert_eq!(names, vec!["Ada Lovelace", "Charles Babbage", "Grace Hopper"]);
+        assert_eq!(resumed.usage.unwrap().calls, 1);
+    }
+
+    #[tokio::test]
+    async fn test_partial_results_keep_failed_chunks() {
+        use wiremock::matchers::{body_string_contains, method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        let responses = [
+            ("Ada Lovelace ", ResponseTemplate::new(200).set_body_json(serde_json::json!({ "response": "{\"person\": \"Ada Lovelace\"}" }))),
+            ("Charles Babbage ", ResponseTemplate::new(200).set_body_json(serde_json::json!({ "response": "This is not JSON at all!" }))),
+            ("Grace Hopper ", ResponseTemplate::new(400).set_body_string("bad request")),
+        ];
+        for (needle, response) in responses {
+            Mock::given(method("POST"))
+                .and(path("/api/generate"))
+                .and(body_string_contains(needle))
+                .respond_with(response)
+                .mount(&server)
+                .await;
+        }
+
+        let raw_dir = tempfile::tempdir().unwrap();
+        let mut config = ExtractConfig {
+            max_char_buffer: 30,
+            ..ollama_config(&server)
+        }
+        .with_partial_results(true);
+        config.resolver_params.insert(
+            "raw_outputs_dir".to_string(),
+            serde_json::json!(raw_dir.path().display().to_string()),
+        );
+
+        let text = "Ada Lovelace wrote notes. Charles Babbage built engines. Grace Hopper wrote compilers.";
+        let result = extract(text, None, &person_examples(), config).await.unwrap();
+
+        assert_eq!(result.extraction_count(), 1);
+        let coverage = result.coverage.unwrap();
+        assert_eq!((coverage.total_chunks, coverage.failed_chunks.len()), (3, 2));
+        assert!(coverage.fraction() > 0.0 && coverage.fraction() < 1.0);
+
+        let unparsed = &coverage.failed_chunks[0];
+        assert!(text[unparsed.char_offset..unparsed.char_offset + unparsed.char_length].contains("Charles Babbage"));
+        let raw = std::fs::read_to_string(unparsed.raw_output_file.as_ref().unwrap()).unwrap();
+        assert!(raw.contains("This is not JSON at all!"));
+
+        let rejected = &coverage.failed_chunks[1];
+        assert!(rejected.error.contains("400"));
+        assert!(rejected.raw_output_file.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_cached_consensus_samples_are_independent() {
+        use crate::providers::{CacheConfig, ProviderConfig};
+        use crate::test_support::{ollama, with_provider};
+
+        let dir = tempfile::tempdir().unwrap();
+        let text = "Ada Lovelace wrote notes.";
+        let run = |server: &wiremock::MockServer| {
+            let provider = ProviderConfig::ollama("mistral", Some(server.uri())).with_cache(CacheConfig::new(dir.path()));
+            // One worker, so each sample runs after the last one was cached
+            ExtractConfig { max_workers: 1, ..with_provider(ExtractConfig::default(), &provider) }
+                .with_consensus(multipass::ConsensusConfig::new(3))
+        };
+
+        let server = ollama().await;
+        let result = extract(text, None, &person_examples(), run(&server)).await.unwrap();
+        assert_eq!(server.received_requests().await.unwrap().len(), 3);
+        assert_eq!(result.extraction_count(), 1);
+
+        // Each sample is served its own cached completion on the next run
+        extract(text, None, &person_examples(), run(&server)).await.unwrap();
+        assert_eq!(server.received_requests().await.unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_timeout_returns_finished_chunks() {
+        use wiremock::matchers::{body_string_contains, method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        let ok = |person: &str| {
+            ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "response": format!("{{\"person\": \"{}\"}}", person)
+            }))
+        };
+        Mock::given(method("POST"))
+            .and(path("/api/generate"))
+            .and(body_string_contains("Ada Lovelace "))
+            .respond_with(ok("Ada Lovelace"))
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/api/generate"))
+            .and(body_string_contains("Grace Hopper "))
+            .respond_with(ok("Grace Hopper").set_delay(std::time::Duration::from_secs(30)))
+            .mount(&server)
+            .await;
+
+        let config = ExtractConfig {
+            max_char_buffer: 30,
+            ..ollama_config(&server)
+        }
+        .with_timeout(Duration::from_millis(500));
+
+        let text = "Ada Lovelace wrote notes. Grace Hopper wrote compilers.";
+        let started = std::time::Instant::now();
+        let result = extract(text, None, &person_examples(), config).await.unwrap();
+
+        assert!(started.elapsed() < Duration::from_secs(10));
+        assert_eq!(result.extraction_count(), 1);
+        let coverage = result.coverage.unwrap();
+        assert_eq!((coverage.total_chunks, coverage.failed_chunks.len()), (2, 1));
+        let dropped = &coverage.failed_chunks[0];
+        assert!(text[dropped.char_offset..dropped.char_offset + dropped.char_length].contains("Grace Hopper"));
+        assert!(dropped.error.contains("timed out"));
+    }
 }
+
@@ -65,6 +65,17 @@ pub enum ProgressEvent {
         max_attempts: usize,
         delay_seconds: u64,
     },
+    /// Extraction parsed from a streamed response before the chunk finished
+    ExtractionFound {
+        extraction_class: String,
+        extraction_text: String,
+    },
+    /// Call held back by the client-side rate limiter
+    RateLimitWait {
+        provider: String,
+        model: String,
+        wait_ms: u64,
+    },
     /// Error occurred
     Error {
         operation: String,
@@ -190,6 +201,18 @@ impl ProgressHandler for ConsoleProgressHandler {
                         &format!("{} failed (attempt {}/{}), retrying in {}s", operation, attempt, max_attempts, delay_seconds)));
                 }
             }
+            ProgressEvent::ExtractionFound { extraction_class, extraction_text } => {
+                if self.show_progress {
+                    println!("{}", self.format_message("found",
+                        &format!("{}: {}", extraction_class, extraction_text)));
+                }
+            }
+            ProgressEvent::RateLimitWait { provider, model: _, wait_ms } => {
+                if self.show_progress {
+                    println!("{}", self.format_message("rate-limit",
+                        &format!("{} budget exhausted, waiting {:.1}s", provider, wait_ms as f64 / 1000.0)));
+                }
+            }
             ProgressEvent::Error { operation, error } => {
                 // Always show errors
                 eprintln!("{}", self.format_message("error", &format!("{}: {}", operation, error)));
@@ -260,6 +283,12 @@ impl ProgressHandler for LogProgressHandler {
             ProgressEvent::RetryAttempt { operation, attempt, max_attempts, delay_seconds } => {
                 log::warn!("Retry {}/{} for {}, waiting {}s", attempt, max_attempts, operation, delay_seconds);
             }
+            ProgressEvent::ExtractionFound { extraction_class, extraction_text } => {
+                log::debug!("Found {}: {}", extraction_class, extraction_text);
+            }
+            ProgressEvent::RateLimitWait { provider, model, wait_ms } => {
+                log::info!("Rate limit for {} model {}: waiting {}ms", provider, model, wait_ms);
+            }
             ProgressEvent::Error { operation, error } => {
                 log::error!("{}: {}", operation, error);
             }
@@ -17,14 +17,22 @@ mod cli {
     use console::style;
     use indicatif::{ProgressBar, ProgressStyle};
     use langextract_rust::{
-        extract, ExampleData, Extraction, ExtractConfig, FormatType,
-        ProviderConfig, ProviderType, LangExtractError,
+        extract, extract_documents, CancellationToken, CheckpointConfig, ConsensusConfig, CoverageReport, Document, ExampleData, Extraction, ExtractConfig, FormatType,
+        CustomHttpMapping, ProviderConfig, ProviderType, LangExtractError, RejectAction, VerificationConfig,
+        providers::{BatchJob, BatchStatus, CacheConfig, RateLimitConfig, ResponseCache},
+        usage::{PriceTable, UsageBudget, UsageTracker},
+        dry_run::DryRunReport,
+        io::{InputFormat, RecordFields},
+        server::ServerConfig,
         visualization::{export_document, ExportConfig, ExportFormat},
     };
 
+    use futures::StreamExt;
+    use std::collections::HashSet;
     use std::fs;
-    use std::path::PathBuf;
-    use std::time::Instant;
+    use std::io::Write;
+    use std::path::{Path, PathBuf};
+    use std::time::{Duration, Instant};
 
     /// CLI for LangExtract - Extract structured information from text using LLMs
     #[derive(Parser)]
@@ -47,7 +55,7 @@ mod cli {
     #[derive(Subcommand)]
     pub enum Commands {
         /// Extract information from text or files
-        Extract(ExtractArgs),
+        Extract(Box<ExtractArgs>),
         /// Initialize configuration templates
         Init(InitArgs),
         /// Test provider connectivity
@@ -59,12 +67,24 @@ mod cli {
         /// Convert extractions between formats
         Convert(ConvertArgs),
         /// Execute a multi-step extraction pipeline
-        Pipeline(PipelineArgs),
+        Pipeline(Box<PipelineArgs>),
+        /// Inspect and maintain the response cache
+        Cache(CacheCommandArgs),
+        /// Check on and collect OpenAI batch jobs
+        Batch(BatchCommandArgs),
+        /// Serve extraction and pipelines over HTTP
+        Serve(Box<ServeArgs>),
+        /// Serve extraction and pipelines as MCP tools over stdio
+        Mcp(Box<ServiceArgs>),
+        /// Score predictions against gold annotations
+        Eval(EvalArgs),
+        /// Compare the extractions of two saved runs
+        Diff(DiffArgs),
     }
 
     #[derive(Args)]
     pub struct ExtractArgs {
-        /// Input text, file path, or URL to process
+        /// Input text, file path, URL, directory, glob pattern, or '-' for stdin
         #[arg(value_name = "INPUT")]
         pub input: String,
 
@@ -72,6 +92,22 @@ mod cli {
         #[arg(short, long)]
         pub output: Option<PathBuf>,
 
+        /// How input files are split into documents (auto: by file extension)
+        #[arg(long, value_enum, default_value = "auto")]
+        pub input_format: InputFormatArg,
+
+        /// JSONL field or CSV column holding the document text
+        #[arg(long, default_value = "text")]
+        pub text_field: String,
+
+        /// JSONL field or CSV column holding the document ID
+        #[arg(long, default_value = "id")]
+        pub id_field: String,
+
+        /// Skip documents already in the output JSONL file and append the rest
+        #[arg(long, requires = "output")]
+        pub skip_existing: bool,
+
         /// Examples file (JSON/YAML format)
         #[arg(short, long)]
         pub examples: Option<PathBuf>,
@@ -96,6 +132,40 @@ mod cli {
         #[arg(long)]
         pub model_url: Option<String>,
 
+        /// Request/response mapping file for the custom provider (JSON/YAML)
+        #[arg(long, value_name = "FILE")]
+        pub custom_mapping: Option<PathBuf>,
+
+        /// Record all model responses to a fixture file
+        #[arg(long, value_name = "FILE", conflicts_with = "replay")]
+        pub record: Option<PathBuf>,
+
+        /// Replay model responses from a fixture file (no network access)
+        #[arg(long, value_name = "FILE")]
+        pub replay: Option<PathBuf>,
+
+        /// Submit as an OpenAI batch job and save its state to FILE
+        #[arg(long, value_name = "FILE", conflicts_with_all = ["record", "replay"])]
+        pub batch: Option<PathBuf>,
+
+        #[command(flatten)]
+        pub cache: CacheArgs,
+
+        #[command(flatten)]
+        pub checkpoint: CheckpointArgs,
+
+        #[command(flatten)]
+        pub timeouts: TimeoutArgs,
+
+        #[command(flatten)]
+        pub rate_limit: RateLimitArgs,
+
+        #[command(flatten)]
+        pub budget: BudgetArgs,
+
+        #[command(flatten)]
+        pub dry_run: DryRunArgs,
+
         /// Output format
         #[arg(long, value_enum, default_value = "json")]
         pub format: OutputFormat,
@@ -128,6 +198,42 @@ mod cli {
         #[arg(long, default_value = "2")]
         pub passes: usize,
 
+        /// Sample each chunk this many times and keep the extractions enough
+        /// samples agree on, instead of running refinement passes
+        #[arg(long, value_name = "SAMPLES", conflicts_with_all = ["checkpoint_dir", "resume"])]
+        pub consensus: Option<usize>,
+
+        /// Samples that must agree to keep an extraction (default: a majority)
+        #[arg(long, value_name = "K", requires = "consensus")]
+        pub min_votes: Option<usize>,
+
+        /// Comma-separated temperatures the consensus samples take in turn
+        #[arg(long, value_name = "TEMPS", value_delimiter = ',', requires = "consensus")]
+        pub consensus_temperatures: Vec<f32>,
+
+        /// Check each extraction against its source text with the model and
+        /// drop the ones it rejects
+        #[arg(long)]
+        pub verify: bool,
+
+        /// Extractions checked per verification call
+        #[arg(long, value_name = "N", default_value = "10", requires = "verify")]
+        pub verify_batch_size: usize,
+
+        /// Keep rejected extractions, flagged in their attributes, instead of
+        /// dropping them
+        #[arg(long, requires = "verify")]
+        pub keep_rejected: bool,
+
+        /// Stream model output and print extractions as they are found
+        #[arg(long)]
+        pub stream: bool,
+
+        /// Keep failed chunks in the output with a coverage report instead of
+        /// dropping them or aborting
+        #[arg(long)]
+        pub allow_partial: bool,
+
         /// Show character intervals in output
         #[arg(long)]
         pub show_intervals: bool,
@@ -238,6 +344,364 @@ mod cli {
         /// Export flattened JSON to file (Rust-exported, replaces python flatten)
         #[arg(long)]
         pub export_flattened: Option<PathBuf>,
+
+        /// Save the step results to a file for `lx-rs diff`
+        #[arg(long, value_name = "FILE")]
+        pub save_run: Option<PathBuf>,
+
+        /// Record all model responses to a fixture file
+        #[arg(long, value_name = "FILE", conflicts_with = "replay")]
+        pub record: Option<PathBuf>,
+
+        /// Replay model responses from a fixture file (no network access)
+        #[arg(long, value_name = "FILE")]
+        pub replay: Option<PathBuf>,
+
+        #[command(flatten)]
+        pub cache: CacheArgs,
+
+        #[command(flatten)]
+        pub rate_limit: RateLimitArgs,
+
+        #[command(flatten)]
+        pub budget: BudgetArgs,
+
+        #[command(flatten)]
+        pub timeouts: TimeoutArgs,
+
+        #[command(flatten)]
+        pub dry_run: DryRunArgs,
+    }
+
+    #[derive(Args)]
+    pub struct EvalArgs {
+        /// Gold annotations: annotated documents (JSON/JSONL) or examples (JSON/YAML)
+        pub gold: PathBuf,
+
+        /// Predicted annotated documents (JSON/JSONL), as written by extract
+        pub predictions: PathBuf,
+
+        /// Report file (default: stdout)
+        #[arg(short, long)]
+        pub output: Option<PathBuf>,
+
+        /// Report format (default: JSON for a .json output file, Markdown otherwise)
+        #[arg(short, long, value_enum)]
+        pub format: Option<ReportFormat>,
+    }
+
+    #[derive(Args)]
+    pub struct DiffArgs {
+        /// First run: annotated documents (JSON/JSONL) or a pipeline run saved with --save-run
+        pub before: PathBuf,
+
+        /// Second run, of the same kind
+        pub after: PathBuf,
+
+        /// Write a side-by-side HTML view
+        #[arg(long, value_name = "FILE")]
+        pub html: Option<PathBuf>,
+
+        /// Write the full diff as JSON
+        #[arg(long, value_name = "FILE")]
+        pub json: Option<PathBuf>,
+
+        /// Exit with status 1 when the runs differ
+        #[arg(long)]
+        pub exit_code: bool,
+    }
+
+    #[derive(Args)]
+    pub struct ServeArgs {
+        /// Address to listen on
+        #[arg(long, default_value = "127.0.0.1:8080")]
+        pub bind: std::net::SocketAddr,
+
+        #[command(flatten)]
+        pub service: ServiceArgs,
+    }
+
+    /// Settings shared by `serve` and `mcp`
+    #[derive(Args)]
+    pub struct ServiceArgs {
+        /// Directory of pipeline YAML files, each run by its file stem
+        #[arg(long, value_name = "DIR")]
+        pub pipelines: Option<PathBuf>,
+
+        /// Model every request uses
+        #[arg(short, long, default_value = "gemini-2.5-flash")]
+        pub model: String,
+
+        /// Provider type (required)
+        #[arg(long, value_enum)]
+        pub provider: ProviderType,
+
+        /// API key (overrides environment variables); requests can never set one
+        #[arg(long)]
+        pub api_key: Option<String>,
+
+        /// Model URL for custom/self-hosted models
+        #[arg(long)]
+        pub model_url: Option<String>,
+
+        /// Request/response mapping file for the custom provider (JSON/YAML)
+        #[arg(long, value_name = "FILE")]
+        pub custom_mapping: Option<PathBuf>,
+
+        /// Record all model responses to a fixture file
+        #[arg(long, value_name = "FILE", conflicts_with = "replay")]
+        pub record: Option<PathBuf>,
+
+        /// Replay model responses from a fixture file (no network access)
+        #[arg(long, value_name = "FILE")]
+        pub replay: Option<PathBuf>,
+
+        #[command(flatten)]
+        pub cache: CacheArgs,
+
+        #[command(flatten)]
+        pub rate_limit: RateLimitArgs,
+
+        /// Limits for each request
+        #[command(flatten)]
+        pub budget: BudgetArgs,
+
+        /// Limits for each request; a request may ask for shorter ones
+        #[command(flatten)]
+        pub timeouts: TimeoutArgs,
+
+        /// Extraction passes per request; a request may ask for fewer
+        #[arg(long, value_name = "N", default_value = "1")]
+        pub passes: usize,
+
+        /// Verify extractions on every request; a request may opt out
+        #[arg(long)]
+        pub verify: bool,
+    }
+
+    #[derive(Args, Clone, Debug)]
+    pub struct CacheArgs {
+        /// Cache model responses on disk and reuse them on reruns
+        #[arg(long)]
+        pub cache: bool,
+
+        /// Response cache directory (implies --cache)
+        #[arg(long, value_name = "DIR")]
+        pub cache_dir: Option<PathBuf>,
+
+        /// Ignore cached responses older than this many seconds
+        #[arg(long, value_name = "SECONDS")]
+        pub cache_ttl: Option<u64>,
+
+        /// Evict the oldest cached responses beyond this size in MB
+        #[arg(long, value_name = "MB")]
+        pub cache_max_mb: Option<u64>,
+    }
+
+    #[derive(Args, Clone, Debug)]
+    pub struct CheckpointArgs {
+        /// Save each chunk result as it completes so an interrupted run can resume
+        #[arg(long, value_name = "DIR")]
+        pub checkpoint_dir: Option<PathBuf>,
+
+        /// Skip chunks completed by an earlier run (default directory: .lx-checkpoints)
+        #[arg(long)]
+        pub resume: bool,
+    }
+
+    impl CheckpointArgs {
+        /// Checkpoint configuration, if checkpointing was requested
+        pub fn to_config(&self) -> Option<CheckpointConfig> {
+            if !self.resume && self.checkpoint_dir.is_none() {
+                return None;
+            }
+            let dir = self.checkpoint_dir.clone().unwrap_or_else(|| PathBuf::from(".lx-checkpoints"));
+            Some(CheckpointConfig::new(dir).with_resume(self.resume))
+        }
+    }
+
+    #[derive(Args, Clone, Debug)]
+    pub struct TimeoutArgs {
+        /// Stop after this many seconds, keeping the results finished so far
+        #[arg(long, value_name = "SECONDS")]
+        pub timeout: Option<u64>,
+
+        /// Fail any model call that takes longer than this many seconds
+        #[arg(long, value_name = "SECONDS")]
+        pub request_timeout: Option<u64>,
+    }
+
+    impl TimeoutArgs {
+        /// `--timeout` in milliseconds; huge values saturate instead of overflowing
+        pub fn timeout_ms(&self) -> Option<u64> {
+            self.timeout.map(|secs| secs.saturating_mul(1000))
+        }
+
+        /// `--request-timeout` in milliseconds
+        pub fn request_timeout_ms(&self) -> Option<u64> {
+            self.request_timeout.map(|secs| secs.saturating_mul(1000))
+        }
+    }
+
+    /// Token cancelled by the first Ctrl-C, so a run stops and keeps what it
+    /// finished; a second Ctrl-C exits immediately
+    fn cancel_on_ctrl_c() -> CancellationToken {
+        let token = CancellationToken::new();
+        let cancel = token.clone();
+        tokio::spawn(async move {
+            if tokio::signal::ctrl_c().await.is_ok() {
+                eprintln!("{} stopping and keeping finished results (Ctrl-C again to quit)",
+                    style("Interrupted:").yellow().bold());
+                cancel.cancel();
+                if tokio::signal::ctrl_c().await.is_ok() {
+                    std::process::exit(130);
+                }
+            }
+        });
+        token
+    }
+
+    #[derive(Args, Clone, Debug)]
+    pub struct RateLimitArgs {
+        /// Maximum model requests per minute
+        #[arg(long, value_name = "N")]
+        pub rpm: Option<u32>,
+
+        /// Maximum estimated tokens per minute (prompt plus max_tokens)
+        #[arg(long, value_name = "N")]
+        pub tpm: Option<u32>,
+    }
+
+    #[derive(Args, Clone, Debug)]
+    pub struct DryRunArgs {
+        /// Render prompts and estimate tokens without calling the model
+        #[arg(long)]
+        pub dry_run: bool,
+
+        /// Directory for the prompts rendered by --dry-run
+        #[arg(long, value_name = "DIR", default_value = "lx-dry-run")]
+        pub prompts_dir: PathBuf,
+    }
+
+    #[derive(Args, Clone, Debug)]
+    pub struct BudgetArgs {
+        /// Abort once the estimated cost exceeds this many USD (needs --prices)
+        #[arg(long, value_name = "USD")]
+        pub max_cost: Option<f64>,
+
+        /// Abort once prompt plus completion tokens exceed this count
+        #[arg(long, value_name = "N")]
+        pub max_tokens: Option<u64>,
+
+        /// Per-model prices in USD per million tokens (YAML/JSON)
+        #[arg(long, value_name = "FILE")]
+        pub prices: Option<PathBuf>,
+    }
+
+    impl BudgetArgs {
+        /// Token and cost limits for the run
+        pub fn to_budget(&self) -> UsageBudget {
+            UsageBudget {
+                max_tokens: self.max_tokens,
+                max_cost_usd: self.max_cost,
+            }
+        }
+
+        /// Price table, if a prices file was given
+        pub fn price_table(&self) -> Result<Option<PriceTable>, LangExtractError> {
+            self.prices.as_deref().map(PriceTable::load).transpose()
+        }
+    }
+
+    impl RateLimitArgs {
+        /// Rate limit configuration, if any limit was given
+        pub fn to_config(&self) -> Option<RateLimitConfig> {
+            let config = RateLimitConfig {
+                requests_per_minute: self.rpm,
+                tokens_per_minute: self.tpm,
+            };
+            config.is_enabled().then_some(config)
+        }
+    }
+
+    impl CacheArgs {
+        /// Cache configuration, if caching was requested
+        pub fn to_config(&self) -> Option<CacheConfig> {
+            if !self.cache && self.cache_dir.is_none() {
+                return None;
+            }
+            Some(cache_config(self.cache_dir.clone(), self.cache_ttl, self.cache_max_mb))
+        }
+    }
+
+    #[derive(Args)]
+    pub struct CacheCommandArgs {
+        #[command(subcommand)]
+        pub action: CacheAction,
+
+        /// Response cache directory
+        #[arg(long, value_name = "DIR", global = true)]
+        pub dir: Option<PathBuf>,
+    }
+
+    #[derive(Subcommand)]
+    pub enum CacheAction {
+        /// Show entry count, size and age of cached responses
+        Stats {
+            /// Count entries older than this many seconds as expired
+            #[arg(long, value_name = "SECONDS")]
+            ttl: Option<u64>,
+        },
+        /// Remove all cached responses
+        Clear,
+        /// Remove expired entries and enforce a size limit
+        Prune {
+            /// Remove entries older than this many seconds
+            #[arg(long, value_name = "SECONDS")]
+            ttl: Option<u64>,
+
+            /// Evict the oldest entries beyond this size in MB
+            #[arg(long, value_name = "MB")]
+            max_mb: Option<u64>,
+        },
+    }
+
+    #[derive(Args)]
+    pub struct BatchCommandArgs {
+        #[command(subcommand)]
+        pub action: BatchAction,
+
+        /// API key (default: OPENAI_API_KEY)
+        #[arg(long, global = true)]
+        pub api_key: Option<String>,
+    }
+
+    #[derive(Subcommand)]
+    pub enum BatchAction {
+        /// Show the status of a submitted batch job
+        Status {
+            /// Job state file written by `extract --batch`
+            #[arg(value_name = "STATE")]
+            state: PathBuf,
+        },
+        /// Download a completed batch and write the extraction results
+        Collect {
+            /// Job state file written by `extract --batch`
+            #[arg(value_name = "STATE")]
+            state: PathBuf,
+
+            /// Output file path (default: stdout)
+            #[arg(short, long)]
+            output: Option<PathBuf>,
+
+            /// Poll until the batch finishes instead of failing while it runs
+            #[arg(long)]
+            wait: bool,
+
+            /// Seconds between status checks with --wait
+            #[arg(long, value_name = "SECONDS", default_value_t = 60)]
+            poll_interval: u64,
+        },
     }
 
     #[derive(ValueEnum, Clone, Debug)]
@@ -247,6 +711,31 @@ mod cli {
         Text,
     }
 
+    #[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+    pub enum ReportFormat {
+        Markdown,
+        Json,
+    }
+
+    #[derive(ValueEnum, Clone, Copy, Debug)]
+    pub enum InputFormatArg {
+        Auto,
+        Text,
+        Jsonl,
+        Csv,
+    }
+
+    impl InputFormatArg {
+        fn resolve(self, path: &Path) -> InputFormat {
+            match self {
+                InputFormatArg::Auto => InputFormat::from_path(path),
+                InputFormatArg::Text => InputFormat::Text,
+                InputFormatArg::Jsonl => InputFormat::Jsonl,
+                InputFormatArg::Csv => InputFormat::Csv,
+            }
+        }
+    }
+
     /// Initialize the CLI application
     pub async fn run() -> Result<(), Box<dyn std::error::Error>> {
         let cli = Cli::parse();
@@ -268,13 +757,19 @@ mod cli {
         }
 
         match cli.command {
-            Commands::Extract(args) => extract_command(args, cli.verbose).await,
+            Commands::Extract(args) => extract_command(*args, cli.verbose).await,
             Commands::Init(args) => init_command(args).await,
             Commands::Test(args) => test_command(args).await,
             Commands::Providers => providers_command().await,
             Commands::Examples => examples_command().await,
             Commands::Convert(args) => convert_command(args).await,
-            Commands::Pipeline(args) => pipeline_command(args).await,
+            Commands::Pipeline(args) => pipeline_command(*args).await,
+            Commands::Cache(args) => cache_command(args).await,
+            Commands::Batch(args) => batch_command(args).await,
+            Commands::Serve(args) => serve_command(*args).await,
+            Commands::Mcp(args) => mcp_command(*args).await,
+            Commands::Eval(args) => eval_command(args),
+            Commands::Diff(args) => diff_command(args),
         }
     }
 
@@ -284,6 +779,10 @@ mod cli {
         // Load environment variables
         dotenvy::dotenv().ok();
 
+        if let Some(documents) = load_corpus(&args)? {
+            return corpus_command(args, documents, verbose).await;
+        }
+
         if !args.quiet {
             println!("{}", style("LangExtract - starting extraction").bold().cyan());
         }
@@ -326,6 +825,8 @@ mod cli {
                 println!("Downloading from URL: {}", args.input);
             }
             langextract_rust::io::download_text_from_url(&args.input).await?
+        } else if args.input == "-" {
+            std::io::read_to_string(std::io::stdin())?
         } else if std::path::Path::new(&args.input).exists() {
             if !args.quiet {
                 println!("Reading file: {}", args.input);
@@ -344,59 +845,45 @@ mod cli {
         if let Some(ref pb) = pb {
             pb.set_message("Configuring extraction...");
         }
-        let mut config = ExtractConfig {
-            model_id: args.model.clone(),
-            api_key: args.api_key.clone(),
-            model_url: args.model_url.clone(),
-            format_type: match args.format {
-                OutputFormat::Json => FormatType::Json,
-                OutputFormat::Yaml => FormatType::Yaml,
-                OutputFormat::Text => FormatType::Json, // Default to JSON for processing
-            },
-            max_char_buffer: args.max_chars,
-            max_workers: args.workers,
-            batch_length: args.batch_size,
-            temperature: args.temperature,
-            enable_multipass: args.multipass || args.passes > 1,
-            multipass_max_passes: args.passes,
-            debug: args.debug || verbose,
-            additional_context: args.context.clone(),
-            ..Default::default()
-        };
+        let mut config = extract_config(&args, verbose)?;
 
-        // Configure progress handling based on CLI options
-        if args.quiet {
-            config = config.with_quiet_mode();
-        } else if args.debug || verbose {
-            config = config.with_verbose_progress();
-        } else {
-            config = config.with_console_progress();
+        if args.dry_run.dry_run {
+            if let Some(ref pb) = pb {
+                pb.finish_and_clear();
+            }
+            let report = langextract_rust::dry_run(&text, args.prompt.as_deref(), &examples, &config)?;
+            return print_dry_run(&report, &args.dry_run.prompts_dir, verbose);
         }
 
-        // Set up provider configuration (required)
-        let provider_config = match args.provider {
-            ProviderType::OpenAI => {
-                let mut config = ProviderConfig::openai(&args.model, args.api_key.clone());
-                if let Some(model_url) = &args.model_url {
-                    config = config.with_base_url(model_url.clone());
-                }
-                config
-            },
-            ProviderType::Ollama => ProviderConfig::ollama(&args.model, args.model_url.clone()),
-            ProviderType::Custom => ProviderConfig::custom(
-                &args.model_url.clone().unwrap_or_else(|| "http://localhost:8000".to_string()),
-                &args.model
-            ),
-        };
-
         config.language_model_params.insert(
             "provider_config".to_string(),
-            serde_json::to_value(&provider_config)?
+            serde_json::to_value(provider_config(&args)?)?
         );
 
+        if let Some(state_path) = &args.batch {
+            if let Some(ref pb) = pb {
+                pb.set_message("Submitting batch job...");
+            }
+            // Later passes depend on earlier results, so a batch is a single
+            // pass unless --multipass asks otherwise (which submit rejects)
+            config.enable_multipass = args.multipass;
+            let job = BatchJob::submit(&text, args.prompt.as_deref(), &examples, &config, state_path)
+                .await
+                .map_err(handle_extraction_error)?;
+            if let Some(ref pb) = pb {
+                pb.finish_with_message("Batch submitted");
+            }
+            println!("{} Submitted batch {} with {} requests ({})",
+                style(">>").green(), job.batch_id, job.requests.len(), job.status);
+            println!("Job state saved to: {}", state_path.display());
+            println!("Check on it with: lx-rs batch status {}", state_path.display());
+            return Ok(());
+        }
+
         if let Some(ref pb) = pb {
             pb.set_message("Performing extraction...");
         }
+        config.cancellation = Some(cancel_on_ctrl_c());
 
         // Perform extraction
         let result = match extract(
@@ -415,6 +902,10 @@ mod cli {
                 if let Some(ref pb) = pb {
                     pb.finish_with_message("Extraction failed");
                 }
+                if let Some(checkpoint) = args.checkpoint.to_config() {
+                    eprintln!("Completed chunks are saved in {}; rerun with --resume to skip them",
+                        checkpoint.dir.display());
+                }
                 return Err(handle_extraction_error(e));
             }
         };
@@ -426,6 +917,16 @@ mod cli {
                 result.extraction_count(), 
                 elapsed.as_secs_f64()
             );
+            if let Some(usage) = result.usage.as_ref().filter(|u| u.calls > 0) {
+                println!("{} Usage: {}", style(">>").green(), usage.describe());
+            }
+        }
+        if let Some(coverage) = result.coverage.as_ref().filter(|c| !c.is_complete()) {
+            print_coverage(coverage);
+            if let Some(checkpoint) = args.checkpoint.to_config() {
+                eprintln!("Completed chunks are saved in {}; rerun with --resume to skip them",
+                    checkpoint.dir.display());
+            }
         }
 
         // Output results
@@ -469,6 +970,333 @@ mod cli {
         Ok(())
     }
 
+    /// Build the extraction settings shared by single and multi-document runs
+    fn extract_config(args: &ExtractArgs, verbose: bool) -> Result<ExtractConfig, Box<dyn std::error::Error>> {
+        let mut config = ExtractConfig {
+            model_id: args.model.clone(),
+            api_key: args.api_key.clone(),
+            model_url: args.model_url.clone(),
+            format_type: match args.format {
+                OutputFormat::Json => FormatType::Json,
+                OutputFormat::Yaml => FormatType::Yaml,
+                OutputFormat::Text => FormatType::Json, // Default to JSON for processing
+            },
+            max_char_buffer: args.max_chars,
+            max_workers: args.workers,
+            batch_length: args.batch_size,
+            temperature: args.temperature,
+            enable_multipass: args.multipass || args.passes > 1,
+            streaming: args.stream,
+            multipass_max_passes: args.passes,
+            debug: args.debug || verbose,
+            additional_context: args.context.clone(),
+            budget: args.budget.to_budget(),
+            price_table: args.budget.price_table()?,
+            allow_partial: args.allow_partial,
+            checkpoint: args.checkpoint.to_config(),
+            request_timeout_ms: args.timeouts.request_timeout_ms(),
+            timeout_ms: args.timeouts.timeout_ms(),
+            ..Default::default()
+        };
+
+        if let Some(samples) = args.consensus {
+            let mut consensus = ConsensusConfig::new(samples)
+                .with_temperatures(args.consensus_temperatures.clone());
+            if let Some(min_votes) = args.min_votes {
+                consensus = consensus.with_min_votes(min_votes);
+            }
+            consensus.validate()?;
+            config = config.with_consensus(consensus);
+        }
+
+        if args.verify {
+            let on_reject = if args.keep_rejected { RejectAction::Flag } else { RejectAction::Drop };
+            config = config.with_verification(
+                VerificationConfig::default()
+                    .with_batch_size(args.verify_batch_size)
+                    .with_on_reject(on_reject),
+            );
+        }
+
+        // Configure progress handling based on CLI options
+        if args.quiet {
+            config = config.with_quiet_mode();
+        } else if args.debug || verbose {
+            config = config.with_verbose_progress();
+        } else {
+            config = config.with_console_progress();
+        }
+
+        Ok(config)
+    }
+
+    /// Provider configuration (required) with recording, caching and rate limits applied
+    fn provider_config(args: &ExtractArgs) -> Result<ProviderConfig, Box<dyn std::error::Error>> {
+        let provider_config = base_provider_config(
+            args.provider,
+            &args.model,
+            args.api_key.clone(),
+            args.model_url.as_ref(),
+            args.custom_mapping.as_ref(),
+        )?;
+
+        let provider_config = match (&args.record, &args.replay) {
+            (Some(path), _) => provider_config.with_record(path),
+            (_, Some(path)) => provider_config.with_replay(path),
+            _ => provider_config,
+        };
+        let provider_config = match args.cache.to_config() {
+            Some(cache) => provider_config.with_cache(cache),
+            None => provider_config,
+        };
+        Ok(match args.rate_limit.to_config() {
+            Some(rate_limit) => provider_config.with_rate_limit(rate_limit),
+            None => provider_config,
+        })
+    }
+
+    /// Provider configuration from the provider, model, key and URL flags
+    fn base_provider_config(
+        provider: ProviderType,
+        model: &str,
+        api_key: Option<String>,
+        model_url: Option<&String>,
+        custom_mapping: Option<&PathBuf>,
+    ) -> Result<ProviderConfig, Box<dyn std::error::Error>> {
+        Ok(match provider {
+            ProviderType::OpenAI => {
+                let mut config = ProviderConfig::openai(model, api_key);
+                if let Some(model_url) = model_url {
+                    config = config.with_base_url(model_url.clone());
+                }
+                config
+            },
+            ProviderType::Ollama => ProviderConfig::ollama(model, model_url.cloned()),
+            ProviderType::Gemini => {
+                let api_key = api_key.or_else(|| std::env::var("GEMINI_API_KEY").ok());
+                let mut config = ProviderConfig::gemini(model, api_key);
+                if let Some(model_url) = model_url {
+                    config = config.with_base_url(model_url.clone());
+                }
+                config
+            },
+            ProviderType::Anthropic => {
+                let api_key = api_key.or_else(|| std::env::var("ANTHROPIC_API_KEY").ok());
+                let mut config = ProviderConfig::anthropic(model, api_key);
+                if let Some(model_url) = model_url {
+                    config = config.with_base_url(model_url.clone());
+                }
+                config
+            },
+            ProviderType::Custom => {
+                let mut config = ProviderConfig::custom(
+                    &model_url.cloned().unwrap_or_else(|| "http://localhost:8000".to_string()),
+                    model
+                );
+                if let Some(api_key) = api_key {
+                    config = config.with_api_key(api_key);
+                }
+                if let Some(mapping_path) = custom_mapping {
+                    config = config.with_custom_mapping(load_custom_mapping(mapping_path)?);
+                }
+                config
+            },
+        })
+    }
+
+    /// Read the documents of a multi-document input: a directory, a glob
+    /// pattern, or a JSONL/CSV file (or stdin with --input-format).
+    /// Returns `None` for single-document inputs.
+    fn load_corpus(args: &ExtractArgs) -> Result<Option<Vec<Document>>, Box<dyn std::error::Error>> {
+        let input = args.input.as_str();
+        let fields = RecordFields {
+            text_field: args.text_field.clone(),
+            id_field: args.id_field.clone(),
+        };
+
+        if input == "-" {
+            let format = args.input_format.resolve(Path::new(input));
+            if format == InputFormat::Text {
+                return Ok(None);
+            }
+            let content = std::io::read_to_string(std::io::stdin())?;
+            let documents = match format {
+                InputFormat::Csv => langextract_rust::io::documents_from_csv(&content, "stdin", &fields)?,
+                _ => langextract_rust::io::documents_from_jsonl(&content, "stdin", &fields)?,
+            };
+            return Ok(Some(documents));
+        }
+
+        let path = Path::new(input);
+        // Literal text often contains '?' or '*', so only space-free inputs count as globs
+        let is_pattern = !path.exists()
+            && !langextract_rust::io::is_url(input)
+            && langextract_rust::io::is_glob(input)
+            && !input.contains(char::is_whitespace);
+        let paths = if path.is_dir() || is_pattern {
+            langextract_rust::io::expand_input_paths(input)?
+        } else if path.is_file() && args.input_format.resolve(path) != InputFormat::Text {
+            vec![path.to_path_buf()]
+        } else {
+            return Ok(None);
+        };
+        if paths.is_empty() {
+            return Err(format!("No input files found for '{}'", input).into());
+        }
+
+        let mut documents = Vec::new();
+        for path in &paths {
+            documents.extend(langextract_rust::io::load_documents(path, args.input_format.resolve(path), &fields)?);
+        }
+        Ok(Some(documents))
+    }
+
+    /// IDs of the documents already written to a JSONL output file
+    fn existing_document_ids(path: &Path) -> Result<HashSet<String>, Box<dyn std::error::Error>> {
+        if !path.exists() {
+            return Ok(HashSet::new());
+        }
+        let mut ids = HashSet::new();
+        for line in fs::read_to_string(path)?.lines().filter(|line| !line.trim().is_empty()) {
+            // A run interrupted mid-write can leave a truncated last line; that document is redone
+            if let Ok(document) = serde_json::from_str::<serde_json::Value>(line) {
+                if let Some(id) = document.get("document_id").and_then(|id| id.as_str()) {
+                    ids.insert(id.to_string());
+                }
+            }
+        }
+        Ok(ids)
+    }
+
+    /// Extract from many documents, writing one annotated document per JSONL line
+    /// as each finishes. Progress goes to stderr so stdout stays valid JSONL.
+    async fn corpus_command(
+        args: ExtractArgs,
+        mut documents: Vec<Document>,
+        verbose: bool,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let start_time = Instant::now();
+
+        if args.dry_run.dry_run || args.batch.is_some() || args.export.is_some() {
+            return Err("--dry-run, --batch and --export take a single document, not a directory, glob or JSONL/CSV input".into());
+        }
+        if !matches!(args.format, OutputFormat::Json) {
+            return Err("Multi-document input is written as JSONL; --format must be json".into());
+        }
+
+        let examples = if let Some(examples_path) = &args.examples {
+            load_examples(examples_path)?
+        } else {
+            if !args.quiet {
+                eprintln!("{}", style("Warning: no examples provided, using default person extraction examples.").yellow());
+            }
+            get_default_examples()
+        };
+
+        if args.skip_existing {
+            if let Some(output_path) = &args.output {
+                let done = existing_document_ids(output_path)?;
+                let before = documents.len();
+                documents.retain(|document| {
+                    document.document_id.as_ref().is_none_or(|id| !done.contains(id))
+                });
+                if !args.quiet {
+                    eprintln!("Skipping {} documents already in {}", before - documents.len(), output_path.display());
+                }
+            }
+        }
+
+        let total = documents.len();
+        if !args.quiet {
+            eprintln!("{}", style(format!("LangExtract - extracting from {} documents", total)).bold().cyan());
+        }
+
+        let mut config = extract_config(&args, verbose)?;
+        let interrupted = cancel_on_ctrl_c();
+        config.cancellation = Some(interrupted.clone());
+        // Per-chunk progress from concurrent documents would interleave; report per document instead
+        config = config.with_quiet_mode();
+        let tracker = std::sync::Arc::new(UsageTracker::new(
+            config.price_table.clone().unwrap_or_default(),
+            config.budget,
+        ));
+        config = config.with_usage_tracker(tracker.clone());
+        config.language_model_params.insert(
+            "provider_config".to_string(),
+            serde_json::to_value(provider_config(&args)?)?
+        );
+
+        let mut out: Box<dyn Write> = match &args.output {
+            Some(path) => Box::new(
+                fs::OpenOptions::new()
+                    .create(true)
+                    .write(true)
+                    .append(args.skip_existing)
+                    .truncate(!args.skip_existing)
+                    .open(path)?,
+            ),
+            None => Box::new(std::io::stdout().lock()),
+        };
+
+        let results = extract_documents(futures::stream::iter(documents), args.prompt.as_deref(), &examples, config)
+            .await
+            .map_err(handle_extraction_error)?;
+        let mut results = std::pin::pin!(results);
+
+        let (mut done, mut failed, mut extractions) = (0, 0, 0);
+        while let Some(result) = results.next().await {
+            match result {
+                // A document cut short by Ctrl-C is left for --skip-existing to redo
+                Ok(document) if document.coverage.as_ref().is_some_and(|c| !c.is_complete())
+                    && interrupted.is_cancelled() =>
+                {
+                    failed += 1;
+                    eprintln!("[{}/{}] {} {}", done + failed, total, style("Interrupted:").yellow().bold(),
+                        document.document_id.as_deref().unwrap_or_default());
+                }
+                Ok(document) => {
+                    writeln!(out, "{}", serde_json::to_string(&document)?)?;
+                    out.flush()?;
+                    done += 1;
+                    extractions += document.extraction_count();
+                    if !args.quiet {
+                        eprintln!("[{}/{}] {}: {} extractions",
+                            done + failed, total,
+                            document.document_id.as_deref().unwrap_or_default(),
+                            document.extraction_count());
+                    }
+                    if let Some(coverage) = document.coverage.as_ref().filter(|c| !c.is_complete()) {
+                        eprintln!("   {} {}", style("Partial:").yellow(), coverage.describe());
+                    }
+                }
+                Err(e) => {
+                    failed += 1;
+                    eprintln!("[{}/{}] {} {}", done + failed, total, style("Failed:").red().bold(), e);
+                }
+            }
+        }
+
+        if !args.quiet {
+            eprintln!("{} Extracted {} documents ({} extractions) in {:.2}s",
+                style(">>").green(), done, extractions, start_time.elapsed().as_secs_f64());
+            let usage = tracker.summary();
+            if usage.calls > 0 {
+                eprintln!("{} Usage: {}", style(">>").green(), usage.describe());
+            }
+            if let Some(output_path) = &args.output {
+                eprintln!("Results saved to: {}", output_path.display());
+            }
+        }
+
+        if interrupted.is_cancelled() && done + failed < total {
+            return Err(format!("Interrupted after {} of {} documents; rerun with --skip-existing to continue", done, total).into());
+        }
+        if failed > 0 {
+            return Err(format!("{} of {} documents failed; rerun with --skip-existing to retry them", failed, total).into());
+        }
+        Ok(())
+    }
+
     async fn init_command(args: InitArgs) -> Result<(), Box<dyn std::error::Error>> {
         println!("{}", style("Initializing LangExtract configuration...").bold().cyan());
 
@@ -496,6 +1324,9 @@ OPENAI_API_KEY=your_openai_api_key_here
 # Gemini Configuration  
 GEMINI_API_KEY=your_gemini_api_key_here
 
+# Anthropic Configuration
+ANTHROPIC_API_KEY=your_anthropic_api_key_here
+
 # Custom provider configuration
 CUSTOM_API_KEY=your_custom_api_key_here
 CUSTOM_MODEL_URL=http://localhost:8000
@@ -534,6 +1365,8 @@ OLLAMA_BASE_URL=http://localhost:11434
         let model = args.model.unwrap_or_else(|| match provider {
             ProviderType::OpenAI => "gpt-3.5-turbo".to_string(),
             ProviderType::Ollama => "mistral".to_string(),
+            ProviderType::Gemini => "gemini-2.5-flash".to_string(),
+            ProviderType::Anthropic => "claude-haiku-4-5".to_string(),
             ProviderType::Custom => "test-model".to_string(),
         });
 
@@ -561,6 +1394,22 @@ OLLAMA_BASE_URL=http://localhost:11434
                 config
             },
             ProviderType::Ollama => ProviderConfig::ollama(&model, args.model_url.clone()),
+            ProviderType::Gemini => {
+                let api_key = args.api_key.clone().or_else(|| std::env::var("GEMINI_API_KEY").ok());
+                let mut config = ProviderConfig::gemini(&model, api_key);
+                if let Some(model_url) = &args.model_url {
+                    config = config.with_base_url(model_url.clone());
+                }
+                config
+            },
+            ProviderType::Anthropic => {
+                let api_key = args.api_key.clone().or_else(|| std::env::var("ANTHROPIC_API_KEY").ok());
+                let mut config = ProviderConfig::anthropic(&model, api_key);
+                if let Some(model_url) = &args.model_url {
+                    config = config.with_base_url(model_url.clone());
+                }
+                config
+            },
             ProviderType::Custom => ProviderConfig::custom(
                 &args.model_url.clone().unwrap_or_else(|| "http://localhost:8000".to_string()),
                 &model
@@ -607,10 +1456,20 @@ OLLAMA_BASE_URL=http://localhost:11434
                         println!("1. Set API key: {}", style("export OPENAI_API_KEY=your_key").cyan());
                         println!("2. Check account: https://platform.openai.com/account/api-keys");
                     }
+                    ProviderType::Gemini => {
+                        println!("\n{}", style("Troubleshooting tips for Gemini:").yellow());
+                        println!("1. Set API key: {}", style("export GEMINI_API_KEY=your_key").cyan());
+                        println!("2. Check account: https://aistudio.google.com/apikey");
+                    }
+                    ProviderType::Anthropic => {
+                        println!("\n{}", style("Troubleshooting tips for Anthropic:").yellow());
+                        println!("1. Set API key: {}", style("export ANTHROPIC_API_KEY=your_key").cyan());
+                        println!("2. Check account: https://console.anthropic.com/settings/keys");
+                    }
                     ProviderType::Custom => {
                         println!("\n{}", style("Troubleshooting tips for Custom provider:").yellow());
                         println!("1. Check URL: {}", style("--model-url http://your-server").cyan());
-                        println!("2. Verify API compatibility with OpenAI format");
+                        println!("2. Verify API compatibility with OpenAI format, or pass {}", style("--custom-mapping mapping.json").cyan());
                     }
                 }
                 
@@ -628,7 +1487,9 @@ OLLAMA_BASE_URL=http://localhost:11434
         let providers = vec![
             ("OpenAI", vec!["gpt-4o", "gpt-4o-mini", "gpt-3.5-turbo"], "High accuracy, JSON mode support"),
             ("Ollama", vec!["mistral", "llama2", "qwen", "codellama"], "Local inference, privacy-focused"),
-            ("Custom", vec!["any-model"], "OpenAI-compatible HTTP APIs"),
+            ("Gemini", vec!["gemini-2.5-flash", "gemini-2.5-pro"], "Native JSON mode, long context"),
+            ("Anthropic", vec!["claude-sonnet-4-5", "claude-haiku-4-5"], "Messages API with prefilled JSON output"),
+            ("Custom", vec!["any-model"], "OpenAI-compatible or mapped HTTP APIs"),
         ];
 
         for (provider, models, description) in providers {
@@ -639,7 +1500,9 @@ OLLAMA_BASE_URL=http://localhost:11434
             match provider {
                 "OpenAI" => println!("  Requires: OPENAI_API_KEY environment variable"),
                 "Ollama" => println!("  Requires: Local Ollama installation (ollama.ai)"),
-                "Custom" => println!("  Requires: --model-url parameter"),
+                "Gemini" => println!("  Requires: GEMINI_API_KEY environment variable"),
+                "Anthropic" => println!("  Requires: ANTHROPIC_API_KEY environment variable"),
+                "Custom" => println!("  Requires: --model-url parameter (optional --custom-mapping file)"),
                 _ => {}
             }
             println!();
@@ -648,6 +1511,8 @@ OLLAMA_BASE_URL=http://localhost:11434
         println!("{}", style("Example usage:").bold().yellow());
         println!("  lx-rs extract 'Hello world' --provider openai --model gpt-4o");
         println!("  lx-rs extract 'Hello world' --provider ollama --model mistral");
+        println!("  lx-rs extract 'Hello world' --provider gemini --model gemini-2.5-flash");
+        println!("  lx-rs extract 'Hello world' --provider anthropic --model claude-sonnet-4-5");
         println!("  lx-rs extract 'Hello world' --provider custom --model-url http://localhost:8000");
 
         Ok(())
@@ -710,6 +1575,7 @@ OLLAMA_BASE_URL=http://localhost:11434
 
     async fn pipeline_command(args: PipelineArgs) -> Result<(), Box<dyn std::error::Error>> {
         use langextract_rust::pipeline::{PipelineExecutor, utils};
+        use langextract_rust::providers::{FixtureConfig, FixtureMode};
 
         // Handle sample pipeline creation
         if args.create_sample {
@@ -737,9 +1603,6 @@ OLLAMA_BASE_URL=http://localhost:11434
         // Execute pipeline
         println!("{}", style("Executing pipeline...").bold().cyan());
 
-        // Load pipeline configuration
-        let executor = PipelineExecutor::from_yaml_file(&args.config)?;
-
         // Read input text
         let input_text = if args.input.starts_with("http") {
             // Handle URL input
@@ -754,6 +1617,35 @@ OLLAMA_BASE_URL=http://localhost:11434
             args.input.clone()
         };
 
+        // Load pipeline configuration
+        let mut executor = PipelineExecutor::from_yaml_file(&args.config)?;
+        if args.dry_run.dry_run {
+            let report = executor.dry_run(&input_text)?;
+            return print_dry_run(&report, &args.dry_run.prompts_dir, false);
+        }
+        if let Some(path) = &args.record {
+            executor = executor.with_fixture(FixtureConfig { mode: FixtureMode::Record, path: path.clone() })?;
+        } else if let Some(path) = &args.replay {
+            executor = executor.with_fixture(FixtureConfig { mode: FixtureMode::Replay, path: path.clone() })?;
+        }
+        if let Some(cache) = args.cache.to_config() {
+            executor = executor.with_cache(cache)?;
+        }
+        if let Some(rate_limit) = args.rate_limit.to_config() {
+            executor = executor.with_rate_limit(rate_limit)?;
+        }
+        executor = executor.with_budget(args.budget.to_budget());
+        if let Some(prices) = args.budget.price_table()? {
+            executor = executor.with_price_table(prices);
+        }
+        if let Some(secs) = args.timeouts.timeout {
+            executor = executor.with_timeout(Duration::from_secs(secs));
+        }
+        if let Some(secs) = args.timeouts.request_timeout {
+            executor = executor.with_request_timeout(Duration::from_secs(secs));
+        }
+        executor = executor.with_cancellation(cancel_on_ctrl_c());
+
         // Execute the pipeline
         let result = executor.execute(&input_text).await?;
 
@@ -767,6 +1659,14 @@ OLLAMA_BASE_URL=http://localhost:11434
             println!("{}", output_content);
         }
 
+        // The run without its configuration, which may hold credentials
+        if let Some(run_path) = &args.save_run {
+            use langextract_rust::server::PipelineRunResponse;
+            let run = PipelineRunResponse::new(result.config.name.clone(), result.clone());
+            std::fs::write(run_path, serde_json::to_string_pretty(&run)?)?;
+            println!("Pipeline run saved to: {}", run_path.display());
+        }
+
         // Optional layered HTML export
         if let Some(html_path) = &args.export_html {
             use langextract_rust::visualization::{ExportConfig, ExportFormat, export_pipeline_html};
@@ -796,6 +1696,12 @@ OLLAMA_BASE_URL=http://localhost:11434
         println!("  Total processing time: {}ms", result.total_time_ms);
         println!("  Steps executed: {}", result.step_results.len());
         println!("  Successful steps: {}", result.step_results.iter().filter(|s| s.success).count());
+        if let Some(message) = &result.error_message {
+            println!("  {} {}", style("Stopped:").yellow().bold(), message);
+        }
+        if result.usage.calls > 0 {
+            println!("  Usage: {}", result.usage.describe());
+        }
 
         // Show step details
         for step_result in &result.step_results {
@@ -810,8 +1716,217 @@ OLLAMA_BASE_URL=http://localhost:11434
         Ok(())
     }
 
+    async fn serve_command(args: ServeArgs) -> Result<(), Box<dyn std::error::Error>> {
+        use langextract_rust::server;
+
+        let config = service_config(&args.service)?;
+        println!("{} Serving {} on http://{}", style(">>").green(), args.service.model, args.bind);
+        if !config.pipelines.is_empty() {
+            let names: Vec<&str> = config.pipelines.keys().map(String::as_str).collect();
+            println!("   Pipelines: {}", names.join(", "));
+        }
+
+        let shutdown = CancellationToken::new();
+        let stop = shutdown.clone();
+        tokio::spawn(async move {
+            if tokio::signal::ctrl_c().await.is_ok() {
+                eprintln!("Shutting down");
+                stop.cancel();
+            }
+        });
+        server::serve(config, args.bind, shutdown).await?;
+        Ok(())
+    }
+
+    async fn mcp_command(args: ServiceArgs) -> Result<(), Box<dyn std::error::Error>> {
+        // Stdout carries protocol messages only
+        let config = service_config(&args)?;
+        eprintln!("Serving {} as MCP tools on stdio", args.model);
+        langextract_rust::mcp::serve_stdio(config).await?;
+        Ok(())
+    }
+
+    /// Provider, per-request defaults and pipelines for `serve` and `mcp`
+    fn service_config(args: &ServiceArgs) -> Result<ServerConfig, Box<dyn std::error::Error>> {
+        dotenvy::dotenv().ok();
+
+        let mut provider = base_provider_config(
+            args.provider,
+            &args.model,
+            args.api_key.clone(),
+            args.model_url.as_ref(),
+            args.custom_mapping.as_ref(),
+        )?;
+        if let Some(cache) = args.cache.to_config() {
+            provider = provider.with_cache(cache);
+        }
+        if let Some(rate_limit) = args.rate_limit.to_config() {
+            provider = provider.with_rate_limit(rate_limit);
+        }
+        let provider = match (&args.record, &args.replay) {
+            (Some(path), _) => provider.with_record(path),
+            (_, Some(path)) => provider.with_replay(path),
+            _ => provider,
+        };
+
+        let defaults = ExtractConfig {
+            model_id: args.model.clone(),
+            budget: args.budget.to_budget(),
+            price_table: args.budget.price_table()?,
+            timeout_ms: args.timeouts.timeout_ms(),
+            request_timeout_ms: args.timeouts.request_timeout_ms(),
+            enable_multipass: args.passes > 1,
+            multipass_max_passes: args.passes.max(1),
+            ..Default::default()
+        }
+        .with_quiet_mode();
+        let defaults = if args.verify {
+            defaults.with_verification(VerificationConfig::default())
+        } else {
+            defaults
+        };
+        let mut config = ServerConfig::new(provider).with_defaults(defaults);
+        if let Some(dir) = &args.pipelines {
+            config = config.with_pipelines_dir(dir)?;
+        }
+        Ok(config)
+    }
+
+    fn eval_command(args: EvalArgs) -> Result<(), Box<dyn std::error::Error>> {
+        use langextract_rust::evaluation::{evaluate, load_annotated_documents};
+
+        let gold = load_annotated_documents(&args.gold)?;
+        let predicted = load_annotated_documents(&args.predictions)?;
+        let report = evaluate(&gold, &predicted);
+
+        let is_json_file = args.output.as_ref().and_then(|p| p.extension()).is_some_and(|e| e == "json");
+        let format = args.format.unwrap_or(if is_json_file { ReportFormat::Json } else { ReportFormat::Markdown });
+        let rendered = match format {
+            ReportFormat::Json => serde_json::to_string_pretty(&report)?,
+            ReportFormat::Markdown => report.to_markdown(),
+        };
+
+        match &args.output {
+            Some(path) => {
+                fs::write(path, rendered)?;
+                println!("{}", style("Evaluation").bold().cyan());
+                for mode in &report.modes {
+                    println!("  {}: P {:.3}  R {:.3}  F1 {:.3}",
+                             mode.mode,
+                             mode.overall.precision,
+                             mode.overall.recall,
+                             mode.overall.f1);
+                }
+                println!("Report written to {}", style(path.display()).cyan());
+            }
+            None => println!("{}", rendered),
+        }
+        Ok(())
+    }
+
+    fn diff_command(args: DiffArgs) -> Result<(), Box<dyn std::error::Error>> {
+        use langextract_rust::diff::{diff_runs, SavedRun};
+        use langextract_rust::visualization::export_diff_html;
+
+        let diff = diff_runs(&SavedRun::load(&args.before)?, &SavedRun::load(&args.after)?)?;
+        let span = |e: &Extraction| match e.char_interval.as_ref().map(|i| (i.start_pos, i.end_pos)) {
+            Some((Some(start), Some(end))) => format!(" [{}..{}]", start, end),
+            _ => String::new(),
+        };
+
+        for section in diff.sections.iter().filter(|s| s.has_changes()) {
+            println!("{}", style(&section.name).bold());
+            for e in &section.removed {
+                println!("  {} {} \"{}\"{}", style("-").red(), e.extraction_class, e.extraction_text, span(e));
+            }
+            for e in &section.added {
+                println!("  {} {} \"{}\"{}", style("+").green(), e.extraction_class, e.extraction_text, span(e));
+            }
+            for change in &section.changed {
+                let (before, after) = (&change.before, &change.after);
+                if change.text_changed || change.span_changed {
+                    println!("  {} {} \"{}\"{} -> \"{}\"{}",
+                             style("~").yellow(),
+                             after.extraction_class,
+                             before.extraction_text,
+                             span(before),
+                             after.extraction_text,
+                             span(after));
+                } else {
+                    println!("  {} {} \"{}\"{}", style("~").yellow(), after.extraction_class, after.extraction_text, span(after));
+                }
+                for delta in &change.attributes {
+                    let value = |v: &Option<serde_json::Value>| v.as_ref().map(|v| v.to_string()).unwrap_or_else(|| "-".to_string());
+                    println!("      {}: {} -> {}", delta.key, value(&delta.before), value(&delta.after));
+                }
+            }
+        }
+
+        let (added, removed, changed, unchanged) = diff.counts();
+        println!("{} added, {} removed, {} changed, {} unchanged", added, removed, changed, unchanged);
+
+        if let Some(path) = &args.json {
+            fs::write(path, serde_json::to_string_pretty(&diff)?)?;
+            println!("Diff saved to: {}", path.display());
+        }
+        if let Some(path) = &args.html {
+            let config = ExportConfig {
+                title: Some(format!("{} vs {}", args.before.display(), args.after.display())),
+                ..Default::default()
+            };
+            fs::write(path, export_diff_html(&diff, &config)?)?;
+            println!("Side-by-side view saved to: {}", path.display());
+        }
+
+        if args.exit_code && diff.has_changes() {
+            std::process::exit(1);
+        }
+        Ok(())
+    }
+
     // Helper functions
 
+    fn print_dry_run(report: &DryRunReport, dir: &Path, verbose: bool) -> Result<(), Box<dyn std::error::Error>> {
+        let paths = report.write_prompts(dir)?;
+
+        println!("{}", style("Dry run - no model calls were made").bold().cyan());
+
+        // One line per pipeline step, then per chunk when verbose
+        let mut steps: Vec<(Option<&str>, usize, usize)> = Vec::new();
+        for preview in &report.prompts {
+            match steps.last_mut() {
+                Some((step, chunks, tokens)) if *step == preview.step_id.as_deref() => {
+                    *chunks += 1;
+                    *tokens += preview.prompt_tokens;
+                }
+                _ => steps.push((preview.step_id.as_deref(), 1, preview.prompt_tokens)),
+            }
+        }
+        for (step, chunks, tokens) in steps.iter().filter(|(step, _, _)| step.is_some()) {
+            let placeholder = report.prompts.iter()
+                .any(|p| p.placeholder && p.step_id.as_deref() == *step);
+            if placeholder {
+                println!("  {}: {} tokens per input item (input comes from an earlier step)", step.unwrap_or_default(), tokens);
+            } else {
+                println!("  {}: {} chunks, {} tokens", step.unwrap_or_default(), chunks, tokens);
+            }
+        }
+        if verbose {
+            for (preview, path) in report.prompts.iter().zip(&paths) {
+                println!("    chunk {} (chars {}..{}): {} tokens -> {}",
+                    preview.chunk_id,
+                    preview.char_offset,
+                    preview.char_offset + preview.char_length,
+                    preview.prompt_tokens,
+                    path.display());
+            }
+        }
+
+        println!("{} {}", style(">>").green(), report.describe());
+        println!("Prompts written to: {}", dir.display());
+        Ok(())
+    }
+
     fn load_examples(path: &PathBuf) -> Result<Vec<ExampleData>, Box<dyn std::error::Error>> {
         let content = fs::read_to_string(path)?;
         
@@ -823,6 +1938,122 @@ OLLAMA_BASE_URL=http://localhost:11434
         }
     }
 
+    async fn cache_command(args: CacheCommandArgs) -> Result<(), Box<dyn std::error::Error>> {
+        let (ttl, max_mb) = match &args.action {
+            CacheAction::Stats { ttl } => (*ttl, None),
+            CacheAction::Clear => (None, None),
+            CacheAction::Prune { ttl, max_mb } => (*ttl, *max_mb),
+        };
+        let cache = ResponseCache::open(cache_config(args.dir, ttl, max_mb))?;
+        let dir = cache.config().dir.display().to_string();
+
+        match args.action {
+            CacheAction::Stats { .. } => {
+                let stats = cache.stats()?;
+                println!("{}", style("Response cache").bold().cyan());
+                println!("Directory: {}", style(&dir).cyan());
+                println!("Entries: {}", stats.entries);
+                println!("Size: {:.2} MB", stats.total_bytes as f64 / (1024.0 * 1024.0));
+                if ttl.is_some() {
+                    println!("Expired: {}", stats.expired);
+                }
+                if let (Some(oldest), Some(newest)) = (stats.oldest_age_secs, stats.newest_age_secs) {
+                    println!("Oldest entry: {}s ago", oldest);
+                    println!("Newest entry: {}s ago", newest);
+                }
+            }
+            CacheAction::Clear => {
+                let removed = cache.clear()?;
+                println!("Removed {} cached responses from {}", removed, dir);
+            }
+            CacheAction::Prune { .. } => {
+                let removed = cache.prune()?;
+                println!("Pruned {} cached responses from {}", removed, dir);
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn batch_command(args: BatchCommandArgs) -> Result<(), Box<dyn std::error::Error>> {
+        dotenvy::dotenv().ok();
+        let api_key = args.api_key
+            .or_else(|| std::env::var("OPENAI_API_KEY").ok())
+            .ok_or("An API key is required: pass --api-key or set OPENAI_API_KEY")?;
+
+        match args.action {
+            BatchAction::Status { state } => {
+                let mut job = BatchJob::load(&state)?;
+                job.refresh(&api_key).await?;
+                job.save(&state)?;
+
+                let counts = job.request_counts;
+                println!("{}", style("Batch job").bold().cyan());
+                println!("Batch: {}", job.batch_id);
+                println!("Model: {}", job.model);
+                println!("Submitted: {}", job.submitted_at.to_rfc3339());
+                println!("Status: {}", job.status);
+                println!("Requests: {} completed, {} failed of {}", counts.completed, counts.failed, counts.total);
+            }
+            BatchAction::Collect { state, output, wait, poll_interval } => {
+                let mut job = BatchJob::load(&state)?;
+                let status = if wait {
+                    job.wait(&api_key, std::time::Duration::from_secs(poll_interval)).await?
+                } else {
+                    job.refresh(&api_key).await?
+                };
+                job.save(&state)?;
+                if status != BatchStatus::Completed {
+                    return Err(format!("Batch {} is {}; nothing to collect yet", job.batch_id, status).into());
+                }
+
+                let result = job.collect(&api_key).await.map_err(handle_extraction_error)?;
+                let content = serde_json::to_string_pretty(&result)?;
+                match &output {
+                    Some(path) => {
+                        fs::write(path, content)?;
+                        eprintln!("Results saved to: {}", path.display());
+                    }
+                    None => println!("{}", content),
+                }
+                eprintln!("{} Found {} extractions", style(">>").green(), result.extraction_count());
+                if let Some(usage) = result.usage.as_ref().filter(|u| u.calls > 0) {
+                    eprintln!("{} Usage: {}", style(">>").green(), usage.describe());
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn cache_config(dir: Option<PathBuf>, ttl_secs: Option<u64>, max_mb: Option<u64>) -> CacheConfig {
+        let dir = dir.unwrap_or_else(|| {
+            dirs::cache_dir()
+                .unwrap_or_else(|| PathBuf::from(".cache"))
+                .join("langextract-rust")
+                .join("responses")
+        });
+        let mut config = CacheConfig::new(dir);
+        if let Some(ttl) = ttl_secs {
+            config = config.with_ttl_secs(ttl);
+        }
+        if let Some(max_mb) = max_mb {
+            config = config.with_max_size_bytes(max_mb * 1024 * 1024);
+        }
+        config
+    }
+
+    fn load_custom_mapping(path: &PathBuf) -> Result<CustomHttpMapping, Box<dyn std::error::Error>> {
+        let content = fs::read_to_string(path)?;
+
+        if path.extension().and_then(|s| s.to_str()) == Some("yaml") ||
+           path.extension().and_then(|s| s.to_str()) == Some("yml") {
+            Ok(serde_yaml::from_str(&content)?)
+        } else {
+            Ok(serde_json::from_str(&content)?)
+        }
+    }
+
     fn get_default_examples() -> Vec<ExampleData> {
         vec![
             ExampleData::new(
@@ -863,6 +2094,22 @@ temperature: 0.3
 max_char_buffer: 8000
 max_workers: 6
 batch_length: 4
+"#,
+            ProviderType::Gemini => r#"# Gemini Configuration
+model: "gemini-2.5-flash"
+provider: "gemini"
+temperature: 0.3
+max_char_buffer: 8000
+max_workers: 6
+batch_length: 4
+"#,
+            ProviderType::Anthropic => r#"# Anthropic Configuration
+model: "claude-sonnet-4-5"
+provider: "anthropic"
+temperature: 0.3
+max_char_buffer: 8000
+max_workers: 6
+batch_length: 4
 "#,
             ProviderType::Custom => r#"# Custom Provider Configuration
 model: "your-model"
@@ -937,6 +2184,21 @@ batch_length: 4
         Ok(())
     }
 
+    /// Report the chunks a partial result is missing, on stderr
+    fn print_coverage(coverage: &CoverageReport) {
+        eprintln!("{} Partial result: {}", style("Warning:").yellow().bold(), coverage.describe());
+        for failure in &coverage.failed_chunks {
+            eprintln!("   chunk {} (chars {}..{}): {}",
+                failure.chunk_id,
+                failure.char_offset,
+                failure.char_offset + failure.char_length,
+                failure.error);
+            if let Some(raw) = &failure.raw_output_file {
+                eprintln!("      raw output: {}", raw);
+            }
+        }
+    }
+
     fn handle_extraction_error(error: LangExtractError) -> Box<dyn std::error::Error> {
         match &error {
             LangExtractError::NetworkError(_) => {
@@ -947,6 +2209,10 @@ batch_length: 4
                 eprintln!("{}", style("Configuration Error:").red().bold());
                 eprintln!("   Check your API keys and model settings");
             }
+            LangExtractError::BudgetExceeded(_) => {
+                eprintln!("{}", style("Budget Exceeded:").red().bold());
+                eprintln!("   Raise --max-tokens/--max-cost or reduce the input size");
+            }
             LangExtractError::InferenceError { provider, .. } => {
                 eprintln!("{}", style("Inference Error:").red().bold());
                 if let Some(provider) = provider {
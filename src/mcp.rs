@@ -0,0 +1,418 @@
+//! Model Context Protocol server exposing extraction as tools.
+//!
+//! [`serve`] speaks MCP over a reader/writer pair: JSON-RPC 2.0 messages, one
+//! per line. [`serve_stdio`] runs it on stdin and stdout, so an MCP client can
+//! launch `lx-rs mcp` as a subprocess. The server lists three tools:
+//!
+//! - `extract`: text, examples and [`RequestConfig`] settings; returns the
+//!   [`AnnotatedDocument`](crate::AnnotatedDocument) with character intervals
+//! - `run_pipeline`: runs a loaded pipeline by name; returns a
+//!   [`PipelineRunResponse`]
+//! - `list_pipelines`: names, descriptions and steps of the loaded pipelines
+//!
+//! Input schemas are generated from the types the HTTP server accepts, so a
+//! tool call can't name a provider or credentials; those come only from
+//! [`ServerConfig`]. Tool calls run concurrently, and a
+//! `notifications/cancelled` from the client cancels the matching call.
+
+use crate::{
+    cancellation::CancellationToken,
+    data::ExampleData,
+    exceptions::{LangExtractError, LangExtractResult},
+    extract,
+    pipeline::PipelineExecutor,
+    server::{PipelineRunResponse, RequestConfig, ServerConfig},
+};
+use schemars::JsonSchema;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::sync::mpsc;
+use tokio::task::JoinSet;
+
+/// Protocol version answered when the client asks for one not supported here
+const PROTOCOL_VERSION: &str = "2025-06-18";
+
+/// Protocol versions a client may negotiate
+const SUPPORTED_VERSIONS: &[&str] = &["2024-11-05", "2025-03-26", PROTOCOL_VERSION];
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+/// Arguments of the `extract` tool
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct ExtractToolInput {
+    /// Text to extract from
+    pub text: String,
+    /// Instructions for what to extract
+    #[serde(default)]
+    pub prompt: Option<String>,
+    /// Examples of the extractions wanted, each with its source text
+    pub examples: Vec<ExampleData>,
+    /// Extraction settings
+    #[serde(default)]
+    pub config: Option<RequestConfig>,
+}
+
+/// Arguments of the `run_pipeline` tool
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct RunPipelineToolInput {
+    /// Pipeline to run, as listed by `list_pipelines`
+    pub name: String,
+    /// Text to run the pipeline on
+    pub text: String,
+    /// Time limit for the run in milliseconds
+    #[serde(default)]
+    pub timeout_ms: Option<u64>,
+}
+
+/// A JSON-RPC error object
+#[derive(Debug)]
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+impl RpcError {
+    fn new(code: i64, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+struct McpServer {
+    config: ServerConfig,
+    /// Cancellation of the tool calls in progress, by JSON-RPC request ID
+    in_flight: Mutex<HashMap<String, CancellationToken>>,
+}
+
+impl McpServer {
+    /// Answer a request other than `tools/call`
+    fn handle(&self, method: &str, params: &Value) -> Result<Value, RpcError> {
+        match method {
+            "initialize" => {
+                let requested = params.get("protocolVersion").and_then(Value::as_str);
+                let version = requested
+                    .filter(|v| SUPPORTED_VERSIONS.contains(v))
+                    .unwrap_or(PROTOCOL_VERSION);
+                Ok(json!({
+                    "protocolVersion": version,
+                    "capabilities": { "tools": { "listChanged": false } },
+                    "serverInfo": { "name": env!("CARGO_PKG_NAME"), "version": env!("CARGO_PKG_VERSION") },
+                }))
+            }
+            "ping" => Ok(json!({})),
+            "tools/list" => Ok(json!({ "tools": self.tools() })),
+            _ => Err(RpcError::new(METHOD_NOT_FOUND, format!("Unknown method '{}'", method))),
+        }
+    }
+
+    fn tools(&self) -> Vec<Value> {
+        let mut run_pipeline = input_schema::<RunPipelineToolInput>();
+        if !self.config.pipelines.is_empty() {
+            run_pipeline["properties"]["name"]["enum"] = json!(self.config.pipelines.keys().collect::<Vec<_>>());
+        }
+        vec![
+            json!({
+                "name": "extract",
+                "description": "Extract structured information from text, guided by examples. \
+                    Returns the annotated document; each extraction carries its character \
+                    interval in the text.",
+                "inputSchema": input_schema::<ExtractToolInput>(),
+            }),
+            json!({
+                "name": "run_pipeline",
+                "description": "Run a multi-step extraction pipeline on text. Returns the nested \
+                    output and the result of each step.",
+                "inputSchema": run_pipeline,
+            }),
+            json!({
+                "name": "list_pipelines",
+                "description": "List the pipelines run_pipeline can run, with their steps.",
+                "inputSchema": { "type": "object", "properties": {} },
+            }),
+        ]
+    }
+
+    /// Run a tool; errors of the tool itself are reported in the result with
+    /// `isError` set, so the model calling it can see them
+    async fn call_tool(&self, params: Value, cancel: CancellationToken) -> Result<Value, RpcError> {
+        let name = params
+            .get("name")
+            .and_then(Value::as_str)
+            .ok_or_else(|| RpcError::new(INVALID_PARAMS, "Missing tool name"))?;
+        let arguments = params.get("arguments").cloned().unwrap_or_else(|| json!({}));
+
+        let output = match name {
+            "extract" => self.extract(arguments_of(arguments)?, cancel).await,
+            "run_pipeline" => self.run_pipeline(arguments_of(arguments)?, cancel).await,
+            "list_pipelines" => Ok(self.config.pipeline_summaries()),
+            _ => return Err(RpcError::new(INVALID_PARAMS, format!("Unknown tool '{}'", name))),
+        };
+        Ok(match output {
+            Ok(value) => json!({
+                "content": [{ "type": "text", "text": value.to_string() }],
+                "structuredContent": value,
+                "isError": false,
+            }),
+            Err(e) => json!({
+                "content": [{ "type": "text", "text": e.to_string() }],
+                "isError": true,
+            }),
+        })
+    }
+
+    async fn extract(&self, input: ExtractToolInput, cancel: CancellationToken) -> LangExtractResult<Value> {
+        let mut config = self.config.extract_config(&input.config.unwrap_or_default())?;
+        config.cancellation = Some(cancel);
+        let document = extract(&input.text, input.prompt.as_deref(), &input.examples, config).await?;
+        serde_json::to_value(document).map_err(|e| LangExtractError::serialization(e.to_string()))
+    }
+
+    async fn run_pipeline(&self, input: RunPipelineToolInput, cancel: CancellationToken) -> LangExtractResult<Value> {
+        let mut pipeline = self.config.pipeline_config(&input.name, input.timeout_ms)?;
+        pipeline.cancellation = Some(cancel);
+        let result = PipelineExecutor::new(pipeline).execute(&input.text).await?;
+        serde_json::to_value(PipelineRunResponse::new(input.name, result))
+            .map_err(|e| LangExtractError::serialization(e.to_string()))
+    }
+
+    /// Handle `notifications/cancelled` for a call in progress
+    fn cancel(&self, params: &Value) {
+        if let Some(id) = params.get("requestId") {
+            if let Some(token) = self.in_flight.lock().unwrap().get(&id.to_string()) {
+                token.cancel();
+            }
+        }
+    }
+}
+
+/// JSON Schema of a tool's arguments
+fn input_schema<T: JsonSchema>() -> Value {
+    schemars::schema_for!(T).to_value()
+}
+
+fn arguments_of<T: for<'de> Deserialize<'de>>(arguments: Value) -> Result<T, RpcError> {
+    serde_json::from_value(arguments).map_err(|e| RpcError::new(INVALID_PARAMS, format!("Invalid arguments: {}", e)))
+}
+
+fn reply(id: Value, outcome: Result<Value, RpcError>) -> Value {
+    match outcome {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err(e) => json!({ "jsonrpc": "2.0", "id": id, "error": { "code": e.code, "message": e.message } }),
+    }
+}
+
+/// Serve MCP on stdin and stdout until stdin closes
+///
+/// Stdout carries protocol messages only; logs go to stderr.
+pub async fn serve_stdio(config: ServerConfig) -> LangExtractResult<()> {
+    serve(config, tokio::io::BufReader::new(tokio::io::stdin()), tokio::io::stdout()).await
+}
+
+/// Serve MCP for `config`, reading requests from `reader` and writing
+/// responses to `writer`, until `reader` ends
+///
+/// Calls still running when the input ends are finished and answered before
+/// this returns.
+pub async fn serve<R, W>(config: ServerConfig, reader: R, writer: W) -> LangExtractResult<()>
+where
+    R: AsyncBufRead + Unpin,
+    W: AsyncWrite + Unpin + Send + 'static,
+{
+    let server = Arc::new(McpServer {
+        config,
+        in_flight: Mutex::new(HashMap::new()),
+    });
+    let (tx, rx) = mpsc::unbounded_channel();
+    let writer = tokio::spawn(write_messages(writer, rx));
+    let mut calls = JoinSet::new();
+
+    let mut lines = reader.lines();
+    while let Some(line) = lines.next_line().await? {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let message = match serde_json::from_str::<Value>(&line) {
+            Ok(message) if message.is_object() => message,
+            Ok(_) => {
+                let _ = tx.send(reply(Value::Null, Err(RpcError::new(INVALID_REQUEST, "Expected a JSON object"))));
+                continue;
+            }
+            Err(e) => {
+                let _ = tx.send(reply(Value::Null, Err(RpcError::new(PARSE_ERROR, e.to_string()))));
+                continue;
+            }
+        };
+        // Messages without a method are responses, and this server sends no
+        // requests to answer
+        let Some(method) = message.get("method").and_then(Value::as_str) else {
+            continue;
+        };
+        let params = message.get("params").cloned().unwrap_or(Value::Null);
+        let Some(id) = message.get("id").cloned() else {
+            if method == "notifications/cancelled" {
+                server.cancel(&params);
+            }
+            continue;
+        };
+
+        if method == "tools/call" {
+            let cancel = CancellationToken::new();
+            server.in_flight.lock().unwrap().insert(id.to_string(), cancel.clone());
+            let (server, tx) = (server.clone(), tx.clone());
+            calls.spawn(async move {
+                let outcome = server.call_tool(params, cancel.clone()).await;
+                server.in_flight.lock().unwrap().remove(&id.to_string());
+                // The protocol asks for no response to a cancelled request
+                if !cancel.is_cancelled() {
+                    let _ = tx.send(reply(id, outcome));
+                }
+            });
+        } else {
+            let _ = tx.send(reply(id, server.handle(method, &params)));
+        }
+        while calls.try_join_next().is_some() {}
+    }
+
+    while calls.join_next().await.is_some() {}
+    drop(tx);
+    writer
+        .await
+        .map_err(|e| LangExtractError::unexpected(format!("MCP writer failed: {}", e)))??;
+    Ok(())
+}
+
+async fn write_messages<W: AsyncWrite + Unpin>(
+    mut writer: W,
+    mut messages: mpsc::UnboundedReceiver<Value>,
+) -> std::io::Result<()> {
+    while let Some(message) = messages.recv().await {
+        writer.write_all(format!("{}\n", message).as_bytes()).await?;
+        writer.flush().await?;
+    }
+    writer.shutdown().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pipeline::utils::create_requirements_pipeline;
+    use crate::providers::ProviderConfig;
+    use crate::test_support::{ollama, person_examples};
+    use tokio::io::BufReader;
+
+    /// Send `messages` to a server for `config` the way a client would,
+    /// close its input, and collect the responses by ID
+    async fn session(config: ServerConfig, messages: &[Value]) -> HashMap<i64, Value> {
+        let (client, server) = tokio::io::duplex(1 << 16);
+        let (server_read, server_write) = tokio::io::split(server);
+        let running = tokio::spawn(serve(config, BufReader::new(server_read), server_write));
+
+        let (client_read, mut client_write) = tokio::io::split(client);
+        for message in messages {
+            client_write.write_all(format!("{}\n", message).as_bytes()).await.unwrap();
+        }
+        client_write.write_all(b"not json\n").await.unwrap();
+        client_write.shutdown().await.unwrap();
+
+        let mut responses = HashMap::new();
+        let mut lines = BufReader::new(client_read).lines();
+        while let Some(line) = lines.next_line().await.unwrap() {
+            let response: Value = serde_json::from_str(&line).unwrap();
+            responses.insert(response["id"].as_i64().unwrap_or(-1), response);
+        }
+        running.await.unwrap().unwrap();
+        responses
+    }
+
+    fn script() -> Vec<Value> {
+        let examples = person_examples();
+        let call = |id: i64, name: &str, arguments: Value| {
+            json!({ "jsonrpc": "2.0", "id": id, "method": "tools/call", "params": { "name": name, "arguments": arguments } })
+        };
+        vec![
+            json!({ "jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {
+                "protocolVersion": "2025-06-18",
+                "capabilities": {},
+                "clientInfo": { "name": "script", "version": "0" },
+            } }),
+            json!({ "jsonrpc": "2.0", "method": "notifications/initialized" }),
+            json!({ "jsonrpc": "2.0", "id": 2, "method": "tools/list" }),
+            call(3, "extract", json!({ "text": "Ada Lovelace wrote notes.", "examples": examples })),
+            call(4, "list_pipelines", json!({})),
+            call(5, "run_pipeline", json!({ "name": "reqs", "text": "Ada Lovelace shall review the notes." })),
+            call(6, "delete_everything", json!({})),
+            json!({ "jsonrpc": "2.0", "id": 7, "method": "resources/list" }),
+        ]
+    }
+
+    fn server_config(provider: ProviderConfig) -> ServerConfig {
+        let mut pipeline = create_requirements_pipeline();
+        pipeline.steps.truncate(1);
+        ServerConfig::new(provider).with_pipeline("reqs", pipeline)
+    }
+
+    #[tokio::test]
+    async fn test_scripted_session_records_then_replays() {
+        let model = ollama().await;
+        let dir = tempfile::tempdir().unwrap();
+        let fixture = dir.path().join("session.json");
+
+        let recorded = session(
+            server_config(ProviderConfig::ollama("mistral", Some(model.uri())).with_record(&fixture)),
+            &script(),
+        )
+        .await;
+
+        // One response per request, none for the notification
+        assert_eq!(recorded.len(), 8);
+        assert_eq!(recorded[&-1]["error"]["code"], PARSE_ERROR);
+        assert_eq!(recorded[&1]["result"]["protocolVersion"], "2025-06-18");
+
+        let tools = recorded[&2]["result"]["tools"].as_array().unwrap();
+        let names: Vec<_> = tools.iter().map(|t| t["name"].as_str().unwrap()).collect();
+        assert_eq!(names, vec!["extract", "run_pipeline", "list_pipelines"]);
+        let extract_schema = tools[0]["inputSchema"].to_string();
+        assert!(extract_schema.contains("max_char_buffer") && extract_schema.contains("extraction_text"));
+        assert!(!extract_schema.contains("api_key"));
+        assert_eq!(tools[1]["inputSchema"]["properties"]["name"]["enum"], json!(["reqs"]));
+
+        let document = &recorded[&3]["result"];
+        assert_eq!(document["isError"], false);
+        let extraction = &document["structuredContent"]["extractions"][0];
+        assert_eq!(extraction["extraction_text"], "Ada Lovelace");
+        assert_eq!(extraction["char_interval"], json!({ "start_pos": 0, "end_pos": 12 }));
+
+        assert_eq!(recorded[&4]["result"]["structuredContent"]["pipelines"][0]["name"], "reqs");
+        let pipeline = &recorded[&5]["result"];
+        assert_eq!(pipeline["isError"], false);
+        assert_eq!(pipeline["structuredContent"]["pipeline"], "reqs");
+        assert_eq!(recorded[&6]["error"]["code"], INVALID_PARAMS);
+        assert_eq!(recorded[&7]["error"]["code"], METHOD_NOT_FOUND);
+
+        // Replaying the fixture gives the same results without the model
+        drop(model);
+        let replayed = session(
+            server_config(ProviderConfig::ollama("mistral", None).with_replay(&fixture)),
+            &script(),
+        )
+        .await;
+        assert_eq!(
+            replayed[&3]["result"]["structuredContent"]["extractions"],
+            document["structuredContent"]["extractions"]
+        );
+        assert_eq!(
+            replayed[&5]["result"]["structuredContent"]["nested_output"]["extract_requirements"]["extractions"],
+            pipeline["structuredContent"]["nested_output"]["extract_requirements"]["extractions"]
+        );
+    }
+}
@@ -5,19 +5,120 @@
 //! - Re-processes low-yield chunks with different strategies
 //! - Uses initial results to refine subsequent passes
 //! - Provides quality scoring and filtering for extractions
+//! - Optionally samples each chunk several times and keeps only the
+//!   extractions enough samples agree on (consensus mode)
 
 use crate::{
     alignment::{AlignmentStats, TextAligner},
     annotation::Annotator,
+    checkpoint::ChunkCheckpoints,
     chunking::{ChunkResult, TextChunk, TextChunker},
-    data::{AnnotatedDocument, Extraction},
-    exceptions::LangExtractResult,
+    data::{AnnotatedDocument, ChunkFailure, CoverageReport, Extraction},
+    diff::same_item,
+    evaluation::match_pairs,
+    exceptions::{LangExtractError, LangExtractResult},
+    providers::ProviderConfig,
     resolver::Resolver,
+    usage::UsageSummary,
 };
-use futures::future::join_all;
-use std::collections::{HashMap, HashSet};
+use futures::stream::{FuturesUnordered, StreamExt, TryStreamExt};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::time::{Duration, Instant};
 
+/// Extraction attribute holding the share of consensus samples that found it
+pub const CONFIDENCE_ATTRIBUTE: &str = "confidence";
+
+/// Extraction attribute holding the number of consensus samples that found it
+pub const CONSENSUS_VOTES_ATTRIBUTE: &str = "consensus_votes";
+
+/// Consensus voting across repeated samples of each chunk
+///
+/// Every chunk is extracted `samples` times; extractions are clustered by
+/// class and aligned span, and a cluster is kept when at least `min_votes`
+/// samples found it. Kept extractions carry their agreement ratio in the
+/// [`CONFIDENCE_ATTRIBUTE`] attribute. Samples are not checkpointed, so a
+/// run can't combine consensus with checkpoints.
+///
+/// Samples run concurrently, sharing the run's `max_workers` between them.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConsensusConfig {
+    /// Number of samples taken per chunk
+    pub samples: usize,
+    /// Samples that must find an extraction for it to be kept
+    pub min_votes: usize,
+    /// Temperature for each sample, repeated when shorter than `samples`;
+    /// the run's temperature when empty
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub temperatures: Vec<f32>,
+    /// Provider for each sample, repeated when shorter than `samples`; the
+    /// run's model when empty
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub models: Vec<ProviderConfig>,
+}
+
+impl ConsensusConfig {
+    /// Take `samples` samples per chunk, keeping what a majority found
+    pub fn new(samples: usize) -> Self {
+        Self {
+            samples,
+            min_votes: samples / 2 + 1,
+            temperatures: Vec::new(),
+            models: Vec::new(),
+        }
+    }
+
+    /// Keep extractions found by at least `min_votes` samples
+    pub fn with_min_votes(mut self, min_votes: usize) -> Self {
+        self.min_votes = min_votes;
+        self
+    }
+
+    /// Sample at these temperatures in turn
+    pub fn with_temperatures(mut self, temperatures: Vec<f32>) -> Self {
+        self.temperatures = temperatures;
+        self
+    }
+
+    /// Sample these providers in turn
+    pub fn with_models(mut self, models: Vec<ProviderConfig>) -> Self {
+        self.models = models;
+        self
+    }
+
+    /// Check that the vote threshold can be met
+    pub fn validate(&self) -> LangExtractResult<()> {
+        if self.samples == 0 {
+            return Err(LangExtractError::configuration("Consensus needs at least one sample"));
+        }
+        if self.min_votes == 0 || self.min_votes > self.samples {
+            return Err(LangExtractError::configuration(format!(
+                "Consensus min_votes must be between 1 and {} (the number of samples), got {}",
+                self.samples, self.min_votes
+            )));
+        }
+        Ok(())
+    }
+
+    /// Temperature for the given sample, if one is configured
+    pub fn temperature_for(&self, sample: usize) -> Option<f32> {
+        if self.temperatures.is_empty() {
+            None
+        } else {
+            Some(self.temperatures[sample % self.temperatures.len()])
+        }
+    }
+
+    /// Provider for the given sample, if one is configured
+    pub fn model_for(&self, sample: usize) -> Option<&ProviderConfig> {
+        if self.models.is_empty() {
+            None
+        } else {
+            Some(&self.models[sample % self.models.len()])
+        }
+    }
+}
+
 /// Configuration for multi-pass extraction
 #[derive(Debug, Clone)]
 pub struct MultiPassConfig {
@@ -41,6 +142,8 @@ pub struct MultiPassConfig {
     pub batch_length: usize,
     /// Maximum number of concurrent workers
     pub max_workers: usize,
+    /// Vote across repeated samples instead of running refinement passes
+    pub consensus: Option<ConsensusConfig>,
 }
 
 impl Default for MultiPassConfig {
@@ -56,6 +159,7 @@ impl Default for MultiPassConfig {
             max_char_buffer: 1000,
             batch_length: 10,
             max_workers: 10,
+            consensus: None,
         }
     }
 }
@@ -77,6 +181,10 @@ pub struct MultiPassStats {
     pub final_alignment_stats: AlignmentStats,
     /// Quality statistics
     pub quality_stats: QualityStats,
+    /// Token usage across all passes
+    pub usage: UsageSummary,
+    /// Spans no pass processed successfully, when partial results are allowed
+    pub coverage: Option<CoverageReport>,
 }
 
 /// Quality statistics for extractions
@@ -111,6 +219,8 @@ pub struct ScoredExtraction {
 pub struct MultiPassProcessor {
     config: MultiPassConfig,
     annotator: Annotator,
+    /// Annotators for the consensus samples after the first
+    samplers: Vec<Annotator>,
     resolver: Resolver,
     aligner: TextAligner,
 }
@@ -125,11 +235,19 @@ impl MultiPassProcessor {
         Self {
             config,
             annotator,
+            samplers: Vec::new(),
             resolver,
             aligner: TextAligner::new(),
         }
     }
 
+    /// Take consensus samples with these annotators in turn after the
+    /// processor's own, e.g. to vary the model or temperature per sample
+    pub fn with_samplers(mut self, samplers: Vec<Annotator>) -> Self {
+        self.samplers = samplers;
+        self
+    }
+
     /// Perform multi-pass extraction on text
     #[tracing::instrument(skip_all, fields(text_len = text.len(), max_passes = self.config.max_passes))]
     pub async fn extract_multipass(
@@ -160,34 +278,38 @@ impl MultiPassProcessor {
                 low_quality_count: 0,
                 filtered_count: 0,
             },
+            usage: UsageSummary::default(),
+            coverage: None,
         };
 
-        let all_scored_extractions: Vec<ScoredExtraction>;
-
-        if text.len() <= self.config.max_char_buffer {
-            // Single text processing with multi-pass
-            all_scored_extractions = self.process_single_text_multipass(
-                text,
-                additional_context,
-                &mut stats,
-                debug,
-            ).await?;
+        let final_extractions = if let Some(consensus) = &self.config.consensus {
+            self.extract_consensus(text, additional_context, consensus, &mut stats, debug).await?
         } else {
-            // Chunked processing with multi-pass
-            all_scored_extractions = self.process_chunked_text_multipass(
-                text,
-                additional_context,
+            let all_scored_extractions = if text.len() <= self.config.max_char_buffer {
+                // Single text processing with multi-pass
+                self.process_single_text_multipass(
+                    text,
+                    additional_context,
+                    &mut stats,
+                    debug,
+                ).await?
+            } else {
+                // Chunked processing with multi-pass
+                self.process_chunked_text_multipass(
+                    text,
+                    additional_context,
+                    &mut stats,
+                    debug,
+                ).await?
+            };
+
+            // Filter extractions by quality and deduplicate
+            self.filter_and_deduplicate_extractions(
+                all_scored_extractions,
                 &mut stats,
                 debug,
-            ).await?;
-        }
-
-        // Filter extractions by quality and deduplicate
-        let final_extractions = self.filter_and_deduplicate_extractions(
-            all_scored_extractions,
-            &mut stats,
-            debug,
-        );
+            )
+        };
 
         // Calculate final alignment statistics
         stats.final_alignment_stats = self.aligner.get_alignment_stats(&final_extractions);
@@ -196,6 +318,8 @@ impl MultiPassProcessor {
         let mut result = AnnotatedDocument::new();
         result.text = Some(text.to_string());
         result.extractions = Some(final_extractions);
+        result.usage = Some(stats.usage.clone());
+        result.coverage = stats.coverage.clone();
 
         if debug {
             self.print_multipass_summary(&stats);
@@ -204,6 +328,70 @@ impl MultiPassProcessor {
         Ok((result, stats))
     }
 
+    /// Extract the text once per consensus sample and keep the extractions
+    /// enough of them agree on
+    ///
+    /// Samples run concurrently with `max_workers` split between them, so no
+    /// more than `max_workers` model calls are in flight across all samples.
+    async fn extract_consensus(
+        &self,
+        text: &str,
+        additional_context: Option<&str>,
+        consensus: &ConsensusConfig,
+        stats: &mut MultiPassStats,
+        debug: bool,
+    ) -> LangExtractResult<Vec<Extraction>> {
+        consensus.validate()?;
+        let start_time = Instant::now();
+
+        let max_workers = self.config.max_workers.max(1);
+        let concurrent_samples = consensus.samples.min(max_workers);
+        let workers_per_sample = max_workers / concurrent_samples;
+
+        let annotators: Vec<&Annotator> = std::iter::once(&self.annotator).chain(&self.samplers).collect();
+        let results: Vec<AnnotatedDocument> = futures::stream::iter(0..consensus.samples)
+            .map(|sample| {
+                annotators[sample % annotators.len()].annotate_text(
+                    text,
+                    &self.resolver,
+                    self.config.max_char_buffer,
+                    self.config.batch_length,
+                    additional_context,
+                    false,
+                    workers_per_sample,
+                )
+            })
+            .buffered(concurrent_samples)
+            .try_collect()
+            .await?;
+
+        let mut samples = Vec::with_capacity(results.len());
+        let mut coverages = Vec::with_capacity(results.len());
+        for result in results {
+            if let Some(usage) = &result.usage {
+                stats.usage.merge(usage);
+            }
+            let extractions = result.extractions.unwrap_or_default();
+            stats.extractions_per_pass.push(extractions.len());
+            stats.time_per_pass.push(start_time.elapsed());
+            coverages.push(result.coverage);
+            samples.push(extractions);
+        }
+        stats.total_passes = samples.len();
+        stats.coverage = merge_sample_coverage(coverages);
+
+        let candidates: usize = samples.iter().map(Vec::len).sum();
+        let kept = vote_on_extractions(samples, consensus.min_votes);
+        stats.quality_stats.filtered_count = candidates - kept.len();
+
+        if debug {
+            log::debug!("[multipass] consensus kept {} extractions found by at least {} of {} samples",
+                kept.len(), consensus.min_votes, consensus.samples);
+        }
+
+        Ok(kept)
+    }
+
     /// Process a single text with multiple passes
     async fn process_single_text_multipass(
         &self,
@@ -240,6 +428,16 @@ impl MultiPassProcessor {
                 self.config.max_workers,
             ).await?;
 
+            if let Some(usage) = &result.usage {
+                stats.usage.merge(usage);
+            }
+            // The text counts as processed once any pass succeeds
+            if let Some(coverage) = &result.coverage {
+                if pass_num == 1 || coverage.is_complete() {
+                    stats.coverage = Some(coverage.clone());
+                }
+            }
+
             // Score and collect new extractions
             let mut pass_extractions = Vec::new();
             if let Some(extractions) = result.extractions {
@@ -269,6 +467,10 @@ impl MultiPassProcessor {
                     pass_num, stats.extractions_per_pass.last().unwrap_or(&0));
             }
 
+            if self.annotator.stop_signal().is_stopped() {
+                break;
+            }
+
             // Early termination if no new extractions found
             if stats.extractions_per_pass.last() == Some(&0) {
                 if debug {
@@ -293,6 +495,16 @@ impl MultiPassProcessor {
         let chunker = TextChunker::new();
         let initial_chunks = chunker.chunk_text(text, None)?;
 
+        // Only the first pass is checkpointed; later passes depend on its results
+        let checkpoints = self.annotator.open_checkpoints(text, &initial_chunks, additional_context)?;
+        let mut restored = match &checkpoints {
+            Some(checkpoints) => checkpoints.completed()?,
+            None => Vec::new(),
+        };
+
+        let total_chunks = initial_chunks.len();
+        let mut failed_chunks: BTreeMap<usize, ChunkFailure> = BTreeMap::new();
+
         let mut all_extractions = Vec::new();
         let mut chunks_to_process = initial_chunks;
         let mut processed_extraction_texts = HashSet::new();
@@ -305,20 +517,40 @@ impl MultiPassProcessor {
                     pass_num, self.config.max_passes, chunks_to_process.len());
             }
 
-            // Process chunks for this pass
-            let pass_results = self.process_chunks_for_pass(
-                &chunks_to_process,
+            // Process chunks for this pass, skipping those restored from a checkpoint
+            let restored_ids = ChunkCheckpoints::completed_ids(&restored);
+            let pending: Vec<TextChunk> = chunks_to_process.iter()
+                .filter(|chunk| !restored_ids.contains(&chunk.id))
+                .cloned()
+                .collect();
+            let mut pass_results = self.process_chunks_for_pass(
+                &pending,
                 additional_context,
                 pass_num,
                 &all_extractions,
+                if pass_num == 1 { checkpoints.as_ref() } else { None },
                 debug,
             ).await?;
+            pass_results.append(&mut restored);
 
             // Collect new extractions and identify low-yield chunks
             let mut pass_extractions = Vec::new();
             let mut low_yield_chunks = Vec::new();
 
             for result in pass_results {
+                if let Some(usage) = &result.usage {
+                    stats.usage.merge(usage);
+                }
+                // A chunk that failed the first pass is covered if a later pass succeeds on it
+                match result.to_failure() {
+                    Some(failure) if pass_num == 1 => {
+                        failed_chunks.insert(result.chunk_id, failure);
+                    }
+                    Some(_) => {}
+                    None => {
+                        failed_chunks.remove(&result.chunk_id);
+                    }
+                }
                 let extractions = result.extractions.unwrap_or_default();
                 let extraction_count = extractions.len();
                 
@@ -366,7 +598,8 @@ impl MultiPassProcessor {
             chunks_to_process = low_yield_chunks;
 
             // Early termination conditions
-            if chunks_to_process.is_empty() 
+            if self.annotator.stop_signal().is_stopped()
+                || chunks_to_process.is_empty() 
                 || stats.extractions_per_pass.last() == Some(&0) {
                 if debug {
                     log::debug!("[multipass] no remaining chunks or extractions, stopping");
@@ -376,6 +609,13 @@ impl MultiPassProcessor {
         }
 
         stats.total_passes = stats.extractions_per_pass.len();
+        if self.annotator.partial_results() || self.annotator.stop_signal().is_stopped() {
+            stats.coverage = Some(CoverageReport::new(
+                text.len(),
+                total_chunks,
+                failed_chunks.into_values().collect(),
+            ));
+        }
         Ok(all_extractions)
     }
 
@@ -386,6 +626,7 @@ impl MultiPassProcessor {
         additional_context: Option<&str>,
         pass_number: usize,
         previous_extractions: &[ScoredExtraction],
+        checkpoints: Option<&ChunkCheckpoints>,
         debug: bool,
     ) -> LangExtractResult<Vec<ChunkResult>> {
         let enhanced_context = if pass_number > 1 && self.config.enable_refinement_passes {
@@ -394,17 +635,28 @@ impl MultiPassProcessor {
             additional_context.map(String::from)
         };
 
-        let chunk_futures = chunks.iter().map(|chunk| {
-            self.process_chunk_for_pass(chunk, enhanced_context.as_deref(), debug)
+        let chunk_futures = chunks.iter().map(|chunk| async {
+            let result = self.process_chunk_for_pass(chunk, enhanced_context.as_deref(), debug).await?;
+            if let Some(checkpoints) = checkpoints {
+                checkpoints.save(&result)?;
+            }
+            Ok(result)
         });
 
-        let results = join_all(chunk_futures).await;
-        
+        // Results finished before the run stopped; the rest are dropped
+        let results: Vec<_> = chunk_futures
+            .collect::<FuturesUnordered<_>>()
+            .take_until(self.annotator.stop_signal().stopped())
+            .collect()
+            .await;
+
         // Collect successful results
         let mut chunk_results = Vec::new();
         for result in results {
             match result {
                 Ok(chunk_result) => chunk_results.push(chunk_result),
+                // A checkpoint that can't be written would silently lose work on resume
+                Err(e @ (LangExtractError::BudgetExceeded(_) | LangExtractError::IoError(_))) => return Err(e),
                 Err(e) => {
                     if debug {
                         log::warn!("[multipass] chunk processing failed: {}", e);
@@ -413,6 +665,15 @@ impl MultiPassProcessor {
             }
         }
 
+        let stop = self.annotator.stop_signal();
+        if stop.is_stopped() {
+            let finished: HashSet<usize> = chunk_results.iter().map(|r: &ChunkResult| r.chunk_id).collect();
+            let error = stop.error().to_string();
+            chunk_results.extend(chunks.iter().filter(|chunk| !finished.contains(&chunk.id)).map(|chunk| {
+                ChunkResult::failure(chunk.id, chunk.char_offset, chunk.char_length, error.clone())
+            }));
+        }
+
         Ok(chunk_results)
     }
 
@@ -427,6 +688,19 @@ impl MultiPassProcessor {
 
         match self.annotator.annotate_text(&chunk.text, &self.resolver, self.config.max_char_buffer, self.config.batch_length, additional_context, false, self.config.max_workers).await {
             Ok(annotated_doc) => {
+                // A failure kept by a partial-results annotator
+                if let Some(failure) = annotated_doc.coverage.as_ref().and_then(|c| c.failed_chunks.first()) {
+                    return Ok(ChunkResult::failure(
+                        chunk.id,
+                        chunk.char_offset,
+                        chunk.char_length,
+                        failure.error.clone(),
+                    )
+                    .with_raw_output_file(failure.raw_output_file.clone())
+                    .with_processing_time(start_time.elapsed())
+                    .with_usage(annotated_doc.usage));
+                }
+
                 let mut extractions = annotated_doc.extractions.unwrap_or_default();
                 
                 // Align extractions with the chunk text
@@ -441,8 +715,11 @@ impl MultiPassProcessor {
                     extractions,
                     chunk.char_offset,
                     chunk.char_length,
-                ).with_processing_time(start_time.elapsed()))
+                )
+                .with_processing_time(start_time.elapsed())
+                .with_usage(annotated_doc.usage))
             }
+            Err(e @ LangExtractError::BudgetExceeded(_)) => Err(e),
             Err(e) => {
                 Ok(ChunkResult::failure(
                     chunk.id,
@@ -616,12 +893,86 @@ impl MultiPassProcessor {
             stats.final_alignment_stats.exact,
             stats.final_alignment_stats.fuzzy,
             stats.final_alignment_stats.success_rate() * 100.0);
+
+        log::info!("  usage: {}", stats.usage.describe());
+    }
+}
+
+/// Coverage across consensus samples: a chunk counts as failed only when
+/// every sample failed on it
+fn merge_sample_coverage(reports: Vec<Option<CoverageReport>>) -> Option<CoverageReport> {
+    let base = reports.iter().flatten().next()?.clone();
+    let failed_chunks = base
+        .failed_chunks
+        .into_iter()
+        .filter(|failure| {
+            reports.iter().all(|report| {
+                report
+                    .as_ref()
+                    .is_some_and(|r| r.failed_chunks.iter().any(|f| f.chunk_id == failure.chunk_id))
+            })
+        })
+        .collect();
+    Some(CoverageReport::new(base.total_chars, base.total_chunks, failed_chunks))
+}
+
+/// Cluster the samples' extractions by class and aligned span, keeping the
+/// clusters at least `min_votes` samples contributed to
+///
+/// Each kept extraction is the cluster's most common span and records its
+/// votes and agreement ratio in its attributes.
+fn vote_on_extractions(samples: Vec<Vec<Extraction>>, min_votes: usize) -> Vec<Extraction> {
+    let total = samples.len();
+
+    // Each cluster holds at most one extraction per sample; the first is its anchor
+    let mut clusters: Vec<Vec<Extraction>> = Vec::new();
+    for extractions in samples {
+        let anchors: Vec<Extraction> = clusters.iter().map(|cluster| cluster[0].clone()).collect();
+        let pairs = match_pairs(&anchors, &extractions, same_item);
+
+        let mut unmatched: Vec<Option<Extraction>> = extractions.into_iter().map(Some).collect();
+        for (cluster, pick) in clusters.iter_mut().zip(pairs) {
+            if let Some(extraction) = pick.and_then(|i| unmatched[i].take()) {
+                cluster.push(extraction);
+            }
+        }
+        clusters.extend(unmatched.into_iter().flatten().map(|extraction| vec![extraction]));
     }
+
+    let mut kept: Vec<Extraction> = clusters
+        .into_iter()
+        .filter(|cluster| cluster.len() >= min_votes)
+        .map(|cluster| {
+            let votes = cluster.len();
+            let span = |e: &Extraction| e.char_interval.as_ref().map(|c| (c.start_pos, c.end_pos));
+            // Most common span, the earliest sample winning ties
+            let best = (0..votes)
+                .max_by_key(|&i| {
+                    let count = cluster.iter().filter(|e| span(e) == span(&cluster[i])).count();
+                    (count, std::cmp::Reverse(i))
+                })
+                .unwrap_or(0);
+
+            let mut extraction = cluster.into_iter().nth(best).expect("cluster is never empty");
+            let confidence = (votes as f64 / total as f64 * 100.0).round() / 100.0;
+            let attributes = extraction.attributes.get_or_insert_with(HashMap::new);
+            attributes.insert(CONFIDENCE_ATTRIBUTE.to_string(), serde_json::json!(confidence));
+            attributes.insert(CONSENSUS_VOTES_ATTRIBUTE.to_string(), serde_json::json!(votes));
+            extraction
+        })
+        .collect();
+
+    // Document order, unaligned extractions last
+    kept.sort_by_key(|e| e.char_interval.as_ref().and_then(|c| c.start_pos).unwrap_or(usize::MAX));
+    kept
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::inference::{BaseLanguageModel, ScoredOutput, SharedModel};
+    use async_trait::async_trait;
+    use std::sync::Arc;
 
     #[test]
     fn test_multipass_config_default() {
@@ -665,4 +1016,170 @@ mod tests {
         assert_eq!(extractions[0].extraction.extraction_class, "person");
         assert_eq!(extractions[1].extraction.extraction_class, "organization");
     }
+
+    fn spanned(class: &str, text: &str, start: usize) -> Extraction {
+        let mut extraction = Extraction::new(class.to_string(), text.to_string());
+        extraction.char_interval = Some(crate::data::CharInterval::new(Some(start), Some(start + text.len())));
+        extraction
+    }
+
+    #[test]
+    fn test_consensus_config_validation() {
+        let consensus = ConsensusConfig::new(4);
+        assert_eq!(consensus.min_votes, 3);
+        assert!(consensus.validate().is_ok());
+        assert!(ConsensusConfig::new(3).with_min_votes(4).validate().is_err());
+        assert!(ConsensusConfig::new(0).validate().is_err());
+
+        let consensus = ConsensusConfig::new(3).with_temperatures(vec![0.2, 0.8]);
+        assert_eq!(consensus.temperature_for(2), Some(0.2));
+        assert!(consensus.model_for(0).is_none());
+    }
+
+    #[test]
+    fn test_vote_clusters_by_class_and_span() {
+        let samples = vec![
+            vec![spanned("person", "Ada Lovelace", 0), spanned("place", "London", 35)],
+            vec![spanned("person", "Ada", 0), spanned("place", "London", 35)],
+            // Same span under another class is a separate cluster
+            vec![spanned("person", "Ada Lovelace", 0), spanned("person", "London", 35)],
+        ];
+
+        let kept = vote_on_extractions(samples, 2);
+        assert_eq!(kept.len(), 2);
+
+        let attribute = |e: &Extraction, key: &str| e.attributes.as_ref().unwrap()[key].clone();
+        assert_eq!(kept[0].extraction_text, "Ada Lovelace");
+        assert_eq!(attribute(&kept[0], CONSENSUS_VOTES_ATTRIBUTE), 3);
+        assert_eq!(attribute(&kept[0], CONFIDENCE_ATTRIBUTE), 1.0);
+        assert_eq!(kept[1].extraction_class, "place");
+        assert_eq!(attribute(&kept[1], CONFIDENCE_ATTRIBUTE), 0.67);
+    }
+
+    /// Answers according to the temperature it is called with
+    struct TemperatureModel {
+        seen: std::sync::Mutex<Vec<f64>>,
+    }
+
+    #[async_trait]
+    impl BaseLanguageModel for TemperatureModel {
+        async fn infer(
+            &self,
+            batch_prompts: &[String],
+            kwargs: &HashMap<String, serde_json::Value>,
+        ) -> LangExtractResult<Vec<Vec<ScoredOutput>>> {
+            let temperature = kwargs["temperature"].as_f64().unwrap();
+            self.seen.lock().unwrap().push((temperature * 10.0).round() / 10.0);
+            let output = if temperature > 0.7 {
+                r#"{"person": "Ada Lovelace", "place": "Charles Babbage"}"#
+            } else {
+                r#"{"person": "Ada Lovelace", "place": "London"}"#
+            };
+            Ok(batch_prompts.iter().map(|_| vec![ScoredOutput::from_text(output.to_string())]).collect())
+        }
+
+        fn model_id(&self) -> &str {
+            "temperature"
+        }
+
+        fn provider_name(&self) -> &str {
+            "test"
+        }
+    }
+
+    #[tokio::test]
+    async fn test_consensus_extraction_keeps_agreed_extractions() {
+        let model = Arc::new(TemperatureModel { seen: Default::default() });
+        let mut prompt_template = crate::prompting::PromptTemplateStructured::new(Some("Extract people and places"));
+        prompt_template.examples.push(crate::data::ExampleData::new(
+            "Alan Turing lived in Manchester.".to_string(),
+            vec![
+                Extraction::new("person".to_string(), "Alan Turing".to_string()),
+                Extraction::new("place".to_string(), "Manchester".to_string()),
+            ],
+        ));
+        let config = crate::ExtractConfig::default()
+            .with_consensus(ConsensusConfig::new(3).with_temperatures(vec![0.1, 0.5, 0.9]));
+
+        let result = crate::annotate_with_model(
+            "Ada Lovelace met Charles Babbage in London.",
+            prompt_template,
+            Box::new(SharedModel(model.clone())),
+            &config,
+        )
+        .await
+        .unwrap();
+
+        let mut seen = model.seen.lock().unwrap().clone();
+        seen.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        assert_eq!(seen, vec![0.1, 0.5, 0.9]);
+
+        let extractions = result.extractions.unwrap();
+        let found: Vec<(&str, f64)> = extractions
+            .iter()
+            .map(|e| {
+                let confidence = e.attributes.as_ref().unwrap()[CONFIDENCE_ATTRIBUTE].as_f64().unwrap();
+                (e.extraction_text.as_str(), confidence)
+            })
+            .collect();
+        assert_eq!(found, vec![("Ada Lovelace", 1.0), ("London", 0.67)]);
+    }
+
+    /// Records the highest number of calls in flight at once
+    #[derive(Default)]
+    struct PeakModel {
+        in_flight: std::sync::atomic::AtomicUsize,
+        peak: std::sync::atomic::AtomicUsize,
+    }
+
+    #[async_trait]
+    impl BaseLanguageModel for PeakModel {
+        async fn infer(
+            &self,
+            batch_prompts: &[String],
+            _kwargs: &HashMap<String, serde_json::Value>,
+        ) -> LangExtractResult<Vec<Vec<ScoredOutput>>> {
+            use std::sync::atomic::Ordering;
+            let now = self.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+            self.peak.fetch_max(now, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            self.in_flight.fetch_sub(1, Ordering::SeqCst);
+            Ok(batch_prompts.iter().map(|_| vec![ScoredOutput::from_text(r#"{"person": "Ada Lovelace"}"#.to_string())]).collect())
+        }
+
+        fn model_id(&self) -> &str {
+            "peak"
+        }
+
+        fn provider_name(&self) -> &str {
+            "test"
+        }
+    }
+
+    #[tokio::test]
+    async fn test_consensus_shares_max_workers_across_samples() {
+        let model = Arc::new(PeakModel::default());
+        let mut prompt_template = crate::prompting::PromptTemplateStructured::new(None);
+        prompt_template.examples.extend(crate::test_support::person_examples());
+        let config = crate::ExtractConfig {
+            max_char_buffer: 30,
+            max_workers: 3,
+            ..Default::default()
+        }
+        .with_consensus(ConsensusConfig::new(4));
+
+        let text = "Ada Lovelace wrote notes. Charles Babbage built engines. Grace Hopper wrote compilers.";
+        crate::annotate_with_model(text, prompt_template.clone(), Box::new(SharedModel(model.clone())), &config)
+            .await
+            .unwrap();
+        assert!(model.peak.load(std::sync::atomic::Ordering::SeqCst) <= 3);
+
+        // Samples can't be resumed, so checkpoints are refused up front
+        let dir = tempfile::tempdir().unwrap();
+        let config = config.with_checkpoint(crate::CheckpointConfig::new(dir.path()));
+        let err = crate::annotate_with_model(text, prompt_template, Box::new(SharedModel(model)), &config)
+            .await
+            .unwrap_err();
+        assert!(err.is_configuration_error());
+    }
 }
@@ -4,12 +4,20 @@
 //! multiple extraction steps, creating nested hierarchical structures from text.
 
 use crate::{
+    cancellation::{CancellationToken, StopSignal},
     data::{ExampleData, Extraction, CharInterval},
+    dry_run::{DryRunReport, PromptRenderer},
     exceptions::{LangExtractError, LangExtractResult},
-    extract, ExtractConfig,
+    extract,
+    providers::{CacheConfig, FixtureConfig, ProviderConfig, RateLimitConfig},
+    usage::{PriceTable, UsageBudget, UsageSummary, UsageTracker},
+    verification::VerificationConfig,
+    ExtractConfig,
 };
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
 use futures::future::join_all;
 
 /// A single step in a processing pipeline
@@ -38,6 +46,11 @@ pub struct PipelineStep {
 
     /// Dependencies - this step depends on output from these step IDs
     pub depends_on: Vec<String>,
+
+    /// Verify this step's extractions against their source text, overriding
+    /// the global setting
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub verification: Option<VerificationConfig>,
 }
 
 /// Filter configuration for processing specific extractions
@@ -74,6 +87,15 @@ pub struct PipelineConfig {
     /// Enable parallel execution of independent steps (default: false)
     #[serde(default)]
     pub enable_parallel_execution: bool,
+
+    /// Time limit for the whole pipeline in milliseconds; steps unfinished
+    /// when it passes are stopped and the finished results returned
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout_ms: Option<u64>,
+
+    /// Token that stops the pipeline when cancelled (not serialized)
+    #[serde(skip)]
+    pub cancellation: Option<CancellationToken>,
 }
 
 /// Results from a single pipeline step
@@ -99,6 +121,10 @@ pub struct StepResult {
 
     /// Error message if failed
     pub error_message: Option<String>,
+
+    /// Token usage and cost of this step's model calls
+    #[serde(default)]
+    pub usage: UsageSummary,
 }
 
 /// Complete pipeline execution result
@@ -121,6 +147,10 @@ pub struct PipelineResult {
 
     /// Error message if pipeline failed
     pub error_message: Option<String>,
+
+    /// Token usage and cost across all steps
+    #[serde(default)]
+    pub usage: UsageSummary,
 }
 
 /// Pipeline executor
@@ -162,6 +192,110 @@ impl PipelineExecutor {
         Ok(Self::new(config))
     }
 
+    /// Record or replay every model call in this pipeline through a fixture file
+    ///
+    /// Requires `global_config.language_model_params["provider_config"]` to be set.
+    pub fn with_fixture(self, fixture: FixtureConfig) -> LangExtractResult<Self> {
+        self.update_provider_config(|provider_config| provider_config.fixture = Some(fixture))
+    }
+
+    /// Serve repeated model calls in this pipeline from an on-disk cache
+    ///
+    /// Requires `global_config.language_model_params["provider_config"]` to be set.
+    pub fn with_cache(self, cache: CacheConfig) -> LangExtractResult<Self> {
+        self.update_provider_config(|provider_config| provider_config.cache = Some(cache))
+    }
+
+    /// Throttle model calls from all steps against one shared request/token budget
+    ///
+    /// Requires `global_config.language_model_params["provider_config"]` to be set.
+    pub fn with_rate_limit(self, rate_limit: RateLimitConfig) -> LangExtractResult<Self> {
+        self.update_provider_config(|provider_config| provider_config.rate_limit = Some(rate_limit))
+    }
+
+    /// Abort the pipeline once all steps together exceed this token or cost budget
+    pub fn with_budget(mut self, budget: UsageBudget) -> Self {
+        self.config.global_config.budget = budget;
+        self
+    }
+
+    /// Price model calls from all steps with this table
+    pub fn with_price_table(mut self, prices: PriceTable) -> Self {
+        self.config.global_config.price_table = Some(prices);
+        self
+    }
+
+    /// Stop the pipeline when `token` is cancelled, returning the steps that
+    /// finished
+    pub fn with_cancellation(mut self, token: CancellationToken) -> Self {
+        self.config.cancellation = Some(token);
+        self
+    }
+
+    /// Stop the pipeline after `timeout`, returning the steps that finished
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.config.timeout_ms = Some(timeout.as_millis() as u64);
+        self
+    }
+
+    /// Fail any model call from any step that takes longer than `timeout`
+    pub fn with_request_timeout(mut self, timeout: Duration) -> Self {
+        self.config.global_config.request_timeout_ms = Some(timeout.as_millis() as u64);
+        self
+    }
+
+    /// Render every step's prompts without calling a model
+    ///
+    /// Steps that read the original text are chunked and rendered in full.
+    /// Steps fed by an earlier step's extractions can't know their input
+    /// yet, so each is rendered once around a placeholder to show the prompt
+    /// overhead per item.
+    pub fn dry_run(&self, input_text: &str) -> LangExtractResult<DryRunReport> {
+        let global_config = &self.config.global_config;
+        let mut prompts = Vec::new();
+        let mut report = DryRunReport::default();
+
+        for step_id in self.resolve_execution_order()? {
+            let step = self.config.steps.iter().find(|s| s.id == step_id)
+                .ok_or_else(|| LangExtractError::configuration(format!("Step '{}' not found", step_id)))?;
+            let renderer = PromptRenderer::new(Some(&step.prompt), &step.examples, global_config)
+                .map_err(|e| LangExtractError::configuration(format!("Step '{}': {}", step.id, e)))?;
+
+            if step.depends_on.is_empty() {
+                prompts.extend(renderer.render_chunks(input_text, Some(&step.id))?);
+            } else {
+                let placeholder = format!("<text of an extraction from {}>", step.depends_on.join(" or "));
+                let mut preview = renderer.render(&placeholder, Some(&step.id))?;
+                preview.placeholder = true;
+                prompts.push(preview);
+            }
+            report = renderer.report(Vec::new());
+        }
+
+        report.prompts = prompts;
+        Ok(report)
+    }
+
+    /// Apply a change to the provider configuration shared by all steps
+    fn update_provider_config<F>(mut self, update: F) -> LangExtractResult<Self>
+    where
+        F: FnOnce(&mut ProviderConfig),
+    {
+        let params = &mut self.config.global_config.language_model_params;
+        let value = params.get("provider_config").ok_or_else(|| {
+            LangExtractError::configuration("Pipeline has no provider_config to update")
+        })?;
+        let mut provider_config: ProviderConfig = serde_json::from_value(value.clone())
+            .map_err(|e| LangExtractError::configuration(format!("Invalid provider_config: {}", e)))?;
+        update(&mut provider_config);
+        params.insert(
+            "provider_config".to_string(),
+            serde_json::to_value(&provider_config)
+                .map_err(|e| LangExtractError::serialization(e.to_string()))?,
+        );
+        Ok(self)
+    }
+
     /// Execute the entire pipeline
     pub async fn execute(&self, input_text: &str) -> LangExtractResult<PipelineResult> {
         let start_time = std::time::Instant::now();
@@ -175,15 +309,50 @@ impl PipelineExecutor {
             log::info!("[pipeline] mode: sequential");
         }
 
+        // All steps draw from one usage budget
+        let global_config = &self.config.global_config;
+        let usage_tracker = global_config.usage_tracker.clone().unwrap_or_else(|| {
+            Arc::new(UsageTracker::new(
+                global_config.price_table.clone().unwrap_or_default(),
+                global_config.budget,
+            ))
+        });
+
+        // Steps still running when the pipeline is cancelled or times out
+        // return what they finished, and no further steps start
+        let stop = StopSignal::new(
+            self.config.cancellation.as_ref(),
+            self.config.timeout_ms.map(Duration::from_millis),
+        );
+        let _stop_guard = stop.guard();
+
         if self.config.enable_parallel_execution {
-            self.execute_parallel(input_text, start_time).await
+            self.execute_parallel(input_text, start_time, &usage_tracker, &stop).await
         } else {
-            self.execute_sequential(input_text, start_time).await
+            self.execute_sequential(input_text, start_time, &usage_tracker, &stop).await
         }
     }
 
+    /// Why the pipeline stopped early, if it did
+    fn stopped_message(&self, stop: &StopSignal, step_results: &[StepResult]) -> Option<String> {
+        stop.is_stopped().then(|| {
+            format!(
+                "Pipeline {} after {} of {} steps completed",
+                stop.reason(),
+                step_results.iter().filter(|r| r.success).count(),
+                self.config.steps.len()
+            )
+        })
+    }
+
     /// Execute pipeline sequentially (original behavior)
-    async fn execute_sequential(&self, input_text: &str, start_time: std::time::Instant) -> LangExtractResult<PipelineResult> {
+    async fn execute_sequential(
+        &self,
+        input_text: &str,
+        start_time: std::time::Instant,
+        usage_tracker: &Arc<UsageTracker>,
+        stop: &StopSignal,
+    ) -> LangExtractResult<PipelineResult> {
         let mut step_results = Vec::new();
         let mut context_data = HashMap::new();
 
@@ -191,8 +360,14 @@ impl PipelineExecutor {
         let execution_order = self.resolve_execution_order()?;
 
         for step_id in execution_order {
-            let step_result = self.execute_step(&step_id, input_text, &context_data).await?;
+            if stop.is_stopped() {
+                break;
+            }
+            let step_result = self.execute_step(&step_id, input_text, &context_data, usage_tracker, stop).await?;
             step_results.push(step_result.clone());
+            if stop.is_stopped() {
+                break;
+            }
 
             // Store results for dependent steps
             if step_result.success {
@@ -213,18 +388,27 @@ impl PipelineExecutor {
 
         log::info!("[pipeline] done in {}ms", total_time);
 
+        let usage = Self::total_usage(&step_results, usage_tracker);
+        let error_message = self.stopped_message(stop, &step_results);
         Ok(PipelineResult {
             config: self.config.clone(),
             step_results,
             nested_output,
             total_time_ms: total_time,
-            success: true,
-            error_message: None,
+            success: error_message.is_none(),
+            error_message,
+            usage,
         })
     }
 
     /// Execute pipeline with parallel execution of independent steps
-    async fn execute_parallel(&self, input_text: &str, start_time: std::time::Instant) -> LangExtractResult<PipelineResult> {
+    async fn execute_parallel(
+        &self,
+        input_text: &str,
+        start_time: std::time::Instant,
+        usage_tracker: &Arc<UsageTracker>,
+        stop: &StopSignal,
+    ) -> LangExtractResult<PipelineResult> {
         let mut all_step_results = Vec::new();
         let mut context_data = HashMap::new();
         
@@ -232,14 +416,19 @@ impl PipelineExecutor {
         let execution_waves = self.resolve_execution_waves()?;
         
         for (wave_index, wave_steps) in execution_waves.iter().enumerate() {
+            if stop.is_stopped() {
+                break;
+            }
             log::debug!("[pipeline] wave {}: {} steps", wave_index + 1, wave_steps.len());
             
             if wave_steps.len() == 1 {
                 // Single step - execute normally
                 let step_id = &wave_steps[0];
-                let step_result = self.execute_step(step_id, input_text, &context_data).await?;
-                
-                if step_result.success {
+                let step_result = self.execute_step(step_id, input_text, &context_data, usage_tracker, stop).await?;
+
+                if stop.is_stopped() {
+                    all_step_results.push(step_result);
+                } else if step_result.success {
                     context_data.insert(step_id.clone(), step_result.extractions.clone());
                     all_step_results.push(step_result);
                 } else {
@@ -254,7 +443,7 @@ impl PipelineExecutor {
                 log::debug!("[pipeline] running {} steps in parallel", wave_steps.len());
                 
                 let parallel_futures: Vec<_> = wave_steps.iter()
-                    .map(|step_id| self.execute_step(step_id, input_text, &context_data))
+                    .map(|step_id| self.execute_step(step_id, input_text, &context_data, usage_tracker, stop))
                     .collect();
                 
                 let wave_results = join_all(parallel_futures).await;
@@ -264,7 +453,9 @@ impl PipelineExecutor {
                     let step_result = result?;
                     let step_id = &wave_steps[i];
                     
-                    if step_result.success {
+                    if stop.is_stopped() {
+                        all_step_results.push(step_result);
+                    } else if step_result.success {
                         context_data.insert(step_id.clone(), step_result.extractions.clone());
                         all_step_results.push(step_result);
                     } else {
@@ -285,16 +476,28 @@ impl PipelineExecutor {
 
         log::info!("[pipeline] done in {}ms", total_time);
 
+        let usage = Self::total_usage(&all_step_results, usage_tracker);
+        let error_message = self.stopped_message(stop, &all_step_results);
         Ok(PipelineResult {
             config: self.config.clone(),
             step_results: all_step_results,
             nested_output,
             total_time_ms: total_time,
-            success: true,
-            error_message: None,
+            success: error_message.is_none(),
+            error_message,
+            usage,
         })
     }
 
+    /// Sum the usage of all steps and price it
+    fn total_usage(step_results: &[StepResult], usage_tracker: &UsageTracker) -> UsageSummary {
+        let mut usage = UsageSummary::default();
+        for step_result in step_results {
+            usage.merge(&step_result.usage);
+        }
+        usage.priced(usage_tracker.prices())
+    }
+
     /// Resolve the execution order based on dependencies
     fn resolve_execution_order(&self) -> LangExtractResult<Vec<String>> {
         let mut order = Vec::new();
@@ -390,6 +593,8 @@ impl PipelineExecutor {
         step_id: &str,
         input_text: &str,
         context_data: &HashMap<String, Vec<Extraction>>,
+        usage_tracker: &Arc<UsageTracker>,
+        stop: &StopSignal,
     ) -> LangExtractResult<StepResult> {
         let step = self.config.steps.iter().find(|s| s.id == step_id)
             .ok_or_else(|| LangExtractError::configuration(format!("Step '{}' not found", step_id)))?;
@@ -405,13 +610,22 @@ impl PipelineExecutor {
         log::debug!("[pipeline] processing {} input items", input_count);
 
         let mut all_extractions = Vec::new();
+        let mut usage = UsageSummary::default();
 
         // Process each input item
         for (i, input_item) in step_input.iter().enumerate() {
+            if stop.is_stopped() {
+                break;
+            }
             log::debug!("[pipeline] item {}/{}", i + 1, input_count);
 
             // Create extraction config for this step
-            let step_config = self.config.global_config.clone();
+            let mut step_config = self.config.global_config.clone();
+            step_config.usage_tracker = Some(usage_tracker.clone());
+            step_config.cancellation = Some(stop.token());
+            if step.verification.is_some() {
+                step_config.verification = step.verification.clone();
+            }
             // Use step-specific examples if provided, otherwise use global
             let examples = if step.examples.is_empty() {
                 vec![] // Will need to be provided externally
@@ -426,6 +640,9 @@ impl PipelineExecutor {
                 step_config,
             ).await {
                 Ok(result) => {
+                    if let Some(item_usage) = &result.usage {
+                        usage.merge(item_usage);
+                    }
                     if let Some(extractions) = result.extractions {
                         for mut ex in extractions {
                             // For dependent steps, transform local intervals to absolute using parent start
@@ -492,6 +709,9 @@ impl PipelineExecutor {
                         }
                     }
                 }
+                // An exceeded budget aborts the whole pipeline
+                Err(e @ LangExtractError::BudgetExceeded(_)) => return Err(e),
+                Err(_) if stop.is_stopped() => break,
                 Err(e) => {
                     log::warn!("[pipeline] step '{}' failed on item {}/{}: {}", step.id, i + 1, input_count, e);
                     return Ok(StepResult {
@@ -502,6 +722,7 @@ impl PipelineExecutor {
                         input_count,
                         success: false,
                         error_message: Some(e.to_string()),
+                        usage: usage.priced(usage_tracker.prices()),
                     });
                 }
             }
@@ -509,6 +730,21 @@ impl PipelineExecutor {
 
         let processing_time = step_start.elapsed().as_millis() as u64;
 
+        // A stopped step keeps the extractions of the items it finished
+        if stop.is_stopped() {
+            log::warn!("[pipeline] step '{}' {} with {} extractions", step.id, stop.reason(), all_extractions.len());
+            return Ok(StepResult {
+                step_id: step.id.clone(),
+                step_name: step.name.clone(),
+                extractions: all_extractions,
+                processing_time_ms: processing_time,
+                input_count,
+                success: false,
+                error_message: Some(format!("Step {} before all input was processed", stop.reason())),
+                usage: usage.priced(usage_tracker.prices()),
+            });
+        }
+
         log::info!("[pipeline] step '{}' done: {} extractions in {}ms",
                 step.name, all_extractions.len(), processing_time);
 
@@ -520,6 +756,7 @@ impl PipelineExecutor {
             input_count,
             success: true,
             error_message: None,
+            usage: usage.priced(usage_tracker.prices()),
         })
     }
 
@@ -648,6 +885,8 @@ pub mod utils {
             description: "Extract requirements and sub-divide into values, units, and specifications".to_string(),
             version: "1.0.0".to_string(),
             enable_parallel_execution: false,
+            timeout_ms: None,
+            cancellation: None,
             global_config: ExtractConfig {
                 model_id: "gemini-2.5-flash".to_string(),
                 api_key: None,
@@ -667,6 +906,18 @@ pub mod utils {
                 multipass_max_passes: 2,
                 multipass_min_extractions: 1,
                 multipass_quality_threshold: 0.3,
+                consensus: None,
+                verification: None,
+                streaming: false,
+                extraction_classes: None,
+                budget: Default::default(),
+                price_table: None,
+                allow_partial: false,
+                checkpoint: None,
+                request_timeout_ms: None,
+                timeout_ms: None,
+                cancellation: None,
+                usage_tracker: None,
                 progress_handler: None,
             },
             steps: vec![
@@ -687,6 +938,7 @@ pub mod utils {
                     output_field: "requirements".to_string(),
                     filter: None,
                     depends_on: vec![],
+                    verification: None,
                 },
                 PipelineStep {
                     id: "extract_values".to_string(),
@@ -711,6 +963,7 @@ pub mod utils {
                         max_items: None,
                     }),
                     depends_on: vec!["extract_requirements".to_string()],
+                    verification: None,
                 },
                 PipelineStep {
                     id: "extract_specifications".to_string(),
@@ -733,6 +986,7 @@ pub mod utils {
                         max_items: None,
                     }),
                     depends_on: vec!["extract_requirements".to_string()],
+                    verification: None,
                 },
             ],
         }
@@ -805,4 +1059,131 @@ mod tests {
         assert_eq!(filtered.len(), 1);
         assert_eq!(filtered[0].extraction_class, "requirement");
     }
+
+    #[tokio::test]
+    async fn test_pipeline_record_then_replay() {
+        use wiremock::matchers::{method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let dir = tempfile::tempdir().unwrap();
+        let fixture_path = dir.path().join("pipeline.json");
+        let input = "The system shall process 100 transactions per second.";
+
+        let mut config = utils::create_requirements_pipeline();
+        config.steps.truncate(1);
+
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/api/generate"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "response": "{\"requirement\": \"The system shall process 100 transactions per second.\"}"
+            })))
+            .mount(&server)
+            .await;
+        let provider_config = ProviderConfig::ollama("mistral", Some(server.uri()));
+        config.global_config = crate::test_support::with_provider(config.global_config.clone(), &provider_config);
+
+        let recorded = PipelineExecutor::new(config.clone())
+            .with_fixture(FixtureConfig { mode: crate::providers::FixtureMode::Record, path: fixture_path.clone() })
+            .unwrap()
+            .execute(input)
+            .await
+            .unwrap();
+
+        // Replay must not touch the network
+        drop(server);
+        let replayed = PipelineExecutor::new(config)
+            .with_fixture(FixtureConfig { mode: crate::providers::FixtureMode::Replay, path: fixture_path })
+            .unwrap()
+            .execute(input)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            recorded.nested_output["extract_requirements"]["extractions"],
+            replayed.nested_output["extract_requirements"]["extractions"]
+        );
+        assert_eq!(replayed.step_results[0].extractions.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_step_verification_flags_rejected_extractions() {
+        use wiremock::matchers::{body_string_contains, method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/api/generate"))
+            .and(body_string_contains("Check each extracted item"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "response": "[{\"id\": 0, \"verdict\": \"rejected\", \"reason\": \"no obligation\"}]"
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/api/generate"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "response": "{\"requirement\": \"The system logs events.\"}"
+            })))
+            .mount(&server)
+            .await;
+
+        let mut config = utils::create_requirements_pipeline();
+        config.steps.truncate(1);
+        config.steps[0].verification = Some(
+            VerificationConfig::default().with_on_reject(crate::verification::RejectAction::Flag),
+        );
+        let provider_config = ProviderConfig::ollama("mistral", Some(server.uri()));
+        config.global_config = crate::test_support::with_provider(config.global_config.clone(), &provider_config);
+
+        let result = PipelineExecutor::new(config).execute("The system logs events.").await.unwrap();
+
+        let extractions = &result.step_results[0].extractions;
+        assert_eq!(extractions.len(), 1);
+        let attributes = extractions[0].attributes.as_ref().unwrap();
+        assert_eq!(attributes[crate::verification::VERDICT_ATTRIBUTE], "rejected");
+        assert_eq!(attributes[crate::verification::VERDICT_REASON_ATTRIBUTE], "no obligation");
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_pipeline_returns_partial_result() {
+        let token = CancellationToken::new();
+        token.cancel();
+        let executor = PipelineExecutor::new(utils::create_requirements_pipeline()).with_cancellation(token);
+
+        let result = executor.execute("The system shall respond within 2 seconds.").await.unwrap();
+
+        assert!(!result.success);
+        assert!(result.step_results.is_empty());
+        assert_eq!(
+            result.error_message.as_deref(),
+            Some("Pipeline cancelled after 0 of 3 steps completed")
+        );
+    }
+
+    #[test]
+    fn test_with_fixture_requires_provider_config() {
+        let executor = PipelineExecutor::new(utils::create_requirements_pipeline());
+        let fixture = FixtureConfig {
+            mode: crate::providers::FixtureMode::Replay,
+            path: "fixture.json".into(),
+        };
+        assert!(executor.with_fixture(fixture).is_err());
+    }
+
+    #[test]
+    fn test_dry_run_renders_every_step() {
+        let executor = PipelineExecutor::new(utils::create_requirements_pipeline());
+        let input = "The system shall process 100 transactions per second.";
+
+        let report = executor.dry_run(input).unwrap();
+
+        let steps: Vec<_> = report.prompts.iter().map(|p| p.step_id.as_deref().unwrap()).collect();
+        assert_eq!(steps, executor.resolve_execution_order().unwrap());
+        assert!(!report.prompts[0].placeholder);
+        assert!(report.prompts[0].prompt.contains(input));
+        assert!(report.prompts[1..].iter().all(|p| p.placeholder && p.prompt_tokens > 0));
+        assert_eq!(report.chunk_count(), 1);
+    }
 }
@@ -138,6 +138,15 @@ impl PromptTemplate {
                 );
                 template.include_reasoning = false; // OpenAI is good with direct instructions
             }
+            ProviderType::Gemini => {
+                template.system_message = Some(
+                    "You are an expert information extraction assistant. Extract structured information exactly as shown in the examples.".to_string()
+                );
+                template.include_reasoning = false; // Gemini follows direct instructions well
+            }
+            ProviderType::Anthropic => {
+                template.include_reasoning = false; // Output is prefilled, so no room for reasoning text
+            }
             ProviderType::Ollama => {
                 template.include_reasoning = true; // Local models benefit from reasoning steps
                 template.max_examples = Some(3); // Keep prompts shorter for local models
@@ -0,0 +1,621 @@
+//! Batch API job mode for OpenAI-compatible providers.
+//!
+//! [`BatchJob::submit`] renders the prompt for every chunk, writes the chat
+//! completion requests to a JSONL file next to the job state, uploads it and
+//! creates a batch. The job state is saved to disk, so a later process can
+//! [`refresh`](BatchJob::refresh) it and [`collect`](BatchJob::collect) the
+//! results, which then run through the same resolver, aligner and aggregator
+//! as a synchronous extraction. API keys and custom headers are never written
+//! to the state file.
+
+use super::config::{ProviderConfig, ProviderType};
+use crate::{
+    data::{AnnotatedDocument, ExampleData, FormatType},
+    dry_run::PromptRenderer,
+    exceptions::{LangExtractError, LangExtractResult},
+    factory,
+    inference::{BaseLanguageModel, ScoredOutput},
+    prompting::PromptTemplateStructured,
+    usage::TokenUsage,
+    ExtractConfig,
+};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Endpoint every request in the batch is sent to
+pub const BATCH_ENDPOINT: &str = "/v1/chat/completions";
+
+/// Lifecycle of a batch, as reported by the provider
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BatchStatus {
+    Validating,
+    Failed,
+    InProgress,
+    Finalizing,
+    Completed,
+    Expired,
+    Cancelling,
+    Cancelled,
+    /// A status this version doesn't know; treated as still pending
+    #[serde(other)]
+    Unknown,
+}
+
+impl BatchStatus {
+    /// Whether the batch will not change any more
+    pub fn is_terminal(&self) -> bool {
+        matches!(self, Self::Failed | Self::Completed | Self::Expired | Self::Cancelled)
+    }
+}
+
+impl std::fmt::Display for BatchStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Self::Validating => "validating",
+            Self::Failed => "failed",
+            Self::InProgress => "in_progress",
+            Self::Finalizing => "finalizing",
+            Self::Completed => "completed",
+            Self::Expired => "expired",
+            Self::Cancelling => "cancelling",
+            Self::Cancelled => "cancelled",
+            Self::Unknown => "unknown",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Request progress within a batch
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BatchRequestCounts {
+    pub total: usize,
+    pub completed: usize,
+    pub failed: usize,
+}
+
+/// One chunk prompt in the batch
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchRequest {
+    /// Identifier matching the request to its result line
+    pub custom_id: String,
+    /// Rendered prompt
+    pub prompt: String,
+}
+
+/// A submitted batch job and everything needed to collect its results
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchJob {
+    /// Provider-assigned batch ID
+    pub batch_id: String,
+    /// ID of the uploaded JSONL request file
+    pub input_file_id: String,
+    /// Last known status
+    pub status: BatchStatus,
+    /// Last known request counts
+    #[serde(default)]
+    pub request_counts: BatchRequestCounts,
+    /// ID of the result file, once the batch has completed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output_file_id: Option<String>,
+    /// ID of the file listing failed requests, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error_file_id: Option<String>,
+    /// API base URL, e.g. `https://api.openai.com/v1`
+    pub base_url: String,
+    /// Model the requests were sent to
+    pub model: String,
+    /// When the batch was submitted
+    pub submitted_at: DateTime<Utc>,
+    /// Source text
+    pub text: String,
+    /// Extraction instructions
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prompt_description: Option<String>,
+    /// Examples the prompts were built from
+    pub examples: Vec<ExampleData>,
+    /// Extraction settings, without credentials
+    pub config: ExtractConfig,
+    /// Fence preference of the provider, used when parsing results
+    pub requires_fence_output: bool,
+    /// One request per chunk
+    pub requests: Vec<BatchRequest>,
+    /// Custom headers sent with every Batch API call (not serialized)
+    #[serde(skip)]
+    pub headers: HashMap<String, String>,
+}
+
+impl BatchJob {
+    /// Render the chunk prompts for `text`, upload them as a batch and save
+    /// the job state to `state_path`
+    ///
+    /// The requests are also written to `<state>.requests.jsonl`. The
+    /// provider configuration in `config` must be OpenAI-compatible.
+    pub async fn submit(
+        text: &str,
+        prompt_description: Option<&str>,
+        examples: &[ExampleData],
+        config: &ExtractConfig,
+        state_path: &Path,
+    ) -> LangExtractResult<Self> {
+        let provider_config = factory::create_provider_config(config)?;
+        if provider_config.provider_type != ProviderType::OpenAI {
+            return Err(LangExtractError::configuration(format!(
+                "Batch mode needs an OpenAI-compatible provider, not {:?}",
+                provider_config.provider_type
+            )));
+        }
+        if config.enable_multipass {
+            return Err(LangExtractError::configuration(
+                "Multi-pass extraction can't run as a batch job: later passes depend on earlier results",
+            ));
+        }
+        if config.consensus.is_some() {
+            return Err(LangExtractError::configuration(
+                "Consensus extraction can't run as a batch job: a batch takes one sample per chunk",
+            ));
+        }
+        if config.verification.is_some() {
+            return Err(LangExtractError::configuration(
+                "Verification can't run as a batch job: it needs the collected extractions",
+            ));
+        }
+        let api_key = provider_config.api_key.clone().ok_or_else(|| {
+            LangExtractError::configuration("API key is required for OpenAI provider")
+        })?;
+
+        // Render exactly the prompts a synchronous run would send
+        let provider = factory::build_universal_provider(provider_config.clone(), config, Some(examples))?;
+        let renderer = PromptRenderer::new(prompt_description, examples, config)?;
+        let kwargs = HashMap::from([("temperature".to_string(), serde_json::json!(config.temperature))]);
+
+        let mut requests = Vec::new();
+        let mut lines = Vec::new();
+        for preview in renderer.render_chunks(text, None)? {
+            let custom_id = format!("chunk-{}", preview.chunk_id);
+            let line = serde_json::json!({
+                "custom_id": custom_id,
+                "method": "POST",
+                "url": BATCH_ENDPOINT,
+                "body": provider.openai_request_body(&preview.prompt, &kwargs)?,
+            });
+            lines.push(line.to_string());
+            requests.push(BatchRequest { custom_id, prompt: preview.prompt });
+        }
+        let jsonl = lines.join("\n") + "\n";
+        std::fs::write(Self::requests_path(state_path), &jsonl)?;
+
+        let client = BatchClient::new(&provider_config.base_url, &api_key, &provider_config.headers);
+        let input_file_id = client.upload(jsonl.into_bytes()).await?;
+        let batch = client.create_batch(&input_file_id).await?;
+
+        let headers = provider_config.headers.clone();
+        let mut job = Self {
+            batch_id: string_field(&batch, "id")?,
+            input_file_id,
+            status: BatchStatus::Validating,
+            request_counts: BatchRequestCounts::default(),
+            output_file_id: None,
+            error_file_id: None,
+            base_url: provider_config.base_url.clone(),
+            model: provider_config.model.clone(),
+            submitted_at: Utc::now(),
+            text: text.to_string(),
+            prompt_description: prompt_description.map(String::from),
+            examples: examples.to_vec(),
+            config: Self::without_credentials(config, provider_config)?,
+            requires_fence_output: provider.requires_fence_output(),
+            requests,
+            headers,
+        };
+        job.update(&batch);
+        job.save(state_path)?;
+        Ok(job)
+    }
+
+    /// Path of the JSONL request file written next to a job state file
+    pub fn requests_path(state_path: &Path) -> PathBuf {
+        state_path.with_extension("requests.jsonl")
+    }
+
+    /// Load job state saved by [`submit`](Self::submit)
+    ///
+    /// Custom headers are not saved; set them again with
+    /// [`with_headers`](Self::with_headers) if the provider needs them.
+    pub fn load(path: &Path) -> LangExtractResult<Self> {
+        let content = std::fs::read_to_string(path)?;
+        serde_json::from_str(&content).map_err(|e| {
+            LangExtractError::configuration(format!("Invalid batch job file {}: {}", path.display(), e))
+        })
+    }
+
+    /// Send `headers` with every Batch API call
+    pub fn with_headers(mut self, headers: HashMap<String, String>) -> Self {
+        self.headers = headers;
+        self
+    }
+
+    /// Save the job state
+    pub fn save(&self, path: &Path) -> LangExtractResult<()> {
+        let content = serde_json::to_string_pretty(self)
+            .map_err(|e| LangExtractError::serialization(e.to_string()))?;
+        std::fs::write(path, content)?;
+        Ok(())
+    }
+
+    /// Fetch the current status from the provider
+    pub async fn refresh(&mut self, api_key: &str) -> LangExtractResult<BatchStatus> {
+        let batch = BatchClient::new(&self.base_url, api_key, &self.headers).retrieve(&self.batch_id).await?;
+        self.update(&batch);
+        Ok(self.status)
+    }
+
+    /// Poll until the batch reaches a terminal status
+    pub async fn wait(&mut self, api_key: &str, poll_interval: Duration) -> LangExtractResult<BatchStatus> {
+        while !self.refresh(api_key).await?.is_terminal() {
+            tokio::time::sleep(poll_interval).await;
+        }
+        Ok(self.status)
+    }
+
+    /// Download the results of a completed batch and turn them into an
+    /// annotated document
+    ///
+    /// Requests that failed in the batch leave their chunk without
+    /// extractions, as a failed call would in a synchronous run.
+    pub async fn collect(&self, api_key: &str) -> LangExtractResult<AnnotatedDocument> {
+        let output_file_id = match (self.status, &self.output_file_id) {
+            (BatchStatus::Completed, Some(id)) => id,
+            _ => {
+                return Err(LangExtractError::invalid_input(format!(
+                    "Batch {} is {}; results can only be collected once it has completed",
+                    self.batch_id, self.status
+                )))
+            }
+        };
+
+        let client = BatchClient::new(&self.base_url, api_key, &self.headers);
+        let mut content = client.content(output_file_id).await?;
+        if let Some(error_file_id) = &self.error_file_id {
+            content.push('\n');
+            content.push_str(&client.content(error_file_id).await?);
+        }
+
+        let mut results = HashMap::new();
+        for line in content.lines().filter(|l| !l.trim().is_empty()) {
+            let (custom_id, result) = parse_result_line(line)?;
+            results.insert(custom_id, result);
+        }
+
+        // Serve each chunk prompt its batch result
+        let outputs = self
+            .requests
+            .iter()
+            .filter_map(|request| {
+                let result = results.remove(&request.custom_id)?;
+                Some((request.prompt.clone(), result))
+            })
+            .collect();
+        let model = BatchResultsModel {
+            outputs,
+            model_id: self.model.clone(),
+            requires_fence_output: self.requires_fence_output,
+        };
+
+        let mut prompt_template = PromptTemplateStructured::new(self.prompt_description.as_deref());
+        prompt_template.examples.extend(self.examples.iter().cloned());
+        crate::annotate_with_model(&self.text, prompt_template, Box::new(model), &self.config).await
+    }
+
+    fn update(&mut self, batch: &serde_json::Value) {
+        self.status = serde_json::from_value(batch["status"].clone()).unwrap_or(BatchStatus::Unknown);
+        if let Ok(counts) = serde_json::from_value(batch["request_counts"].clone()) {
+            self.request_counts = counts;
+        }
+        self.output_file_id = batch["output_file_id"].as_str().map(String::from);
+        self.error_file_id = batch["error_file_id"].as_str().map(String::from);
+    }
+
+    /// Copy of `config` with the API keys and custom headers removed, safe to
+    /// write to disk
+    fn without_credentials(config: &ExtractConfig, mut provider_config: ProviderConfig) -> LangExtractResult<ExtractConfig> {
+        let mut config = config.clone();
+        config.api_key = None;
+        config.usage_tracker = None;
+        provider_config.api_key = None;
+        provider_config.headers.clear();
+        for fallback in &mut provider_config.fallbacks {
+            fallback.api_key = None;
+            fallback.headers.clear();
+        }
+        config.language_model_params.insert(
+            "provider_config".to_string(),
+            serde_json::to_value(&provider_config)
+                .map_err(|e| LangExtractError::serialization(e.to_string()))?,
+        );
+        Ok(config)
+    }
+}
+
+/// Split a result line into its `custom_id` and the output or error message
+fn parse_result_line(line: &str) -> LangExtractResult<(String, Result<ScoredOutput, String>)> {
+    let value: serde_json::Value = serde_json::from_str(line)
+        .map_err(|e| LangExtractError::parsing(format!("Invalid batch result line: {}", e)))?;
+    let custom_id = string_field(&value, "custom_id")?;
+
+    if let Some(error) = value.get("error").filter(|e| !e.is_null()) {
+        let message = error["message"].as_str().map(String::from).unwrap_or_else(|| error.to_string());
+        return Ok((custom_id, Err(message)));
+    }
+
+    let response = &value["response"];
+    let body = &response["body"];
+    let status = response["status_code"].as_u64().unwrap_or(200);
+    if status != 200 {
+        let message = body["error"]["message"].as_str().map(String::from).unwrap_or_else(|| body.to_string());
+        return Ok((custom_id, Err(format!("HTTP {}: {}", status, message))));
+    }
+
+    let result = match body.pointer("/choices/0/message/content").and_then(|c| c.as_str()) {
+        Some(content) => {
+            let mut output = ScoredOutput::from_text(content.to_string());
+            let usage = &body["usage"];
+            if let (Some(prompt), Some(completion)) = (usage["prompt_tokens"].as_u64(), usage["completion_tokens"].as_u64()) {
+                output = output.with_usage(TokenUsage::new(prompt, completion));
+            }
+            Ok(output)
+        }
+        None => Err("No content in OpenAI response".to_string()),
+    };
+    Ok((custom_id, result))
+}
+
+fn string_field(value: &serde_json::Value, field: &str) -> LangExtractResult<String> {
+    value[field]
+        .as_str()
+        .map(String::from)
+        .ok_or_else(|| LangExtractError::parsing(format!("Batch API response has no '{}'", field)))
+}
+
+/// Minimal client for the files and batches endpoints
+struct BatchClient<'a> {
+    client: reqwest::Client,
+    base_url: String,
+    api_key: &'a str,
+    headers: &'a HashMap<String, String>,
+}
+
+impl<'a> BatchClient<'a> {
+    fn new(base_url: &str, api_key: &'a str, headers: &'a HashMap<String, String>) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            api_key,
+            headers,
+        }
+    }
+
+    async fn send(&self, request: reqwest::RequestBuilder) -> LangExtractResult<reqwest::Response> {
+        let mut request = request.bearer_auth(self.api_key);
+        for (key, value) in self.headers {
+            request = request.header(key, value);
+        }
+        let response = request.send().await?;
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            return Err(LangExtractError::http_status("OpenAI batch", status.as_u16(), None, body));
+        }
+        Ok(response)
+    }
+
+    async fn send_json(&self, request: reqwest::RequestBuilder) -> LangExtractResult<serde_json::Value> {
+        Ok(self.send(request).await?.json().await?)
+    }
+
+    /// Upload a JSONL request file and return its ID
+    async fn upload(&self, jsonl: Vec<u8>) -> LangExtractResult<String> {
+        let file = reqwest::multipart::Part::bytes(jsonl)
+            .file_name("batch.jsonl")
+            .mime_str("application/jsonl")?;
+        let form = reqwest::multipart::Form::new().text("purpose", "batch").part("file", file);
+        let uploaded = self
+            .send_json(self.client.post(format!("{}/files", self.base_url)).multipart(form))
+            .await?;
+        string_field(&uploaded, "id")
+    }
+
+    async fn create_batch(&self, input_file_id: &str) -> LangExtractResult<serde_json::Value> {
+        let body = serde_json::json!({
+            "input_file_id": input_file_id,
+            "endpoint": BATCH_ENDPOINT,
+            "completion_window": "24h",
+        });
+        self.send_json(self.client.post(format!("{}/batches", self.base_url)).json(&body))
+            .await
+    }
+
+    async fn retrieve(&self, batch_id: &str) -> LangExtractResult<serde_json::Value> {
+        self.send_json(self.client.get(format!("{}/batches/{}", self.base_url, batch_id)))
+            .await
+    }
+
+    async fn content(&self, file_id: &str) -> LangExtractResult<String> {
+        let response = self
+            .send(self.client.get(format!("{}/files/{}/content", self.base_url, file_id)))
+            .await?;
+        Ok(response.text().await?)
+    }
+}
+
+/// Serves downloaded batch results to the annotator, keyed by prompt
+struct BatchResultsModel {
+    outputs: HashMap<String, Result<ScoredOutput, String>>,
+    model_id: String,
+    requires_fence_output: bool,
+}
+
+#[async_trait]
+impl BaseLanguageModel for BatchResultsModel {
+    async fn infer(
+        &self,
+        batch_prompts: &[String],
+        _kwargs: &HashMap<String, serde_json::Value>,
+    ) -> LangExtractResult<Vec<Vec<ScoredOutput>>> {
+        batch_prompts
+            .iter()
+            .map(|prompt| match self.outputs.get(prompt) {
+                Some(Ok(output)) => Ok(vec![output.clone()]),
+                Some(Err(message)) => Err(LangExtractError::inference_simple(format!(
+                    "Batch request failed: {}",
+                    message
+                ))),
+                None => Err(LangExtractError::inference_simple("No batch result for this prompt")),
+            })
+            .collect()
+    }
+
+    fn requires_fence_output(&self) -> bool {
+        self.requires_fence_output
+    }
+
+    fn format_type(&self) -> FormatType {
+        FormatType::Json
+    }
+
+    fn model_id(&self) -> &str {
+        &self.model_id
+    }
+
+    fn provider_name(&self) -> &str {
+        "openai"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{person_examples, with_provider};
+    use wiremock::matchers::{bearer_token, header, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn batch_body(status: &str, output_file_id: Option<&str>) -> serde_json::Value {
+        serde_json::json!({
+            "id": "batch_1",
+            "object": "batch",
+            "status": status,
+            "input_file_id": "file_in",
+            "output_file_id": output_file_id,
+            "error_file_id": null,
+            "request_counts": { "total": 1, "completed": u8::from(output_file_id.is_some()), "failed": 0 },
+        })
+    }
+
+    #[test]
+    fn test_parse_result_line() {
+        let ok = r#"{"custom_id": "chunk-0", "response": {"status_code": 200, "body": {"choices": [{"message": {"content": "{}"}}], "usage": {"prompt_tokens": 12, "completion_tokens": 3}}}, "error": null}"#;
+        let (id, result) = parse_result_line(ok).unwrap();
+        assert_eq!(id, "chunk-0");
+        assert_eq!(result.unwrap().usage, Some(TokenUsage::new(12, 3)));
+
+        let failed = r#"{"custom_id": "chunk-1", "response": {"status_code": 400, "body": {"error": {"message": "bad request"}}}, "error": null}"#;
+        assert_eq!(parse_result_line(failed).unwrap().1.unwrap_err(), "HTTP 400: bad request");
+
+        let expired = r#"{"custom_id": "chunk-2", "response": null, "error": {"code": "batch_expired", "message": "expired"}}"#;
+        assert_eq!(parse_result_line(expired).unwrap().1.unwrap_err(), "expired");
+    }
+
+    #[tokio::test]
+    async fn test_submit_status_collect() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/v1/files"))
+            .and(bearer_token("sk-test"))
+            .and(header("x-org-token", "org-secret"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({ "id": "file_in" })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/v1/batches"))
+            .and(header("x-org-token", "org-secret"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(batch_body("validating", None)))
+            .expect(1)
+            .mount(&server)
+            .await;
+        // A status this version doesn't know keeps the job pending
+        Mock::given(method("GET"))
+            .and(path("/v1/batches/batch_1"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(batch_body("queued_for_review", None)))
+            .up_to_n_times(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/v1/batches/batch_1"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(batch_body("completed", Some("file_out"))))
+            .mount(&server)
+            .await;
+        let result_line = serde_json::json!({
+            "custom_id": "chunk-0",
+            "response": {
+                "status_code": 200,
+                "body": {
+                    "choices": [{ "message": { "content": "{\"person\": \"Ada Lovelace\"}" } }],
+                    "usage": { "prompt_tokens": 90, "completion_tokens": 8 }
+                }
+            },
+            "error": null
+        });
+        Mock::given(method("GET"))
+            .and(path("/v1/files/file_out/content"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(format!("{}\n", result_line)))
+            .mount(&server)
+            .await;
+
+        let provider_config = ProviderConfig::openai("gpt-4o-mini", Some("sk-test".to_string()))
+            .with_base_url(format!("{}/v1", server.uri()))
+            .with_header("x-org-token".to_string(), "org-secret".to_string());
+        let config = with_provider(ExtractConfig::default(), &provider_config);
+        let examples = person_examples();
+
+        let dir = tempfile::tempdir().unwrap();
+        let state_path = dir.path().join("job.json");
+        let text = "Ada Lovelace wrote notes.";
+        let job = BatchJob::submit(text, Some("Extract people"), &examples, &config, &state_path)
+            .await
+            .unwrap();
+        assert_eq!(job.status, BatchStatus::Validating);
+
+        // The request file holds one chat completion per chunk
+        let jsonl = std::fs::read_to_string(BatchJob::requests_path(&state_path)).unwrap();
+        let request: serde_json::Value = serde_json::from_str(jsonl.lines().next().unwrap()).unwrap();
+        assert_eq!(request["url"], BATCH_ENDPOINT);
+        assert_eq!(request["body"]["model"], "gpt-4o-mini");
+
+        // A later process resumes from the state file, which holds no key or header
+        let saved = std::fs::read_to_string(&state_path).unwrap();
+        assert!(!saved.contains("sk-test") && !saved.contains("org-secret"));
+        let mut job = BatchJob::load(&state_path).unwrap();
+        assert!(job.collect("sk-test").await.is_err());
+        assert_eq!(job.refresh("sk-test").await.unwrap(), BatchStatus::Unknown);
+        assert_eq!(job.wait("sk-test", Duration::from_millis(1)).await.unwrap(), BatchStatus::Completed);
+
+        let result = job.collect("sk-test").await.unwrap();
+        let extractions = result.extractions.unwrap();
+        assert_eq!(extractions[0].extraction_text, "Ada Lovelace");
+        assert_eq!(extractions[0].char_interval.as_ref().unwrap().start_pos, Some(0));
+        assert_eq!(result.usage.unwrap().prompt_tokens, 90);
+    }
+
+    #[tokio::test]
+    async fn test_submit_rejects_other_providers() {
+        let config = with_provider(ExtractConfig::default(), &ProviderConfig::ollama("mistral", None));
+        let dir = tempfile::tempdir().unwrap();
+        let result = BatchJob::submit("text", None, &person_examples(), &config, &dir.path().join("job.json")).await;
+        assert!(matches!(result, Err(LangExtractError::ConfigurationError(_))));
+    }
+}
@@ -0,0 +1,567 @@
+//! Persistent on-disk cache for language model responses.
+//!
+//! Responses are stored one file per entry under the cache directory, keyed by
+//! a SHA-256 hash of provider, model, endpoint, response schema, prompt and
+//! inference parameters. Only
+//! prompts that miss the cache are sent to the wrapped provider, so re-running
+//! an extraction, a multipass run or a pipeline pays only for chunks whose
+//! prompt actually changed.
+
+use super::config::CacheConfig;
+use crate::{
+    data::FormatType,
+    exceptions::{LangExtractError, LangExtractResult},
+    inference::{BaseLanguageModel, ScoredOutput},
+    logging::{report_progress, ProgressEvent},
+    schema::BaseSchema,
+    usage::TokenUsage,
+};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime};
+
+/// How often a size-limited cache is pruned even if little was written
+const PRUNE_INTERVAL: Duration = Duration::from_secs(600);
+
+/// A cached response as stored on disk
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CacheEntry {
+    provider: String,
+    model: String,
+    outputs: Vec<ScoredOutput>,
+}
+
+/// File-level information about a cache entry
+struct EntryFile {
+    path: PathBuf,
+    size: u64,
+    modified: SystemTime,
+}
+
+/// Summary of the cache contents
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CacheStats {
+    /// Number of cached responses
+    pub entries: usize,
+    /// Total size of all entries in bytes
+    pub total_bytes: u64,
+    /// Entries older than the configured TTL
+    pub expired: usize,
+    /// Age of the oldest entry in seconds
+    pub oldest_age_secs: Option<u64>,
+    /// Age of the newest entry in seconds
+    pub newest_age_secs: Option<u64>,
+}
+
+/// On-disk response store
+#[derive(Debug, Clone)]
+pub struct ResponseCache {
+    config: CacheConfig,
+}
+
+impl ResponseCache {
+    /// Open (and create if needed) the cache directory
+    pub fn open(config: CacheConfig) -> LangExtractResult<Self> {
+        std::fs::create_dir_all(&config.dir)?;
+        Ok(Self { config })
+    }
+
+    /// Cache settings
+    pub fn config(&self) -> &CacheConfig {
+        &self.config
+    }
+
+    /// Cache key for a provider, model, endpoint, response schema, prompt and
+    /// inference parameters
+    ///
+    /// `schema` is the provider configuration of the applied schema, if any.
+    pub fn key(
+        provider: &str,
+        model: &str,
+        base_url: &str,
+        schema: Option<&HashMap<String, serde_json::Value>>,
+        prompt: &str,
+        kwargs: &HashMap<String, serde_json::Value>,
+    ) -> String {
+        let mut hasher = Sha256::new();
+        for part in [provider.as_bytes(), model.as_bytes(), base_url.as_bytes(), prompt.as_bytes()] {
+            hasher.update(part);
+            hasher.update([0u8]);
+        }
+        let canonical_schema: Option<BTreeMap<_, _>> = schema.map(|s| s.iter().collect());
+        hasher.update(serde_json::to_vec(&canonical_schema).unwrap_or_default());
+        hasher.update([0u8]);
+        let canonical: BTreeMap<_, _> = kwargs.iter().collect();
+        hasher.update(serde_json::to_vec(&canonical).unwrap_or_default());
+        hasher
+            .finalize()
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect()
+    }
+
+    fn entry_path(&self, key: &str) -> PathBuf {
+        self.config.dir.join(&key[..2]).join(format!("{}.json", key))
+    }
+
+    fn is_expired(&self, modified: SystemTime, now: SystemTime) -> bool {
+        match self.config.ttl_secs {
+            Some(ttl) => now
+                .duration_since(modified)
+                .map(|age| age > Duration::from_secs(ttl))
+                .unwrap_or(false),
+            None => false,
+        }
+    }
+
+    /// Look up a cached response; expired or unreadable entries count as misses
+    pub fn get(&self, key: &str) -> Option<Vec<ScoredOutput>> {
+        let path = self.entry_path(key);
+        let modified = std::fs::metadata(&path).and_then(|m| m.modified()).ok()?;
+        if self.is_expired(modified, SystemTime::now()) {
+            let _ = std::fs::remove_file(&path);
+            return None;
+        }
+
+        let content = std::fs::read_to_string(&path).ok()?;
+        match serde_json::from_str::<CacheEntry>(&content) {
+            Ok(entry) => Some(entry.outputs),
+            Err(_) => {
+                let _ = std::fs::remove_file(&path);
+                None
+            }
+        }
+    }
+
+    /// Store a response; returns the number of bytes written
+    pub fn put(
+        &self,
+        key: &str,
+        provider: &str,
+        model: &str,
+        outputs: &[ScoredOutput],
+    ) -> LangExtractResult<u64> {
+        let path = self.entry_path(key);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let entry = CacheEntry {
+            provider: provider.to_string(),
+            model: model.to_string(),
+            outputs: outputs.to_vec(),
+        };
+        let content = serde_json::to_vec(&entry)
+            .map_err(|e| LangExtractError::serialization(format!("Failed to serialize cache entry: {}", e)))?;
+
+        // Write to a temporary file first so readers never see a partial entry
+        let tmp_path = path.with_extension(format!("tmp-{}", uuid::Uuid::new_v4()));
+        std::fs::write(&tmp_path, &content)?;
+        std::fs::rename(&tmp_path, &path)?;
+        Ok(content.len() as u64)
+    }
+
+    fn entry_files(&self) -> LangExtractResult<Vec<EntryFile>> {
+        let mut files = Vec::new();
+        if !self.config.dir.exists() {
+            return Ok(files);
+        }
+
+        for shard in std::fs::read_dir(&self.config.dir)? {
+            let shard = shard?;
+            if !shard.file_type()?.is_dir() {
+                continue;
+            }
+            for entry in std::fs::read_dir(shard.path())? {
+                let entry = entry?;
+                let path = entry.path();
+                if path.extension().and_then(|e| e.to_str()) != Some("json") {
+                    continue;
+                }
+                let metadata = entry.metadata()?;
+                files.push(EntryFile {
+                    path,
+                    size: metadata.len(),
+                    modified: metadata.modified()?,
+                });
+            }
+        }
+
+        Ok(files)
+    }
+
+    /// Summarize the cache contents
+    pub fn stats(&self) -> LangExtractResult<CacheStats> {
+        let now = SystemTime::now();
+        let files = self.entry_files()?;
+        let age = |f: &EntryFile| now.duration_since(f.modified).unwrap_or_default().as_secs();
+
+        Ok(CacheStats {
+            entries: files.len(),
+            total_bytes: files.iter().map(|f| f.size).sum(),
+            expired: files.iter().filter(|f| self.is_expired(f.modified, now)).count(),
+            oldest_age_secs: files.iter().map(age).max(),
+            newest_age_secs: files.iter().map(age).min(),
+        })
+    }
+
+    /// Remove every entry; returns the number removed
+    pub fn clear(&self) -> LangExtractResult<usize> {
+        let files = self.entry_files()?;
+        for file in &files {
+            std::fs::remove_file(&file.path)?;
+        }
+        Ok(files.len())
+    }
+
+    /// Remove expired entries, then the oldest entries until the cache fits
+    /// its size limit; returns the number removed
+    pub fn prune(&self) -> LangExtractResult<usize> {
+        let now = SystemTime::now();
+        let (expired, mut live): (Vec<_>, Vec<_>) = self
+            .entry_files()?
+            .into_iter()
+            .partition(|f| self.is_expired(f.modified, now));
+
+        let mut removed = 0;
+        for file in &expired {
+            std::fs::remove_file(&file.path)?;
+            removed += 1;
+        }
+
+        if let Some(max_size) = self.config.max_size_bytes {
+            live.sort_by_key(|f| f.modified);
+            let mut total: u64 = live.iter().map(|f| f.size).sum();
+            for file in &live {
+                if total <= max_size {
+                    break;
+                }
+                // Another process may have evicted it already
+                if std::fs::remove_file(&file.path).is_ok() {
+                    removed += 1;
+                }
+                total -= file.size;
+            }
+        }
+
+        Ok(removed)
+    }
+}
+
+/// Bytes written since the cache was last pruned
+#[derive(Debug)]
+struct PruneState {
+    written_bytes: u64,
+    last_prune: Instant,
+}
+
+/// Language model wrapper that serves repeated prompts from a [`ResponseCache`]
+pub struct CachedModel {
+    inner: Box<dyn BaseLanguageModel>,
+    cache: ResponseCache,
+    base_url: String,
+    prune_state: Mutex<PruneState>,
+}
+
+impl CachedModel {
+    /// Wrap a provider with the cache described by `config`
+    pub fn new(inner: Box<dyn BaseLanguageModel>, config: CacheConfig) -> LangExtractResult<Self> {
+        Ok(Self {
+            inner,
+            cache: ResponseCache::open(config)?,
+            base_url: String::new(),
+            prune_state: Mutex::new(PruneState {
+                written_bytes: 0,
+                last_prune: Instant::now(),
+            }),
+        })
+    }
+
+    /// Key entries by the endpoint the provider talks to
+    pub fn with_base_url<S: Into<String>>(mut self, base_url: S) -> Self {
+        self.base_url = base_url.into();
+        self
+    }
+
+    /// The underlying cache
+    pub fn cache(&self) -> &ResponseCache {
+        &self.cache
+    }
+
+    /// Whether enough was written, or enough time has passed, to prune again
+    ///
+    /// Pruning walks the whole cache directory, so a size-limited cache is only
+    /// pruned once a tenth of its limit has been written since the last prune,
+    /// or every [`PRUNE_INTERVAL`].
+    fn should_prune(&self, written: u64) -> bool {
+        let Some(max_size) = self.cache.config().max_size_bytes else {
+            return false;
+        };
+        let mut state = self.prune_state.lock().unwrap_or_else(|e| e.into_inner());
+        state.written_bytes += written;
+        if state.written_bytes < (max_size / 10).max(1) && state.last_prune.elapsed() < PRUNE_INTERVAL {
+            return false;
+        }
+        state.written_bytes = 0;
+        state.last_prune = Instant::now();
+        true
+    }
+}
+
+#[async_trait]
+impl BaseLanguageModel for CachedModel {
+    fn get_schema_class(&self) -> Option<Box<dyn BaseSchema>> {
+        self.inner.get_schema_class()
+    }
+
+    fn apply_schema(&mut self, schema: Option<Box<dyn BaseSchema>>) {
+        self.inner.apply_schema(schema);
+    }
+
+    fn applied_schema_config(&self) -> Option<HashMap<String, serde_json::Value>> {
+        self.inner.applied_schema_config()
+    }
+
+    fn set_fence_output(&mut self, fence_output: Option<bool>) {
+        self.inner.set_fence_output(fence_output);
+    }
+
+    fn requires_fence_output(&self) -> bool {
+        self.inner.requires_fence_output()
+    }
+
+    async fn infer(
+        &self,
+        batch_prompts: &[String],
+        kwargs: &HashMap<String, serde_json::Value>,
+    ) -> LangExtractResult<Vec<Vec<ScoredOutput>>> {
+        let provider = self.inner.provider_name();
+        let model = self.inner.model_id();
+        let schema = self.inner.applied_schema_config();
+        let keys: Vec<String> = batch_prompts
+            .iter()
+            .map(|prompt| ResponseCache::key(provider, model, &self.base_url, schema.as_ref(), prompt, kwargs))
+            .collect();
+
+        // Cache hits cost no tokens
+        let mut results: Vec<Option<Vec<ScoredOutput>>> = keys
+            .iter()
+            .map(|key| {
+                self.cache.get(key).map(|outputs| {
+                    outputs.into_iter().map(|o| o.with_usage(TokenUsage::default())).collect()
+                })
+            })
+            .collect();
+        let misses: Vec<usize> = (0..results.len()).filter(|&i| results[i].is_none()).collect();
+
+        report_progress(ProgressEvent::Debug {
+            operation: "response_cache".to_string(),
+            details: format!(
+                "{} cached, {} sent to {}",
+                batch_prompts.len() - misses.len(),
+                misses.len(),
+                provider
+            ),
+        });
+
+        if !misses.is_empty() {
+            let miss_prompts: Vec<String> = misses.iter().map(|&i| batch_prompts[i].clone()).collect();
+            let fresh = self.inner.infer(&miss_prompts, kwargs).await?;
+
+            let mut written = 0;
+            for (&i, outputs) in misses.iter().zip(fresh) {
+                // A failed cache write should never fail the extraction
+                match self.cache.put(&keys[i], provider, model, &outputs) {
+                    Ok(bytes) => written += bytes,
+                    Err(e) => log::warn!("Failed to write response cache entry: {}", e),
+                }
+                results[i] = Some(outputs);
+            }
+
+            if self.should_prune(written) {
+                if let Err(e) = self.cache.prune() {
+                    log::warn!("Failed to prune response cache: {}", e);
+                }
+            }
+        }
+
+        Ok(results.into_iter().map(|r| r.unwrap_or_default()).collect())
+    }
+
+    fn format_type(&self) -> FormatType {
+        self.inner.format_type()
+    }
+
+    fn model_id(&self) -> &str {
+        self.inner.model_id()
+    }
+
+    fn provider_name(&self) -> &str {
+        self.inner.provider_name()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    /// Provider stub that echoes prompts and counts prompts it was sent
+    struct EchoModel {
+        prompts_seen: Arc<AtomicUsize>,
+    }
+
+    #[async_trait]
+    impl BaseLanguageModel for EchoModel {
+        async fn infer(
+            &self,
+            batch_prompts: &[String],
+            _kwargs: &HashMap<String, serde_json::Value>,
+        ) -> LangExtractResult<Vec<Vec<ScoredOutput>>> {
+            self.prompts_seen.fetch_add(batch_prompts.len(), Ordering::SeqCst);
+            Ok(batch_prompts
+                .iter()
+                .map(|p| vec![ScoredOutput::from_text(format!("echo: {}", p))])
+                .collect())
+        }
+
+        fn model_id(&self) -> &str {
+            "echo-1"
+        }
+
+        fn provider_name(&self) -> &str {
+            "echo"
+        }
+    }
+
+    fn cached_echo(config: CacheConfig) -> (CachedModel, Arc<AtomicUsize>) {
+        let prompts_seen = Arc::new(AtomicUsize::new(0));
+        let model = CachedModel::new(
+            Box::new(EchoModel { prompts_seen: prompts_seen.clone() }),
+            config,
+        )
+        .unwrap();
+        (model, prompts_seen)
+    }
+
+    #[test]
+    fn test_key_depends_on_model_endpoint_and_kwargs() {
+        let url = "https://api.openai.com/v1";
+        let kwargs = HashMap::from([("temperature".to_string(), serde_json::json!(0.3))]);
+        let base = ResponseCache::key("openai", "gpt-4o", url, None, "prompt", &kwargs);
+
+        assert_eq!(base, ResponseCache::key("openai", "gpt-4o", url, None, "prompt", &kwargs.clone()));
+        assert_ne!(base, ResponseCache::key("openai", "gpt-4o-mini", url, None, "prompt", &kwargs));
+        assert_ne!(base, ResponseCache::key("openai", "gpt-4o", url, None, "prompt", &HashMap::new()));
+        assert_ne!(base, ResponseCache::key("openai", "gpt-4o", "http://localhost:8000/v1", None, "prompt", &kwargs));
+    }
+
+    #[tokio::test]
+    async fn test_applied_schema_changes_key() {
+        use crate::data::{ExampleData, Extraction};
+        use crate::providers::{ProviderConfig, UniversalProvider};
+        use crate::schema::ExtractionClass;
+        use crate::test_support;
+
+        let server = test_support::ollama().await;
+        let dir = tempfile::tempdir().unwrap();
+        let cached_with_classes = |examples: Vec<ExampleData>| {
+            let mut provider = UniversalProvider::new(ProviderConfig::ollama("mistral", Some(server.uri()))).unwrap();
+            let schema = provider.schema_for_classes(&ExtractionClass::from_examples(&examples)).unwrap();
+            provider.apply_schema(schema);
+            CachedModel::new(Box::new(provider), CacheConfig::new(dir.path())).unwrap()
+        };
+        let places = vec![ExampleData::new(
+            "They met in London.".to_string(),
+            vec![Extraction::new("place".to_string(), "London".to_string())],
+        )];
+        let prompts = ["Ada met Charles in London.".to_string()];
+
+        cached_with_classes(test_support::person_examples()).infer(&prompts, &HashMap::new()).await.unwrap();
+        cached_with_classes(places).infer(&prompts, &HashMap::new()).await.unwrap();
+        assert_eq!(server.received_requests().await.unwrap().len(), 2);
+
+        // The same schema again is a hit
+        cached_with_classes(test_support::person_examples()).infer(&prompts, &HashMap::new()).await.unwrap();
+        assert_eq!(server.received_requests().await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_prune_waits_for_size_threshold() {
+        let dir = tempfile::tempdir().unwrap();
+        let (model, _) = cached_echo(CacheConfig::new(dir.path()).with_max_size_bytes(1_000_000));
+
+        // Far below a tenth of the limit, so no directory walk is due yet
+        model.infer(&["a".to_string()], &HashMap::new()).await.unwrap();
+        assert!(!model.should_prune(0));
+        assert!(model.should_prune(100_000));
+        assert!(!model.should_prune(0));
+
+        let (unlimited, _) = cached_echo(CacheConfig::new(dir.path()));
+        assert!(!unlimited.should_prune(u64::MAX));
+    }
+
+    #[tokio::test]
+    async fn test_only_misses_reach_provider() {
+        let dir = tempfile::tempdir().unwrap();
+        let (model, prompts_seen) = cached_echo(CacheConfig::new(dir.path()));
+        let kwargs = HashMap::new();
+
+        let first = model.infer(&["a".to_string(), "b".to_string()], &kwargs).await.unwrap();
+        assert_eq!(prompts_seen.load(Ordering::SeqCst), 2);
+
+        // A fresh wrapper over the same directory sees the persisted entries
+        let (model, prompts_seen) = cached_echo(CacheConfig::new(dir.path()));
+        let second = model
+            .infer(&["a".to_string(), "c".to_string(), "b".to_string()], &kwargs)
+            .await
+            .unwrap();
+        assert_eq!(prompts_seen.load(Ordering::SeqCst), 1);
+        // Cache hits carry the same output, reported as costing no tokens
+        let hit = |outputs: &[ScoredOutput]| -> Vec<ScoredOutput> {
+            outputs.iter().cloned().map(|o| o.with_usage(TokenUsage::default())).collect()
+        };
+        assert_eq!(second[0], hit(&first[0]));
+        assert_eq!(second[1][0].text(), "echo: c");
+        assert_eq!(second[2], hit(&first[1]));
+        assert_eq!(model.cache().stats().unwrap().entries, 3);
+    }
+
+    #[tokio::test]
+    async fn test_expired_entries_are_misses() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = ResponseCache::open(CacheConfig::new(dir.path()).with_ttl_secs(0)).unwrap();
+        cache.put("abcdef", "echo", "echo-1", &[ScoredOutput::from_text("x".to_string())]).unwrap();
+
+        std::thread::sleep(Duration::from_millis(20));
+        assert_eq!(cache.stats().unwrap().expired, 1);
+        assert!(cache.get("abcdef").is_none());
+        assert_eq!(cache.stats().unwrap().entries, 0);
+    }
+
+    #[test]
+    fn test_prune_enforces_size_limit() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = ResponseCache::open(CacheConfig::new(dir.path())).unwrap();
+        let outputs = [ScoredOutput::from_text("x".repeat(100))];
+        for key in ["aa01", "aa02", "aa03"] {
+            cache.put(key, "echo", "echo-1", &outputs).unwrap();
+        }
+        let entry_size = cache.stats().unwrap().total_bytes / 3;
+
+        let limited = ResponseCache::open(
+            CacheConfig::new(dir.path()).with_max_size_bytes(entry_size * 2),
+        )
+        .unwrap();
+        assert_eq!(limited.prune().unwrap(), 1);
+        assert_eq!(limited.stats().unwrap().entries, 2);
+
+        assert_eq!(limited.clear().unwrap(), 2);
+        assert_eq!(limited.stats().unwrap(), CacheStats::default());
+    }
+}
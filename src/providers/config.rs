@@ -1,7 +1,11 @@
 //! Provider configuration types.
 
+use super::fallback::FallbackPolicy;
+use super::rate_limit::RateLimitConfig;
+use super::retry::RetryPolicy;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::path::PathBuf;
 
 /// Provider type enumeration
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
@@ -12,6 +16,10 @@ pub enum ProviderType {
     OpenAI,
     /// Ollama local server
     Ollama,
+    /// Google Gemini generateContent API
+    Gemini,
+    /// Anthropic Messages API
+    Anthropic,
     /// Custom HTTP API
     Custom,
 }
@@ -21,6 +29,8 @@ impl std::fmt::Display for ProviderType {
         match self {
             ProviderType::OpenAI => write!(f, "openai"),
             ProviderType::Ollama => write!(f, "ollama"),
+            ProviderType::Gemini => write!(f, "gemini"),
+            ProviderType::Anthropic => write!(f, "anthropic"),
             ProviderType::Custom => write!(f, "custom"),
         }
     }
@@ -33,6 +43,8 @@ impl std::str::FromStr for ProviderType {
         match s.to_lowercase().as_str() {
             "openai" => Ok(ProviderType::OpenAI),
             "ollama" => Ok(ProviderType::Ollama),
+            "gemini" => Ok(ProviderType::Gemini),
+            "anthropic" => Ok(ProviderType::Anthropic),
             "custom" => Ok(ProviderType::Custom),
             _ => Err(format!("Unknown provider type: {}", s)),
         }
@@ -54,6 +66,139 @@ pub struct ProviderConfig {
     pub headers: HashMap<String, String>,
     /// Provider-specific parameters
     pub extra_params: HashMap<String, serde_json::Value>,
+    /// Request/response mapping for custom HTTP providers
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub custom_mapping: Option<CustomHttpMapping>,
+    /// Record responses to, or replay them from, a fixture file
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fixture: Option<FixtureConfig>,
+    /// Persistent on-disk response cache
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache: Option<CacheConfig>,
+    /// How failed calls are retried
+    #[serde(default)]
+    pub retry: RetryPolicy,
+    /// Client-side request and token budgets
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rate_limit: Option<RateLimitConfig>,
+    /// Providers to try, in order, when this one fails
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub fallbacks: Vec<ProviderConfig>,
+    /// Which failures move a request on to the next fallback provider
+    #[serde(default)]
+    pub fallback_policy: FallbackPolicy,
+}
+
+/// On-disk response cache settings
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CacheConfig {
+    /// Directory holding cached responses
+    pub dir: PathBuf,
+    /// Entries older than this many seconds are ignored and pruned
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ttl_secs: Option<u64>,
+    /// Oldest entries are evicted once the cache grows beyond this many bytes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_size_bytes: Option<u64>,
+}
+
+impl CacheConfig {
+    /// Cache responses in `dir` with no expiry or size limit
+    pub fn new<P: Into<PathBuf>>(dir: P) -> Self {
+        Self {
+            dir: dir.into(),
+            ttl_secs: None,
+            max_size_bytes: None,
+        }
+    }
+
+    /// Expire entries after `ttl_secs` seconds
+    pub fn with_ttl_secs(mut self, ttl_secs: u64) -> Self {
+        self.ttl_secs = Some(ttl_secs);
+        self
+    }
+
+    /// Limit the total size of the cache
+    pub fn with_max_size_bytes(mut self, max_size_bytes: u64) -> Self {
+        self.max_size_bytes = Some(max_size_bytes);
+        self
+    }
+}
+
+/// Whether a fixture file is being written or read
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FixtureMode {
+    /// Call the real provider and store every response
+    Record,
+    /// Serve stored responses only; a missing response is an error
+    Replay,
+}
+
+/// Fixture file used by the record/replay model
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FixtureConfig {
+    /// Record or replay
+    pub mode: FixtureMode,
+    /// Path to the JSON fixture file
+    pub path: PathBuf,
+}
+
+/// Declarative request/response mapping for a custom HTTP provider
+///
+/// String values in `body_template` of the form `{{name}}` are replaced with
+/// `prompt`, `model`, or any inference parameter (e.g. `temperature`,
+/// `max_tokens`, `stop_sequences`). A value that is exactly one placeholder
+/// keeps the parameter's JSON type; if the parameter is not set, the entry is
+/// dropped from the request body.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CustomHttpMapping {
+    /// Path appended to the base URL (e.g. "/v1/generate")
+    #[serde(default)]
+    pub path: String,
+    /// JSON request body with `{{placeholder}}` substitutions
+    pub body_template: serde_json::Value,
+    /// JSON pointer (RFC 6901) to the completion text in the response
+    pub text_pointer: String,
+    /// Optional JSON pointer to a numeric score in the response
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub score_pointer: Option<String>,
+}
+
+impl Default for CustomHttpMapping {
+    /// OpenAI-compatible chat completions shape
+    fn default() -> Self {
+        Self {
+            path: "/chat/completions".to_string(),
+            body_template: serde_json::json!({
+                "model": "{{model}}",
+                "messages": [{ "role": "user", "content": "{{prompt}}" }],
+                "temperature": "{{temperature}}",
+                "max_tokens": "{{max_tokens}}",
+                "stop": "{{stop_sequences}}",
+            }),
+            text_pointer: "/choices/0/message/content".to_string(),
+            score_pointer: None,
+        }
+    }
+}
+
+impl CustomHttpMapping {
+    /// Create a mapping from a path, body template and text pointer
+    pub fn new(path: &str, body_template: serde_json::Value, text_pointer: &str) -> Self {
+        Self {
+            path: path.to_string(),
+            body_template,
+            text_pointer: text_pointer.to_string(),
+            score_pointer: None,
+        }
+    }
+
+    /// Set the JSON pointer to a numeric score
+    pub fn with_score_pointer(mut self, score_pointer: &str) -> Self {
+        self.score_pointer = Some(score_pointer.to_string());
+        self
+    }
 }
 
 impl ProviderConfig {
@@ -66,6 +211,13 @@ impl ProviderConfig {
             api_key,
             headers: HashMap::new(),
             extra_params: HashMap::new(),
+            custom_mapping: None,
+            fixture: None,
+            cache: None,
+            retry: RetryPolicy::default(),
+            rate_limit: None,
+            fallbacks: Vec::new(),
+            fallback_policy: FallbackPolicy::default(),
         }
     }
 
@@ -87,6 +239,13 @@ impl ProviderConfig {
             api_key,
             headers: HashMap::new(),
             extra_params: HashMap::new(),
+            custom_mapping: None,
+            fixture: None,
+            cache: None,
+            retry: RetryPolicy::default(),
+            rate_limit: None,
+            fallbacks: Vec::new(),
+            fallback_policy: FallbackPolicy::default(),
         };
         
         // Add Azure-specific headers
@@ -103,6 +262,13 @@ impl ProviderConfig {
             api_key,
             headers: HashMap::new(),
             extra_params: HashMap::new(),
+            custom_mapping: None,
+            fixture: None,
+            cache: None,
+            retry: RetryPolicy::default(),
+            rate_limit: None,
+            fallbacks: Vec::new(),
+            fallback_policy: FallbackPolicy::default(),
         }
     }
 
@@ -115,6 +281,54 @@ impl ProviderConfig {
             api_key: None,
             headers: HashMap::new(),
             extra_params: HashMap::new(),
+            custom_mapping: None,
+            fixture: None,
+            cache: None,
+            retry: RetryPolicy::default(),
+            rate_limit: None,
+            fallbacks: Vec::new(),
+            fallback_policy: FallbackPolicy::default(),
+        }
+    }
+
+    /// Create a new Gemini provider config
+    pub fn gemini(model: &str, api_key: Option<String>) -> Self {
+        Self {
+            provider_type: ProviderType::Gemini,
+            base_url: "https://generativelanguage.googleapis.com/v1beta".to_string(),
+            model: model.to_string(),
+            api_key,
+            headers: HashMap::new(),
+            extra_params: HashMap::new(),
+            custom_mapping: None,
+            fixture: None,
+            cache: None,
+            retry: RetryPolicy::default(),
+            rate_limit: None,
+            fallbacks: Vec::new(),
+            fallback_policy: FallbackPolicy::default(),
+        }
+    }
+
+    /// Create a new Anthropic provider config
+    ///
+    /// The assistant turn is prefilled so the model continues directly into
+    /// structured output; set the `prefill` extra param to `false` to disable.
+    pub fn anthropic(model: &str, api_key: Option<String>) -> Self {
+        Self {
+            provider_type: ProviderType::Anthropic,
+            base_url: "https://api.anthropic.com/v1".to_string(),
+            model: model.to_string(),
+            api_key,
+            headers: HashMap::new(),
+            extra_params: HashMap::new(),
+            custom_mapping: None,
+            fixture: None,
+            cache: None,
+            retry: RetryPolicy::default(),
+            rate_limit: None,
+            fallbacks: Vec::new(),
+            fallback_policy: FallbackPolicy::default(),
         }
     }
 
@@ -127,6 +341,13 @@ impl ProviderConfig {
             api_key: None,
             headers: HashMap::new(),
             extra_params: HashMap::new(),
+            custom_mapping: None,
+            fixture: None,
+            cache: None,
+            retry: RetryPolicy::default(),
+            rate_limit: None,
+            fallbacks: Vec::new(),
+            fallback_policy: FallbackPolicy::default(),
         }
     }
 
@@ -153,6 +374,54 @@ impl ProviderConfig {
         self.base_url = base_url;
         self
     }
+
+    /// Set the request/response mapping for a custom provider
+    pub fn with_custom_mapping(mut self, mapping: CustomHttpMapping) -> Self {
+        self.custom_mapping = Some(mapping);
+        self
+    }
+
+    /// Record all responses from this provider to a fixture file
+    pub fn with_record<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.fixture = Some(FixtureConfig { mode: FixtureMode::Record, path: path.into() });
+        self
+    }
+
+    /// Replay responses from a fixture file instead of calling the provider
+    pub fn with_replay<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.fixture = Some(FixtureConfig { mode: FixtureMode::Replay, path: path.into() });
+        self
+    }
+
+    /// Cache responses on disk so repeated prompts are not re-billed
+    pub fn with_cache(mut self, cache: CacheConfig) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    /// Set the retry policy
+    pub fn with_retry_policy(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Throttle calls to stay within per-minute request and token budgets
+    pub fn with_rate_limit(mut self, rate_limit: RateLimitConfig) -> Self {
+        self.rate_limit = Some(rate_limit);
+        self
+    }
+
+    /// Append a provider to try when the previous ones fail
+    pub fn with_fallback(mut self, fallback: ProviderConfig) -> Self {
+        self.fallbacks.push(fallback);
+        self
+    }
+
+    /// Set which failures trigger the fallback providers
+    pub fn with_fallback_policy(mut self, policy: FallbackPolicy) -> Self {
+        self.fallback_policy = policy;
+        self
+    }
 }
 
 #[cfg(test)]
@@ -164,11 +433,15 @@ mod tests {
         assert_eq!("openai".parse::<ProviderType>().unwrap(), ProviderType::OpenAI);
         assert_eq!("ollama".parse::<ProviderType>().unwrap(), ProviderType::Ollama);
         assert_eq!("OPENAI".parse::<ProviderType>().unwrap(), ProviderType::OpenAI);
+        assert_eq!("gemini".parse::<ProviderType>().unwrap(), ProviderType::Gemini);
+        assert_eq!("anthropic".parse::<ProviderType>().unwrap(), ProviderType::Anthropic);
         
         assert!(matches!("unknown".parse::<ProviderType>(), Err(_)));
         
         assert_eq!(ProviderType::OpenAI.to_string(), "openai");
         assert_eq!(ProviderType::Ollama.to_string(), "ollama");
+        assert_eq!(ProviderType::Gemini.to_string(), "gemini");
+        assert_eq!(ProviderType::Anthropic.to_string(), "anthropic");
     }
 
     #[test]
@@ -184,6 +457,15 @@ mod tests {
         assert_eq!(ollama_config.model, "mistral");
         assert_eq!(ollama_config.base_url, "http://localhost:11434");
         assert!(ollama_config.api_key.is_none());
+
+        let gemini_config = ProviderConfig::gemini("gemini-2.5-flash", Some("test-key".to_string()));
+        assert_eq!(gemini_config.provider_type, ProviderType::Gemini);
+        assert_eq!(gemini_config.model, "gemini-2.5-flash");
+        assert_eq!(gemini_config.base_url, "https://generativelanguage.googleapis.com/v1beta");
+
+        let anthropic_config = ProviderConfig::anthropic("claude-sonnet-4-5", Some("test-key".to_string()));
+        assert_eq!(anthropic_config.provider_type, ProviderType::Anthropic);
+        assert_eq!(anthropic_config.base_url, "https://api.anthropic.com/v1");
     }
 
     #[test]
@@ -206,5 +488,30 @@ mod tests {
         let deserialized: ProviderConfig = serde_json::from_str(&json).unwrap();
         assert_eq!(config.provider_type, deserialized.provider_type);
         assert_eq!(config.model, deserialized.model);
+        assert!(deserialized.custom_mapping.is_none());
+    }
+
+    #[test]
+    fn test_custom_mapping_deserialization() {
+        let json = serde_json::json!({
+            "provider_type": "custom",
+            "base_url": "http://gateway.internal",
+            "model": "house-llm",
+            "api_key": null,
+            "headers": {},
+            "extra_params": {},
+            "custom_mapping": {
+                "path": "/v2/complete",
+                "body_template": { "input": "{{prompt}}", "params": { "temp": "{{temperature}}" } },
+                "text_pointer": "/result/text",
+                "score_pointer": "/result/logprob"
+            }
+        });
+
+        let config: ProviderConfig = serde_json::from_value(json).unwrap();
+        let mapping = config.custom_mapping.unwrap();
+        assert_eq!(mapping.path, "/v2/complete");
+        assert_eq!(mapping.text_pointer, "/result/text");
+        assert_eq!(mapping.score_pointer.as_deref(), Some("/result/logprob"));
     }
 }
@@ -0,0 +1,444 @@
+//! Provider fallback chains.
+//!
+//! [`FallbackModel`] holds an ordered list of models and sends each prompt to
+//! the first one. When that provider fails in a way the [`FallbackPolicy`]
+//! covers, the prompt moves on to the next provider, independently of the
+//! other prompts in the batch. Every output is tagged with the provider that
+//! produced it.
+
+use super::config::ProviderConfig;
+use super::create_provider;
+use crate::{
+    exceptions::{LangExtractError, LangExtractResult},
+    inference::{BaseLanguageModel, ScoredOutput},
+    logging::{report_progress, ProgressEvent},
+    resolver::Resolver,
+    schema::BaseSchema,
+};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// When a prompt moves on to the next provider in a fallback chain
+///
+/// Each provider exhausts its own [`RetryPolicy`](super::RetryPolicy) first,
+/// so these conditions only apply to failures that survived retrying.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FallbackPolicy {
+    /// Fail over on HTTP 429
+    pub on_rate_limit: bool,
+    /// Fail over on HTTP 5xx and malformed response bodies
+    pub on_server_error: bool,
+    /// Fail over on connection failures, request timeouts and HTTP 408
+    pub on_network_error: bool,
+    /// Fail over on other HTTP 4xx responses, such as auth failures
+    pub on_client_error: bool,
+    /// Fail over when the resolver cannot parse the response
+    pub on_parse_failure: bool,
+    /// Fail over when a provider has not answered within this many
+    /// milliseconds, retries included
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timeout_ms: Option<u64>,
+}
+
+impl Default for FallbackPolicy {
+    fn default() -> Self {
+        Self {
+            on_rate_limit: true,
+            on_server_error: true,
+            on_network_error: true,
+            on_client_error: false,
+            on_parse_failure: true,
+            timeout_ms: None,
+        }
+    }
+}
+
+impl FallbackPolicy {
+    /// Fail over on every error class and on parse failures
+    pub fn any_failure() -> Self {
+        Self {
+            on_client_error: true,
+            ..Self::default()
+        }
+    }
+
+    /// Fail over when a provider takes longer than `timeout`
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout_ms = Some(timeout.as_millis() as u64);
+        self
+    }
+
+    /// Enable or disable failing over on unparseable responses
+    pub fn with_parse_failure(mut self, on_parse_failure: bool) -> Self {
+        self.on_parse_failure = on_parse_failure;
+        self
+    }
+
+    /// Whether an error should send the prompt to the next provider
+    pub fn should_fail_over(&self, error: &LangExtractError) -> bool {
+        if let Some(status_error) = error.status_error() {
+            return match status_error.status {
+                429 => self.on_rate_limit,
+                408 => self.on_network_error,
+                500..=599 => self.on_server_error,
+                400..=499 => self.on_client_error,
+                _ => false,
+            };
+        }
+
+        match error {
+            LangExtractError::NetworkError(e) => match e.status().map(|s| s.as_u16()) {
+                Some(429) => self.on_rate_limit,
+                Some(500..=599) => self.on_server_error,
+                Some(400..=499) => self.on_client_error,
+                _ => self.on_network_error,
+            },
+            LangExtractError::ParsingError(_) => self.on_server_error,
+            _ => false,
+        }
+    }
+}
+
+/// One provider in a fallback chain
+struct FallbackMember {
+    /// `provider/model`, recorded on every output this member produces
+    label: String,
+    model: Box<dyn BaseLanguageModel>,
+}
+
+/// Composite model that fails over between providers per prompt
+pub struct FallbackModel {
+    members: Vec<FallbackMember>,
+    policy: FallbackPolicy,
+    /// Resolver and expected fields used to detect unparseable responses
+    resolver: Option<(Resolver, Vec<String>)>,
+}
+
+impl FallbackModel {
+    /// Chain `models` in order of preference
+    pub fn new(models: Vec<Box<dyn BaseLanguageModel>>, policy: FallbackPolicy) -> LangExtractResult<Self> {
+        if models.is_empty() {
+            return Err(LangExtractError::configuration(
+                "A fallback chain needs at least one provider",
+            ));
+        }
+
+        let members = models
+            .into_iter()
+            .map(|model| FallbackMember {
+                label: format!("{}/{}", model.provider_name(), model.model_id()),
+                model,
+            })
+            .collect();
+
+        Ok(Self {
+            members,
+            policy,
+            resolver: None,
+        })
+    }
+
+    /// Build a chain of providers from their configurations
+    pub fn from_configs(configs: Vec<ProviderConfig>, policy: FallbackPolicy) -> LangExtractResult<Self> {
+        let models = configs
+            .into_iter()
+            .map(|config| create_provider(config).map(|p| Box::new(p) as Box<dyn BaseLanguageModel>))
+            .collect::<LangExtractResult<Vec<_>>>()?;
+        Self::new(models, policy)
+    }
+
+    /// Check responses with `resolver` so unparseable ones can fail over
+    pub fn with_resolver(mut self, resolver: Resolver, expected_fields: Vec<String>) -> Self {
+        self.resolver = Some((resolver, expected_fields));
+        self
+    }
+
+    /// `provider/model` labels of the chain, in order
+    pub fn labels(&self) -> Vec<&str> {
+        self.members.iter().map(|m| m.label.as_str()).collect()
+    }
+
+    /// Why `outputs` should fail over, if the resolver cannot parse them
+    fn parse_failure(&self, outputs: &[ScoredOutput]) -> Option<String> {
+        if !self.policy.on_parse_failure {
+            return None;
+        }
+        let (resolver, expected_fields) = self.resolver.as_ref()?;
+        let text = outputs.first().map(|o| o.text()).unwrap_or_default();
+        resolver
+            .parse(text, expected_fields)
+            .err()
+            .map(|e| format!("unparseable response: {}", e))
+    }
+
+    /// Run one prompt down the chain until a provider answers acceptably
+    async fn infer_prompt(
+        &self,
+        prompt: &str,
+        kwargs: &HashMap<String, serde_json::Value>,
+    ) -> LangExtractResult<Vec<ScoredOutput>> {
+        let timeout = self.policy.timeout_ms.map(Duration::from_millis);
+        let batch = [prompt.to_string()];
+
+        for (index, member) in self.members.iter().enumerate() {
+            let is_last = index + 1 == self.members.len();
+
+            let result = match timeout {
+                Some(timeout) => tokio::time::timeout(timeout, member.model.infer(&batch, kwargs))
+                    .await
+                    .unwrap_or_else(|_| {
+                        Err(LangExtractError::inference_simple(format!(
+                            "{} did not answer within {}ms",
+                            member.label,
+                            timeout.as_millis()
+                        )))
+                    })
+                    .map_err(|e| (e, true)),
+                None => member.model.infer(&batch, kwargs).await.map_err(|e| (e, false)),
+            };
+
+            let reason = match result {
+                Ok(batches) => {
+                    let outputs = batches.into_iter().next().unwrap_or_default();
+                    match self.parse_failure(&outputs) {
+                        Some(reason) if !is_last => reason,
+                        // The last provider's answer is returned as is; the
+                        // annotator handles unparseable output on its own
+                        _ => {
+                            return Ok(outputs
+                                .into_iter()
+                                .map(|o| o.with_provider(member.label.clone()))
+                                .collect())
+                        }
+                    }
+                }
+                Err((e, timed_out)) => {
+                    if is_last || !(timed_out || self.policy.should_fail_over(&e)) {
+                        return Err(e);
+                    }
+                    e.to_string()
+                }
+            };
+
+            report_progress(ProgressEvent::Error {
+                operation: format!("{} (falling back to {})", member.label, self.members[index + 1].label),
+                error: reason,
+            });
+        }
+
+        unreachable!("the last provider in a fallback chain always returns")
+    }
+}
+
+#[async_trait]
+impl BaseLanguageModel for FallbackModel {
+    fn get_schema_class(&self) -> Option<Box<dyn BaseSchema>> {
+        self.members[0].model.get_schema_class()
+    }
+
+    /// The schema goes to the primary; the other providers get their own
+    /// schema class, since schemas are provider-specific
+    fn apply_schema(&mut self, schema: Option<Box<dyn BaseSchema>>) {
+        let enabled = schema.is_some();
+        let (primary, others) = self.members.split_first_mut().expect("chain is never empty");
+        primary.model.apply_schema(schema);
+        for member in others {
+            let own_schema = if enabled { member.model.get_schema_class() } else { None };
+            member.model.apply_schema(own_schema);
+        }
+    }
+
+    fn applied_schema_config(&self) -> Option<HashMap<String, serde_json::Value>> {
+        self.members[0].model.applied_schema_config()
+    }
+
+    fn set_fence_output(&mut self, fence_output: Option<bool>) {
+        for member in &mut self.members {
+            member.model.set_fence_output(fence_output);
+        }
+    }
+
+    fn requires_fence_output(&self) -> bool {
+        self.members[0].model.requires_fence_output()
+    }
+
+    async fn infer(
+        &self,
+        batch_prompts: &[String],
+        kwargs: &HashMap<String, serde_json::Value>,
+    ) -> LangExtractResult<Vec<Vec<ScoredOutput>>> {
+        use futures::future::join_all;
+
+        let futures: Vec<_> = batch_prompts
+            .iter()
+            .map(|prompt| self.infer_prompt(prompt, kwargs))
+            .collect();
+
+        join_all(futures).await.into_iter().collect()
+    }
+
+    fn model_id(&self) -> &str {
+        self.members[0].model.model_id()
+    }
+
+    fn provider_name(&self) -> &str {
+        "fallback"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ExtractConfig;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    /// Model that returns a fixed result and counts its calls
+    struct StubModel {
+        name: &'static str,
+        result: Result<&'static str, u16>,
+        delay: Duration,
+        calls: Arc<AtomicUsize>,
+    }
+
+    impl StubModel {
+        fn answering(name: &'static str, text: &'static str) -> Self {
+            Self { name, result: Ok(text), delay: Duration::ZERO, calls: Arc::default() }
+        }
+
+        fn failing(name: &'static str, status: u16) -> Self {
+            Self { name, result: Err(status), delay: Duration::ZERO, calls: Arc::default() }
+        }
+    }
+
+    #[async_trait]
+    impl BaseLanguageModel for StubModel {
+        async fn infer(
+            &self,
+            batch_prompts: &[String],
+            _kwargs: &HashMap<String, serde_json::Value>,
+        ) -> LangExtractResult<Vec<Vec<ScoredOutput>>> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            tokio::time::sleep(self.delay).await;
+            match self.result {
+                Ok(text) => Ok(batch_prompts
+                    .iter()
+                    .map(|_| vec![ScoredOutput::from_text(text.to_string())])
+                    .collect()),
+                Err(status) => Err(LangExtractError::http_status(self.name, status, None, "stub failure")),
+            }
+        }
+
+        fn model_id(&self) -> &str {
+            self.name
+        }
+
+        fn provider_name(&self) -> &str {
+            "stub"
+        }
+    }
+
+    fn chain(models: Vec<StubModel>, policy: FallbackPolicy) -> FallbackModel {
+        let models = models
+            .into_iter()
+            .map(|m| Box::new(m) as Box<dyn BaseLanguageModel>)
+            .collect();
+        FallbackModel::new(models, policy).unwrap()
+    }
+
+    #[test]
+    fn test_policy_error_classes() {
+        let policy = FallbackPolicy::default();
+        let status = |code| LangExtractError::http_status("test", code, None, "boom");
+        assert!(policy.should_fail_over(&status(429)));
+        assert!(policy.should_fail_over(&status(503)));
+        assert!(policy.should_fail_over(&status(408)));
+        assert!(!policy.should_fail_over(&status(401)));
+        assert!(FallbackPolicy::any_failure().should_fail_over(&status(401)));
+        assert!(!policy.should_fail_over(&LangExtractError::configuration("no key")));
+    }
+
+    #[tokio::test]
+    async fn test_fails_over_and_records_provider() {
+        let model = chain(
+            vec![StubModel::failing("primary", 503), StubModel::answering("backup", "{}")],
+            FallbackPolicy::default(),
+        );
+
+        let outputs = model.infer(&["a".to_string(), "b".to_string()], &HashMap::new()).await.unwrap();
+        assert_eq!(outputs.len(), 2);
+        for batch in outputs {
+            assert_eq!(batch[0].text(), "{}");
+            assert_eq!(batch[0].provider.as_deref(), Some("stub/backup"));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_uncovered_error_does_not_fail_over() {
+        let backup = StubModel::answering("backup", "{}");
+        let backup_calls = backup.calls.clone();
+        let model = chain(vec![StubModel::failing("primary", 401), backup], FallbackPolicy::default());
+
+        let err = model.infer(&["a".to_string()], &HashMap::new()).await.unwrap_err();
+        assert_eq!(err.status_error().map(|e| e.status), Some(401));
+        assert_eq!(backup_calls.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn test_last_provider_error_is_returned() {
+        let model = chain(
+            vec![StubModel::failing("primary", 503), StubModel::failing("backup", 502)],
+            FallbackPolicy::default(),
+        );
+
+        let err = model.infer(&["a".to_string()], &HashMap::new()).await.unwrap_err();
+        assert_eq!(err.status_error().map(|e| e.status), Some(502));
+    }
+
+    #[tokio::test]
+    async fn test_timeout_fails_over() {
+        let mut slow = StubModel::answering("slow", "{\"slow\": \"yes\"}");
+        slow.delay = Duration::from_secs(5);
+        let model = chain(
+            vec![slow, StubModel::answering("fast", "{}")],
+            FallbackPolicy::default().with_timeout(Duration::from_millis(50)),
+        );
+
+        let outputs = model.infer_single("a", &HashMap::new()).await.unwrap();
+        assert_eq!(outputs[0].provider.as_deref(), Some("stub/fast"));
+    }
+
+    #[tokio::test]
+    async fn test_parse_failure_fails_over() {
+        let resolver = Resolver::new(&ExtractConfig::default(), false).unwrap();
+        let fields = vec!["person".to_string()];
+        let model = chain(
+            vec![
+                StubModel::answering("chatty", "Sorry, I can't help with that."),
+                StubModel::answering("strict", "{\"person\": \"Ada\"}"),
+            ],
+            FallbackPolicy::default(),
+        )
+        .with_resolver(resolver, fields.clone());
+
+        let outputs = model.infer_single("a", &HashMap::new()).await.unwrap();
+        assert_eq!(outputs[0].provider.as_deref(), Some("stub/strict"));
+
+        // With parse checks disabled the first answer is accepted
+        let resolver = Resolver::new(&ExtractConfig::default(), false).unwrap();
+        let model = chain(
+            vec![
+                StubModel::answering("chatty", "Sorry, I can't help with that."),
+                StubModel::answering("strict", "{\"person\": \"Ada\"}"),
+            ],
+            FallbackPolicy::default().with_parse_failure(false),
+        )
+        .with_resolver(resolver, fields);
+
+        let outputs = model.infer_single("a", &HashMap::new()).await.unwrap();
+        assert_eq!(outputs[0].provider.as_deref(), Some("stub/chatty"));
+    }
+}
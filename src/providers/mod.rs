@@ -1,9 +1,25 @@
 //! Language model provider implementations.
 
+#[cfg(feature = "openai")]
+pub mod batch;
+pub mod cache;
 pub mod config;
+pub mod fallback;
+pub mod rate_limit;
+pub mod replay;
+pub mod retry;
 pub mod universal;
 
-pub use config::{ProviderConfig, ProviderType};
+#[cfg(feature = "openai")]
+pub use batch::{BatchJob, BatchStatus};
+pub use cache::{CachedModel, CacheStats, ResponseCache};
+pub use config::{
+    CacheConfig, CustomHttpMapping, FixtureConfig, FixtureMode, ProviderConfig, ProviderType,
+};
+pub use fallback::{FallbackModel, FallbackPolicy};
+pub use rate_limit::{RateLimitConfig, RateLimiter};
+pub use replay::RecordReplayModel;
+pub use retry::RetryPolicy;
 pub use universal::UniversalProvider;
 
 use crate::exceptions::LangExtractResult;
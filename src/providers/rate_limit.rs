@@ -0,0 +1,250 @@
+//! Client-side rate limiting for provider calls.
+//!
+//! Each limiter holds two token buckets: one for requests per minute and one
+//! for estimated tokens per minute. Buckets start full and refill continuously.
+//! A call reserves its share up front; if a bucket goes negative the caller
+//! sleeps until the deficit has been refilled, so concurrent callers queue in
+//! the order they arrived.
+
+use super::config::ProviderConfig;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Limiters shared by every provider in the process with the same endpoint,
+/// model and limits. Pipeline steps build a fresh provider per step, so this
+/// is what makes them draw from one budget.
+static SHARED_LIMITERS: Lazy<Mutex<HashMap<String, Arc<RateLimiter>>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Request and token budgets for a provider
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RateLimitConfig {
+    /// Maximum requests per minute
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub requests_per_minute: Option<u32>,
+    /// Maximum estimated tokens (prompt plus completion) per minute
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tokens_per_minute: Option<u32>,
+}
+
+impl RateLimitConfig {
+    /// Limit requests per minute only
+    pub fn requests_per_minute(requests_per_minute: u32) -> Self {
+        Self {
+            requests_per_minute: Some(requests_per_minute),
+            tokens_per_minute: None,
+        }
+    }
+
+    /// Also limit estimated tokens per minute
+    pub fn with_tokens_per_minute(mut self, tokens_per_minute: u32) -> Self {
+        self.tokens_per_minute = Some(tokens_per_minute);
+        self
+    }
+
+    /// Whether any limit is set
+    pub fn is_enabled(&self) -> bool {
+        self.requests_per_minute.is_some_and(|n| n > 0) || self.tokens_per_minute.is_some_and(|n| n > 0)
+    }
+}
+
+/// A continuously refilling bucket that may go negative to queue callers
+#[derive(Debug)]
+struct Bucket {
+    capacity: f64,
+    per_second: f64,
+    level: f64,
+}
+
+impl Bucket {
+    fn per_minute(limit: u32) -> Self {
+        let capacity = limit as f64;
+        Self {
+            capacity,
+            per_second: capacity / 60.0,
+            level: capacity,
+        }
+    }
+
+    fn refill(&mut self, elapsed: Duration) {
+        self.level = (self.level + elapsed.as_secs_f64() * self.per_second).min(self.capacity);
+    }
+
+    /// Take `amount` and return how long until the bucket is back at zero
+    fn take(&mut self, amount: f64) -> Duration {
+        self.level -= amount.min(self.capacity);
+        if self.level >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-self.level / self.per_second)
+        }
+    }
+}
+
+#[derive(Debug)]
+struct LimiterState {
+    requests: Option<Bucket>,
+    tokens: Option<Bucket>,
+    last_refill: Instant,
+}
+
+/// Token-bucket limiter for requests and estimated tokens per minute
+#[derive(Debug)]
+pub struct RateLimiter {
+    config: RateLimitConfig,
+    state: Mutex<LimiterState>,
+}
+
+impl RateLimiter {
+    /// Create a limiter with full buckets
+    pub fn new(config: RateLimitConfig) -> Self {
+        let bucket = |limit: Option<u32>| limit.filter(|n| *n > 0).map(Bucket::per_minute);
+        Self {
+            config,
+            state: Mutex::new(LimiterState {
+                requests: bucket(config.requests_per_minute),
+                tokens: bucket(config.tokens_per_minute),
+                last_refill: Instant::now(),
+            }),
+        }
+    }
+
+    /// Get the limiter shared by all providers configured like `config`, or
+    /// `None` when the config has no rate limit
+    pub fn shared(config: &ProviderConfig) -> Option<Arc<Self>> {
+        let limits = config.rate_limit.filter(RateLimitConfig::is_enabled)?;
+        let key = format!(
+            "{}|{}|{}|{:?}|{:?}",
+            config.provider_type,
+            config.base_url,
+            config.model,
+            limits.requests_per_minute,
+            limits.tokens_per_minute
+        );
+
+        let mut limiters = SHARED_LIMITERS.lock().unwrap_or_else(|e| e.into_inner());
+        Some(
+            limiters
+                .entry(key)
+                .or_insert_with(|| Arc::new(Self::new(limits)))
+                .clone(),
+        )
+    }
+
+    /// The limits this limiter enforces
+    pub fn config(&self) -> RateLimitConfig {
+        self.config
+    }
+
+    /// Reserve one request and `tokens` estimated tokens, returning how long
+    /// the caller must wait before sending
+    ///
+    /// A reservation larger than the token budget is clamped to the budget so
+    /// an oversized prompt waits for a full minute instead of forever.
+    pub(super) fn reserve(&self, tokens: u64, now: Instant) -> Duration {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let elapsed = now.saturating_duration_since(state.last_refill);
+        state.last_refill = state.last_refill.max(now);
+
+        let mut wait = Duration::ZERO;
+        if let Some(bucket) = state.requests.as_mut() {
+            bucket.refill(elapsed);
+            wait = wait.max(bucket.take(1.0));
+        }
+        if let Some(bucket) = state.tokens.as_mut() {
+            bucket.refill(elapsed);
+            wait = wait.max(bucket.take(tokens as f64));
+        }
+        wait
+    }
+
+    /// Wait until one request of `tokens` estimated tokens fits in the budget
+    ///
+    /// Returns the time spent waiting.
+    pub async fn acquire(&self, tokens: u64) -> Duration {
+        let wait = self.reserve(tokens, Instant::now());
+        if !wait.is_zero() {
+            tokio::time::sleep(wait).await;
+        }
+        wait
+    }
+}
+
+/// Rough token estimate for a request: about four characters per prompt token
+/// plus the requested completion budget
+pub fn estimate_tokens(prompt: &str, kwargs: &HashMap<String, serde_json::Value>) -> u64 {
+    let prompt_tokens = (prompt.chars().count() as u64).div_ceil(4);
+    let completion_tokens = kwargs
+        .get("max_tokens")
+        .or_else(|| kwargs.get("max_completion_tokens"))
+        .and_then(|v| v.as_u64())
+        .unwrap_or(0);
+    prompt_tokens + completion_tokens
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close_to(actual: Duration, expected_ms: u64) -> bool {
+        actual.as_millis().abs_diff(expected_ms as u128) <= 1
+    }
+
+    #[test]
+    fn test_requests_bucket_allows_burst_then_waits() {
+        let limiter = RateLimiter::new(RateLimitConfig::requests_per_minute(60));
+        let now = Instant::now();
+
+        for _ in 0..60 {
+            assert_eq!(limiter.reserve(0, now), Duration::ZERO);
+        }
+        // One request per second once the burst is spent, and callers queue
+        assert!(close_to(limiter.reserve(0, now), 1_000));
+        assert!(close_to(limiter.reserve(0, now), 2_000));
+        // Refill catches up over time
+        assert!(close_to(limiter.reserve(0, now + Duration::from_secs(3)), 0));
+    }
+
+    #[test]
+    fn test_token_bucket_limits_by_estimate() {
+        let limiter = RateLimiter::new(RateLimitConfig::default().with_tokens_per_minute(6_000));
+        let now = Instant::now();
+
+        assert_eq!(limiter.reserve(5_000, now), Duration::ZERO);
+        // 2,000 more tokens leave a 1,000 token deficit at 100 tokens/s
+        assert!(close_to(limiter.reserve(2_000, now), 10_000));
+    }
+
+    #[test]
+    fn test_oversized_request_is_clamped() {
+        let limiter = RateLimiter::new(RateLimitConfig::default().with_tokens_per_minute(600));
+        let now = Instant::now();
+
+        assert_eq!(limiter.reserve(10_000, now), Duration::ZERO);
+        assert!(close_to(limiter.reserve(10_000, now), 60_000));
+    }
+
+    #[test]
+    fn test_shared_limiter_per_provider() {
+        let limits = RateLimitConfig::requests_per_minute(17);
+        let a = ProviderConfig::ollama("shared-limit-model", None).with_rate_limit(limits);
+        let b = ProviderConfig::ollama("shared-limit-model", None).with_rate_limit(limits);
+        let other = ProviderConfig::ollama("other-limit-model", None).with_rate_limit(limits);
+
+        let limiter_a = RateLimiter::shared(&a).unwrap();
+        assert!(Arc::ptr_eq(&limiter_a, &RateLimiter::shared(&b).unwrap()));
+        assert!(!Arc::ptr_eq(&limiter_a, &RateLimiter::shared(&other).unwrap()));
+        assert!(RateLimiter::shared(&ProviderConfig::ollama("shared-limit-model", None)).is_none());
+    }
+
+    #[test]
+    fn test_estimate_tokens() {
+        let mut kwargs = HashMap::new();
+        assert_eq!(estimate_tokens("abcdefgh", &kwargs), 2);
+        kwargs.insert("max_tokens".to_string(), serde_json::json!(100));
+        assert_eq!(estimate_tokens("abcdefghi", &kwargs), 103);
+    }
+}
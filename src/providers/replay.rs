@@ -0,0 +1,475 @@
+//! Record/replay language model for offline, reproducible runs.
+//!
+//! In record mode every prompt→response pair produced by a real provider is
+//! kept in memory and written to a JSON fixture file once, by
+//! [`RecordReplayModel::finish`] or when the model is dropped. In replay mode responses are served from
+//! that file only, so `extract` and whole pipelines can run without network
+//! access. Entries are keyed by a SHA-256 hash of the prompt and the inference
+//! parameters, so any change to the prompt template or parameters shows up as
+//! a fixture miss rather than a silently different result.
+
+use super::config::FixtureMode;
+use crate::{
+    data::FormatType,
+    exceptions::{LangExtractError, LangExtractResult},
+    inference::{BaseLanguageModel, ScoredOutput},
+    schema::BaseSchema,
+};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+/// Serializes fixture writes across all models in the process, so concurrent
+/// pipeline steps recording to the same file don't drop each other's entries
+static FIXTURE_WRITE_LOCK: Mutex<()> = Mutex::new(());
+
+/// A recorded response
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FixtureEntry {
+    /// Prompt sent to the provider
+    pub prompt: String,
+    /// Inference parameters sent with the prompt
+    pub kwargs: BTreeMap<String, serde_json::Value>,
+    /// Outputs returned by the provider
+    pub outputs: Vec<ScoredOutput>,
+}
+
+/// On-disk fixture file format
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Fixture {
+    /// Provider that produced the recordings
+    #[serde(default)]
+    pub provider: String,
+    /// Model that produced the recordings
+    #[serde(default)]
+    pub model: String,
+    /// Fence preference of the recording provider, reused on replay
+    #[serde(default = "default_requires_fence_output")]
+    pub requires_fence_output: bool,
+    /// Recorded responses keyed by prompt/kwargs hash
+    #[serde(default)]
+    pub entries: BTreeMap<String, FixtureEntry>,
+}
+
+fn default_requires_fence_output() -> bool {
+    true
+}
+
+impl Fixture {
+    /// Load a fixture file
+    pub fn load(path: &Path) -> LangExtractResult<Self> {
+        let content = std::fs::read_to_string(path).map_err(|e| {
+            LangExtractError::configuration(format!(
+                "Failed to read fixture file '{}': {}",
+                path.display(),
+                e
+            ))
+        })?;
+        serde_json::from_str(&content).map_err(|e| {
+            LangExtractError::configuration(format!(
+                "Failed to parse fixture file '{}': {}",
+                path.display(),
+                e
+            ))
+        })
+    }
+
+    /// Write the fixture file, creating parent directories as needed
+    pub fn save(&self, path: &Path) -> LangExtractResult<()> {
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)?;
+        }
+        let content = serde_json::to_string_pretty(self)
+            .map_err(|e| LangExtractError::serialization(format!("Failed to serialize fixture: {}", e)))?;
+        std::fs::write(path, content)?;
+        Ok(())
+    }
+}
+
+/// Language model that records responses from, or replays them instead of,
+/// a real provider
+pub struct RecordReplayModel {
+    inner: Option<Box<dyn BaseLanguageModel>>,
+    mode: FixtureMode,
+    path: PathBuf,
+    model_id: String,
+    provider_name: String,
+    fence_output_override: Option<bool>,
+    fixture: Mutex<Fixture>,
+    /// Whether entries were recorded since the fixture was last written
+    unsaved: AtomicBool,
+}
+
+impl RecordReplayModel {
+    /// Wrap a real provider and record its responses to `path`
+    ///
+    /// Existing entries in the file are kept, so several runs can add to the
+    /// same fixture.
+    pub fn record<P: Into<PathBuf>>(inner: Box<dyn BaseLanguageModel>, path: P) -> LangExtractResult<Self> {
+        let path = path.into();
+        let mut fixture = if path.exists() {
+            Fixture::load(&path)?
+        } else {
+            Fixture::default()
+        };
+        fixture.provider = inner.provider_name().to_string();
+        fixture.model = inner.model_id().to_string();
+        fixture.requires_fence_output = inner.requires_fence_output();
+
+        Ok(Self {
+            model_id: inner.model_id().to_string(),
+            provider_name: inner.provider_name().to_string(),
+            inner: Some(inner),
+            mode: FixtureMode::Record,
+            path,
+            fence_output_override: None,
+            fixture: Mutex::new(fixture),
+            unsaved: AtomicBool::new(false),
+        })
+    }
+
+    /// Serve responses from the fixture at `path` without calling any provider
+    pub fn replay<P: Into<PathBuf>>(path: P) -> LangExtractResult<Self> {
+        let path = path.into();
+        let fixture = Fixture::load(&path)?;
+
+        Ok(Self {
+            inner: None,
+            mode: FixtureMode::Replay,
+            model_id: fixture.model.clone(),
+            provider_name: fixture.provider.clone(),
+            path,
+            fence_output_override: None,
+            fixture: Mutex::new(fixture),
+            unsaved: AtomicBool::new(false),
+        })
+    }
+
+    /// Fixture key for a prompt and its inference parameters
+    pub fn fixture_key(prompt: &str, kwargs: &HashMap<String, serde_json::Value>) -> String {
+        let canonical: BTreeMap<_, _> = kwargs.iter().collect();
+        let mut hasher = Sha256::new();
+        hasher.update(prompt.as_bytes());
+        hasher.update([0u8]);
+        hasher.update(serde_json::to_vec(&canonical).unwrap_or_default());
+        hasher
+            .finalize()
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect()
+    }
+
+    /// Whether this model is recording or replaying
+    pub fn mode(&self) -> FixtureMode {
+        self.mode
+    }
+
+    /// Path of the fixture file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Number of entries currently in the fixture
+    pub fn len(&self) -> usize {
+        self.fixture.lock().map(|f| f.entries.len()).unwrap_or(0)
+    }
+
+    /// Whether the fixture has no entries
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Write the entries recorded so far to the fixture file
+    ///
+    /// Called on drop; call it directly to see write errors. Entries other
+    /// models recorded to the same file in the meantime are kept.
+    pub fn finish(&self) -> LangExtractResult<()> {
+        if !self.unsaved.swap(false, Ordering::SeqCst) {
+            return Ok(());
+        }
+
+        let _write_guard = FIXTURE_WRITE_LOCK
+            .lock()
+            .map_err(|_| LangExtractError::unexpected("Fixture lock poisoned"))?;
+        let mut fixture = self
+            .fixture
+            .lock()
+            .map_err(|_| LangExtractError::unexpected("Fixture lock poisoned"))?;
+
+        if self.path.exists() {
+            let on_disk = Fixture::load(&self.path)?;
+            for (key, entry) in on_disk.entries {
+                fixture.entries.entry(key).or_insert(entry);
+            }
+        }
+        fixture.save(&self.path)
+    }
+
+    fn replay_batch(
+        &self,
+        batch_prompts: &[String],
+        kwargs: &HashMap<String, serde_json::Value>,
+    ) -> LangExtractResult<Vec<Vec<ScoredOutput>>> {
+        let fixture = self
+            .fixture
+            .lock()
+            .map_err(|_| LangExtractError::unexpected("Fixture lock poisoned"))?;
+
+        batch_prompts
+            .iter()
+            .map(|prompt| {
+                let key = Self::fixture_key(prompt, kwargs);
+                fixture
+                    .entries
+                    .get(&key)
+                    .map(|entry| entry.outputs.clone())
+                    .ok_or_else(|| {
+                        let preview: String = prompt.chars().take(80).collect();
+                        LangExtractError::configuration(format!(
+                            "No recorded response for prompt {} in fixture '{}' (prompt starts with {:?}). \
+                             Re-record the fixture with --record.",
+                            key,
+                            self.path.display(),
+                            preview
+                        ))
+                    })
+            })
+            .collect()
+    }
+
+    async fn record_batch(
+        &self,
+        inner: &dyn BaseLanguageModel,
+        batch_prompts: &[String],
+        kwargs: &HashMap<String, serde_json::Value>,
+    ) -> LangExtractResult<Vec<Vec<ScoredOutput>>> {
+        let results = inner.infer(batch_prompts, kwargs).await?;
+
+        let mut fixture = self
+            .fixture
+            .lock()
+            .map_err(|_| LangExtractError::unexpected("Fixture lock poisoned"))?;
+
+        let recorded_kwargs: BTreeMap<String, serde_json::Value> =
+            kwargs.iter().map(|(k, v)| (k.clone(), v.clone())).collect();
+        for (prompt, outputs) in batch_prompts.iter().zip(&results) {
+            fixture.entries.insert(
+                Self::fixture_key(prompt, kwargs),
+                FixtureEntry {
+                    prompt: prompt.clone(),
+                    kwargs: recorded_kwargs.clone(),
+                    outputs: outputs.clone(),
+                },
+            );
+        }
+        self.unsaved.store(true, Ordering::SeqCst);
+
+        Ok(results)
+    }
+}
+
+impl Drop for RecordReplayModel {
+    fn drop(&mut self) {
+        if let Err(e) = self.finish() {
+            log::warn!("Failed to write fixture '{}': {}", self.path.display(), e);
+        }
+    }
+}
+
+#[async_trait]
+impl BaseLanguageModel for RecordReplayModel {
+    fn get_schema_class(&self) -> Option<Box<dyn BaseSchema>> {
+        self.inner.as_ref().and_then(|inner| inner.get_schema_class())
+    }
+
+    fn apply_schema(&mut self, schema: Option<Box<dyn BaseSchema>>) {
+        if let Some(inner) = self.inner.as_mut() {
+            inner.apply_schema(schema);
+        }
+    }
+
+    fn applied_schema_config(&self) -> Option<HashMap<String, serde_json::Value>> {
+        self.inner.as_ref().and_then(|inner| inner.applied_schema_config())
+    }
+
+    fn set_fence_output(&mut self, fence_output: Option<bool>) {
+        match self.inner.as_mut() {
+            Some(inner) => inner.set_fence_output(fence_output),
+            None => self.fence_output_override = fence_output,
+        }
+    }
+
+    fn requires_fence_output(&self) -> bool {
+        match &self.inner {
+            Some(inner) => inner.requires_fence_output(),
+            None => self.fence_output_override.unwrap_or_else(|| {
+                self.fixture
+                    .lock()
+                    .map(|f| f.requires_fence_output)
+                    .unwrap_or(true)
+            }),
+        }
+    }
+
+    async fn infer(
+        &self,
+        batch_prompts: &[String],
+        kwargs: &HashMap<String, serde_json::Value>,
+    ) -> LangExtractResult<Vec<Vec<ScoredOutput>>> {
+        match (self.mode, &self.inner) {
+            (FixtureMode::Record, Some(inner)) => {
+                self.record_batch(inner.as_ref(), batch_prompts, kwargs).await
+            }
+            (FixtureMode::Record, None) => Err(LangExtractError::configuration(
+                "Record mode requires a wrapped provider",
+            )),
+            (FixtureMode::Replay, _) => self.replay_batch(batch_prompts, kwargs),
+        }
+    }
+
+    fn format_type(&self) -> FormatType {
+        self.inner
+            .as_ref()
+            .map(|inner| inner.format_type())
+            .unwrap_or(FormatType::Json)
+    }
+
+    fn model_id(&self) -> &str {
+        &self.model_id
+    }
+
+    fn provider_name(&self) -> &str {
+        &self.provider_name
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    /// Provider stub that echoes prompts and counts calls
+    struct EchoModel {
+        calls: Arc<AtomicUsize>,
+    }
+
+    #[async_trait]
+    impl BaseLanguageModel for EchoModel {
+        async fn infer(
+            &self,
+            batch_prompts: &[String],
+            _kwargs: &HashMap<String, serde_json::Value>,
+        ) -> LangExtractResult<Vec<Vec<ScoredOutput>>> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            Ok(batch_prompts
+                .iter()
+                .map(|p| vec![ScoredOutput::from_text(format!("echo: {}", p))])
+                .collect())
+        }
+
+        fn requires_fence_output(&self) -> bool {
+            false
+        }
+
+        fn model_id(&self) -> &str {
+            "echo-1"
+        }
+
+        fn provider_name(&self) -> &str {
+            "echo"
+        }
+    }
+
+    #[test]
+    fn test_fixture_key_is_order_independent() {
+        let mut a = HashMap::new();
+        a.insert("temperature".to_string(), serde_json::json!(0.3));
+        a.insert("max_tokens".to_string(), serde_json::json!(100));
+        let mut b = HashMap::new();
+        b.insert("max_tokens".to_string(), serde_json::json!(100));
+        b.insert("temperature".to_string(), serde_json::json!(0.3));
+
+        assert_eq!(
+            RecordReplayModel::fixture_key("prompt", &a),
+            RecordReplayModel::fixture_key("prompt", &b)
+        );
+        assert_ne!(
+            RecordReplayModel::fixture_key("prompt", &a),
+            RecordReplayModel::fixture_key("other prompt", &a)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_record_then_replay() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("fixtures/run.json");
+        let calls = Arc::new(AtomicUsize::new(0));
+        let kwargs = HashMap::from([("temperature".to_string(), serde_json::json!(0.3))]);
+        let prompts = vec!["first".to_string(), "second".to_string()];
+
+        let recorder = RecordReplayModel::record(
+            Box::new(EchoModel { calls: calls.clone() }),
+            &path,
+        )
+        .unwrap();
+        let recorded = recorder.infer(&prompts, &kwargs).await.unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert_eq!(recorder.len(), 2);
+        // Nothing is written until the recording is finished
+        assert!(!path.exists());
+        recorder.finish().unwrap();
+
+        let replayer = RecordReplayModel::replay(&path).unwrap();
+        assert_eq!(replayer.provider_name(), "echo");
+        assert_eq!(replayer.model_id(), "echo-1");
+        assert!(!replayer.requires_fence_output());
+
+        let replayed = replayer.infer(&prompts, &kwargs).await.unwrap();
+        assert_eq!(replayed, recorded);
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_replay_miss_fails_loudly() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("run.json");
+        Fixture::default().save(&path).unwrap();
+
+        let replayer = RecordReplayModel::replay(&path).unwrap();
+        let err = replayer
+            .infer(&["unseen".to_string()], &HashMap::new())
+            .await
+            .unwrap_err();
+        assert!(matches!(err, LangExtractError::ConfigurationError(_)));
+        assert!(err.to_string().contains("No recorded response"));
+    }
+
+    #[tokio::test]
+    async fn test_recorders_sharing_a_file_keep_each_others_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("shared.json");
+        let recorder = |calls: &Arc<AtomicUsize>| {
+            RecordReplayModel::record(Box::new(EchoModel { calls: calls.clone() }), &path).unwrap()
+        };
+        let calls = Arc::new(AtomicUsize::new(0));
+
+        let (first, second) = (recorder(&calls), recorder(&calls));
+        first.infer(&["first".to_string()], &HashMap::new()).await.unwrap();
+        second.infer(&["second".to_string()], &HashMap::new()).await.unwrap();
+        drop(first);
+        drop(second);
+
+        assert_eq!(Fixture::load(&path).unwrap().entries.len(), 2);
+    }
+
+    #[test]
+    fn test_replay_requires_existing_fixture() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(RecordReplayModel::replay(dir.path().join("missing.json")).is_err());
+    }
+}
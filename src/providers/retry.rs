@@ -0,0 +1,218 @@
+//! Retry policy for provider HTTP calls.
+
+use crate::exceptions::LangExtractError;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// How provider calls are retried after a failure
+///
+/// Delays grow exponentially from `base_delay_ms`, capped at `max_delay_ms`.
+/// Only errors in the enabled classes are retried; everything else (bad
+/// requests, auth failures, parsing errors) fails on the first attempt.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetryPolicy {
+    /// Total number of attempts, including the first call
+    pub max_attempts: u32,
+    /// Delay before the first retry, in milliseconds
+    pub base_delay_ms: u64,
+    /// Upper bound for any delay, including one asked for by `Retry-After`,
+    /// in milliseconds
+    pub max_delay_ms: u64,
+    /// Randomize each delay between 50% and 100% of its computed value
+    pub jitter: bool,
+    /// Retry HTTP 429 responses
+    pub retry_on_rate_limit: bool,
+    /// Retry HTTP 5xx responses
+    pub retry_on_server_error: bool,
+    /// Retry timeouts, connection failures and HTTP 408
+    pub retry_on_timeout: bool,
+    /// Wait for the duration given in a `Retry-After` header when present,
+    /// up to `max_delay_ms`
+    pub respect_retry_after: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 4,
+            base_delay_ms: 1_000,
+            max_delay_ms: 60_000,
+            jitter: true,
+            retry_on_rate_limit: true,
+            retry_on_server_error: true,
+            retry_on_timeout: true,
+            respect_retry_after: true,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Never retry
+    pub fn none() -> Self {
+        Self {
+            max_attempts: 1,
+            ..Self::default()
+        }
+    }
+
+    /// Retry retryable errors without waiting (useful in tests)
+    pub fn immediate(max_attempts: u32) -> Self {
+        Self {
+            max_attempts,
+            base_delay_ms: 0,
+            max_delay_ms: 0,
+            jitter: false,
+            respect_retry_after: false,
+            ..Self::default()
+        }
+    }
+
+    /// Set the total number of attempts
+    pub fn with_max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = max_attempts;
+        self
+    }
+
+    /// Set the base and maximum delay
+    pub fn with_delays(mut self, base: Duration, max: Duration) -> Self {
+        self.base_delay_ms = base.as_millis() as u64;
+        self.max_delay_ms = max.as_millis() as u64;
+        self
+    }
+
+    /// Enable or disable jitter
+    pub fn with_jitter(mut self, jitter: bool) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    /// Whether an error belongs to one of the retryable classes
+    pub fn is_retryable(&self, error: &LangExtractError) -> bool {
+        if let Some(status_error) = error.status_error() {
+            return match status_error.status {
+                429 => self.retry_on_rate_limit,
+                408 => self.retry_on_timeout,
+                500..=599 => self.retry_on_server_error,
+                _ => false,
+            };
+        }
+
+        match error {
+            LangExtractError::NetworkError(e) => {
+                if e.is_timeout() || e.is_connect() {
+                    return self.retry_on_timeout;
+                }
+                match e.status().map(|s| s.as_u16()) {
+                    Some(429) => self.retry_on_rate_limit,
+                    Some(408) => self.retry_on_timeout,
+                    Some(500..=599) => self.retry_on_server_error,
+                    _ => false,
+                }
+            }
+            _ => false,
+        }
+    }
+
+    /// Delay before the retry following failed attempt number `attempt` (1-based)
+    pub fn delay_for(&self, attempt: u32, error: &LangExtractError) -> Duration {
+        if self.respect_retry_after {
+            if let Some(retry_after) = error.status_error().and_then(|e| e.retry_after) {
+                return retry_after.min(Duration::from_millis(self.max_delay_ms));
+            }
+        }
+
+        let exponent = attempt.saturating_sub(1).min(31);
+        let delay_ms = self
+            .base_delay_ms
+            .saturating_mul(1u64 << exponent)
+            .min(self.max_delay_ms);
+
+        let delay_ms = if self.jitter && delay_ms > 0 {
+            delay_ms / 2 + fastrand::u64(0..=delay_ms / 2)
+        } else {
+            delay_ms
+        };
+
+        Duration::from_millis(delay_ms)
+    }
+}
+
+/// Parse a `Retry-After` header value (delay in seconds or an HTTP date)
+pub fn parse_retry_after(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+
+    let date = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    let wait = date.with_timezone(&chrono::Utc) - chrono::Utc::now();
+    Some(wait.to_std().unwrap_or(Duration::ZERO))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn status(code: u16, retry_after: Option<Duration>) -> LangExtractError {
+        LangExtractError::http_status("test", code, retry_after, "boom")
+    }
+
+    #[test]
+    fn test_retryable_classes() {
+        let policy = RetryPolicy::default();
+        assert!(policy.is_retryable(&status(429, None)));
+        assert!(policy.is_retryable(&status(503, None)));
+        assert!(policy.is_retryable(&status(408, None)));
+        assert!(!policy.is_retryable(&status(400, None)));
+        assert!(!policy.is_retryable(&status(401, None)));
+        assert!(!policy.is_retryable(&LangExtractError::parsing("bad json")));
+        assert!(!policy.is_retryable(&LangExtractError::configuration("no key")));
+
+        let policy = RetryPolicy {
+            retry_on_rate_limit: false,
+            ..RetryPolicy::default()
+        };
+        assert!(!policy.is_retryable(&status(429, None)));
+    }
+
+    #[test]
+    fn test_exponential_delay_is_capped() {
+        let policy = RetryPolicy::default()
+            .with_delays(Duration::from_millis(100), Duration::from_millis(350))
+            .with_jitter(false);
+        let error = status(503, None);
+
+        assert_eq!(policy.delay_for(1, &error), Duration::from_millis(100));
+        assert_eq!(policy.delay_for(2, &error), Duration::from_millis(200));
+        assert_eq!(policy.delay_for(3, &error), Duration::from_millis(350));
+        assert_eq!(policy.delay_for(40, &error), Duration::from_millis(350));
+    }
+
+    #[test]
+    fn test_jitter_stays_within_half_delay() {
+        let policy = RetryPolicy::default().with_delays(Duration::from_millis(1000), Duration::from_secs(60));
+        for _ in 0..50 {
+            let delay = policy.delay_for(1, &status(503, None));
+            assert!(delay >= Duration::from_millis(500) && delay <= Duration::from_millis(1000));
+        }
+    }
+
+    #[test]
+    fn test_retry_after_overrides_backoff() {
+        let error = status(429, Some(Duration::from_secs(7)));
+        assert_eq!(RetryPolicy::default().delay_for(1, &error), Duration::from_secs(7));
+        assert_eq!(RetryPolicy::immediate(3).delay_for(1, &error), Duration::ZERO);
+
+        // A server asking for a day's wait gets the policy's maximum
+        let error = status(429, Some(Duration::from_secs(86_400)));
+        assert_eq!(RetryPolicy::default().delay_for(1, &error), Duration::from_secs(60));
+    }
+
+    #[test]
+    fn test_parse_retry_after() {
+        assert_eq!(parse_retry_after("120"), Some(Duration::from_secs(120)));
+        assert_eq!(parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT"), Some(Duration::ZERO));
+        assert_eq!(parse_retry_after("soon"), None);
+    }
+}
@@ -1,16 +1,25 @@
 //! Universal provider implementation.
 
-use super::config::{ProviderConfig, ProviderType};
+use super::config::{CustomHttpMapping, ProviderConfig, ProviderType};
+use super::rate_limit::{estimate_tokens, RateLimiter};
+use super::retry::parse_retry_after;
 use crate::{
     data::FormatType,
     exceptions::{LangExtractError, LangExtractResult},
-    inference::{BaseLanguageModel, ScoredOutput},
+    inference::{BaseLanguageModel, ScoredOutput, TextStream},
     logging::{report_progress, ProgressEvent},
-    schema::BaseSchema,
+    schema::{BaseSchema, ExtractionClass, ExtractionSchema, ATTRIBUTE_SUFFIX},
+    usage::TokenUsage,
 };
 use async_trait::async_trait;
 use std::collections::HashMap;
-use tokio::time::Duration;
+use std::sync::Arc;
+
+/// Messages API version sent with every Anthropic request
+const ANTHROPIC_VERSION: &str = "2023-06-01";
+
+/// Default `max_tokens` for Anthropic, which requires the field on every request
+const ANTHROPIC_DEFAULT_MAX_TOKENS: u64 = 4096;
 
 /// Universal language model provider
 pub struct UniversalProvider {
@@ -21,11 +30,15 @@ pub struct UniversalProvider {
     openai_client: Option<async_openai::Client<async_openai::config::OpenAIConfig>>,
     schema: Option<Box<dyn BaseSchema>>,
     fence_output_override: Option<bool>,
+    rate_limiter: Option<Arc<RateLimiter>>,
 }
 
 impl UniversalProvider {
-    /// Retry helper function with exponential backoff
-    /// Retries at least 3 times with 30-second delays between attempts
+    /// Run `operation`, retrying retryable failures according to the
+    /// provider's [`RetryPolicy`](super::retry::RetryPolicy)
+    ///
+    /// Non-retryable errors (bad requests, auth failures, parsing errors) are
+    /// returned immediately.
     pub async fn retry_with_backoff<T, F, Fut>(
         &self,
         mut operation: F,
@@ -35,39 +48,60 @@ impl UniversalProvider {
         F: FnMut() -> Fut,
         Fut: std::future::Future<Output = LangExtractResult<T>>,
     {
-        let max_retries = 3;
-        let base_delay = Duration::from_secs(30);
+        let policy = &self.config.retry;
+        let max_attempts = policy.max_attempts.max(1);
+        let mut attempt = 1;
 
-        for attempt in 0..=max_retries {
+        loop {
             match operation().await {
                 Ok(result) => return Ok(result),
                 Err(e) => {
-                    if attempt == max_retries {
-                        // Last attempt failed, return the error
-                        return Err(LangExtractError::inference_simple(
-                            format!("{} failed after {} attempts. Last error: {}", operation_name, max_retries + 1, e)
-                        ));
+                    if attempt >= max_attempts || !policy.is_retryable(&e) {
+                        return Err(e);
                     }
 
-                    // Calculate delay with exponential backoff (30s, 60s, 90s)
-                    let delay = base_delay * (attempt + 1) as u32;
+                    let delay = policy.delay_for(attempt, &e);
                     report_progress(ProgressEvent::RetryAttempt {
                         operation: operation_name.to_string(),
-                        attempt: attempt + 1,
-                        max_attempts: max_retries + 1,
+                        attempt: attempt as usize,
+                        max_attempts: max_attempts as usize,
                         delay_seconds: delay.as_secs(),
                     });
 
-                    // Add a small delay before the main sleep to ensure logs are printed
-                    tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
-
-                    // Use tokio::time::sleep explicitly
                     tokio::time::sleep(delay).await;
+                    attempt += 1;
                 }
             }
         }
+    }
+
+    /// Wait for the provider's rate limiter, if any, before sending `prompt`
+    async fn throttle(&self, provider: &str, prompt: &str, kwargs: &HashMap<String, serde_json::Value>) {
+        let Some(limiter) = &self.rate_limiter else {
+            return;
+        };
+
+        let waited = limiter.acquire(estimate_tokens(prompt, kwargs)).await;
+        if !waited.is_zero() {
+            report_progress(ProgressEvent::RateLimitWait {
+                provider: provider.to_string(),
+                model: self.config.model.clone(),
+                wait_ms: waited.as_millis() as u64,
+            });
+        }
+    }
 
-        unreachable!("Should have returned from the loop")
+    /// Convert a non-success HTTP response into an error carrying its status
+    /// and any `Retry-After` delay
+    async fn http_status_error(provider: &str, response: reqwest::Response) -> LangExtractError {
+        let status = response.status().as_u16();
+        let retry_after = response
+            .headers()
+            .get(reqwest::header::RETRY_AFTER)
+            .and_then(|v| v.to_str().ok())
+            .and_then(parse_retry_after);
+        let body = response.text().await.unwrap_or_default();
+        LangExtractError::http_status(provider, status, retry_after, body)
     }
 
     /// Create a new universal provider
@@ -80,7 +114,13 @@ impl UniversalProvider {
                 let openai_config = async_openai::config::OpenAIConfig::new()
                     .with_api_key(api_key)
                     .with_api_base(&config.base_url);
-                Some(async_openai::Client::with_config(openai_config))
+                // The client retries 429s on its own for minutes; leave retries
+                // to the RetryPolicy so it and Retry-After are honored
+                let no_retries = backoff::ExponentialBackoff {
+                    max_elapsed_time: Some(std::time::Duration::ZERO),
+                    ..Default::default()
+                };
+                Some(async_openai::Client::with_config(openai_config).with_backoff(no_retries))
             } else {
                 return Err(LangExtractError::configuration(
                     "API key is required for OpenAI provider",
@@ -93,6 +133,32 @@ impl UniversalProvider {
         #[cfg(not(feature = "openai"))]
         let openai_client = None;
 
+        if config.provider_type == ProviderType::Gemini && config.api_key.is_none() {
+            return Err(LangExtractError::configuration(
+                "API key is required for Gemini provider",
+            ));
+        }
+
+        if config.provider_type == ProviderType::Anthropic && config.api_key.is_none() {
+            return Err(LangExtractError::configuration(
+                "API key is required for Anthropic provider",
+            ));
+        }
+
+        if let Some(mapping) = &config.custom_mapping {
+            let pointers = std::iter::once(&mapping.text_pointer).chain(mapping.score_pointer.as_ref());
+            for pointer in pointers {
+                if !pointer.is_empty() && !pointer.starts_with('/') {
+                    return Err(LangExtractError::configuration(format!(
+                        "Invalid JSON pointer '{}' in custom mapping: must be empty or start with '/'",
+                        pointer
+                    )));
+                }
+            }
+        }
+
+        let rate_limiter = RateLimiter::shared(&config);
+
         Ok(Self {
             config,
             format_type: FormatType::Json,
@@ -100,26 +166,88 @@ impl UniversalProvider {
             openai_client,
             schema: None,
             fence_output_override: None,
+            rate_limiter,
         })
     }
 
-    /// Process a single OpenAI prompt
+    /// Map an async-openai error onto the error classes used by the retry policy
     #[cfg(feature = "openai")]
-    #[tracing::instrument(skip_all, fields(provider = "openai", model = %self.config.model, prompt_len = prompt.len()))]
-    async fn infer_openai_single(
+    fn openai_error(error: async_openai::error::OpenAIError) -> LangExtractError {
+        use async_openai::error::OpenAIError;
+
+        match error {
+            OpenAIError::Reqwest(e) => LangExtractError::NetworkError(e),
+            OpenAIError::ApiError(api) => {
+                // The client does not expose the status code, so infer it from the error type
+                let status = match (api.r#type.as_deref(), api.code.as_deref()) {
+                    (_, Some("rate_limit_exceeded")) => Some(429),
+                    (Some("server_error"), _) => Some(500),
+                    _ => None,
+                };
+                match status {
+                    Some(status) => LangExtractError::http_status("openai", status, None, api.to_string()),
+                    None => LangExtractError::inference_simple(format!("OpenAI API error: {}", api)),
+                }
+            }
+            // Streams report HTTP failures as text, e.g. "Invalid status code: 429 Too Many Requests"
+            OpenAIError::StreamError(message) => {
+                let status = message
+                    .strip_prefix("Invalid status code: ")
+                    .and_then(|rest| rest.split_whitespace().next())
+                    .and_then(|code| code.parse::<u16>().ok());
+                match status {
+                    Some(status) => LangExtractError::http_status("openai", status, None, message),
+                    None => LangExtractError::inference_simple(format!("OpenAI API error: stream failed: {}", message)),
+                }
+            }
+            other => LangExtractError::inference_simple(format!("OpenAI API error: {}", other)),
+        }
+    }
+
+    /// Map an error from a non-streaming OpenAI call
+    ///
+    /// The client hides the HTTP status, so an error body that is not JSON
+    /// (e.g. a gateway's HTML 502 page) or an API error of an unrecognized type
+    /// is treated as a server error and retried.
+    #[cfg(feature = "openai")]
+    fn openai_request_error(error: async_openai::error::OpenAIError) -> LangExtractError {
+        use async_openai::error::OpenAIError;
+
+        const CLIENT_ERROR_TYPES: [&str; 5] = [
+            "invalid_request_error",
+            "authentication_error",
+            "permission_error",
+            "not_found_error",
+            "insufficient_quota",
+        ];
+
+        match error {
+            OpenAIError::JSONDeserialize(e) => {
+                LangExtractError::http_status("openai", 502, None, format!("Unreadable OpenAI response: {}", e))
+            }
+            OpenAIError::ApiError(api)
+                if api.code.as_deref() != Some("rate_limit_exceeded")
+                    && !api.r#type.as_deref().is_some_and(|t| CLIENT_ERROR_TYPES.contains(&t)) =>
+            {
+                LangExtractError::http_status("openai", 500, None, api.to_string())
+            }
+            other => Self::openai_error(other),
+        }
+    }
+
+    /// Build the chat completion request for an OpenAI prompt
+    #[cfg(feature = "openai")]
+    fn build_openai_request(
         &self,
         prompt: &str,
         kwargs: &HashMap<String, serde_json::Value>,
-    ) -> LangExtractResult<Vec<ScoredOutput>> {
+    ) -> async_openai::types::CreateChatCompletionRequest {
         use async_openai::types::{
             ChatCompletionRequestMessage, ChatCompletionRequestSystemMessage,
-            ChatCompletionRequestSystemMessageContent, CreateChatCompletionRequest,
+            ChatCompletionRequestSystemMessageContent, CreateChatCompletionRequest, ResponseFormat,
+            ResponseFormatJsonSchema,
         };
 
-        let client = self.openai_client.as_ref().ok_or_else(|| {
-            LangExtractError::configuration("OpenAI client not initialized")
-        })?;
-
         // Create system message for format instructions
         let system_message = match self.format_type {
             FormatType::Json => "You are a helpful assistant that responds in JSON format. Always return valid JSON that matches the expected structure from the examples.",
@@ -160,6 +288,51 @@ impl UniversalProvider {
             }
         }
 
+        // Structured output: a strict JSON schema, or plain JSON mode
+        if let Some(schema) = &self.schema {
+            let schema_config = schema.to_provider_config();
+            if let Some(json_schema) = schema_config.get("json_schema") {
+                request.response_format = Some(ResponseFormat::JsonSchema {
+                    json_schema: ResponseFormatJsonSchema {
+                        description: None,
+                        name: "extractions".to_string(),
+                        schema: Some(json_schema.clone()),
+                        strict: Some(true),
+                    },
+                });
+            } else if schema_config.get("format").and_then(|f| f.as_str()) == Some("json") {
+                request.response_format = Some(ResponseFormat::JsonObject);
+            }
+        }
+
+        request
+    }
+
+    /// The chat completion body for a prompt, as sent to `/v1/chat/completions`
+    #[cfg(feature = "openai")]
+    pub(crate) fn openai_request_body(
+        &self,
+        prompt: &str,
+        kwargs: &HashMap<String, serde_json::Value>,
+    ) -> LangExtractResult<serde_json::Value> {
+        serde_json::to_value(self.build_openai_request(prompt, kwargs))
+            .map_err(|e| LangExtractError::serialization(e.to_string()))
+    }
+
+    /// Process a single OpenAI prompt
+    #[cfg(feature = "openai")]
+    #[tracing::instrument(skip_all, fields(provider = "openai", model = %self.config.model, prompt_len = prompt.len()))]
+    async fn infer_openai_single(
+        &self,
+        prompt: &str,
+        kwargs: &HashMap<String, serde_json::Value>,
+    ) -> LangExtractResult<Vec<ScoredOutput>> {
+        let client = self.openai_client.as_ref().ok_or_else(|| {
+            LangExtractError::configuration("OpenAI client not initialized")
+        })?;
+
+        let request = self.build_openai_request(prompt, kwargs);
+
         // Make the API call with retry logic
         report_progress(ProgressEvent::ModelCall {
             provider: "OpenAI".to_string(),
@@ -169,12 +342,14 @@ impl UniversalProvider {
         
         let response = self.retry_with_backoff(
             || async {
+                self.throttle("OpenAI", prompt, kwargs).await;
+
                 let result = client.chat().create(request.clone()).await.map_err(|e| {
                     report_progress(ProgressEvent::Error {
                         operation: "OpenAI API request".to_string(),
                         error: format!("OpenAI API error: {}", e),
                     });
-                    LangExtractError::inference_simple(format!("OpenAI API error: {}", e))
+                    Self::openai_request_error(e)
                 });
                 result
             },
@@ -190,7 +365,11 @@ impl UniversalProvider {
                 LangExtractError::parsing("No content in OpenAI response")
             })?;
 
-        Ok(vec![ScoredOutput::from_text(content.clone())])
+        let mut output = ScoredOutput::from_text(content.clone());
+        if let Some(usage) = &response.usage {
+            output = output.with_usage(TokenUsage::new(usage.prompt_tokens as u64, usage.completion_tokens as u64));
+        }
+        Ok(vec![output])
     }
 
     /// Inference implementation for OpenAI-compatible APIs — processes prompts concurrently
@@ -213,22 +392,23 @@ impl UniversalProvider {
         results.into_iter().collect()
     }
 
-    /// Process a single Ollama prompt
-    #[tracing::instrument(skip_all, fields(provider = "ollama", model = %self.config.model, prompt_len = prompt.len()))]
-    async fn infer_ollama_single(
+    /// Build the generate request body for an Ollama prompt
+    fn build_ollama_request(
         &self,
         prompt: &str,
         kwargs: &HashMap<String, serde_json::Value>,
-    ) -> LangExtractResult<Vec<ScoredOutput>> {
+        stream: bool,
+    ) -> serde_json::Value {
         let mut request_body = serde_json::json!({
             "model": self.config.model,
             "prompt": prompt,
-            "stream": false,
+            "stream": stream,
         });
 
-        // Set format for JSON output if needed
+        // Set format for JSON output if needed, preferring a full JSON schema
         if self.format_type == FormatType::Json {
-            request_body["format"] = serde_json::json!("json");
+            let json_schema = self.schema.as_ref().and_then(|s| s.to_provider_config().remove("json_schema"));
+            request_body["format"] = json_schema.unwrap_or_else(|| serde_json::json!("json"));
         }
 
         // Apply parameters from kwargs
@@ -243,6 +423,17 @@ impl UniversalProvider {
             request_body["options"] = serde_json::Value::Object(options);
         }
 
+        request_body
+    }
+
+    /// Process a single Ollama prompt
+    #[tracing::instrument(skip_all, fields(provider = "ollama", model = %self.config.model, prompt_len = prompt.len()))]
+    async fn infer_ollama_single(
+        &self,
+        prompt: &str,
+        kwargs: &HashMap<String, serde_json::Value>,
+    ) -> LangExtractResult<Vec<ScoredOutput>> {
+        let request_body = self.build_ollama_request(prompt, kwargs, false);
         let url = format!("{}/api/generate", self.config.base_url);
 
         // Make the API call with retry logic
@@ -254,6 +445,8 @@ impl UniversalProvider {
         
         let response_body = self.retry_with_backoff(
             || async {
+                self.throttle("Ollama", prompt, kwargs).await;
+
                 let mut request = self.client.post(&url).json(&request_body);
 
                 // Add headers
@@ -270,15 +463,11 @@ impl UniversalProvider {
                 })?;
 
                 if !response.status().is_success() {
-                    let status = response.status();
                     report_progress(ProgressEvent::Error {
                         operation: "Ollama HTTP status".to_string(),
-                        error: format!("HTTP error status: {}", status),
+                        error: format!("HTTP error status: {}", response.status()),
                     });
-                    return Err(LangExtractError::inference_simple(format!(
-                        "Ollama API error: HTTP {}",
-                        status
-                    )));
+                    return Err(Self::http_status_error("ollama", response).await);
                 }
 
                 let response_body: serde_json::Value = response.json().await.map_err(|e| {
@@ -301,7 +490,20 @@ impl UniversalProvider {
                 LangExtractError::parsing("Missing 'response' field in Ollama response")
             })?;
 
-        Ok(vec![ScoredOutput::from_text(content.to_string())])
+        let mut output = ScoredOutput::from_text(content.to_string());
+        if let Some(usage) = Self::ollama_usage(&response_body) {
+            output = output.with_usage(usage);
+        }
+        Ok(vec![output])
+    }
+
+    /// Token counts from an Ollama response, when it reports them
+    fn ollama_usage(response_body: &serde_json::Value) -> Option<TokenUsage> {
+        let count = |key: &str| response_body.get(key).and_then(|v| v.as_u64());
+        match (count("prompt_eval_count"), count("eval_count")) {
+            (None, None) => None,
+            (prompt, completion) => Some(TokenUsage::new(prompt.unwrap_or(0), completion.unwrap_or(0))),
+        }
     }
 
     /// Inference implementation for Ollama — processes prompts concurrently
@@ -322,113 +524,865 @@ impl UniversalProvider {
         // Collect results, propagating errors
         results.into_iter().collect()
     }
-}
 
-#[async_trait]
-impl BaseLanguageModel for UniversalProvider {
-    fn get_schema_class(&self) -> Option<Box<dyn BaseSchema>> {
-        // Return a format mode schema for now
-        crate::schema::FormatModeSchema::from_examples(&[], "_attributes").ok()
-    }
+    /// Stream an OpenAI completion over server-sent events
+    #[cfg(feature = "openai")]
+    #[tracing::instrument(skip_all, fields(provider = "openai", model = %self.config.model, prompt_len = prompt.len()))]
+    async fn infer_openai_stream(
+        &self,
+        prompt: &str,
+        kwargs: &HashMap<String, serde_json::Value>,
+    ) -> LangExtractResult<TextStream> {
+        use futures::StreamExt;
 
-    fn apply_schema(&mut self, schema: Option<Box<dyn BaseSchema>>) {
-        self.schema = schema;
+        let client = self.openai_client.as_ref().ok_or_else(|| {
+            LangExtractError::configuration("OpenAI client not initialized")
+        })?;
+
+        let request = self.build_openai_request(prompt, kwargs);
+
+        report_progress(ProgressEvent::ModelCall {
+            provider: "OpenAI".to_string(),
+            model: self.config.model.clone(),
+            input_length: prompt.len(),
+        });
+
+        // Connection and HTTP status failures arrive as the first event, so
+        // establishing the stream includes reading it; as with Ollama, a
+        // stream that breaks part-way surfaces as an error item
+        let (first, events) = self.retry_with_backoff(
+            || async {
+                self.throttle("OpenAI", prompt, kwargs).await;
+
+                let mut events = client.chat().create_stream(request.clone()).await.map_err(Self::openai_error)?;
+                match events.next().await {
+                    Some(Err(e)) => Err(Self::openai_error(e)),
+                    first => Ok((first, events)),
+                }
+            },
+            &format!("OpenAI streaming call for prompt len {}", prompt.len())
+        ).await?;
+
+        Ok(Box::pin(futures::stream::iter(first).chain(events).filter_map(|event| async move {
+            match event {
+                Ok(response) => response
+                    .choices
+                    .into_iter()
+                    .next()
+                    .and_then(|choice| choice.delta.content)
+                    .filter(|delta| !delta.is_empty())
+                    .map(Ok),
+                Err(e) => Some(Err(Self::openai_error(e))),
+            }
+        })))
     }
 
-    fn set_fence_output(&mut self, fence_output: Option<bool>) {
-        self.fence_output_override = fence_output;
+    /// Stream an Ollama completion as newline-delimited JSON
+    #[tracing::instrument(skip_all, fields(provider = "ollama", model = %self.config.model, prompt_len = prompt.len()))]
+    async fn infer_ollama_stream(
+        &self,
+        prompt: &str,
+        kwargs: &HashMap<String, serde_json::Value>,
+    ) -> LangExtractResult<TextStream> {
+        let request_body = self.build_ollama_request(prompt, kwargs, true);
+        let url = format!("{}/api/generate", self.config.base_url);
+
+        report_progress(ProgressEvent::ModelCall {
+            provider: "Ollama".to_string(),
+            model: self.config.model.clone(),
+            input_length: prompt.len(),
+        });
+
+        // Only establishing the stream is retried; a stream that breaks
+        // part-way surfaces as an error item
+        let response = self.retry_with_backoff(
+            || async {
+                self.throttle("Ollama", prompt, kwargs).await;
+
+                let mut request = self.client.post(&url).json(&request_body);
+
+                // Add headers
+                for (key, value) in &self.config.headers {
+                    request = request.header(key, value);
+                }
+
+                let response = request.send().await.map_err(LangExtractError::NetworkError)?;
+                if !response.status().is_success() {
+                    return Err(Self::http_status_error("ollama", response).await);
+                }
+                Ok(response)
+            },
+            &format!("Ollama streaming call for prompt len {}", prompt.len())
+        ).await?;
+
+        Ok(ndjson_text_stream(response.bytes_stream(), Self::parse_ollama_stream_line))
     }
 
-    fn requires_fence_output(&self) -> bool {
-        if let Some(override_val) = self.fence_output_override {
-            return override_val;
-        }
+    /// Parse one NDJSON line from Ollama into its text delta and `done` flag
+    fn parse_ollama_stream_line(line: &str) -> LangExtractResult<(String, bool)> {
+        let value: serde_json::Value = serde_json::from_str(line).map_err(|e| {
+            LangExtractError::parsing(format!("Failed to parse Ollama stream line: {}", e))
+        })?;
 
-        // OpenAI with JSON mode doesn't need fences, Ollama might
-        match self.config.provider_type {
-            ProviderType::OpenAI if self.schema.is_some() => false,
-            _ => true,
+        if let Some(error) = value.get("error").and_then(|e| e.as_str()) {
+            return Err(LangExtractError::inference_simple(format!("Ollama stream error: {}", error)));
         }
+
+        let text = value.get("response").and_then(|r| r.as_str()).unwrap_or_default();
+        let done = value.get("done").and_then(|d| d.as_bool()).unwrap_or(false);
+        Ok((text.to_string(), done))
     }
 
-    #[tracing::instrument(skip_all, fields(batch_size = batch_prompts.len()))]
-    async fn infer(
+    /// Build the generateContent request body for a Gemini prompt
+    fn build_gemini_request(
         &self,
-        batch_prompts: &[String],
+        prompt: &str,
         kwargs: &HashMap<String, serde_json::Value>,
-    ) -> LangExtractResult<Vec<Vec<ScoredOutput>>> {
-        match self.config.provider_type {
-            #[cfg(feature = "openai")]
-            ProviderType::OpenAI => self.infer_openai(batch_prompts, kwargs).await,
-            ProviderType::Ollama => self.infer_ollama(batch_prompts, kwargs).await,
-            ProviderType::Custom => {
-                Err(LangExtractError::configuration(
-                    "Custom provider inference not yet implemented"
-                ))
+    ) -> serde_json::Value {
+        let mut request_body = serde_json::json!({
+            "contents": [{
+                "role": "user",
+                "parts": [{ "text": prompt }],
+            }],
+        });
+
+        // Map inference parameters onto Gemini's generationConfig
+        let mut generation_config = serde_json::Map::new();
+        if let Some(temp) = kwargs.get("temperature") {
+            generation_config.insert("temperature".to_string(), temp.clone());
+        }
+        if let Some(max_tokens) = kwargs.get("max_tokens").or_else(|| kwargs.get("max_completion_tokens")) {
+            generation_config.insert("maxOutputTokens".to_string(), max_tokens.clone());
+        }
+        if let Some(stop_sequences) = kwargs.get("stop_sequences") {
+            generation_config.insert("stopSequences".to_string(), stop_sequences.clone());
+        }
+        if let Some(num_candidates) = kwargs.get("num_candidates") {
+            generation_config.insert("candidateCount".to_string(), num_candidates.clone());
+        }
+
+        // Structured output: JSON mode, constrained by the class schema when
+        // one was applied via `schema_for_classes`
+        if let Some(schema) = &self.schema {
+            let mut schema_config = schema.to_provider_config();
+            let response_schema = schema_config.remove("response_schema");
+            if response_schema.is_some() || schema_config.get("format").and_then(|f| f.as_str()) == Some("json") {
+                generation_config.insert("responseMimeType".to_string(), serde_json::json!("application/json"));
             }
-            #[cfg(not(feature = "openai"))]
-            ProviderType::OpenAI => {
-                Err(LangExtractError::configuration(
-                    "OpenAI feature not enabled. Enable with --features openai"
-                ))
+            if let Some(response_schema) = response_schema {
+                generation_config.insert("responseSchema".to_string(), response_schema);
             }
         }
-    }
 
-    fn format_type(&self) -> FormatType {
-        self.format_type
-    }
+        if !generation_config.is_empty() {
+            request_body["generationConfig"] = serde_json::Value::Object(generation_config);
+        }
 
-    fn model_id(&self) -> &str {
-        &self.config.model
+        request_body
     }
 
-    fn provider_name(&self) -> &str {
-        match self.config.provider_type {
-            ProviderType::OpenAI => "openai",
-            ProviderType::Ollama => "ollama",
-            ProviderType::Custom => "custom",
+    /// Extract candidate texts from a generateContent response
+    fn parse_gemini_response(response_body: &serde_json::Value) -> LangExtractResult<Vec<ScoredOutput>> {
+        let candidates = match response_body.get("candidates").and_then(|c| c.as_array()) {
+            Some(candidates) if !candidates.is_empty() => candidates,
+            _ => {
+                let block_reason = response_body
+                    .get("promptFeedback")
+                    .and_then(|f| f.get("blockReason"))
+                    .and_then(|r| r.as_str());
+                return Err(match block_reason {
+                    Some(reason) => LangExtractError::inference(
+                        format!("Gemini blocked the prompt: {}", reason),
+                        Some("gemini".to_string()),
+                        None,
+                    ),
+                    None => LangExtractError::parsing("Missing 'candidates' field in Gemini response"),
+                });
+            }
+        };
+
+        let outputs: Vec<ScoredOutput> = candidates
+            .iter()
+            .filter_map(|candidate| {
+                let parts = candidate.get("content")?.get("parts")?.as_array()?;
+                let text: String = parts
+                    .iter()
+                    .filter_map(|part| part.get("text").and_then(|t| t.as_str()))
+                    .collect();
+                Some(ScoredOutput::from_text(text))
+            })
+            .collect();
+
+        if outputs.is_empty() {
+            return Err(LangExtractError::parsing("No content in Gemini response"));
         }
+
+        Ok(outputs)
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::providers::config::ProviderConfig;
-    use std::sync::atomic::{AtomicUsize, Ordering};
-    use std::sync::Arc;
+    /// Process a single Gemini prompt
+    #[tracing::instrument(skip_all, fields(provider = "gemini", model = %self.config.model, prompt_len = prompt.len()))]
+    async fn infer_gemini_single(
+        &self,
+        prompt: &str,
+        kwargs: &HashMap<String, serde_json::Value>,
+    ) -> LangExtractResult<Vec<ScoredOutput>> {
+        let request_body = self.build_gemini_request(prompt, kwargs);
+        let url = format!(
+            "{}/models/{}:generateContent",
+            self.config.base_url.trim_end_matches('/'),
+            self.config.model
+        );
+        let api_key = self.config.api_key.as_deref().unwrap_or_default();
 
-    #[tokio::test]
-    async fn test_retry_with_backoff_success_after_failures() {
-        let config = ProviderConfig::ollama("test-model", None);
-        let provider = UniversalProvider::new(config).unwrap();
+        // Make the API call with retry logic
+        report_progress(ProgressEvent::ModelCall {
+            provider: "Gemini".to_string(),
+            model: self.config.model.clone(),
+            input_length: prompt.len(),
+        });
 
-        let attempt_count = Arc::new(AtomicUsize::new(0));
-        let attempt_count_clone = attempt_count.clone();
+        let response_body = self.retry_with_backoff(
+            || async {
+                self.throttle("Gemini", prompt, kwargs).await;
 
-        let result = provider.retry_with_backoff(
-            move || {
-                let attempt_count = attempt_count_clone.clone();
-                async move {
-                    let current = attempt_count.fetch_add(1, Ordering::SeqCst);
-                    if current < 2 {
-                        Err::<String, _>(LangExtractError::inference_simple(format!("Attempt {} failed", current + 1)))
-                    } else {
-                        Ok("Success!".to_string())
-                    }
+                let mut request = self.client
+                    .post(&url)
+                    .header("x-goog-api-key", api_key)
+                    .json(&request_body);
+
+                // Add headers
+                for (key, value) in &self.config.headers {
+                    request = request.header(key, value);
                 }
-            },
-            "Test operation"
-        ).await;
 
-        assert!(result.is_ok());
-        assert_eq!(result.unwrap(), "Success!");
-        assert_eq!(attempt_count.load(Ordering::SeqCst), 3);
-    }
+                let response = request.send().await.map_err(|e| {
+                    report_progress(ProgressEvent::Error {
+                        operation: "Gemini HTTP request".to_string(),
+                        error: format!("HTTP request failed: {}", e),
+                    });
+                    LangExtractError::NetworkError(e)
+                })?;
 
-    #[tokio::test]
-    async fn test_retry_with_backoff_immediate_success() {
+                if !response.status().is_success() {
+                    report_progress(ProgressEvent::Error {
+                        operation: "Gemini HTTP status".to_string(),
+                        error: format!("HTTP error status: {}", response.status()),
+                    });
+                    return Err(Self::http_status_error("gemini", response).await);
+                }
+
+                let response_body: serde_json::Value = response.json().await.map_err(|e| {
+                    report_progress(ProgressEvent::Error {
+                        operation: "Gemini JSON parsing".to_string(),
+                        error: format!("JSON parsing failed: {}", e),
+                    });
+                    LangExtractError::parsing(format!("Failed to parse Gemini response: {}", e))
+                })?;
+
+                Ok(response_body)
+            },
+            &format!("Gemini API call for prompt len {}", prompt.len())
+        ).await?;
+
+        Self::parse_gemini_response(&response_body)
+    }
+
+    /// Inference implementation for Gemini — processes prompts concurrently
+    async fn infer_gemini(
+        &self,
+        batch_prompts: &[String],
+        kwargs: &HashMap<String, serde_json::Value>,
+    ) -> LangExtractResult<Vec<Vec<ScoredOutput>>> {
+        use futures::future::join_all;
+
+        let futures: Vec<_> = batch_prompts.iter()
+            .map(|prompt| self.infer_gemini_single(prompt, kwargs))
+            .collect();
+
+        let results = join_all(futures).await;
+
+        results.into_iter().collect()
+    }
+
+    /// Text used to prefill the assistant turn for Anthropic, if enabled
+    fn anthropic_prefill(&self) -> Option<&'static str> {
+        let enabled = self.config.extra_params
+            .get("prefill")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(true);
+        if !enabled {
+            return None;
+        }
+        match self.format_type {
+            FormatType::Json => Some("{"),
+            FormatType::Yaml => Some("```yaml\n"),
+        }
+    }
+
+    /// Build the Messages API request body for an Anthropic prompt
+    fn build_anthropic_request(
+        &self,
+        prompt: &str,
+        kwargs: &HashMap<String, serde_json::Value>,
+    ) -> serde_json::Value {
+        // System instructions travel separately from the user turn
+        let system_message = self.config.extra_params
+            .get("system")
+            .and_then(|v| v.as_str())
+            .unwrap_or(match self.format_type {
+                FormatType::Json => "You are a helpful assistant that responds in JSON format. Always return valid JSON that matches the expected structure from the examples.",
+                FormatType::Yaml => "You are a helpful assistant that responds in YAML format. Always return valid YAML that matches the expected structure from the examples.",
+            });
+
+        let mut messages = vec![serde_json::json!({ "role": "user", "content": prompt })];
+        if let Some(prefill) = self.anthropic_prefill() {
+            messages.push(serde_json::json!({ "role": "assistant", "content": prefill }));
+        }
+
+        let max_tokens = kwargs.get("max_tokens")
+            .or_else(|| kwargs.get("max_completion_tokens"))
+            .cloned()
+            .unwrap_or_else(|| serde_json::json!(ANTHROPIC_DEFAULT_MAX_TOKENS));
+
+        let mut request_body = serde_json::json!({
+            "model": self.config.model,
+            "system": system_message,
+            "messages": messages,
+            "max_tokens": max_tokens,
+        });
+
+        if let Some(temp) = kwargs.get("temperature") {
+            request_body["temperature"] = temp.clone();
+        }
+        if let Some(stop_sequences) = kwargs.get("stop_sequences") {
+            request_body["stop_sequences"] = stop_sequences.clone();
+        }
+
+        request_body
+    }
+
+    /// Extract the completion text from a Messages API response
+    fn parse_anthropic_response(
+        response_body: &serde_json::Value,
+        prefill: Option<&str>,
+    ) -> LangExtractResult<ScoredOutput> {
+        let content = response_body
+            .get("content")
+            .and_then(|c| c.as_array())
+            .ok_or_else(|| LangExtractError::parsing("Missing 'content' field in Anthropic response"))?;
+
+        let text: String = content
+            .iter()
+            .filter(|block| block.get("type").and_then(|t| t.as_str()) == Some("text"))
+            .filter_map(|block| block.get("text").and_then(|t| t.as_str()))
+            .collect();
+
+        // The prefill is not echoed back, so restore it for the resolver
+        Ok(ScoredOutput::from_text(format!("{}{}", prefill.unwrap_or_default(), text)))
+    }
+
+    /// Process a single Anthropic prompt
+    #[tracing::instrument(skip_all, fields(provider = "anthropic", model = %self.config.model, prompt_len = prompt.len()))]
+    async fn infer_anthropic_single(
+        &self,
+        prompt: &str,
+        kwargs: &HashMap<String, serde_json::Value>,
+    ) -> LangExtractResult<Vec<ScoredOutput>> {
+        let request_body = self.build_anthropic_request(prompt, kwargs);
+        let url = format!("{}/messages", self.config.base_url.trim_end_matches('/'));
+        let api_key = self.config.api_key.as_deref().unwrap_or_default();
+
+        // Make the API call with retry logic
+        report_progress(ProgressEvent::ModelCall {
+            provider: "Anthropic".to_string(),
+            model: self.config.model.clone(),
+            input_length: prompt.len(),
+        });
+
+        let response_body = self.retry_with_backoff(
+            || async {
+                self.throttle("Anthropic", prompt, kwargs).await;
+
+                let mut request = self.client
+                    .post(&url)
+                    .header("x-api-key", api_key)
+                    .header("anthropic-version", ANTHROPIC_VERSION)
+                    .json(&request_body);
+
+                // Add headers
+                for (key, value) in &self.config.headers {
+                    request = request.header(key, value);
+                }
+
+                let response = request.send().await.map_err(|e| {
+                    report_progress(ProgressEvent::Error {
+                        operation: "Anthropic HTTP request".to_string(),
+                        error: format!("HTTP request failed: {}", e),
+                    });
+                    LangExtractError::NetworkError(e)
+                })?;
+
+                if !response.status().is_success() {
+                    report_progress(ProgressEvent::Error {
+                        operation: "Anthropic HTTP status".to_string(),
+                        error: format!("HTTP error status: {}", response.status()),
+                    });
+                    return Err(Self::http_status_error("anthropic", response).await);
+                }
+
+                let response_body: serde_json::Value = response.json().await.map_err(|e| {
+                    report_progress(ProgressEvent::Error {
+                        operation: "Anthropic JSON parsing".to_string(),
+                        error: format!("JSON parsing failed: {}", e),
+                    });
+                    LangExtractError::parsing(format!("Failed to parse Anthropic response: {}", e))
+                })?;
+
+                Ok(response_body)
+            },
+            &format!("Anthropic API call for prompt len {}", prompt.len())
+        ).await?;
+
+        Ok(vec![Self::parse_anthropic_response(&response_body, self.anthropic_prefill())?])
+    }
+
+    /// Inference implementation for Anthropic — processes prompts concurrently
+    async fn infer_anthropic(
+        &self,
+        batch_prompts: &[String],
+        kwargs: &HashMap<String, serde_json::Value>,
+    ) -> LangExtractResult<Vec<Vec<ScoredOutput>>> {
+        use futures::future::join_all;
+
+        let futures: Vec<_> = batch_prompts.iter()
+            .map(|prompt| self.infer_anthropic_single(prompt, kwargs))
+            .collect();
+
+        let results = join_all(futures).await;
+
+        results.into_iter().collect()
+    }
+
+    /// Substitute `{{placeholder}}` values into a custom request body template
+    ///
+    /// Returns `None` when the value is a single placeholder without a value,
+    /// so that the enclosing object or array can drop the entry. Strings are
+    /// rendered in one pass over the template, so placeholders inside
+    /// substituted values (e.g. in the prompt) are left as they are.
+    /// Unknown placeholders are kept verbatim.
+    fn render_body_template(
+        template: &serde_json::Value,
+        vars: &HashMap<String, serde_json::Value>,
+    ) -> Option<serde_json::Value> {
+        match template {
+            serde_json::Value::String(text) => {
+                let trimmed = text.trim();
+                if let Some(name) = trimmed
+                    .strip_prefix("{{")
+                    .and_then(|rest| rest.strip_suffix("}}"))
+                    .filter(|name| !name.contains("{{"))
+                {
+                    return vars.get(name.trim()).cloned();
+                }
+
+                let mut rendered = String::with_capacity(text.len());
+                let mut rest = text.as_str();
+                while let Some(start) = rest.find("{{") {
+                    rendered.push_str(&rest[..start]);
+                    let after = &rest[start + 2..];
+                    let substitution = after
+                        .find("}}")
+                        .and_then(|end| vars.get(after[..end].trim()).map(|value| (value, end)));
+                    match substitution {
+                        Some((value, end)) => {
+                            match value {
+                                serde_json::Value::String(s) => rendered.push_str(s),
+                                other => rendered.push_str(&other.to_string()),
+                            }
+                            rest = &after[end + 2..];
+                        }
+                        None => {
+                            rendered.push_str("{{");
+                            rest = after;
+                        }
+                    }
+                }
+                rendered.push_str(rest);
+                Some(serde_json::Value::String(rendered))
+            }
+            serde_json::Value::Array(items) => Some(serde_json::Value::Array(
+                items
+                    .iter()
+                    .filter_map(|item| Self::render_body_template(item, vars))
+                    .collect(),
+            )),
+            serde_json::Value::Object(map) => Some(serde_json::Value::Object(
+                map.iter()
+                    .filter_map(|(key, value)| {
+                        Self::render_body_template(value, vars).map(|v| (key.clone(), v))
+                    })
+                    .collect(),
+            )),
+            other => Some(other.clone()),
+        }
+    }
+
+    /// Build the request body for a custom provider from its mapping
+    fn build_custom_request(
+        &self,
+        mapping: &CustomHttpMapping,
+        prompt: &str,
+        kwargs: &HashMap<String, serde_json::Value>,
+    ) -> serde_json::Value {
+        let mut vars = kwargs.clone();
+        if !vars.contains_key("max_tokens") {
+            if let Some(max_tokens) = kwargs.get("max_completion_tokens") {
+                vars.insert("max_tokens".to_string(), max_tokens.clone());
+            }
+        }
+        vars.insert("prompt".to_string(), serde_json::json!(prompt));
+        vars.insert("model".to_string(), serde_json::json!(self.config.model));
+
+        Self::render_body_template(&mapping.body_template, &vars)
+            .unwrap_or(serde_json::Value::Null)
+    }
+
+    /// Extract the completion text and optional score from a custom response
+    fn parse_custom_response(
+        mapping: &CustomHttpMapping,
+        response_body: &serde_json::Value,
+    ) -> LangExtractResult<ScoredOutput> {
+        let text = match response_body.pointer(&mapping.text_pointer) {
+            Some(serde_json::Value::String(text)) => text.clone(),
+            Some(serde_json::Value::Null) | None => {
+                return Err(LangExtractError::parsing(format!(
+                    "Custom provider response has no value at '{}'",
+                    mapping.text_pointer
+                )));
+            }
+            // Structured completions are passed on as JSON text for the resolver
+            Some(other) => other.to_string(),
+        };
+
+        let score = mapping
+            .score_pointer
+            .as_ref()
+            .and_then(|pointer| response_body.pointer(pointer))
+            .and_then(|value| value.as_f64())
+            .map(|score| score as f32);
+
+        Ok(ScoredOutput::new(text, score))
+    }
+
+    /// Process a single prompt against a custom HTTP provider
+    #[tracing::instrument(skip_all, fields(provider = "custom", model = %self.config.model, prompt_len = prompt.len()))]
+    async fn infer_custom_single(
+        &self,
+        prompt: &str,
+        kwargs: &HashMap<String, serde_json::Value>,
+    ) -> LangExtractResult<Vec<ScoredOutput>> {
+        let mapping = self.config.custom_mapping.clone().unwrap_or_default();
+        let request_body = self.build_custom_request(&mapping, prompt, kwargs);
+        let url = format!("{}{}", self.config.base_url.trim_end_matches('/'), mapping.path);
+
+        // Make the API call with retry logic
+        report_progress(ProgressEvent::ModelCall {
+            provider: "Custom".to_string(),
+            model: self.config.model.clone(),
+            input_length: prompt.len(),
+        });
+
+        let response_body = self.retry_with_backoff(
+            || async {
+                self.throttle("Custom", prompt, kwargs).await;
+
+                let mut request = self.client.post(&url).json(&request_body);
+
+                if let Some(api_key) = &self.config.api_key {
+                    request = request.bearer_auth(api_key);
+                }
+
+                // Add headers
+                for (key, value) in &self.config.headers {
+                    request = request.header(key, value);
+                }
+
+                let response = request.send().await.map_err(|e| {
+                    report_progress(ProgressEvent::Error {
+                        operation: "Custom HTTP request".to_string(),
+                        error: format!("HTTP request failed: {}", e),
+                    });
+                    LangExtractError::NetworkError(e)
+                })?;
+
+                if !response.status().is_success() {
+                    report_progress(ProgressEvent::Error {
+                        operation: "Custom HTTP status".to_string(),
+                        error: format!("HTTP error status: {}", response.status()),
+                    });
+                    return Err(Self::http_status_error("custom", response).await);
+                }
+
+                let response_body: serde_json::Value = response.json().await.map_err(|e| {
+                    report_progress(ProgressEvent::Error {
+                        operation: "Custom JSON parsing".to_string(),
+                        error: format!("JSON parsing failed: {}", e),
+                    });
+                    LangExtractError::parsing(format!("Failed to parse custom provider response: {}", e))
+                })?;
+
+                Ok(response_body)
+            },
+            &format!("Custom API call for prompt len {}", prompt.len())
+        ).await?;
+
+        Ok(vec![Self::parse_custom_response(&mapping, &response_body)?])
+    }
+
+    /// Inference implementation for custom HTTP providers — processes prompts concurrently
+    async fn infer_custom(
+        &self,
+        batch_prompts: &[String],
+        kwargs: &HashMap<String, serde_json::Value>,
+    ) -> LangExtractResult<Vec<Vec<ScoredOutput>>> {
+        use futures::future::join_all;
+
+        let futures: Vec<_> = batch_prompts.iter()
+            .map(|prompt| self.infer_custom_single(prompt, kwargs))
+            .collect();
+
+        let results = join_all(futures).await;
+
+        results.into_iter().collect()
+    }
+}
+
+#[async_trait]
+impl BaseLanguageModel for UniversalProvider {
+    fn get_schema_class(&self) -> Option<Box<dyn BaseSchema>> {
+        // Return a format mode schema for now
+        crate::schema::FormatModeSchema::from_examples(&[], "_attributes").ok()
+    }
+
+    /// OpenAI, Ollama and Gemini get a full schema for the classes; other
+    /// providers use JSON mode
+    fn schema_for_classes(&self, classes: &[ExtractionClass]) -> LangExtractResult<Option<Box<dyn BaseSchema>>> {
+        match self.config.provider_type {
+            ProviderType::OpenAI | ProviderType::Ollama | ProviderType::Gemini if self.format_type == FormatType::Json => {
+                Ok(Some(Box::new(ExtractionSchema::from_classes(classes, ATTRIBUTE_SUFFIX))))
+            }
+            _ => Ok(self.get_schema_class()),
+        }
+    }
+
+    fn apply_schema(&mut self, schema: Option<Box<dyn BaseSchema>>) {
+        self.schema = schema;
+    }
+
+    fn applied_schema_config(&self) -> Option<HashMap<String, serde_json::Value>> {
+        self.schema.as_ref().map(|schema| schema.to_provider_config())
+    }
+
+    fn set_fence_output(&mut self, fence_output: Option<bool>) {
+        self.fence_output_override = fence_output;
+    }
+
+    fn requires_fence_output(&self) -> bool {
+        if let Some(override_val) = self.fence_output_override {
+            return override_val;
+        }
+
+        // OpenAI and Gemini with JSON mode don't need fences, Ollama might
+        match self.config.provider_type {
+            ProviderType::OpenAI | ProviderType::Gemini if self.schema.is_some() => false,
+            // A `{` prefill forces bare JSON; a YAML prefill opens the fence itself
+            ProviderType::Anthropic if self.anthropic_prefill().is_some() => {
+                self.format_type == FormatType::Yaml
+            }
+            _ => true,
+        }
+    }
+
+    #[tracing::instrument(skip_all, fields(batch_size = batch_prompts.len()))]
+    async fn infer(
+        &self,
+        batch_prompts: &[String],
+        kwargs: &HashMap<String, serde_json::Value>,
+    ) -> LangExtractResult<Vec<Vec<ScoredOutput>>> {
+        match self.config.provider_type {
+            #[cfg(feature = "openai")]
+            ProviderType::OpenAI => self.infer_openai(batch_prompts, kwargs).await,
+            ProviderType::Ollama => self.infer_ollama(batch_prompts, kwargs).await,
+            ProviderType::Gemini => self.infer_gemini(batch_prompts, kwargs).await,
+            ProviderType::Anthropic => self.infer_anthropic(batch_prompts, kwargs).await,
+            ProviderType::Custom => self.infer_custom(batch_prompts, kwargs).await,
+            #[cfg(not(feature = "openai"))]
+            ProviderType::OpenAI => {
+                Err(LangExtractError::configuration(
+                    "OpenAI feature not enabled. Enable with --features openai"
+                ))
+            }
+        }
+    }
+
+    fn supports_streaming(&self) -> bool {
+        match self.config.provider_type {
+            ProviderType::OpenAI => cfg!(feature = "openai"),
+            ProviderType::Ollama => true,
+            ProviderType::Gemini | ProviderType::Anthropic | ProviderType::Custom => false,
+        }
+    }
+
+    async fn infer_stream(
+        &self,
+        prompt: &str,
+        kwargs: &HashMap<String, serde_json::Value>,
+    ) -> LangExtractResult<TextStream> {
+        match self.config.provider_type {
+            #[cfg(feature = "openai")]
+            ProviderType::OpenAI => self.infer_openai_stream(prompt, kwargs).await,
+            ProviderType::Ollama => self.infer_ollama_stream(prompt, kwargs).await,
+            // Providers without streaming support answer in a single delta
+            _ => {
+                let outputs = self.infer_single(prompt, kwargs).await?;
+                let text = outputs.first().map(|o| o.text().to_string()).unwrap_or_default();
+                Ok(Box::pin(futures::stream::once(async move { Ok(text) })))
+            }
+        }
+    }
+
+    fn format_type(&self) -> FormatType {
+        self.format_type
+    }
+
+    fn model_id(&self) -> &str {
+        &self.config.model
+    }
+
+    fn provider_name(&self) -> &str {
+        match self.config.provider_type {
+            ProviderType::OpenAI => "openai",
+            ProviderType::Ollama => "ollama",
+            ProviderType::Gemini => "gemini",
+            ProviderType::Anthropic => "anthropic",
+            ProviderType::Custom => "custom",
+        }
+    }
+}
+
+/// Turn a byte stream of newline-delimited JSON into text deltas
+///
+/// `parse_line` maps each non-empty line to its delta and whether it is the
+/// last one. Empty deltas are skipped.
+fn ndjson_text_stream<S, B>(
+    bytes: S,
+    parse_line: fn(&str) -> LangExtractResult<(String, bool)>,
+) -> TextStream
+where
+    S: futures::Stream<Item = reqwest::Result<B>> + Send + 'static,
+    B: AsRef<[u8]>,
+{
+    use futures::StreamExt;
+
+    struct State<S> {
+        bytes: std::pin::Pin<Box<S>>,
+        buffer: Vec<u8>,
+        finished: bool,
+    }
+
+    let state = State { bytes: Box::pin(bytes), buffer: Vec::new(), finished: false };
+
+    Box::pin(futures::stream::unfold(state, move |mut state| async move {
+        loop {
+            if let Some(newline) = state.buffer.iter().position(|&b| b == b'\n') {
+                let line: Vec<u8> = state.buffer.drain(..=newline).collect();
+                let line = String::from_utf8_lossy(&line);
+                if line.trim().is_empty() {
+                    continue;
+                }
+
+                match parse_line(line.trim()) {
+                    Ok((delta, done)) => {
+                        if done {
+                            state.finished = true;
+                            state.buffer.clear();
+                        }
+                        if !delta.is_empty() {
+                            return Some((Ok(delta), state));
+                        }
+                        continue;
+                    }
+                    Err(e) => {
+                        state.finished = true;
+                        state.buffer.clear();
+                        return Some((Err(e), state));
+                    }
+                }
+            }
+
+            if state.finished {
+                return None;
+            }
+
+            match state.bytes.next().await {
+                Some(Ok(chunk)) => state.buffer.extend_from_slice(chunk.as_ref()),
+                Some(Err(e)) => {
+                    state.finished = true;
+                    return Some((Err(LangExtractError::NetworkError(e)), state));
+                }
+                None => {
+                    // Flush a final line that has no trailing newline
+                    state.finished = true;
+                    if state.buffer.is_empty() {
+                        return None;
+                    }
+                    state.buffer.push(b'\n');
+                }
+            }
+        }
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::config::ProviderConfig;
+    use crate::providers::retry::RetryPolicy;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_retry_with_backoff_success_after_failures() {
+        let config = ProviderConfig::ollama("test-model", None)
+            .with_retry_policy(RetryPolicy::immediate(3));
+        let provider = UniversalProvider::new(config).unwrap();
+
+        let attempt_count = Arc::new(AtomicUsize::new(0));
+        let attempt_count_clone = attempt_count.clone();
+
+        let result = provider.retry_with_backoff(
+            move || {
+                let attempt_count = attempt_count_clone.clone();
+                async move {
+                    let current = attempt_count.fetch_add(1, Ordering::SeqCst);
+                    if current < 2 {
+                        Err::<String, _>(LangExtractError::http_status("test", 503, None, format!("Attempt {} failed", current + 1)))
+                    } else {
+                        Ok("Success!".to_string())
+                    }
+                }
+            },
+            "Test operation"
+        ).await;
+
+        assert!(result.is_ok());
+        assert_eq!(result.unwrap(), "Success!");
+        assert_eq!(attempt_count.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_retry_with_backoff_immediate_success() {
         let config = ProviderConfig::ollama("test-model", None);
         let provider = UniversalProvider::new(config).unwrap();
 
@@ -450,4 +1404,635 @@ mod tests {
         assert_eq!(result.unwrap(), "Immediate success!");
         assert_eq!(attempt_count.load(Ordering::SeqCst), 1);
     }
+
+    #[tokio::test]
+    async fn test_retry_skips_non_retryable_errors() {
+        let config = ProviderConfig::ollama("test-model", None)
+            .with_retry_policy(RetryPolicy::immediate(5));
+        let provider = UniversalProvider::new(config).unwrap();
+
+        let attempt_count = Arc::new(AtomicUsize::new(0));
+        let result = provider.retry_with_backoff(
+            || {
+                attempt_count.fetch_add(1, Ordering::SeqCst);
+                async { Err::<String, _>(LangExtractError::http_status("test", 401, None, "bad key")) }
+            },
+            "Test operation"
+        ).await;
+
+        assert_eq!(result.unwrap_err().status_error().map(|e| e.status), Some(401));
+        assert_eq!(attempt_count.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_retry_gives_up_after_max_attempts() {
+        let config = ProviderConfig::ollama("test-model", None)
+            .with_retry_policy(RetryPolicy::immediate(3));
+        let provider = UniversalProvider::new(config).unwrap();
+
+        let attempt_count = Arc::new(AtomicUsize::new(0));
+        let result = provider.retry_with_backoff(
+            || {
+                attempt_count.fetch_add(1, Ordering::SeqCst);
+                async { Err::<String, _>(LangExtractError::http_status("test", 429, None, "slow down")) }
+            },
+            "Test operation"
+        ).await;
+
+        assert_eq!(result.unwrap_err().status_error().map(|e| e.status), Some(429));
+        assert_eq!(attempt_count.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_http_retry_honors_status_and_retry_after() {
+        use wiremock::matchers::{method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/api/generate"))
+            .respond_with(ResponseTemplate::new(503).insert_header("retry-after", "0"))
+            .up_to_n_times(1)
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/api/generate"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({ "response": "{}" })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        // Default backoff would wait at least 500ms; Retry-After: 0 overrides it
+        let config = ProviderConfig::ollama("mistral", Some(server.uri()));
+        let provider = UniversalProvider::new(config).unwrap();
+        let started = std::time::Instant::now();
+        let results = provider.infer(&["hi".to_string()], &HashMap::new()).await.unwrap();
+        assert_eq!(results[0][0].text(), "{}");
+        assert!(started.elapsed() < std::time::Duration::from_millis(500));
+    }
+
+    #[tokio::test]
+    async fn test_http_client_errors_are_not_retried() {
+        use wiremock::matchers::{method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/api/generate"))
+            .respond_with(ResponseTemplate::new(400).set_body_string("bad request"))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let config = ProviderConfig::ollama("mistral", Some(server.uri()));
+        let provider = UniversalProvider::new(config).unwrap();
+        let err = provider.infer(&["hi".to_string()], &HashMap::new()).await.unwrap_err();
+        assert_eq!(err.status_error().map(|e| e.status), Some(400));
+        assert!(err.to_string().contains("bad request"));
+    }
+
+    #[tokio::test]
+    async fn test_ollama_stream_yields_deltas() {
+        use futures::StreamExt;
+        use wiremock::matchers::{body_partial_json, method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        let body = [
+            r#"{"response": "{\"person\": ", "done": false}"#,
+            r#"{"response": "\"Ada\"}", "done": false}"#,
+            r#"{"response": "", "done": true}"#,
+        ]
+        .join("\n");
+        Mock::given(method("POST"))
+            .and(path("/api/generate"))
+            .and(body_partial_json(serde_json::json!({ "stream": true })))
+            .respond_with(ResponseTemplate::new(200).set_body_string(body))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let provider = UniversalProvider::new(ProviderConfig::ollama("mistral", Some(server.uri()))).unwrap();
+        assert!(provider.supports_streaming());
+
+        let deltas: Vec<String> = provider
+            .infer_stream("hi", &HashMap::new())
+            .await
+            .unwrap()
+            .map(|delta| delta.unwrap())
+            .collect()
+            .await;
+        assert_eq!(deltas, vec!["{\"person\": ", "\"Ada\"}"]);
+    }
+
+    fn medication_classes() -> Vec<ExtractionClass> {
+        use crate::data::{ExampleData, Extraction};
+
+        let mut extraction = Extraction::new("medication".to_string(), "aspirin".to_string());
+        extraction.attributes = Some(HashMap::from([("dose_mg".to_string(), serde_json::json!(100))]));
+        ExtractionClass::from_examples(&[ExampleData::new("Take aspirin 100mg.".to_string(), vec![extraction])])
+    }
+
+    #[test]
+    fn test_ollama_format_uses_class_schema() {
+        let mut provider = UniversalProvider::new(ProviderConfig::ollama("mistral", None)).unwrap();
+        assert_eq!(provider.build_ollama_request("hi", &HashMap::new(), false)["format"], "json");
+
+        let schema = provider.schema_for_classes(&medication_classes()).unwrap();
+        provider.apply_schema(schema);
+        let format = &provider.build_ollama_request("hi", &HashMap::new(), false)["format"];
+        let item = &format["properties"]["extractions"]["items"];
+        assert_eq!(item["properties"]["medication"]["type"], serde_json::json!(["string", "null"]));
+        assert_eq!(
+            item["properties"]["medication_attributes"]["properties"]["dose_mg"]["type"],
+            serde_json::json!(["number", "null"])
+        );
+    }
+
+    #[cfg(feature = "openai")]
+    #[test]
+    fn test_openai_response_format_uses_json_schema() {
+        let mut provider = UniversalProvider::new(ProviderConfig::openai("gpt-4o-mini", Some("test-key".to_string()))).unwrap();
+        let schema = provider.schema_for_classes(&medication_classes()).unwrap();
+        provider.apply_schema(schema);
+
+        let request = serde_json::to_value(provider.build_openai_request("hi", &HashMap::new())).unwrap();
+        let response_format = &request["response_format"];
+        assert_eq!(response_format["type"], "json_schema");
+        assert_eq!(response_format["json_schema"]["strict"], true);
+        assert_eq!(response_format["json_schema"]["schema"]["required"], serde_json::json!(["extractions"]));
+    }
+
+    #[test]
+    fn test_other_providers_keep_json_mode() {
+        let provider = UniversalProvider::new(ProviderConfig::custom("http://localhost:8080", "model")).unwrap();
+        let schema = provider.schema_for_classes(&medication_classes()).unwrap().unwrap();
+        assert!(!schema.to_provider_config().contains_key("json_schema"));
+    }
+
+    #[tokio::test]
+    async fn test_ndjson_lines_split_across_chunks() {
+        use futures::StreamExt;
+
+        let chunks: Vec<reqwest::Result<&'static [u8]>> = vec![
+            Ok(b"{\"response\": \"a\"}\n{\"resp"),
+            Ok(b"onse\": \"b\"}\n\n{\"error\": \"model "),
+            Ok(b"unloaded\"}"),
+        ];
+        let items: Vec<LangExtractResult<String>> = ndjson_text_stream(
+            futures::stream::iter(chunks),
+            UniversalProvider::parse_ollama_stream_line,
+        )
+        .collect()
+        .await;
+
+        assert_eq!(items.len(), 3);
+        assert_eq!(items[0].as_deref().unwrap(), "a");
+        assert_eq!(items[1].as_deref().unwrap(), "b");
+        assert!(items[2].as_ref().unwrap_err().to_string().contains("model unloaded"));
+    }
+
+    #[cfg(feature = "openai")]
+    #[tokio::test]
+    async fn test_openai_stream_reads_server_sent_events() {
+        use futures::StreamExt;
+        use wiremock::matchers::{method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let chunk = |content: &str| {
+            serde_json::json!({
+                "id": "chatcmpl-1",
+                "object": "chat.completion.chunk",
+                "created": 0,
+                "model": "gpt-4o-mini",
+                "choices": [{ "index": 0, "delta": { "content": content }, "finish_reason": null }]
+            })
+        };
+        let body = format!(
+            "data: {}\n\ndata: {}\n\ndata: [DONE]\n\n",
+            chunk("{\"person\": "),
+            chunk("\"Ada\"}")
+        );
+
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(body, "text/event-stream"))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let config = ProviderConfig::openai_compatible(&server.uri(), "gpt-4o-mini", Some("test-key".to_string()));
+        let provider = UniversalProvider::new(config).unwrap();
+        let text: String = provider
+            .infer_stream("hi", &HashMap::new())
+            .await
+            .unwrap()
+            .map(|delta| delta.unwrap())
+            .collect::<Vec<_>>()
+            .await
+            .concat();
+        assert_eq!(text, "{\"person\": \"Ada\"}");
+    }
+
+    #[cfg(feature = "openai")]
+    #[tokio::test]
+    async fn test_openai_retries_follow_policy() {
+        use crate::providers::RetryPolicy;
+        use wiremock::matchers::{method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        let rate_limited = serde_json::json!({
+            "error": { "message": "slow down", "type": "requests", "param": null, "code": "rate_limit_exceeded" }
+        });
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .respond_with(ResponseTemplate::new(429).set_body_json(rate_limited))
+            .up_to_n_times(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .respond_with(ResponseTemplate::new(502).set_body_raw("<html>Bad Gateway</html>", "text/html"))
+            .mount(&server)
+            .await;
+
+        let policy = RetryPolicy::immediate(3);
+        let config = ProviderConfig::openai_compatible(&server.uri(), "gpt-4o-mini", Some("test-key".to_string()))
+            .with_retry_policy(policy.clone());
+        let provider = UniversalProvider::new(config).unwrap();
+        let err = provider.infer(&["hi".to_string()], &HashMap::new()).await.unwrap_err();
+
+        assert_eq!(err.status_error().map(|e| e.status), Some(502));
+        assert_eq!(server.received_requests().await.unwrap().len(), policy.max_attempts as usize);
+    }
+
+    #[cfg(feature = "openai")]
+    #[tokio::test]
+    async fn test_openai_stream_retries_failed_connection() {
+        use crate::providers::RetryPolicy;
+        use futures::StreamExt;
+        use wiremock::matchers::{method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let chunk = serde_json::json!({
+            "id": "chatcmpl-1",
+            "object": "chat.completion.chunk",
+            "created": 0,
+            "model": "gpt-4o-mini",
+            "choices": [{ "index": 0, "delta": { "content": "{}" }, "finish_reason": null }]
+        });
+
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .respond_with(ResponseTemplate::new(429))
+            .up_to_n_times(1)
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_raw(format!("data: {}\n\ndata: [DONE]\n\n", chunk), "text/event-stream"),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let config = ProviderConfig::openai_compatible(&server.uri(), "gpt-4o-mini", Some("test-key".to_string()))
+            .with_retry_policy(RetryPolicy::default().with_delays(std::time::Duration::ZERO, std::time::Duration::ZERO));
+        let provider = UniversalProvider::new(config).unwrap();
+        let deltas: Vec<String> = provider
+            .infer_stream("hi", &HashMap::new())
+            .await
+            .unwrap()
+            .map(|delta| delta.unwrap())
+            .collect()
+            .await;
+        assert_eq!(deltas, vec!["{}"]);
+    }
+
+    #[test]
+    fn test_rate_limit_shared_across_providers() {
+        use crate::providers::rate_limit::RateLimitConfig;
+
+        // 6,000 tokens/min refills at 100 tokens/s
+        let config = ProviderConfig::ollama("shared-budget-model", None)
+            .with_rate_limit(RateLimitConfig::default().with_tokens_per_minute(6_000));
+        let first = UniversalProvider::new(config.clone()).unwrap();
+        let second = UniversalProvider::new(config).unwrap();
+        let now = std::time::Instant::now();
+
+        // The first provider spends the whole budget, so the second waits for the refill
+        assert_eq!(first.rate_limiter.as_ref().unwrap().reserve(6_000, now), std::time::Duration::ZERO);
+        let wait = second.rate_limiter.as_ref().unwrap().reserve(100, now);
+        assert!(wait.as_millis().abs_diff(1_000) <= 1);
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit_applies_to_retries() {
+        use crate::providers::rate_limit::RateLimitConfig;
+        use wiremock::matchers::{method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/api/generate"))
+            .respond_with(ResponseTemplate::new(429))
+            .up_to_n_times(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/api/generate"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({ "response": "{}" })))
+            .mount(&server)
+            .await;
+
+        // Two requests per minute: the failed attempt and its retry use both
+        let config = ProviderConfig::ollama("mistral", Some(server.uri()))
+            .with_retry_policy(RetryPolicy::immediate(2))
+            .with_rate_limit(RateLimitConfig::requests_per_minute(2));
+        let provider = UniversalProvider::new(config).unwrap();
+        provider.infer(&["hi".to_string()], &HashMap::new()).await.unwrap();
+
+        let wait = provider.rate_limiter.as_ref().unwrap().reserve(0, std::time::Instant::now());
+        assert!(wait > std::time::Duration::from_secs(20));
+    }
+
+    #[test]
+    fn test_gemini_requires_api_key() {
+        let config = ProviderConfig::gemini("gemini-2.5-flash", None);
+        assert!(UniversalProvider::new(config).is_err());
+    }
+
+    #[test]
+    fn test_gemini_request_uses_json_mode_with_schema() {
+        let config = ProviderConfig::gemini("gemini-2.5-flash", Some("test-key".to_string()));
+        let mut provider = UniversalProvider::new(config).unwrap();
+        assert!(provider.requires_fence_output());
+
+        provider.apply_schema(Some(Box::new(crate::schema::FormatModeSchema::new("json"))));
+        assert!(!provider.requires_fence_output());
+
+        let request = provider.build_gemini_request("hello", &HashMap::new());
+        assert_eq!(request["generationConfig"]["responseMimeType"], "application/json");
+        assert!(request["generationConfig"].get("responseSchema").is_none());
+
+        let schema = provider.schema_for_classes(&medication_classes()).unwrap();
+        provider.apply_schema(schema);
+        let request = provider.build_gemini_request("hello", &HashMap::new());
+        let response_schema = &request["generationConfig"]["responseSchema"];
+        assert_eq!(request["generationConfig"]["responseMimeType"], "application/json");
+        assert_eq!(response_schema["required"], serde_json::json!(["extractions"]));
+        assert_eq!(
+            response_schema["properties"]["extractions"]["items"]["properties"]["medication_attributes"]["nullable"],
+            true
+        );
+    }
+
+    #[tokio::test]
+    async fn test_gemini_sends_class_response_schema() {
+        use wiremock::matchers::{body_partial_json, method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/models/gemini-2.5-flash:generateContent"))
+            .and(body_partial_json(serde_json::json!({
+                "generationConfig": {
+                    "responseMimeType": "application/json",
+                    "responseSchema": { "type": "OBJECT", "required": ["extractions"] }
+                }
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "candidates": [{ "content": { "role": "model", "parts": [{ "text": "{\"extractions\": []}" }] } }]
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let config = ProviderConfig::gemini("gemini-2.5-flash", Some("test-key".to_string()))
+            .with_base_url(server.uri());
+        let mut provider = UniversalProvider::new(config).unwrap();
+        let schema = provider.schema_for_classes(&medication_classes()).unwrap();
+        provider.apply_schema(schema);
+
+        let results = provider.infer(&["Take aspirin.".to_string()], &HashMap::new()).await.unwrap();
+        assert_eq!(results[0][0].text(), "{\"extractions\": []}");
+
+        let requests = server.received_requests().await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&requests[0].body).unwrap();
+        let item = &body["generationConfig"]["responseSchema"]["properties"]["extractions"]["items"];
+        assert_eq!(item["properties"]["medication"], serde_json::json!({ "type": "STRING", "nullable": true }));
+        assert!(item.get("additionalProperties").is_none());
+    }
+
+    #[test]
+    fn test_gemini_blocked_prompt_is_reported() {
+        let response = serde_json::json!({
+            "promptFeedback": { "blockReason": "SAFETY" }
+        });
+        let err = UniversalProvider::parse_gemini_response(&response).unwrap_err();
+        assert!(err.to_string().contains("SAFETY"));
+    }
+
+    #[tokio::test]
+    async fn test_gemini_generate_content() {
+        use wiremock::matchers::{body_json, header, method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/models/gemini-2.5-flash:generateContent"))
+            .and(header("x-goog-api-key", "test-key"))
+            .and(body_json(serde_json::json!({
+                "contents": [{ "role": "user", "parts": [{ "text": "Extract people" }] }],
+                "generationConfig": {
+                    "temperature": 0.5,
+                    "maxOutputTokens": 256,
+                    "stopSequences": ["```"]
+                }
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "candidates": [{
+                    "content": {
+                        "role": "model",
+                        "parts": [{ "text": "{\"person\": " }, { "text": "\"Alice\"}" }]
+                    },
+                    "finishReason": "STOP"
+                }]
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let config = ProviderConfig::gemini("gemini-2.5-flash", Some("test-key".to_string()))
+            .with_base_url(server.uri());
+        let provider = UniversalProvider::new(config).unwrap();
+
+        let mut kwargs = HashMap::new();
+        kwargs.insert("temperature".to_string(), serde_json::json!(0.5));
+        kwargs.insert("max_completion_tokens".to_string(), serde_json::json!(256));
+        kwargs.insert("stop_sequences".to_string(), serde_json::json!(["```"]));
+
+        let results = provider.infer(&["Extract people".to_string()], &kwargs).await.unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0][0].text(), "{\"person\": \"Alice\"}");
+    }
+
+    #[test]
+    fn test_custom_body_template_rendering() {
+        let config = ProviderConfig::custom("http://localhost:8000", "house-llm");
+        let provider = UniversalProvider::new(config).unwrap();
+
+        let mut kwargs = HashMap::new();
+        kwargs.insert("temperature".to_string(), serde_json::json!(0.5));
+        kwargs.insert("max_completion_tokens".to_string(), serde_json::json!(128));
+
+        let request = provider.build_custom_request(&CustomHttpMapping::default(), "Hi", &kwargs);
+        assert_eq!(request, serde_json::json!({
+            "model": "house-llm",
+            "messages": [{ "role": "user", "content": "Hi" }],
+            "temperature": 0.5,
+            "max_tokens": 128
+        }));
+
+        let mapping = CustomHttpMapping::new(
+            "/generate",
+            serde_json::json!({ "input": "Model {{model}} says: {{prompt}}", "seed": 7 }),
+            "/text",
+        );
+        let request = provider.build_custom_request(&mapping, "Hi", &kwargs);
+        assert_eq!(request, serde_json::json!({ "input": "Model house-llm says: Hi", "seed": 7 }));
+
+        // Placeholders inside the prompt are not substituted again
+        let request = provider.build_custom_request(&mapping, "Echo {{model}} and {{unknown}}", &kwargs);
+        assert_eq!(request["input"], "Model house-llm says: Echo {{model}} and {{unknown}}");
+        let mapping = CustomHttpMapping::new("/generate", serde_json::json!({ "input": "{{prompt}} {{ missing }}" }), "/text");
+        let request = provider.build_custom_request(&mapping, "{{temperature}}", &kwargs);
+        assert_eq!(request["input"], "{{temperature}} {{ missing }}");
+    }
+
+    #[test]
+    fn test_custom_mapping_rejects_invalid_pointer() {
+        let mapping = CustomHttpMapping::new("", serde_json::json!({}), "result.text");
+        let config = ProviderConfig::custom("http://localhost:8000", "house-llm")
+            .with_custom_mapping(mapping);
+        assert!(UniversalProvider::new(config).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_custom_provider_mapping() {
+        use wiremock::matchers::{body_json, header, method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/v2/complete"))
+            .and(header("authorization", "Bearer secret"))
+            .and(body_json(serde_json::json!({
+                "input": "Extract people",
+                "params": { "temp": 0.5 }
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "result": { "text": "{\"person\": \"Alice\"}", "logprob": -0.25 }
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let mapping = CustomHttpMapping::new(
+            "/v2/complete",
+            serde_json::json!({
+                "input": "{{prompt}}",
+                "params": { "temp": "{{temperature}}", "limit": "{{max_tokens}}" }
+            }),
+            "/result/text",
+        )
+        .with_score_pointer("/result/logprob");
+        let config = ProviderConfig::custom(&server.uri(), "house-llm")
+            .with_api_key("secret".to_string())
+            .with_custom_mapping(mapping);
+        let provider = UniversalProvider::new(config).unwrap();
+
+        let mut kwargs = HashMap::new();
+        kwargs.insert("temperature".to_string(), serde_json::json!(0.5));
+
+        let results = provider.infer(&["Extract people".to_string()], &kwargs).await.unwrap();
+        assert_eq!(results[0][0].text(), "{\"person\": \"Alice\"}");
+        assert_eq!(results[0][0].score, Some(-0.25));
+    }
+
+    #[test]
+    fn test_anthropic_requires_api_key() {
+        let config = ProviderConfig::anthropic("claude-sonnet-4-5", None);
+        assert!(UniversalProvider::new(config).is_err());
+    }
+
+    #[test]
+    fn test_anthropic_prefill_controls_fences() {
+        let config = ProviderConfig::anthropic("claude-sonnet-4-5", Some("test-key".to_string()));
+        let provider = UniversalProvider::new(config.clone()).unwrap();
+        assert!(!provider.requires_fence_output());
+
+        let request = provider.build_anthropic_request("hello", &HashMap::new());
+        assert_eq!(request["max_tokens"], ANTHROPIC_DEFAULT_MAX_TOKENS);
+        assert_eq!(request["messages"].as_array().unwrap().len(), 2);
+
+        let config = config.with_extra_param("prefill".to_string(), serde_json::json!(false));
+        let provider = UniversalProvider::new(config).unwrap();
+        assert!(provider.requires_fence_output());
+
+        let request = provider.build_anthropic_request("hello", &HashMap::new());
+        assert_eq!(request["messages"].as_array().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_anthropic_messages() {
+        use wiremock::matchers::{body_partial_json, header, method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/messages"))
+            .and(header("x-api-key", "test-key"))
+            .and(header("anthropic-version", ANTHROPIC_VERSION))
+            .and(body_partial_json(serde_json::json!({
+                "model": "claude-sonnet-4-5",
+                "system": "Be precise.",
+                "messages": [
+                    { "role": "user", "content": "Extract people" },
+                    { "role": "assistant", "content": "{" }
+                ],
+                "max_tokens": 512,
+                "temperature": 0.5,
+                "stop_sequences": ["\n\n"]
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "type": "message",
+                "role": "assistant",
+                "content": [{ "type": "text", "text": "\"person\": \"Alice\"}" }],
+                "stop_reason": "end_turn"
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let config = ProviderConfig::anthropic("claude-sonnet-4-5", Some("test-key".to_string()))
+            .with_base_url(server.uri())
+            .with_extra_param("system".to_string(), serde_json::json!("Be precise."));
+        let provider = UniversalProvider::new(config).unwrap();
+
+        let mut kwargs = HashMap::new();
+        kwargs.insert("temperature".to_string(), serde_json::json!(0.5));
+        kwargs.insert("max_completion_tokens".to_string(), serde_json::json!(512));
+        kwargs.insert("stop_sequences".to_string(), serde_json::json!(["\n\n"]));
+
+        let results = provider.infer(&["Extract people".to_string()], &kwargs).await.unwrap();
+        assert_eq!(results[0][0].text(), "{\"person\": \"Alice\"}");
+    }
 }
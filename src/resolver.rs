@@ -3,9 +3,11 @@
 use crate::{
     data::{FormatType, Extraction}, 
     exceptions::{LangExtractError, LangExtractResult}, 
+    schema::{ATTRIBUTE_SUFFIX, EXTRACTIONS_KEY},
     ExtractConfig
 };
 use serde_json::Value;
+use std::collections::HashMap;
 use std::fs;
 use std::path::Path;
 use uuid::Uuid;
@@ -123,6 +125,7 @@ pub enum CoercionTargetType {
 }
 
 /// Type coercion engine
+#[derive(Clone)]
 pub struct TypeCoercer {
     enable_coercion: bool,
     // Pre-compiled regex patterns for performance
@@ -433,6 +436,7 @@ impl TypeCoercer {
 }
 
 /// Resolver for parsing language model outputs with validation
+#[derive(Clone)]
 pub struct Resolver {
     /// Whether to expect fenced output
     fence_output: bool,
@@ -447,10 +451,13 @@ pub struct Resolver {
 impl Resolver {
     /// Create a new resolver
     pub fn new(config: &ExtractConfig, fence_output: bool) -> LangExtractResult<Self> {
-        let validation_config = ValidationConfig {
+        let mut validation_config = ValidationConfig {
             save_raw_outputs: config.debug, // Enable for debug mode by default
             ..Default::default()
         };
+        if let Some(dir) = config.resolver_params.get("raw_outputs_dir").and_then(|v| v.as_str()) {
+            validation_config.raw_outputs_dir = dir.to_string();
+        }
 
         // Create raw outputs directory if it doesn't exist
         if validation_config.save_raw_outputs {
@@ -502,7 +509,16 @@ impl Resolver {
         if !self.validation_config.save_raw_outputs {
             return Err(LangExtractError::configuration("Raw output saving is disabled"));
         }
+        self.write_raw_output(raw_output, metadata)
+    }
+
+    /// Save model output that could not be parsed, even when raw output
+    /// saving is disabled, so a partial result can point at it
+    pub fn save_failed_output(&self, raw_output: &str) -> LangExtractResult<String> {
+        self.write_raw_output(raw_output, Some("parse_failure"))
+    }
 
+    fn write_raw_output(&self, raw_output: &str, metadata: Option<&str>) -> LangExtractResult<String> {
         // Ensure output directory exists
         let output_dir = Path::new(&self.validation_config.raw_outputs_dir);
         if !output_dir.exists() {
@@ -537,9 +553,25 @@ impl Resolver {
         Ok(path_str)
     }
 
+    /// Parse a model response without saving raw output or validating fields
+    pub fn parse(&self, raw_response: &str, expected_fields: &[String]) -> LangExtractResult<Vec<Extraction>> {
+        self.parse_response_with_repair(raw_response, expected_fields)
+    }
+
     /// Validate and parse model response with raw data preservation
-    #[tracing::instrument(skip_all, fields(response_len = raw_response.len(), num_expected_fields = expected_fields.len()))]
     pub fn validate_and_parse(&self, raw_response: &str, expected_fields: &[String]) -> LangExtractResult<(Vec<Extraction>, ValidationResult)> {
+        let (parse_result, validation_result) = self.validate_response(raw_response, expected_fields);
+        parse_result.map(|extractions| (extractions, validation_result))
+    }
+
+    /// Like [`validate_and_parse`](Self::validate_and_parse), but returns the
+    /// validation result (with the raw output path) even when parsing fails
+    #[tracing::instrument(skip_all, fields(response_len = raw_response.len(), num_expected_fields = expected_fields.len()))]
+    pub fn validate_response(
+        &self,
+        raw_response: &str,
+        expected_fields: &[String],
+    ) -> (LangExtractResult<Vec<Extraction>>, ValidationResult) {
         // Step 1: Always save raw output first if enabled
         let raw_file_path = if self.validation_config.save_raw_outputs {
             match self.save_raw_output(raw_response, Some("validation_parse")) {
@@ -592,7 +624,7 @@ impl Resolver {
 
         // Step 5: Return results - even if validation fails, we preserve the raw data
         match parse_result {
-            Ok(extractions) => Ok((extractions, validation_result)),
+            Ok(extractions) => (Ok(extractions), validation_result),
             Err(e) => {
                 // Improved error reporting
                 match &validation_result.raw_output_file {
@@ -605,7 +637,7 @@ impl Resolver {
                         log::warn!("Parse failed and raw data could not be saved");
                     }
                 }
-                Err(e)
+                (Err(e), validation_result)
             }
         }
     }
@@ -769,6 +801,12 @@ impl Resolver {
                 }
                 return Ok(extractions);
             }
+            if let Some(extractions_array) = obj.get(EXTRACTIONS_KEY).and_then(|v| v.as_array()) {
+                for (index, item) in extractions_array.iter().enumerate() {
+                    extractions.extend(self.parse_single_item(item, Some(index))?);
+                }
+                return Ok(extractions);
+            }
 
             // Handle flat JSON structure like {"name": "John", "age": "25"}
             extractions.extend(self.parse_single_item(json, None)?);
@@ -779,42 +817,7 @@ impl Resolver {
 
     /// Parse a single item (object or primitive) into extractions
     fn parse_single_item(&self, item: &serde_json::Value, index: Option<usize>) -> LangExtractResult<Vec<Extraction>> {
-        let mut extractions = Vec::new();
-
-        match item {
-            Value::Object(obj) => {
-                for (key, value) in obj {
-                    let extraction_text = match value {
-                        Value::String(s) => s.clone(),
-                        Value::Number(n) => n.to_string(),
-                        Value::Bool(b) => b.to_string(),
-                        Value::Array(_) | Value::Object(_) => value.to_string(),
-                        Value::Null => continue,
-                    };
-
-                    let mut extraction = Extraction::new(key.clone(), extraction_text);
-                    if let Some(idx) = index {
-                        extraction.group_index = Some(idx);
-                    }
-                    extractions.push(extraction);
-                }
-            }
-            Value::String(s) => {
-                let extraction_class = if let Some(idx) = index {
-                    format!("item_{}", idx)
-                } else {
-                    "text".to_string()
-                };
-                extractions.push(Extraction::new(extraction_class, s.clone()));
-            }
-            _ => {
-                return Err(LangExtractError::parsing(
-                    format!("Unsupported item type: {:?}", item)
-                ));
-            }
-        }
-
-        Ok(extractions)
+        item_extractions(item, index)
     }
 
     /// Validate extractions against expected schema
@@ -925,6 +928,278 @@ impl Resolver {
     }
 }
 
+/// Convert a parsed item (object or primitive) into extractions
+fn item_extractions(item: &Value, index: Option<usize>) -> LangExtractResult<Vec<Extraction>> {
+    let mut extractions = Vec::new();
+
+    match item {
+        Value::Object(obj) => {
+            for (key, value) in obj {
+                // `<class>_attributes` is folded into the `<class>` extraction
+                if key.strip_suffix(ATTRIBUTE_SUFFIX).is_some_and(|class| obj.contains_key(class)) {
+                    continue;
+                }
+
+                let extraction_text = match value {
+                    Value::String(s) => s.clone(),
+                    Value::Number(n) => n.to_string(),
+                    Value::Bool(b) => b.to_string(),
+                    Value::Array(_) | Value::Object(_) => value.to_string(),
+                    Value::Null => continue,
+                };
+
+                let mut extraction = Extraction::new(key.clone(), extraction_text);
+                if let Some(idx) = index {
+                    extraction.group_index = Some(idx);
+                }
+                if let Some(Value::Object(attributes)) = obj.get(&format!("{}{}", key, ATTRIBUTE_SUFFIX)) {
+                    let attributes: HashMap<String, Value> = attributes
+                        .iter()
+                        .filter(|(_, v)| !v.is_null())
+                        .map(|(k, v)| (k.clone(), v.clone()))
+                        .collect();
+                    if !attributes.is_empty() {
+                        extraction.attributes = Some(attributes);
+                    }
+                }
+                extractions.push(extraction);
+            }
+        }
+        Value::String(s) => {
+            let extraction_class = if let Some(idx) = index {
+                format!("item_{}", idx)
+            } else {
+                "text".to_string()
+            };
+            extractions.push(Extraction::new(extraction_class, s.clone()));
+        }
+        _ => {
+            return Err(LangExtractError::parsing(
+                format!("Unsupported item type: {:?}", item)
+            ));
+        }
+    }
+
+    Ok(extractions)
+}
+
+/// Which values of the root JSON container are emitted as they complete
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum EmitMode {
+    /// Members of a flat root object, one extraction per key
+    Members,
+    /// Items of an array: the root itself or a `data`/`results` wrapper
+    Items,
+    /// Root members after a `data`/`results` array, which a full parse ignores
+    Ignore,
+}
+
+/// Incremental parser that turns streamed JSON output into extractions
+///
+/// Feed it text deltas as they arrive. Each extraction is returned as soon as
+/// the value it comes from is complete: a member of a flat root object, or an
+/// item of a root array or a `data`/`results`/`extractions` wrapper array. Text before the
+/// first `{` or `[` (such as a code fence) is skipped. The extractions match
+/// what [`Resolver::validate_and_parse`] produces for the same well-formed
+/// JSON, but alignment and validation still happen on the full response.
+#[derive(Debug)]
+pub struct IncrementalJsonParser {
+    buffer: String,
+    /// Open containers, innermost last
+    stack: Vec<char>,
+    in_string: bool,
+    escaped: bool,
+    mode: EmitMode,
+    /// Depth of the container whose values are emitted
+    emit_depth: usize,
+    /// Byte offset where the key or value being read at `emit_depth` starts
+    unit_start: Option<usize>,
+    /// Whether the string being read at `emit_depth` is a member key
+    reading_key: bool,
+    /// Key of the member whose value is being read
+    current_key: Option<String>,
+    item_index: usize,
+    done: bool,
+}
+
+impl Default for IncrementalJsonParser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl IncrementalJsonParser {
+    /// Create a parser waiting for the start of a JSON document
+    pub fn new() -> Self {
+        Self {
+            buffer: String::new(),
+            stack: Vec::new(),
+            in_string: false,
+            escaped: false,
+            mode: EmitMode::Members,
+            emit_depth: 1,
+            unit_start: None,
+            reading_key: false,
+            current_key: None,
+            item_index: 0,
+            done: false,
+        }
+    }
+
+    /// Whether the root JSON value has been closed
+    pub fn is_done(&self) -> bool {
+        self.done
+    }
+
+    /// All text fed so far
+    pub fn text(&self) -> &str {
+        &self.buffer
+    }
+
+    /// Consume a text delta and return the extractions it completed
+    pub fn feed(&mut self, delta: &str) -> Vec<Extraction> {
+        let offset = self.buffer.len();
+        self.buffer.push_str(delta);
+
+        let mut found = Vec::new();
+        for (i, c) in delta.char_indices() {
+            if self.done {
+                break;
+            }
+            self.step(offset + i, c, &mut found);
+        }
+        found
+    }
+
+    fn step(&mut self, pos: usize, c: char, found: &mut Vec<Extraction>) {
+        let depth = self.stack.len();
+
+        // Skip everything before the root container
+        if depth == 0 {
+            match c {
+                '{' => self.mode = EmitMode::Members,
+                '[' => self.mode = EmitMode::Items,
+                _ => return,
+            }
+            self.stack.push(c);
+            return;
+        }
+
+        if self.in_string {
+            if self.escaped {
+                self.escaped = false;
+            } else if c == '\\' {
+                self.escaped = true;
+            } else if c == '"' {
+                self.in_string = false;
+                if depth == self.emit_depth {
+                    if self.reading_key {
+                        self.reading_key = false;
+                        let key = self.unit_start.take().map(|start| &self.buffer[start..=pos]);
+                        self.current_key = key.and_then(|k| serde_json::from_str(k).ok());
+                    } else {
+                        self.emit(pos + 1, found);
+                    }
+                }
+            }
+            return;
+        }
+
+        let at_emit_depth = depth == self.emit_depth;
+        match c {
+            '"' => {
+                self.in_string = true;
+                if at_emit_depth && self.unit_start.is_none() {
+                    self.reading_key = self.mode != EmitMode::Items && self.current_key.is_none();
+                    self.unit_start = Some(pos);
+                }
+            }
+            '{' | '[' => {
+                if at_emit_depth && self.unit_start.is_none() {
+                    let wrapper = matches!(self.current_key.as_deref(), Some("data" | "results" | EXTRACTIONS_KEY));
+                    if self.mode == EmitMode::Members && c == '[' && wrapper {
+                        // Emit the wrapped items instead of the member
+                        self.mode = EmitMode::Items;
+                        self.emit_depth = depth + 1;
+                        self.current_key = None;
+                    } else {
+                        self.unit_start = Some(pos);
+                    }
+                }
+                self.stack.push(c);
+            }
+            '}' | ']' => {
+                if at_emit_depth {
+                    // A number or literal ends at the closing bracket
+                    self.emit(pos, found);
+                }
+                self.stack.pop();
+                let depth = self.stack.len();
+                if depth == self.emit_depth && self.unit_start.is_some() {
+                    self.emit(pos + 1, found);
+                } else if depth < self.emit_depth {
+                    if depth == 0 {
+                        self.done = true;
+                    } else {
+                        // A `data`/`results` array closed; the rest of the root is ignored
+                        self.mode = EmitMode::Ignore;
+                        self.emit_depth = depth;
+                        self.unit_start = None;
+                        self.current_key = None;
+                    }
+                }
+            }
+            ',' => {
+                if at_emit_depth {
+                    self.emit(pos, found);
+                }
+            }
+            ':' => {}
+            c if c.is_whitespace() => {}
+            _ => {
+                // Start of a number or literal
+                let expecting_value = self.mode == EmitMode::Items || self.current_key.is_some();
+                if at_emit_depth && self.unit_start.is_none() && expecting_value {
+                    self.unit_start = Some(pos);
+                }
+            }
+        }
+    }
+
+    /// Parse the pending value ending at `end` and collect its extractions
+    fn emit(&mut self, end: usize, found: &mut Vec<Extraction>) {
+        let Some(start) = self.unit_start.take() else {
+            return;
+        };
+        let Ok(value) = serde_json::from_str::<Value>(&self.buffer[start..end]) else {
+            self.current_key = None;
+            return;
+        };
+
+        let extractions = match self.mode {
+            // Attributes only reach an extraction through the full parse
+            EmitMode::Members => self.current_key.take().filter(|key| !key.ends_with(ATTRIBUTE_SUFFIX)).map(|key| {
+                let mut member = serde_json::Map::new();
+                member.insert(key, value);
+                item_extractions(&Value::Object(member), None)
+            }),
+            EmitMode::Items => {
+                let index = self.item_index;
+                self.item_index += 1;
+                Some(item_extractions(&value, Some(index)))
+            }
+            EmitMode::Ignore => {
+                self.current_key = None;
+                None
+            }
+        };
+
+        if let Some(Ok(extractions)) = extractions {
+            found.extend(extractions);
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1033,6 +1308,25 @@ mod tests {
         assert_eq!(city_extraction.extraction_text, "NYC");
     }
 
+    #[test]
+    fn test_parse_schema_extractions() {
+        let resolver = create_test_resolver();
+        let json_response = r#"{"extractions": [
+            {"medication": "aspirin", "medication_attributes": {"dose_mg": 100, "route": null}, "condition": null},
+            {"medication": null, "condition": "headache"}
+        ]}"#;
+
+        let extractions = resolver.parse(json_response, &[]).unwrap();
+        assert_eq!(class_text_pairs(&extractions).len(), 2);
+        assert_eq!(extractions[0].extraction_class, "medication");
+        assert_eq!(extractions[0].extraction_text, "aspirin");
+        let attributes = extractions[0].attributes.as_ref().unwrap();
+        assert_eq!(attributes["dose_mg"], 100);
+        assert!(!attributes.contains_key("route"));
+        assert_eq!(extractions[1].extraction_class, "condition");
+        assert_eq!(extractions[1].extraction_text, "headache");
+    }
+
     #[test]
     fn test_parse_invalid_json() {
         let resolver = create_test_resolver();
@@ -1528,4 +1822,65 @@ mod tests {
             assert_eq!(corrected_obj.get("invalid").unwrap().as_str().unwrap(), "not_a_number");
         }
     }
+
+    fn feed_in_pieces(response: &str, piece_len: usize) -> (IncrementalJsonParser, Vec<Vec<Extraction>>) {
+        let mut parser = IncrementalJsonParser::new();
+        let chars: Vec<char> = response.chars().collect();
+        let batches = chars
+            .chunks(piece_len)
+            .map(|piece| parser.feed(&piece.iter().collect::<String>()))
+            .collect();
+        (parser, batches)
+    }
+
+    fn class_text_pairs(extractions: &[Extraction]) -> Vec<(String, String, Option<usize>)> {
+        let mut pairs: Vec<_> = extractions
+            .iter()
+            .map(|e| (e.extraction_class.clone(), e.extraction_text.clone(), e.group_index))
+            .collect();
+        pairs.sort();
+        pairs
+    }
+
+    #[test]
+    fn test_incremental_parser_matches_full_parse() {
+        let resolver = create_test_resolver();
+        let responses = [
+            r#"{"person": "Ada \"Countess\" Lovelace", "age": 36, "active": true, "tags": ["math", "{poetry}"], "note": null}"#,
+            r#"{"data": [{"person": "Ada"}, {"person": "Alan", "year": 1936}], "count": 2}"#,
+            r#"["first", {"city": "London"}]"#,
+            r#"{"extractions": [{"drug": "aspirin", "drug_attributes": {"dose_mg": 100}}, {"drug": null}]}"#,
+            "```json\n{\"drug\": \"aspirin\", \"dose\": {\"amount\": 100, \"unit\": \"mg\"}}\n```",
+        ];
+
+        for response in responses {
+            let expected = resolver.parse(response, &[]).unwrap();
+            for piece_len in [1, 3, 1000] {
+                let (parser, batches) = feed_in_pieces(response, piece_len);
+                let streamed: Vec<Extraction> = batches.into_iter().flatten().collect();
+                assert!(parser.is_done(), "{}", response);
+                assert_eq!(class_text_pairs(&streamed), class_text_pairs(&expected), "{}", response);
+            }
+        }
+    }
+
+    #[test]
+    fn test_incremental_parser_emits_before_response_ends() {
+        let response = r#"{"data": [{"person": "Ada"}, {"person": "Alan"}]}"#;
+        let (_, batches) = feed_in_pieces(response, 1);
+
+        // "Ada" is reported as soon as its object closes, long before the end
+        let first = batches.iter().position(|b| !b.is_empty()).unwrap();
+        assert_eq!(batches[first][0].extraction_text, "Ada");
+        assert_eq!(first, response.find('}').unwrap());
+    }
+
+    #[test]
+    fn test_incremental_parser_ignores_incomplete_values() {
+        let mut parser = IncrementalJsonParser::new();
+        assert!(parser.feed(r#"Sure! {"person": "Ada", "age": 3"#).len() == 1);
+        assert!(!parser.is_done());
+        assert_eq!(parser.feed("6}")[0].extraction_text, "36");
+        assert!(parser.is_done());
+    }
 }
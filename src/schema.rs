@@ -2,11 +2,14 @@
 
 use crate::{data::ExampleData, exceptions::LangExtractResult};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
 /// Shared key for extraction arrays in JSON/YAML
 pub const EXTRACTIONS_KEY: &str = "extractions";
 
+/// Suffix of the key holding an extraction's attributes, e.g. `medication_attributes`
+pub const ATTRIBUTE_SUFFIX: &str = "_attributes";
+
 /// Enumeration of constraint types
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
@@ -136,6 +139,263 @@ impl BaseSchema for FormatModeSchema {
     }
 }
 
+/// JSON type of an extraction attribute
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "mcp", derive(schemars::JsonSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum AttributeType {
+    String,
+    Integer,
+    Number,
+    Boolean,
+    StringList,
+}
+
+impl AttributeType {
+    /// Infer the type of an example attribute value, or `None` for null
+    pub fn of_value(value: &serde_json::Value) -> Option<Self> {
+        match value {
+            serde_json::Value::Null => None,
+            serde_json::Value::Number(_) => Some(Self::Number),
+            serde_json::Value::Bool(_) => Some(Self::Boolean),
+            serde_json::Value::Array(_) => Some(Self::StringList),
+            _ => Some(Self::String),
+        }
+    }
+
+    /// Name used for this type in prompts
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::String => "string",
+            Self::Integer => "integer",
+            Self::Number => "number",
+            Self::Boolean => "boolean",
+            Self::StringList => "list of strings",
+        }
+    }
+
+    /// JSON Schema for a value of this type, optionally allowing null
+    fn json_schema(&self, nullable: bool) -> serde_json::Value {
+        let json_type = match self {
+            Self::String => "string",
+            Self::Integer => "integer",
+            Self::Number => "number",
+            Self::Boolean => "boolean",
+            Self::StringList => "array",
+        };
+        let mut schema = if nullable {
+            serde_json::json!({ "type": [json_type, "null"] })
+        } else {
+            serde_json::json!({ "type": json_type })
+        };
+        if *self == Self::StringList {
+            schema["items"] = serde_json::json!({ "type": "string" });
+        }
+        schema
+    }
+}
+
+/// An attribute of an extraction class
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "mcp", derive(schemars::JsonSchema))]
+pub struct AttributeSpec {
+    /// Attribute key
+    pub name: String,
+    /// Expected JSON type
+    pub attribute_type: AttributeType,
+    /// Whether the model must always provide a value
+    #[serde(default)]
+    pub required: bool,
+    /// Human-readable description for the prompt
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+}
+
+/// An extraction class and the attributes it carries
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "mcp", derive(schemars::JsonSchema))]
+pub struct ExtractionClass {
+    /// Class name, as used in `extraction_class`
+    pub name: String,
+    /// Human-readable description for the prompt
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// Attributes extracted alongside the text
+    #[serde(default)]
+    pub attributes: Vec<AttributeSpec>,
+}
+
+impl ExtractionClass {
+    /// Collect the classes used in the examples, sorted by name
+    ///
+    /// Attribute types come from the first non-null example value, and no
+    /// attribute is required.
+    pub fn from_examples(examples_data: &[ExampleData]) -> Vec<Self> {
+        let mut classes: BTreeMap<String, BTreeMap<String, Option<AttributeType>>> = BTreeMap::new();
+        for extraction in examples_data.iter().flat_map(|example| &example.extractions) {
+            let attributes = classes.entry(extraction.extraction_class.clone()).or_default();
+            for (key, value) in extraction.attributes.iter().flatten() {
+                let attribute_type = attributes.entry(key.clone()).or_default();
+                if attribute_type.is_none() {
+                    *attribute_type = AttributeType::of_value(value);
+                }
+            }
+        }
+
+        classes
+            .into_iter()
+            .map(|(name, attributes)| Self {
+                name,
+                description: None,
+                attributes: attributes
+                    .into_iter()
+                    .map(|(name, attribute_type)| AttributeSpec {
+                        name,
+                        attribute_type: attribute_type.unwrap_or(AttributeType::String),
+                        required: false,
+                        description: None,
+                    })
+                    .collect(),
+            })
+            .collect()
+    }
+}
+
+/// JSON Schema describing the extraction classes and their attributes
+///
+/// The schema describes `{"extractions": [{"<class>": "...", "<class>_attributes": {...}}]}`,
+/// which the resolver reads directly. It is written for strict structured
+/// output: every object lists all of its properties as required and forbids
+/// others, and optional values are nullable instead.
+#[derive(Debug, Clone)]
+pub struct ExtractionSchema {
+    schema: serde_json::Value,
+}
+
+impl ExtractionSchema {
+    /// Build the schema for the classes used in the given examples
+    pub fn new(examples_data: &[ExampleData], attribute_suffix: &str) -> Self {
+        Self::from_classes(&ExtractionClass::from_examples(examples_data), attribute_suffix)
+    }
+
+    /// Build the schema for explicitly described classes
+    pub fn from_classes(classes: &[ExtractionClass], attribute_suffix: &str) -> Self {
+        let mut properties = serde_json::Map::new();
+        for class in classes {
+            properties.insert(class.name.clone(), serde_json::json!({ "type": ["string", "null"] }));
+            if !class.attributes.is_empty() {
+                let attributes = class
+                    .attributes
+                    .iter()
+                    .map(|a| (a.name.clone(), a.attribute_type.json_schema(!a.required)))
+                    .collect();
+                properties.insert(
+                    format!("{}{}", class.name, attribute_suffix),
+                    Self::strict_object(attributes, true),
+                );
+            }
+        }
+
+        let item = Self::strict_object(properties, false);
+        let mut root = serde_json::Map::new();
+        root.insert(
+            EXTRACTIONS_KEY.to_string(),
+            serde_json::json!({ "type": "array", "items": item }),
+        );
+
+        Self {
+            schema: Self::strict_object(root, false),
+        }
+    }
+
+    /// The JSON Schema document
+    pub fn json_schema(&self) -> &serde_json::Value {
+        &self.schema
+    }
+
+    /// The schema in the OpenAPI subset Gemini accepts as `responseSchema`:
+    /// upper-case single types with a `nullable` flag instead of type lists,
+    /// and no `additionalProperties`
+    pub fn openapi_schema(&self) -> serde_json::Value {
+        Self::to_openapi(&self.schema)
+    }
+
+    fn to_openapi(schema: &serde_json::Value) -> serde_json::Value {
+        let Some(object) = schema.as_object() else {
+            return schema.clone();
+        };
+
+        let mut converted = serde_json::Map::new();
+        for (key, value) in object {
+            match key.as_str() {
+                "additionalProperties" => {}
+                "type" => {
+                    let types: Vec<&str> = match value {
+                        serde_json::Value::Array(types) => types.iter().filter_map(|t| t.as_str()).collect(),
+                        other => other.as_str().into_iter().collect(),
+                    };
+                    if types.contains(&"null") {
+                        converted.insert("nullable".to_string(), serde_json::json!(true));
+                    }
+                    if let Some(json_type) = types.iter().find(|t| **t != "null") {
+                        converted.insert(key.clone(), serde_json::json!(json_type.to_uppercase()));
+                    }
+                }
+                "properties" => {
+                    let properties = value
+                        .as_object()
+                        .map(|p| p.iter().map(|(name, s)| (name.clone(), Self::to_openapi(s))).collect())
+                        .unwrap_or_default();
+                    converted.insert(key.clone(), serde_json::Value::Object(properties));
+                }
+                "items" => {
+                    converted.insert(key.clone(), Self::to_openapi(value));
+                }
+                _ => {
+                    converted.insert(key.clone(), value.clone());
+                }
+            }
+        }
+        serde_json::Value::Object(converted)
+    }
+
+    /// Object schema requiring exactly `properties`
+    fn strict_object(properties: serde_json::Map<String, serde_json::Value>, nullable: bool) -> serde_json::Value {
+        let required: Vec<&String> = properties.keys().collect();
+        serde_json::json!({
+            "type": if nullable { serde_json::json!(["object", "null"]) } else { serde_json::json!("object") },
+            "properties": properties,
+            "required": required,
+            "additionalProperties": false,
+        })
+    }
+}
+
+impl BaseSchema for ExtractionSchema {
+    fn from_examples(
+        examples_data: &[ExampleData],
+        attribute_suffix: &str,
+    ) -> LangExtractResult<Box<dyn BaseSchema>> {
+        Ok(Box::new(Self::new(examples_data, attribute_suffix)))
+    }
+
+    fn to_provider_config(&self) -> HashMap<String, serde_json::Value> {
+        let mut config = HashMap::new();
+        config.insert("format".to_string(), serde_json::json!("json"));
+        config.insert("json_schema".to_string(), self.schema.clone());
+        config.insert("response_schema".to_string(), self.openapi_schema());
+        config
+    }
+
+    fn supports_strict_mode(&self) -> bool {
+        true
+    }
+
+    fn clone_box(&self) -> Box<dyn BaseSchema> {
+        Box::new(self.clone())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -198,4 +458,66 @@ mod tests {
         let deserialized: Constraint = serde_json::from_str(&json).unwrap();
         assert_eq!(constraint, deserialized);
     }
+
+    #[test]
+    fn test_extraction_schema_from_examples() {
+        let mut medication = Extraction::new("medication".to_string(), "aspirin".to_string());
+        medication.attributes = Some(HashMap::from([
+            ("dose_mg".to_string(), serde_json::json!(100)),
+            ("route".to_string(), serde_json::json!("oral")),
+        ]));
+        let examples = vec![ExampleData::new(
+            "Take 100mg aspirin orally. Dr. Smith".to_string(),
+            vec![medication, Extraction::new("person".to_string(), "Dr. Smith".to_string())],
+        )];
+
+        let schema = ExtractionSchema::new(&examples, ATTRIBUTE_SUFFIX);
+        let item = &schema.json_schema()["properties"][EXTRACTIONS_KEY]["items"];
+        assert_eq!(item["additionalProperties"], false);
+        assert_eq!(
+            item["required"],
+            serde_json::json!(["medication", "medication_attributes", "person"])
+        );
+        assert_eq!(item["properties"]["person"]["type"], serde_json::json!(["string", "null"]));
+        assert!(item["properties"].get("person_attributes").is_none());
+
+        let attributes = &item["properties"]["medication_attributes"];
+        assert_eq!(attributes["properties"]["dose_mg"]["type"], serde_json::json!(["number", "null"]));
+        assert_eq!(attributes["required"], serde_json::json!(["dose_mg", "route"]));
+
+        let config = schema.to_provider_config();
+        assert_eq!(config["format"], "json");
+        assert_eq!(&config["json_schema"], schema.json_schema());
+        assert!(schema.supports_strict_mode());
+    }
+
+    #[test]
+    fn test_extraction_schema_from_classes() {
+        let classes = vec![ExtractionClass {
+            name: "invoice".to_string(),
+            description: None,
+            attributes: vec![
+                AttributeSpec {
+                    name: "total".to_string(),
+                    attribute_type: AttributeType::Integer,
+                    required: true,
+                    description: None,
+                },
+                AttributeSpec {
+                    name: "tags".to_string(),
+                    attribute_type: AttributeType::StringList,
+                    required: false,
+                    description: None,
+                },
+            ],
+        }];
+
+        let schema = ExtractionSchema::from_classes(&classes, ATTRIBUTE_SUFFIX);
+        let attributes = &schema.json_schema()["properties"][EXTRACTIONS_KEY]["items"]["properties"]["invoice_attributes"];
+        assert_eq!(attributes["properties"]["total"], serde_json::json!({ "type": "integer" }));
+        assert_eq!(
+            attributes["properties"]["tags"],
+            serde_json::json!({ "type": ["array", "null"], "items": { "type": "string" } })
+        );
+    }
 }
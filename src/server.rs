@@ -0,0 +1,697 @@
+//! HTTP server exposing extraction as a REST service.
+//!
+//! [`router`] serves:
+//!
+//! - `GET /health`: status, version, provider and loaded pipelines
+//! - `POST /extract`: text, examples and per-request settings
+//! - `GET /pipelines` and `POST /pipelines/{name}/run`: pipelines loaded
+//!   from a directory of YAML files
+//! - `GET /jobs/{id}` and `DELETE /jobs/{id}`: poll or cancel a request sent
+//!   with `"async": true`
+//!
+//! The provider and its credentials come only from [`ServerConfig`]. Request
+//! settings are limited to [`RequestConfig`], which rejects unknown fields,
+//! so a request can't name a provider, a URL or an API key. Limits a request
+//! sets (timeouts, budget, concurrency, passes) can tighten the server's but
+//! never loosen them.
+
+use crate::{
+    cancellation::CancellationToken,
+    data::{AnnotatedDocument, ExampleData},
+    exceptions::{LangExtractError, LangExtractResult},
+    extract,
+    pipeline::{self, PipelineConfig, PipelineExecutor, PipelineResult, StepResult},
+    providers::ProviderConfig,
+    schema::ExtractionClass,
+    usage::{UsageBudget, UsageSummary},
+    ExtractConfig,
+};
+use axum::{
+    extract::{Path as UrlPath, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::future::Future;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+/// Finished jobs kept for polling; the oldest are dropped beyond this
+const MAX_FINISHED_JOBS: usize = 1000;
+
+/// Server-side settings: the provider with its credentials, the defaults
+/// every request starts from, and the pipelines it can run
+#[derive(Clone)]
+pub struct ServerConfig {
+    /// Provider all model calls go to
+    pub provider: ProviderConfig,
+    /// Settings a request starts from before its own [`RequestConfig`]
+    pub defaults: ExtractConfig,
+    /// Pipelines by name
+    pub pipelines: BTreeMap<String, PipelineConfig>,
+}
+
+impl ServerConfig {
+    /// Serve extraction with `provider` and default settings
+    pub fn new(provider: ProviderConfig) -> Self {
+        let defaults = ExtractConfig {
+            model_id: provider.model.clone(),
+            ..Default::default()
+        }
+        .with_quiet_mode();
+        Self {
+            provider,
+            defaults,
+            pipelines: BTreeMap::new(),
+        }
+    }
+
+    /// Start every request from `defaults`
+    pub fn with_defaults(mut self, defaults: ExtractConfig) -> Self {
+        self.defaults = defaults;
+        self
+    }
+
+    /// Serve `pipeline` as `/pipelines/{name}/run`
+    pub fn with_pipeline(mut self, name: impl Into<String>, pipeline: PipelineConfig) -> Self {
+        self.pipelines.insert(name.into(), pipeline);
+        self
+    }
+
+    /// Serve every `.yaml`/`.yml` pipeline in `dir`, named by file stem
+    pub fn with_pipelines_dir(mut self, dir: &Path) -> LangExtractResult<Self> {
+        let mut paths: Vec<_> = std::fs::read_dir(dir)?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| matches!(path.extension().and_then(|e| e.to_str()), Some("yaml" | "yml")))
+            .collect();
+        paths.sort();
+        for path in paths {
+            let name = path.file_stem().and_then(|s| s.to_str()).unwrap_or_default().to_string();
+            let pipeline = pipeline::utils::load_pipeline_from_file(&path)
+                .map_err(|e| LangExtractError::configuration(format!("{}: {}", path.display(), e)))?;
+            self.pipelines.insert(name, pipeline);
+        }
+        Ok(self)
+    }
+
+    /// The settings for one request: the defaults, the request's own settings
+    /// and the server's provider
+    pub(crate) fn extract_config(&self, request: &RequestConfig) -> LangExtractResult<ExtractConfig> {
+        let mut config = self.defaults.clone();
+        request.apply(&mut config);
+        config.language_model_params.insert(
+            "provider_config".to_string(),
+            serde_json::to_value(&self.provider).map_err(|e| LangExtractError::serialization(e.to_string()))?,
+        );
+        Ok(config)
+    }
+
+    /// Name, description, version and step IDs of each pipeline
+    pub(crate) fn pipeline_summaries(&self) -> serde_json::Value {
+        let pipelines: Vec<_> = self
+            .pipelines
+            .iter()
+            .map(|(name, pipeline)| {
+                serde_json::json!({
+                    "name": name,
+                    "description": pipeline.description,
+                    "version": pipeline.version,
+                    "steps": pipeline.steps.iter().map(|s| &s.id).collect::<Vec<_>>(),
+                })
+            })
+            .collect();
+        serde_json::json!({ "pipelines": pipelines })
+    }
+
+    /// A pipeline with the server's provider (unless the pipeline file names
+    /// its own) and limits
+    pub(crate) fn pipeline_config(&self, name: &str, timeout_ms: Option<u64>) -> LangExtractResult<PipelineConfig> {
+        let mut pipeline = self
+            .pipelines
+            .get(name)
+            .cloned()
+            .ok_or_else(|| LangExtractError::invalid_input(format!("Unknown pipeline '{}'", name)))?;
+        let params = &mut pipeline.global_config.language_model_params;
+        if !params.contains_key("provider_config") {
+            params.insert(
+                "provider_config".to_string(),
+                serde_json::to_value(&self.provider).map_err(|e| LangExtractError::serialization(e.to_string()))?,
+            );
+        }
+        pipeline.global_config = pipeline.global_config.with_quiet_mode();
+        pipeline.timeout_ms = tighter(tighter(pipeline.timeout_ms, self.defaults.timeout_ms), timeout_ms);
+        Ok(pipeline)
+    }
+}
+
+/// Extraction settings a request may set
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "mcp", derive(schemars::JsonSchema))]
+#[serde(deny_unknown_fields)]
+pub struct RequestConfig {
+    /// Maximum characters per chunk, up to the server's
+    pub max_char_buffer: Option<usize>,
+    /// Sampling temperature
+    pub temperature: Option<f32>,
+    /// Chunks per batch, up to the server's
+    pub batch_length: Option<usize>,
+    /// Concurrent model calls, up to the server's
+    pub max_workers: Option<usize>,
+    /// Extra context added to the prompt
+    pub additional_context: Option<String>,
+    /// Extraction passes, up to the server's; more than one enables
+    /// multi-pass extraction
+    pub passes: Option<usize>,
+    /// Set to false to skip the server's verification pass; a request can't
+    /// turn it on
+    pub verify: Option<bool>,
+    /// Classes and attribute types to constrain output to
+    pub extraction_classes: Option<Vec<ExtractionClass>>,
+    /// Keep failed chunks in the result with a coverage report
+    pub allow_partial: Option<bool>,
+    /// Time limit for the request in milliseconds
+    pub timeout_ms: Option<u64>,
+    /// Time limit for each model call in milliseconds
+    pub request_timeout_ms: Option<u64>,
+    /// Token and cost limits for the request
+    pub budget: Option<UsageBudget>,
+}
+
+impl RequestConfig {
+    /// Apply these settings over `config`
+    ///
+    /// Settings that add load or cost (chunk and batch size, concurrency,
+    /// passes, verification, timeouts and budgets) can only be lowered or
+    /// turned off; the others replace the server's.
+    fn apply(&self, config: &mut ExtractConfig) {
+        if let Some(max_char_buffer) = self.max_char_buffer {
+            config.max_char_buffer = max_char_buffer.clamp(1, config.max_char_buffer.max(1));
+        }
+        if let Some(temperature) = self.temperature {
+            config.temperature = temperature;
+        }
+        if let Some(batch_length) = self.batch_length {
+            config.batch_length = batch_length.clamp(1, config.batch_length.max(1));
+        }
+        if let Some(max_workers) = self.max_workers {
+            config.max_workers = max_workers.clamp(1, config.max_workers.max(1));
+        }
+        if self.additional_context.is_some() {
+            config.additional_context = self.additional_context.clone();
+        }
+        if let Some(passes) = self.passes {
+            let server_passes = if config.enable_multipass { config.multipass_max_passes.max(1) } else { 1 };
+            let passes = passes.clamp(1, server_passes);
+            config.enable_multipass = passes > 1;
+            config.multipass_max_passes = passes;
+        }
+        if self.verify == Some(false) {
+            config.verification = None;
+        }
+        if self.extraction_classes.is_some() {
+            config.extraction_classes = self.extraction_classes.clone();
+        }
+        if let Some(allow_partial) = self.allow_partial {
+            config.allow_partial = allow_partial;
+        }
+        config.timeout_ms = tighter(config.timeout_ms, self.timeout_ms);
+        config.request_timeout_ms = tighter(config.request_timeout_ms, self.request_timeout_ms);
+        if let Some(budget) = self.budget {
+            config.budget = UsageBudget {
+                max_tokens: tighter(config.budget.max_tokens, budget.max_tokens),
+                max_cost_usd: tighter(config.budget.max_cost_usd, budget.max_cost_usd),
+            };
+        }
+    }
+}
+
+/// The smaller of two optional limits
+fn tighter<T: PartialOrd>(server: Option<T>, request: Option<T>) -> Option<T> {
+    match (server, request) {
+        (Some(a), Some(b)) => Some(if b < a { b } else { a }),
+        (a, b) => a.or(b),
+    }
+}
+
+/// Body of `POST /extract`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ExtractRequest {
+    /// Text to extract from
+    pub text: String,
+    /// Instructions for what to extract
+    #[serde(default)]
+    pub prompt: Option<String>,
+    /// Examples guiding the extraction
+    pub examples: Vec<ExampleData>,
+    /// Settings for this request
+    #[serde(default)]
+    pub config: RequestConfig,
+    /// Return a job to poll instead of waiting for the result
+    #[serde(default, rename = "async")]
+    pub run_async: bool,
+}
+
+/// Body of `POST /pipelines/{name}/run`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PipelineRunRequest {
+    /// Text to run the pipeline on
+    pub text: String,
+    /// Time limit for the run in milliseconds
+    #[serde(default)]
+    pub timeout_ms: Option<u64>,
+    /// Return a job to poll instead of waiting for the result
+    #[serde(default, rename = "async")]
+    pub run_async: bool,
+}
+
+/// Result of a pipeline run, without the pipeline's configuration (which may
+/// hold credentials)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PipelineRunResponse {
+    /// Pipeline name
+    pub pipeline: String,
+    /// Whether every step finished
+    pub success: bool,
+    /// Why the run stopped early, if it did
+    pub error_message: Option<String>,
+    /// Nested output of all steps
+    pub nested_output: serde_json::Value,
+    /// Per-step results
+    pub step_results: Vec<StepResult>,
+    /// Token usage and cost of the run
+    pub usage: UsageSummary,
+    /// Total processing time
+    pub total_time_ms: u64,
+}
+
+impl PipelineRunResponse {
+    /// The parts of `result` safe to return to a client
+    pub fn new(pipeline: String, result: PipelineResult) -> Self {
+        Self {
+            pipeline,
+            success: result.success,
+            error_message: result.error_message,
+            nested_output: result.nested_output,
+            step_results: result.step_results,
+            usage: result.usage,
+            total_time_ms: result.total_time_ms,
+        }
+    }
+}
+
+/// State of an asynchronous job
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    /// Still running
+    Running,
+    /// Finished with a result
+    Succeeded,
+    /// Finished with an error
+    Failed,
+    /// Cancelled; the result holds what finished before
+    Cancelled,
+}
+
+/// A job as returned by `GET /jobs/{id}`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JobView {
+    /// Job ID
+    pub job_id: String,
+    /// What the job runs: `extract` or `pipeline`
+    pub kind: String,
+    /// Current state
+    pub status: JobStatus,
+    /// Annotated document or pipeline result, once finished
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result: Option<serde_json::Value>,
+    /// Error message, if the job failed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+struct Job {
+    view: JobView,
+    cancel: CancellationToken,
+}
+
+/// Jobs by ID, with their creation order for eviction
+#[derive(Default)]
+struct JobTable {
+    jobs: HashMap<String, Job>,
+    order: VecDeque<String>,
+}
+
+impl JobTable {
+    fn insert(&mut self, job: Job) {
+        self.order.push_back(job.view.job_id.clone());
+        self.jobs.insert(job.view.job_id.clone(), job);
+    }
+
+    /// Record how a job ended, then drop the oldest finished jobs beyond the
+    /// limit; running ones are always kept
+    fn finish(&mut self, id: &str, outcome: LangExtractResult<serde_json::Value>, cancelled: bool) {
+        let Some(job) = self.jobs.get_mut(id) else {
+            return;
+        };
+        match outcome {
+            Ok(result) => {
+                job.view.status = if cancelled { JobStatus::Cancelled } else { JobStatus::Succeeded };
+                job.view.result = Some(result);
+            }
+            Err(e) => {
+                job.view.status = if e.is_cancelled() { JobStatus::Cancelled } else { JobStatus::Failed };
+                job.view.error = Some(e.to_string());
+            }
+        }
+
+        let finished = self.jobs.values().filter(|j| j.view.status != JobStatus::Running).count();
+        let mut excess = finished.saturating_sub(MAX_FINISHED_JOBS);
+        let jobs = &mut self.jobs;
+        self.order.retain(|id| {
+            let evict = excess > 0 && jobs.get(id).is_some_and(|j| j.view.status != JobStatus::Running);
+            if evict {
+                jobs.remove(id);
+                excess -= 1;
+            }
+            !evict
+        });
+    }
+}
+
+struct ServerState {
+    config: ServerConfig,
+    jobs: Mutex<JobTable>,
+}
+
+impl ServerState {
+    /// Run `work` in the background as a job, returning 202 with its ID
+    fn spawn_job<F>(self: &Arc<Self>, kind: &str, cancel: CancellationToken, work: F) -> Response
+    where
+        F: Future<Output = LangExtractResult<serde_json::Value>> + Send + 'static,
+    {
+        let job_id = uuid::Uuid::new_v4().to_string();
+        let view = JobView {
+            job_id: job_id.clone(),
+            kind: kind.to_string(),
+            status: JobStatus::Running,
+            result: None,
+            error: None,
+        };
+        self.jobs.lock().unwrap().insert(Job { view: view.clone(), cancel: cancel.clone() });
+
+        let state = self.clone();
+        let id = job_id.clone();
+        tokio::spawn(async move {
+            let outcome = work.await;
+            state.jobs.lock().unwrap().finish(&id, outcome, cancel.is_cancelled());
+        });
+
+        let location = format!("/jobs/{}", job_id);
+        (StatusCode::ACCEPTED, [(header::LOCATION, location)], Json(view)).into_response()
+    }
+}
+
+/// Build the HTTP routes for `config`
+pub fn router(config: ServerConfig) -> Router {
+    let state = Arc::new(ServerState {
+        config,
+        jobs: Mutex::new(JobTable::default()),
+    });
+    Router::new()
+        .route("/health", get(health))
+        .route("/extract", post(extract_handler))
+        .route("/pipelines", get(list_pipelines))
+        .route("/pipelines/{name}/run", post(run_pipeline))
+        .route("/jobs/{id}", get(get_job).delete(cancel_job))
+        .with_state(state)
+}
+
+/// Serve `config` on `addr` until `shutdown` is cancelled
+pub async fn serve(config: ServerConfig, addr: SocketAddr, shutdown: CancellationToken) -> LangExtractResult<()> {
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    log::info!("[server] listening on {}", listener.local_addr()?);
+    axum::serve(listener, router(config))
+        .with_graceful_shutdown(shutdown.cancelled_owned())
+        .await?;
+    Ok(())
+}
+
+async fn health(State(state): State<Arc<ServerState>>) -> Json<serde_json::Value> {
+    let provider = &state.config.provider;
+    Json(serde_json::json!({
+        "status": "ok",
+        "version": env!("CARGO_PKG_VERSION"),
+        "provider": provider.provider_type,
+        "model": provider.model,
+        "pipelines": state.config.pipelines.keys().collect::<Vec<_>>(),
+    }))
+}
+
+async fn extract_handler(State(state): State<Arc<ServerState>>, Json(request): Json<ExtractRequest>) -> Response {
+    let mut config = match state.config.extract_config(&request.config) {
+        Ok(config) => config,
+        Err(e) => return error_response(e),
+    };
+    let cancel = CancellationToken::new();
+    config.cancellation = Some(cancel.clone());
+
+    let work = async move {
+        let document: AnnotatedDocument =
+            extract(&request.text, request.prompt.as_deref(), &request.examples, config).await?;
+        serde_json::to_value(document).map_err(|e| LangExtractError::serialization(e.to_string()))
+    };
+    if request.run_async {
+        return state.spawn_job("extract", cancel, work);
+    }
+    // A client that disconnects drops this future, and the extraction with it
+    match work.await {
+        Ok(document) => Json(document).into_response(),
+        Err(e) => error_response(e),
+    }
+}
+
+async fn list_pipelines(State(state): State<Arc<ServerState>>) -> Json<serde_json::Value> {
+    Json(state.config.pipeline_summaries())
+}
+
+async fn run_pipeline(
+    State(state): State<Arc<ServerState>>,
+    UrlPath(name): UrlPath<String>,
+    Json(request): Json<PipelineRunRequest>,
+) -> Response {
+    if !state.config.pipelines.contains_key(&name) {
+        return pipeline_not_found(&name);
+    }
+    let mut pipeline = match state.config.pipeline_config(&name, request.timeout_ms) {
+        Ok(pipeline) => pipeline,
+        Err(e) => return error_response(e),
+    };
+    let cancel = CancellationToken::new();
+    pipeline.cancellation = Some(cancel.clone());
+
+    let work = async move {
+        let result = PipelineExecutor::new(pipeline).execute(&request.text).await?;
+        serde_json::to_value(PipelineRunResponse::new(name, result)).map_err(|e| LangExtractError::serialization(e.to_string()))
+    };
+    if request.run_async {
+        return state.spawn_job("pipeline", cancel, work);
+    }
+    match work.await {
+        Ok(result) => Json(result).into_response(),
+        Err(e) => error_response(e),
+    }
+}
+
+async fn get_job(State(state): State<Arc<ServerState>>, UrlPath(id): UrlPath<String>) -> Response {
+    match state.jobs.lock().unwrap().jobs.get(&id) {
+        Some(job) => Json(job.view.clone()).into_response(),
+        None => job_not_found(&id),
+    }
+}
+
+/// Cancel a running job; it keeps what finished and ends as `cancelled`
+async fn cancel_job(State(state): State<Arc<ServerState>>, UrlPath(id): UrlPath<String>) -> Response {
+    match state.jobs.lock().unwrap().jobs.get(&id) {
+        Some(job) => {
+            job.cancel.cancel();
+            (StatusCode::ACCEPTED, Json(job.view.clone())).into_response()
+        }
+        None => job_not_found(&id),
+    }
+}
+
+fn job_not_found(id: &str) -> Response {
+    (StatusCode::NOT_FOUND, Json(serde_json::json!({ "error": format!("Unknown job '{}'", id) }))).into_response()
+}
+
+fn pipeline_not_found(name: &str) -> Response {
+    (StatusCode::NOT_FOUND, Json(serde_json::json!({ "error": format!("Unknown pipeline '{}'", name) }))).into_response()
+}
+
+/// Map an extraction error to a status code and JSON body
+fn error_response(error: LangExtractError) -> Response {
+    let status = match &error {
+        LangExtractError::InvalidInput(_) | LangExtractError::SerializationError(_) => StatusCode::BAD_REQUEST,
+        LangExtractError::BudgetExceeded(_) => StatusCode::UNPROCESSABLE_ENTITY,
+        LangExtractError::Cancelled(_) => StatusCode::REQUEST_TIMEOUT,
+        LangExtractError::InferenceError { .. } | LangExtractError::NetworkError(_) => StatusCode::BAD_GATEWAY,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+    (status, Json(serde_json::json!({ "error": error.to_string() }))).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{ollama, person_examples as examples};
+    use crate::verification::VerificationConfig;
+    use std::time::Duration;
+
+    /// Serve `config` on a free port, returning its base URL
+    async fn start(config: ServerConfig) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, router(config)).await.unwrap() });
+        format!("http://{}", addr)
+    }
+
+    #[tokio::test]
+    async fn test_extract_sync_and_async() {
+        let model = ollama().await;
+        let base = start(ServerConfig::new(ProviderConfig::ollama("mistral", Some(model.uri())))).await;
+        let client = reqwest::Client::new();
+
+        let health: serde_json::Value = client.get(format!("{}/health", base)).send().await.unwrap().json().await.unwrap();
+        assert_eq!(health["status"], "ok");
+        assert_eq!(health["model"], "mistral");
+
+        let body = serde_json::json!({ "text": "Ada Lovelace wrote notes.", "examples": examples() });
+        let response = client.post(format!("{}/extract", base)).json(&body).send().await.unwrap();
+        assert_eq!(response.status(), 200);
+        let document: AnnotatedDocument = response.json().await.unwrap();
+        assert_eq!(document.extractions.unwrap()[0].extraction_text, "Ada Lovelace");
+
+        let mut body = body;
+        body["async"] = serde_json::json!(true);
+        let response = client.post(format!("{}/extract", base)).json(&body).send().await.unwrap();
+        assert_eq!(response.status(), 202);
+        let location = response.headers()[header::LOCATION.as_str()].to_str().unwrap().to_string();
+
+        let mut job: JobView;
+        loop {
+            job = client.get(format!("{}{}", base, location)).send().await.unwrap().json().await.unwrap();
+            if job.status != JobStatus::Running {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        assert_eq!(job.status, JobStatus::Succeeded);
+        assert_eq!(job.result.unwrap()["extractions"][0]["extraction_text"], "Ada Lovelace");
+    }
+
+    #[tokio::test]
+    async fn test_requests_cannot_set_credentials() {
+        let model = ollama().await;
+        let base = start(ServerConfig::new(ProviderConfig::ollama("mistral", Some(model.uri())))).await;
+        let client = reqwest::Client::new();
+
+        let body = serde_json::json!({
+            "text": "Ada Lovelace wrote notes.",
+            "examples": examples(),
+            "config": { "api_key": "sk-from-request", "model_url": "http://attacker.example" },
+        });
+        let response = client.post(format!("{}/extract", base)).json(&body).send().await.unwrap();
+        assert!(response.status().is_client_error());
+        assert!(response.text().await.unwrap().contains("unknown field"));
+        assert!(model.received_requests().await.unwrap().is_empty());
+
+        let response = client
+            .post(format!("{}/pipelines/missing/run", base))
+            .json(&serde_json::json!({ "text": "x" }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 404);
+    }
+
+    #[test]
+    fn test_finished_jobs_are_evicted_when_they_finish() {
+        let mut table = JobTable::default();
+        let ids: Vec<String> = (0..=MAX_FINISHED_JOBS + 1).map(|i| format!("job-{}", i)).collect();
+        for id in &ids {
+            let view = JobView {
+                job_id: id.clone(),
+                kind: "extract".to_string(),
+                status: JobStatus::Running,
+                result: None,
+                error: None,
+            };
+            table.insert(Job { view, cancel: CancellationToken::new() });
+        }
+
+        // Finish all but the last; only the oldest finished job goes
+        for id in &ids[..=MAX_FINISHED_JOBS] {
+            table.finish(id, Ok(serde_json::json!({})), false);
+        }
+        assert_eq!(table.jobs.len(), MAX_FINISHED_JOBS + 1);
+        assert!(!table.jobs.contains_key(&ids[0]));
+        assert_eq!(table.jobs[&ids[MAX_FINISHED_JOBS + 1]].view.status, JobStatus::Running);
+    }
+
+    #[test]
+    fn test_request_limits_only_tighten() {
+        let mut config = ExtractConfig { timeout_ms: Some(60_000), ..Default::default() };
+        RequestConfig { timeout_ms: Some(600_000), request_timeout_ms: Some(5_000), ..Default::default() }
+            .apply(&mut config);
+        assert_eq!(config.timeout_ms, Some(60_000));
+        assert_eq!(config.request_timeout_ms, Some(5_000));
+
+        RequestConfig { timeout_ms: Some(1_000), ..Default::default() }.apply(&mut config);
+        assert_eq!(config.timeout_ms, Some(1_000));
+    }
+
+    #[test]
+    fn test_request_cannot_raise_load() {
+        let defaults = ExtractConfig {
+            max_workers: 4,
+            max_char_buffer: 1_000,
+            batch_length: 8,
+            enable_multipass: true,
+            multipass_max_passes: 2,
+            ..Default::default()
+        };
+        let greedy = RequestConfig {
+            max_workers: Some(500),
+            max_char_buffer: Some(100_000),
+            batch_length: Some(1_000),
+            passes: Some(10),
+            verify: Some(true),
+            ..Default::default()
+        };
+
+        let mut config = defaults.clone();
+        greedy.apply(&mut config);
+        assert_eq!((config.max_workers, config.max_char_buffer, config.batch_length), (4, 1_000, 8));
+        assert_eq!(config.multipass_max_passes, 2);
+        assert!(config.verification.is_none());
+
+        // Lower values and opting out of verification still apply
+        let mut config = defaults.with_verification(VerificationConfig::default());
+        RequestConfig { max_workers: Some(2), passes: Some(1), verify: Some(false), ..Default::default() }
+            .apply(&mut config);
+        assert_eq!(config.max_workers, 2);
+        assert!(!config.enable_multipass);
+        assert!(config.verification.is_none());
+
+        let mut config = ExtractConfig::default();
+        RequestConfig { passes: Some(3), ..Default::default() }.apply(&mut config);
+        assert!(!config.enable_multipass);
+    }
+}
@@ -0,0 +1,50 @@
+//! Fixtures shared by unit tests across the crate.
+
+use crate::{
+    data::{ExampleData, Extraction},
+    providers::ProviderConfig,
+    ExtractConfig,
+};
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+/// The model reply [`ollama`] sends back
+pub(crate) const PERSON_REPLY: &str = r#"{"person": "Ada Lovelace"}"#;
+
+/// One example teaching the model to extract people
+pub(crate) fn person_examples() -> Vec<ExampleData> {
+    vec![ExampleData::new(
+        "Alan Turing wrote the paper.".to_string(),
+        vec![Extraction::new("person".to_string(), "Alan Turing".to_string())],
+    )]
+}
+
+/// `config` with `provider` as its provider configuration
+pub(crate) fn with_provider(mut config: ExtractConfig, provider: &ProviderConfig) -> ExtractConfig {
+    config.language_model_params.insert(
+        "provider_config".to_string(),
+        serde_json::to_value(provider).unwrap(),
+    );
+    config
+}
+
+/// Default settings that call `server` as an Ollama `mistral` endpoint
+pub(crate) fn ollama_config(server: &MockServer) -> ExtractConfig {
+    with_provider(ExtractConfig::default(), &ProviderConfig::ollama("mistral", Some(server.uri())))
+}
+
+/// A mock Ollama server answering every generate call with `body`
+pub(crate) async fn ollama_with(body: serde_json::Value) -> MockServer {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/api/generate"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(body))
+        .mount(&server)
+        .await;
+    server
+}
+
+/// A mock Ollama server that finds Ada Lovelace in every prompt
+pub(crate) async fn ollama() -> MockServer {
+    ollama_with(serde_json::json!({ "response": PERSON_REPLY })).await
+}
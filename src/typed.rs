@@ -0,0 +1,470 @@
+//! Typed extraction into Rust structs and enums.
+//!
+//! A type implementing [`Extractable`], usually through
+//! `#[derive(Extractable)]`, describes the extraction classes it is built from
+//! and the type of each attribute. [`extract_typed`] uses that description for
+//! the prompt's class list and the structured output schema, then converts
+//! every extraction into the type while keeping where it was found.
+
+use crate::{
+    data::{AlignmentStatus, CharInterval, ExampleData, Extraction},
+    exceptions::{LangExtractError, LangExtractResult},
+    schema::{AttributeSpec, AttributeType, ExtractionClass},
+    ExtractConfig,
+};
+use serde::{Deserialize, Serialize};
+
+/// A type that can be built from extractions
+pub trait Extractable: Sized {
+    /// The extraction classes this type is built from
+    fn extraction_classes() -> Vec<ExtractionClass>;
+
+    /// Convert an extraction of one of [`extraction_classes`](Self::extraction_classes)
+    fn from_extraction(extraction: &Extraction) -> LangExtractResult<Self>;
+
+    /// Prompt section listing the classes and their attributes
+    fn class_list() -> String {
+        class_list(&Self::extraction_classes())
+    }
+}
+
+/// A value usable as an extraction attribute or text field
+///
+/// Values are coerced from strings where it is unambiguous, since models
+/// often quote numbers and booleans.
+pub trait AttributeValue: Sized {
+    /// The JSON type the model should produce
+    fn attribute_type() -> AttributeType;
+
+    /// Whether a value must always be present
+    fn is_required() -> bool {
+        true
+    }
+
+    /// Convert the attribute value, `None` when the key is missing
+    fn from_attribute(value: Option<&serde_json::Value>) -> Result<Self, String>;
+}
+
+impl AttributeValue for String {
+    fn attribute_type() -> AttributeType {
+        AttributeType::String
+    }
+
+    fn from_attribute(value: Option<&serde_json::Value>) -> Result<Self, String> {
+        match value {
+            Some(serde_json::Value::String(s)) => Ok(s.clone()),
+            Some(v @ (serde_json::Value::Number(_) | serde_json::Value::Bool(_))) => Ok(v.to_string()),
+            Some(serde_json::Value::Null) | None => Err("missing value".to_string()),
+            Some(other) => Err(format!("expected a string, got {}", other)),
+        }
+    }
+}
+
+macro_rules! integer_attribute {
+    ($($ty:ty),*) => {$(
+        impl AttributeValue for $ty {
+            fn attribute_type() -> AttributeType {
+                AttributeType::Integer
+            }
+
+            fn from_attribute(value: Option<&serde_json::Value>) -> Result<Self, String> {
+                let number = match value {
+                    Some(serde_json::Value::Number(n)) => n
+                        .as_i64()
+                        .map(i128::from)
+                        .or_else(|| n.as_u64().map(i128::from))
+                        .or_else(|| n.as_f64().filter(|f| f.fract() == 0.0).map(|f| f as i128)),
+                    Some(serde_json::Value::String(s)) => s.trim().replace(',', "").parse::<i128>().ok(),
+                    Some(serde_json::Value::Null) | None => return Err("missing value".to_string()),
+                    Some(_) => None,
+                };
+                number
+                    .and_then(|n| <$ty>::try_from(n).ok())
+                    .ok_or_else(|| format!("expected {}, got {}", stringify!($ty), value.unwrap()))
+            }
+        }
+    )*};
+}
+
+integer_attribute!(i8, i16, i32, i64, isize, u8, u16, u32, u64, usize);
+
+macro_rules! float_attribute {
+    ($($ty:ty),*) => {$(
+        impl AttributeValue for $ty {
+            fn attribute_type() -> AttributeType {
+                AttributeType::Number
+            }
+
+            fn from_attribute(value: Option<&serde_json::Value>) -> Result<Self, String> {
+                let number = match value {
+                    Some(serde_json::Value::Number(n)) => n.as_f64(),
+                    Some(serde_json::Value::String(s)) => s.trim().replace(',', "").parse::<f64>().ok(),
+                    Some(serde_json::Value::Null) | None => return Err("missing value".to_string()),
+                    Some(_) => None,
+                };
+                number
+                    .map(|n| n as $ty)
+                    .ok_or_else(|| format!("expected a number, got {}", value.unwrap()))
+            }
+        }
+    )*};
+}
+
+float_attribute!(f32, f64);
+
+impl AttributeValue for bool {
+    fn attribute_type() -> AttributeType {
+        AttributeType::Boolean
+    }
+
+    fn from_attribute(value: Option<&serde_json::Value>) -> Result<Self, String> {
+        match value {
+            Some(serde_json::Value::Bool(b)) => Ok(*b),
+            Some(serde_json::Value::String(s)) => match s.trim().to_lowercase().as_str() {
+                "true" | "yes" => Ok(true),
+                "false" | "no" => Ok(false),
+                _ => Err(format!("expected a boolean, got \"{}\"", s)),
+            },
+            Some(serde_json::Value::Null) | None => Err("missing value".to_string()),
+            Some(other) => Err(format!("expected a boolean, got {}", other)),
+        }
+    }
+}
+
+impl AttributeValue for Vec<String> {
+    fn attribute_type() -> AttributeType {
+        AttributeType::StringList
+    }
+
+    fn from_attribute(value: Option<&serde_json::Value>) -> Result<Self, String> {
+        match value {
+            Some(serde_json::Value::Array(items)) => items
+                .iter()
+                .map(|item| String::from_attribute(Some(item)))
+                .collect(),
+            Some(serde_json::Value::String(s)) => Ok(vec![s.clone()]),
+            Some(serde_json::Value::Null) | None => Err("missing value".to_string()),
+            Some(other) => Err(format!("expected a list of strings, got {}", other)),
+        }
+    }
+}
+
+impl<T: AttributeValue> AttributeValue for Option<T> {
+    fn attribute_type() -> AttributeType {
+        T::attribute_type()
+    }
+
+    fn is_required() -> bool {
+        false
+    }
+
+    fn from_attribute(value: Option<&serde_json::Value>) -> Result<Self, String> {
+        match value {
+            Some(serde_json::Value::Null) | None => Ok(None),
+            Some(value) => T::from_attribute(Some(value)).map(Some),
+        }
+    }
+}
+
+/// A typed value together with the source span it was extracted from
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Grounded<T> {
+    /// The converted value
+    pub value: T,
+    /// Class of the underlying extraction
+    pub extraction_class: String,
+    /// Extracted text
+    pub text: String,
+    /// Character position in the source text
+    pub char_interval: Option<CharInterval>,
+    /// How well the text aligns with the source
+    pub alignment_status: Option<AlignmentStatus>,
+}
+
+/// Extract values of type `T` from text
+///
+/// Works like [`extract`](crate::extract), with the classes and attribute
+/// types of `T` appended to the prompt description and used as the output
+/// schema. Extractions of other classes are dropped; extractions that cannot
+/// be converted are logged and skipped.
+pub async fn extract_typed<T: Extractable>(
+    text_or_documents: &str,
+    prompt_description: Option<&str>,
+    examples: &[ExampleData],
+    mut config: ExtractConfig,
+) -> LangExtractResult<Vec<Grounded<T>>> {
+    let classes = T::extraction_classes();
+    let is_known = |class: &str| classes.iter().any(|c| c.name == class);
+
+    if let Some(extraction) = examples
+        .iter()
+        .flat_map(|example| &example.extractions)
+        .find(|extraction| !is_known(&extraction.extraction_class))
+    {
+        return Err(LangExtractError::invalid_input(format!(
+            "Example extraction class '{}' is not one of {}",
+            extraction.extraction_class,
+            class_names(&classes)
+        )));
+    }
+
+    let description = match prompt_description {
+        Some(description) => format!("{}\n\n{}", description, T::class_list()),
+        None => T::class_list(),
+    };
+    config.extraction_classes = Some(classes.clone());
+
+    let document = crate::extract(text_or_documents, Some(&description), examples, config).await?;
+
+    let mut grounded = Vec::new();
+    for extraction in document.extractions.unwrap_or_default() {
+        if !is_known(&extraction.extraction_class) {
+            continue;
+        }
+        match T::from_extraction(&extraction) {
+            Ok(value) => grounded.push(Grounded {
+                value,
+                extraction_class: extraction.extraction_class,
+                text: extraction.extraction_text,
+                char_interval: extraction.char_interval,
+                alignment_status: extraction.alignment_status,
+            }),
+            Err(e) => log::warn!("Skipping extraction '{}': {}", extraction.extraction_text, e),
+        }
+    }
+    Ok(grounded)
+}
+
+/// Format classes as a prompt section, one line per class and attribute
+pub fn class_list(classes: &[ExtractionClass]) -> String {
+    let mut out = String::from("Extraction classes:");
+    for class in classes {
+        out.push_str(&format!("\n- {}", class.name));
+        if let Some(description) = &class.description {
+            out.push_str(&format!(": {}", description));
+        }
+        for attribute in &class.attributes {
+            out.push_str(&format!(
+                "\n  - {} ({}{})",
+                attribute.name,
+                attribute.attribute_type.name(),
+                if attribute.required { "" } else { ", optional" }
+            ));
+            if let Some(description) = &attribute.description {
+                out.push_str(&format!(": {}", description));
+            }
+        }
+    }
+    out
+}
+
+fn class_names(classes: &[ExtractionClass]) -> String {
+    classes.iter().map(|c| format!("'{}'", c.name)).collect::<Vec<_>>().join(", ")
+}
+
+/// Attribute description for a field of type `T`, used by the derive macro
+#[doc(hidden)]
+pub fn attribute_spec<T: AttributeValue>(name: &str, description: Option<String>) -> AttributeSpec {
+    AttributeSpec {
+        name: name.to_string(),
+        attribute_type: T::attribute_type(),
+        required: T::is_required(),
+        description,
+    }
+}
+
+/// Convert the attribute `name` of an extraction, used by the derive macro
+#[doc(hidden)]
+pub fn attribute<T: AttributeValue>(extraction: &Extraction, name: &str) -> LangExtractResult<T> {
+    let value = extraction.attributes.as_ref().and_then(|a| a.get(name));
+    T::from_attribute(value).map_err(|e| {
+        LangExtractError::parsing(format!(
+            "Attribute '{}' of '{}' extraction: {}",
+            name, extraction.extraction_class, e
+        ))
+    })
+}
+
+/// Convert the text of an extraction, used by the derive macro
+#[doc(hidden)]
+pub fn text<T: AttributeValue>(extraction: &Extraction) -> LangExtractResult<T> {
+    let value = serde_json::Value::String(extraction.extraction_text.clone());
+    T::from_attribute(Some(&value)).map_err(|e| {
+        LangExtractError::parsing(format!(
+            "Text of '{}' extraction: {}",
+            extraction.extraction_class, e
+        ))
+    })
+}
+
+/// Error for an extraction whose class the type does not know, used by the
+/// derive macro
+#[doc(hidden)]
+pub fn unknown_class(extraction: &Extraction, expected: &[&str]) -> LangExtractError {
+    LangExtractError::parsing(format!(
+        "Unexpected extraction class '{}', expected one of: {}",
+        extraction.extraction_class,
+        expected.join(", ")
+    ))
+}
+
+#[cfg(all(test, feature = "derive"))]
+mod tests {
+    use super::*;
+    use crate::Extractable;
+    use std::collections::HashMap;
+
+    /// A drug mentioned in the text
+    #[derive(Debug, PartialEq, Extractable)]
+    struct Medication {
+        #[extract(text)]
+        name: String,
+        /// Dose in milligrams
+        dose_mg: Option<u32>,
+        #[extract(rename = "route")]
+        route_of_administration: String,
+        #[extract(skip)]
+        reviewed: bool,
+    }
+
+    #[derive(Debug, PartialEq, Extractable)]
+    enum Entity {
+        /// A person's name
+        Person,
+        #[extract(class = "place")]
+        Location {
+            #[extract(text)]
+            name: String,
+            is_city: bool,
+        },
+    }
+
+    fn extraction(class: &str, text: &str, attributes: serde_json::Value) -> Extraction {
+        let mut extraction = Extraction::new(class.to_string(), text.to_string());
+        extraction.attributes = serde_json::from_value::<HashMap<_, _>>(attributes).ok();
+        extraction
+    }
+
+    #[test]
+    fn test_derive_struct_classes() {
+        let classes = Medication::extraction_classes();
+        assert_eq!(classes.len(), 1);
+        assert_eq!(classes[0].name, "medication");
+        assert_eq!(classes[0].description.as_deref(), Some("A drug mentioned in the text"));
+
+        let attributes = &classes[0].attributes;
+        assert_eq!(attributes.len(), 2);
+        assert_eq!(attributes[0].name, "dose_mg");
+        assert_eq!(attributes[0].attribute_type, AttributeType::Integer);
+        assert!(!attributes[0].required);
+        assert_eq!(attributes[0].description.as_deref(), Some("Dose in milligrams"));
+        assert_eq!(attributes[1].name, "route");
+        assert!(attributes[1].required);
+
+        assert_eq!(
+            Medication::class_list(),
+            "Extraction classes:\n- medication: A drug mentioned in the text\n  \
+             - dose_mg (integer, optional): Dose in milligrams\n  - route (string)"
+        );
+    }
+
+    #[test]
+    fn test_derive_struct_from_extraction() {
+        let value = Medication::from_extraction(&extraction(
+            "medication",
+            "aspirin",
+            serde_json::json!({ "dose_mg": "100", "route": "oral" }),
+        ))
+        .unwrap();
+        assert_eq!(
+            value,
+            Medication {
+                name: "aspirin".to_string(),
+                dose_mg: Some(100),
+                route_of_administration: "oral".to_string(),
+                reviewed: false,
+            }
+        );
+
+        let missing = Medication::from_extraction(&extraction("medication", "aspirin", serde_json::json!({})));
+        assert!(missing.unwrap_err().to_string().contains("Attribute 'route'"));
+        let wrong_class = Medication::from_extraction(&extraction("person", "Ada", serde_json::json!({})));
+        assert!(wrong_class.is_err());
+    }
+
+    #[test]
+    fn test_derive_enum() {
+        let classes = Entity::extraction_classes();
+        let names: Vec<&str> = classes.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, vec!["person", "place"]);
+        assert_eq!(classes[1].attributes[0].attribute_type, AttributeType::Boolean);
+
+        assert_eq!(
+            Entity::from_extraction(&extraction("person", "Ada", serde_json::json!(null))).unwrap(),
+            Entity::Person
+        );
+        assert_eq!(
+            Entity::from_extraction(&extraction("place", "London", serde_json::json!({ "is_city": "yes" }))).unwrap(),
+            Entity::Location {
+                name: "London".to_string(),
+                is_city: true,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_extract_typed_keeps_grounding() {
+        use wiremock::matchers::{body_partial_json, method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        let response = serde_json::json!({
+            "extractions": [
+                { "medication": "ibuprofen", "medication_attributes": { "dose_mg": 200, "route": "oral" } },
+                { "medication": "warfarin", "medication_attributes": { "dose_mg": "a lot", "route": "oral" } }
+            ]
+        });
+        let dose_schema = serde_json::json!({ "type": ["integer", "null"] });
+        Mock::given(method("POST"))
+            .and(path("/api/generate"))
+            .and(body_partial_json(serde_json::json!({
+                "format": { "properties": { "extractions": { "items": { "properties": {
+                    "medication_attributes": { "properties": { "dose_mg": dose_schema } }
+                } } } } }
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "response": response.to_string()
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let config = crate::test_support::ollama_config(&server);
+        let examples = vec![ExampleData::new(
+            "Take aspirin 100mg by mouth.".to_string(),
+            vec![extraction("medication", "aspirin", serde_json::json!({ "dose_mg": 100, "route": "oral" }))],
+        )];
+
+        let text = "Patient takes ibuprofen 200mg and warfarin.";
+        let found = extract_typed::<Medication>(text, None, &examples, config).await.unwrap();
+
+        // The unconvertible dose is skipped
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].value.name, "ibuprofen");
+        assert_eq!(found[0].value.dose_mg, Some(200));
+        let interval = found[0].char_interval.as_ref().unwrap();
+        assert_eq!(&text[interval.start_pos.unwrap()..interval.end_pos.unwrap()], "ibuprofen");
+    }
+
+    #[tokio::test]
+    async fn test_extract_typed_rejects_unknown_example_class() {
+        let examples = vec![ExampleData::new(
+            "Ada".to_string(),
+            vec![Extraction::new("person".to_string(), "Ada".to_string())],
+        )];
+        let err = extract_typed::<Medication>("text", None, &examples, ExtractConfig::default())
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("'person'"));
+    }
+}
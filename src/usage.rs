@@ -0,0 +1,436 @@
+//! Token usage and cost accounting.
+//!
+//! Providers report prompt and completion token counts on each
+//! [`ScoredOutput`](crate::inference::ScoredOutput). The annotator and verifier
+//! record them in a [`UsageTracker`], which enforces an optional
+//! [`UsageBudget`], and results carry a [`UsageSummary`] broken down by model.
+//! Costs are filled in from an optional [`PriceTable`].
+
+use crate::exceptions::{LangExtractError, LangExtractResult};
+use crate::inference::ScoredOutput;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use std::sync::Mutex;
+
+/// Token counts for one model call
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TokenUsage {
+    /// Tokens in the prompt
+    pub prompt_tokens: u64,
+    /// Tokens in the completion
+    pub completion_tokens: u64,
+}
+
+impl TokenUsage {
+    /// Create a usage record
+    pub fn new(prompt_tokens: u64, completion_tokens: u64) -> Self {
+        Self {
+            prompt_tokens,
+            completion_tokens,
+        }
+    }
+
+    /// Rough count for providers that report nothing: about four characters
+    /// per token
+    pub fn estimate(prompt: &str, completion: &str) -> Self {
+        let tokens = |text: &str| (text.chars().count() as u64).div_ceil(4);
+        Self::new(tokens(prompt), tokens(completion))
+    }
+
+    /// Prompt plus completion tokens
+    pub fn total_tokens(&self) -> u64 {
+        self.prompt_tokens + self.completion_tokens
+    }
+}
+
+/// Usage and cost for a single model
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ModelUsage {
+    /// Tokens in all prompts
+    pub prompt_tokens: u64,
+    /// Tokens in all completions
+    pub completion_tokens: u64,
+    /// Number of model calls
+    pub calls: usize,
+    /// Calls whose token counts were estimated because the provider reported none
+    #[serde(default)]
+    pub estimated_calls: usize,
+    /// Cost in USD, when the model is in the price table
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cost_usd: Option<f64>,
+}
+
+/// Token usage and cost across a run, broken down by model
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct UsageSummary {
+    /// Tokens in all prompts
+    pub prompt_tokens: u64,
+    /// Tokens in all completions
+    pub completion_tokens: u64,
+    /// Number of model calls
+    pub calls: usize,
+    /// Calls whose token counts were estimated
+    #[serde(default)]
+    pub estimated_calls: usize,
+    /// Total cost in USD, when every model used is in the price table
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cost_usd: Option<f64>,
+    /// Usage per model
+    #[serde(default)]
+    pub by_model: BTreeMap<String, ModelUsage>,
+}
+
+impl UsageSummary {
+    /// Add one model call
+    pub fn record(&mut self, model: &str, usage: TokenUsage, estimated: bool) {
+        let entry = self.by_model.entry(model.to_string()).or_default();
+        entry.prompt_tokens += usage.prompt_tokens;
+        entry.completion_tokens += usage.completion_tokens;
+        entry.calls += 1;
+        entry.estimated_calls += estimated as usize;
+
+        self.prompt_tokens += usage.prompt_tokens;
+        self.completion_tokens += usage.completion_tokens;
+        self.calls += 1;
+        self.estimated_calls += estimated as usize;
+    }
+
+    /// Add the calls from another summary
+    ///
+    /// Costs are cleared; call [`priced`](Self::priced) to recompute them.
+    pub fn merge(&mut self, other: &UsageSummary) {
+        for (model, usage) in &other.by_model {
+            let entry = self.by_model.entry(model.clone()).or_default();
+            entry.prompt_tokens += usage.prompt_tokens;
+            entry.completion_tokens += usage.completion_tokens;
+            entry.calls += usage.calls;
+            entry.estimated_calls += usage.estimated_calls;
+            entry.cost_usd = None;
+        }
+        self.prompt_tokens += other.prompt_tokens;
+        self.completion_tokens += other.completion_tokens;
+        self.calls += other.calls;
+        self.estimated_calls += other.estimated_calls;
+        self.cost_usd = None;
+    }
+
+    /// Prompt plus completion tokens
+    pub fn total_tokens(&self) -> u64 {
+        self.prompt_tokens + self.completion_tokens
+    }
+
+    /// Fill in costs from a price table
+    ///
+    /// The total cost is left unset if any model used is missing from the table.
+    pub fn priced(mut self, prices: &PriceTable) -> Self {
+        let mut total = Some(0.0);
+        for (model, usage) in self.by_model.iter_mut() {
+            usage.cost_usd = prices.cost(model, &TokenUsage::new(usage.prompt_tokens, usage.completion_tokens));
+            total = total.zip(usage.cost_usd).map(|(a, b)| a + b);
+        }
+        self.cost_usd = total.filter(|_| !self.by_model.is_empty());
+        self
+    }
+
+    /// One-line description, e.g. `1200 prompt + 300 completion tokens in 4 calls, $0.0042`
+    pub fn describe(&self) -> String {
+        let mut out = format!(
+            "{} prompt + {} completion tokens in {} call{}",
+            self.prompt_tokens,
+            self.completion_tokens,
+            self.calls,
+            if self.calls == 1 { "" } else { "s" }
+        );
+        if self.estimated_calls > 0 {
+            out.push_str(&format!(" ({} estimated)", self.estimated_calls));
+        }
+        if let Some(cost) = self.cost_usd {
+            out.push_str(&format!(", ${:.4}", cost));
+        }
+        out
+    }
+}
+
+/// Price of a model in USD per million tokens
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ModelPrice {
+    /// USD per million prompt tokens
+    pub input_per_million: f64,
+    /// USD per million completion tokens
+    pub output_per_million: f64,
+}
+
+/// Per-model prices used to turn token counts into cost
+///
+/// Serialized as a map from model name to [`ModelPrice`]:
+///
+/// ```yaml
+/// gpt-4o-mini: { input_per_million: 0.15, output_per_million: 0.60 }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct PriceTable {
+    models: HashMap<String, ModelPrice>,
+}
+
+impl PriceTable {
+    /// Create an empty price table
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the price of a model
+    pub fn with_price(mut self, model: &str, input_per_million: f64, output_per_million: f64) -> Self {
+        self.models.insert(
+            model.to_string(),
+            ModelPrice {
+                input_per_million,
+                output_per_million,
+            },
+        );
+        self
+    }
+
+    /// Load a price table from a JSON or YAML file
+    pub fn load(path: &Path) -> LangExtractResult<Self> {
+        let content = std::fs::read_to_string(path).map_err(|e| {
+            LangExtractError::configuration(format!("Failed to read price table {}: {}", path.display(), e))
+        })?;
+        serde_yaml::from_str(&content).map_err(|e| {
+            LangExtractError::configuration(format!("Failed to parse price table {}: {}", path.display(), e))
+        })
+    }
+
+    /// Price of a model
+    ///
+    /// Labels like `ollama/llama3` from fallback chains fall back to the part
+    /// after the last `/`.
+    pub fn get(&self, model: &str) -> Option<&ModelPrice> {
+        self.models.get(model).or_else(|| {
+            let (_, name) = model.rsplit_once('/')?;
+            self.models.get(name)
+        })
+    }
+
+    /// Cost in USD of `usage` on `model`, if the model has a price
+    pub fn cost(&self, model: &str, usage: &TokenUsage) -> Option<f64> {
+        self.get(model).map(|price| {
+            (usage.prompt_tokens as f64 * price.input_per_million
+                + usage.completion_tokens as f64 * price.output_per_million)
+                / 1_000_000.0
+        })
+    }
+
+    /// Whether the table has no prices
+    pub fn is_empty(&self) -> bool {
+        self.models.is_empty()
+    }
+}
+
+/// Limits that abort a run once exceeded
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "mcp", derive(schemars::JsonSchema))]
+pub struct UsageBudget {
+    /// Maximum prompt plus completion tokens
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<u64>,
+    /// Maximum cost in USD; every model used needs a price
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_cost_usd: Option<f64>,
+}
+
+impl UsageBudget {
+    /// Whether any limit is set
+    pub fn is_limited(&self) -> bool {
+        self.max_tokens.is_some() || self.max_cost_usd.is_some()
+    }
+}
+
+/// Running usage total shared by every model call in a run
+///
+/// Share one tracker (through [`ExtractConfig::usage_tracker`](crate::ExtractConfig))
+/// to hold several `extract` calls, such as pipeline steps, to one budget.
+#[derive(Debug, Default)]
+pub struct UsageTracker {
+    prices: PriceTable,
+    budget: UsageBudget,
+    summary: Mutex<UsageSummary>,
+}
+
+impl UsageTracker {
+    /// Create a tracker with the given prices and budget
+    pub fn new(prices: PriceTable, budget: UsageBudget) -> Self {
+        Self {
+            prices,
+            budget,
+            summary: Mutex::new(UsageSummary::default()),
+        }
+    }
+
+    /// The price table used for costs
+    pub fn prices(&self) -> &PriceTable {
+        &self.prices
+    }
+
+    /// The budget this tracker enforces
+    pub fn budget(&self) -> UsageBudget {
+        self.budget
+    }
+
+    /// Record one model call, failing if the budget is now exceeded
+    pub fn record(&self, model: &str, usage: TokenUsage, estimated: bool) -> LangExtractResult<()> {
+        if self.budget.max_cost_usd.is_some() && self.prices.get(model).is_none() {
+            return Err(LangExtractError::configuration(format!(
+                "No price for model '{}'; a cost budget needs a price table entry for every model",
+                model
+            )));
+        }
+
+        self.summary
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .record(model, usage, estimated);
+        self.check()
+    }
+
+    /// Record the call that answered `prompt` with `output`, estimating its
+    /// usage when the provider reports none, and return it as a summary
+    ///
+    /// The output's `provider` names the model when set (a fallback answered),
+    /// otherwise `model_id` does.
+    pub fn record_call(&self, model_id: &str, prompt: &str, output: &ScoredOutput) -> LangExtractResult<UsageSummary> {
+        let model = output.provider.as_deref().unwrap_or(model_id);
+        let (tokens, estimated) = match output.usage {
+            Some(tokens) => (tokens, false),
+            None => (TokenUsage::estimate(prompt, output.text()), true),
+        };
+        let mut usage = UsageSummary::default();
+        usage.record(model, tokens, estimated);
+        self.record(model, tokens, estimated)?;
+        Ok(usage)
+    }
+
+    /// Fail if the budget has been exceeded
+    pub fn check(&self) -> LangExtractResult<()> {
+        let summary = self.summary();
+        if let Some(max_tokens) = self.budget.max_tokens {
+            if summary.total_tokens() > max_tokens {
+                return Err(LangExtractError::budget_exceeded(format!(
+                    "{} tokens used, limit is {}",
+                    summary.total_tokens(),
+                    max_tokens
+                )));
+            }
+        }
+        if let (Some(max_cost), Some(cost)) = (self.budget.max_cost_usd, summary.cost_usd) {
+            if cost > max_cost {
+                return Err(LangExtractError::budget_exceeded(format!(
+                    "${:.4} spent, limit is ${:.4}",
+                    cost, max_cost
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Usage so far, with costs
+    pub fn summary(&self) -> UsageSummary {
+        self.summary
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+            .priced(&self.prices)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_summary_by_model_and_cost() {
+        let prices = PriceTable::new().with_price("gpt-4o-mini", 0.15, 0.60);
+        let mut summary = UsageSummary::default();
+        summary.record("gpt-4o-mini", TokenUsage::new(1_000_000, 500_000), false);
+        summary.record("fallback/gpt-4o-mini", TokenUsage::new(0, 1_000_000), false);
+
+        let priced = summary.clone().priced(&prices);
+        assert_eq!(priced.total_tokens(), 2_500_000);
+        assert_eq!(priced.by_model["gpt-4o-mini"].cost_usd, Some(0.45));
+        assert_eq!(priced.by_model["fallback/gpt-4o-mini"].cost_usd, Some(0.60));
+        assert!((priced.cost_usd.unwrap() - 1.05).abs() < 1e-9);
+
+        summary.record("llama3", TokenUsage::new(10, 10), true);
+        let partial = summary.priced(&prices);
+        assert_eq!(partial.cost_usd, None);
+        assert_eq!(partial.estimated_calls, 1);
+    }
+
+    #[test]
+    fn test_merge_adds_calls() {
+        let mut a = UsageSummary::default();
+        a.record("m", TokenUsage::new(10, 5), false);
+        let mut b = UsageSummary::default();
+        b.record("m", TokenUsage::new(1, 2), true);
+        b.record("n", TokenUsage::new(3, 4), false);
+
+        a.merge(&b);
+        assert_eq!(a.calls, 3);
+        assert_eq!(a.prompt_tokens, 14);
+        assert_eq!(a.by_model["m"].completion_tokens, 7);
+        assert_eq!(a.by_model["m"].estimated_calls, 1);
+        assert_eq!(a.describe(), "14 prompt + 11 completion tokens in 3 calls (1 estimated)");
+    }
+
+    #[test]
+    fn test_tracker_enforces_budget() {
+        let tracker = UsageTracker::new(
+            PriceTable::default(),
+            UsageBudget {
+                max_tokens: Some(100),
+                max_cost_usd: None,
+            },
+        );
+        tracker.record("m", TokenUsage::new(60, 40), false).unwrap();
+        let err = tracker.record("m", TokenUsage::new(1, 0), false).unwrap_err();
+        assert!(matches!(err, LangExtractError::BudgetExceeded(_)));
+        assert!(tracker.check().is_err());
+
+        let priced = UsageTracker::new(
+            PriceTable::new().with_price("m", 1_000.0, 1_000.0),
+            UsageBudget {
+                max_tokens: None,
+                max_cost_usd: Some(0.5),
+            },
+        );
+        priced.record("m", TokenUsage::new(400, 0), false).unwrap();
+        assert!(priced.record("m", TokenUsage::new(200, 0), false).is_err());
+        assert!(priced.record("unpriced", TokenUsage::default(), false).unwrap_err().is_configuration_error());
+    }
+
+    #[test]
+    fn test_record_call_estimates_missing_usage() {
+        let tracker = UsageTracker::new(PriceTable::default(), UsageBudget::default());
+        let reported = ScoredOutput::from_text("{}".to_string())
+            .with_usage(TokenUsage::new(12, 3))
+            .with_provider("ollama/llama3");
+        let summary = tracker.record_call("mistral", "prompt", &reported).unwrap();
+        assert_eq!(summary.by_model["ollama/llama3"].prompt_tokens, 12);
+        assert_eq!(summary.estimated_calls, 0);
+
+        let unreported = ScoredOutput::from_text("{\"person\": \"Ada\"}".to_string());
+        let summary = tracker.record_call("mistral", "a prompt of some length", &unreported).unwrap();
+        assert_eq!(summary.estimated_calls, 1);
+        assert!(summary.by_model["mistral"].prompt_tokens > 0);
+        assert_eq!(tracker.summary().calls, 2);
+    }
+
+    #[test]
+    fn test_price_table_yaml() {
+        let table: PriceTable =
+            serde_yaml::from_str("gpt-4o: { input_per_million: 2.5, output_per_million: 10 }").unwrap();
+        assert_eq!(table.cost("gpt-4o", &TokenUsage::new(1_000, 1_000)), Some(0.0125));
+        assert_eq!(table.cost("other", &TokenUsage::new(1, 1)), None);
+    }
+}
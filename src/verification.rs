@@ -0,0 +1,689 @@
+//! Verification pass that checks each extraction against its source text.
+//!
+//! Multi-pass and consensus extraction improve recall; this pass checks
+//! precision. Once results are aggregated, extractions are sent to the model
+//! in batches, each with a window of the source text around its span, and the
+//! model confirms, corrects or rejects its class and attributes. Verdicts are
+//! recorded in each extraction's attributes, and rejected extractions are
+//! dropped or flagged.
+
+use crate::{
+    annotation::ANSWERED_BY_ATTRIBUTE,
+    cancellation::StopSignal,
+    data::{AnnotatedDocument, Extraction},
+    exceptions::{LangExtractError, LangExtractResult},
+    inference::BaseLanguageModel,
+    multipass::{CONFIDENCE_ATTRIBUTE, CONSENSUS_VOTES_ATTRIBUTE},
+    providers::ProviderConfig,
+    usage::{UsageSummary, UsageTracker},
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Extraction attribute holding the verification verdict
+pub const VERDICT_ATTRIBUTE: &str = "verification";
+
+/// Extraction attribute holding the verifier's reason for its verdict
+pub const VERDICT_REASON_ATTRIBUTE: &str = "verification_reason";
+
+/// Extraction attribute holding the class a correction replaced
+pub const ORIGINAL_CLASS_ATTRIBUTE: &str = "original_class";
+
+/// Bookkeeping attributes the verifier neither sees nor corrects
+const INTERNAL_ATTRIBUTES: [&str; 6] = [
+    ANSWERED_BY_ATTRIBUTE,
+    CONFIDENCE_ATTRIBUTE,
+    CONSENSUS_VOTES_ATTRIBUTE,
+    VERDICT_ATTRIBUTE,
+    VERDICT_REASON_ATTRIBUTE,
+    ORIGINAL_CLASS_ATTRIBUTE,
+];
+
+/// The verifier's judgement of one extraction
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Verdict {
+    /// The source supports the class and attributes
+    Confirmed,
+    /// The item is in the source but its class or attributes were corrected
+    Corrected,
+    /// The source does not support the item
+    Rejected,
+    /// No usable verdict, e.g. the verification call failed
+    Unverified,
+}
+
+impl Verdict {
+    /// Name recorded in [`VERDICT_ATTRIBUTE`]
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Verdict::Confirmed => "confirmed",
+            Verdict::Corrected => "corrected",
+            Verdict::Rejected => "rejected",
+            Verdict::Unverified => "unverified",
+        }
+    }
+
+    fn parse(verdict: &str) -> Option<Self> {
+        match verdict.trim().to_lowercase().as_str() {
+            "confirmed" | "confirm" => Some(Verdict::Confirmed),
+            "corrected" | "correct" => Some(Verdict::Corrected),
+            "rejected" | "reject" => Some(Verdict::Rejected),
+            _ => None,
+        }
+    }
+}
+
+/// What happens to rejected extractions
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RejectAction {
+    /// Remove them from the result
+    #[default]
+    Drop,
+    /// Keep them, with a `rejected` verdict in their attributes
+    Flag,
+}
+
+/// Settings for the verification pass
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VerificationConfig {
+    /// Extractions checked per model call
+    #[serde(default = "default_batch_size")]
+    pub batch_size: usize,
+    /// Characters of source text shown on each side of an extraction
+    #[serde(default = "default_context_chars")]
+    pub context_chars: usize,
+    /// Sampling temperature for verification calls
+    #[serde(default)]
+    pub temperature: f32,
+    /// Whether rejected extractions are dropped or kept flagged
+    #[serde(default)]
+    pub on_reject: RejectAction,
+    /// Provider to verify with; the run's model when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<ProviderConfig>,
+}
+
+fn default_batch_size() -> usize {
+    10
+}
+
+fn default_context_chars() -> usize {
+    200
+}
+
+impl Default for VerificationConfig {
+    fn default() -> Self {
+        Self {
+            batch_size: default_batch_size(),
+            context_chars: default_context_chars(),
+            temperature: 0.0,
+            on_reject: RejectAction::Drop,
+            model: None,
+        }
+    }
+}
+
+impl VerificationConfig {
+    /// Check this many extractions per model call
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size;
+        self
+    }
+
+    /// Show this many characters of source on each side of an extraction
+    pub fn with_context_chars(mut self, context_chars: usize) -> Self {
+        self.context_chars = context_chars;
+        self
+    }
+
+    /// Drop rejected extractions, or keep them flagged
+    pub fn with_on_reject(mut self, on_reject: RejectAction) -> Self {
+        self.on_reject = on_reject;
+        self
+    }
+
+    /// Verify with this provider instead of the run's model
+    pub fn with_model(mut self, model: ProviderConfig) -> Self {
+        self.model = Some(model);
+        self
+    }
+}
+
+/// Verdict counts from one verification pass
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VerificationSummary {
+    /// Extractions the source supports as they are
+    pub confirmed: usize,
+    /// Extractions whose class or attributes were corrected
+    pub corrected: usize,
+    /// Extractions the source does not support, whether dropped or flagged
+    pub rejected: usize,
+    /// Extractions left without a verdict
+    pub unverified: usize,
+}
+
+impl VerificationSummary {
+    fn count(&mut self, verdict: Verdict) {
+        match verdict {
+            Verdict::Confirmed => self.confirmed += 1,
+            Verdict::Corrected => self.corrected += 1,
+            Verdict::Rejected => self.rejected += 1,
+            Verdict::Unverified => self.unverified += 1,
+        }
+    }
+}
+
+/// One item of the verifier's reply
+#[derive(Debug, Deserialize)]
+struct ItemVerdict {
+    id: usize,
+    verdict: String,
+    #[serde(default)]
+    extraction_class: Option<String>,
+    #[serde(default)]
+    attributes: Option<Map<String, Value>>,
+    #[serde(default)]
+    reason: Option<String>,
+}
+
+/// Checks extractions against their source text with a language model
+pub struct Verifier {
+    language_model: Box<dyn BaseLanguageModel>,
+    config: VerificationConfig,
+    /// Classes a correction may assign; any class when empty
+    classes: Vec<String>,
+    usage_tracker: Arc<UsageTracker>,
+    stop: StopSignal,
+    request_timeout: Option<Duration>,
+    max_workers: usize,
+}
+
+impl Verifier {
+    /// Create a verifier that may correct extractions to any of `classes`
+    pub fn new(language_model: Box<dyn BaseLanguageModel>, config: VerificationConfig, classes: Vec<String>) -> Self {
+        Self {
+            language_model,
+            config,
+            classes,
+            usage_tracker: Arc::new(UsageTracker::default()),
+            stop: StopSignal::default(),
+            request_timeout: None,
+            max_workers: 1,
+        }
+    }
+
+    /// Record token usage in a shared tracker, which aborts the pass once its
+    /// budget is exceeded
+    pub fn with_usage_tracker(mut self, usage_tracker: Arc<UsageTracker>) -> Self {
+        self.usage_tracker = usage_tracker;
+        self
+    }
+
+    /// Stop when `stop` fires, leaving the remaining extractions unverified,
+    /// and fail any call that takes longer than `request_timeout`
+    pub fn with_stop_signal(mut self, stop: StopSignal, request_timeout: Option<Duration>) -> Self {
+        self.stop = stop;
+        self.request_timeout = request_timeout;
+        self
+    }
+
+    /// Send up to this many batches at once
+    pub fn with_max_workers(mut self, max_workers: usize) -> Self {
+        self.max_workers = max_workers;
+        self
+    }
+
+    /// Verify the document's extractions in place, adding the pass's token
+    /// usage to the document's
+    ///
+    /// A batch whose call fails leaves its extractions unverified; only an
+    /// exceeded budget fails the pass.
+    pub async fn verify(&self, document: &mut AnnotatedDocument) -> LangExtractResult<VerificationSummary> {
+        let mut summary = VerificationSummary::default();
+        let Some(text) = document.text.as_deref() else {
+            return Ok(summary);
+        };
+        let extractions = document.extractions.take().unwrap_or_default();
+
+        let indices: Vec<usize> = (0..extractions.len()).collect();
+        let batches: Vec<&[usize]> = indices.chunks(self.config.batch_size.max(1)).collect();
+        let mut verdicts: Vec<Option<ItemVerdict>> = extractions.iter().map(|_| None).collect();
+        let mut usage = UsageSummary::default();
+
+        for group in batches.chunks(self.max_workers.max(1)) {
+            if self.stop.is_stopped() {
+                break;
+            }
+            let calls: Vec<_> = group.iter().map(|batch| self.verify_batch(text, &extractions, batch)).collect();
+            for (batch, outcome) in group.iter().zip(futures::future::join_all(calls).await) {
+                match outcome {
+                    Ok((items, batch_usage)) => {
+                        usage.merge(&batch_usage);
+                        for item in items {
+                            if let Some(&index) = batch.get(item.id) {
+                                verdicts[index] = Some(item);
+                            }
+                        }
+                    }
+                    Err(e @ LangExtractError::BudgetExceeded(_)) => return Err(e),
+                    Err(e) => {
+                        log::warn!("[verification] {} extractions left unverified: {}", batch.len(), e);
+                    }
+                }
+            }
+        }
+
+        let verified = extractions
+            .into_iter()
+            .zip(verdicts)
+            .filter_map(|(extraction, item)| self.apply_verdict(extraction, item, &mut summary))
+            .collect();
+        document.extractions = Some(verified);
+        document.usage.get_or_insert_with(UsageSummary::default).merge(&usage);
+
+        log::debug!(
+            "[verification] {} confirmed, {} corrected, {} rejected, {} unverified",
+            summary.confirmed, summary.corrected, summary.rejected, summary.unverified
+        );
+        Ok(summary)
+    }
+
+    /// Ask the model for verdicts on one batch of extractions
+    async fn verify_batch(
+        &self,
+        text: &str,
+        extractions: &[Extraction],
+        batch: &[usize],
+    ) -> LangExtractResult<(Vec<ItemVerdict>, UsageSummary)> {
+        let prompt = self.build_prompt(text, batch.iter().map(|&i| &extractions[i]));
+
+        let mut kwargs = HashMap::new();
+        kwargs.insert("temperature".to_string(), json!(self.config.temperature));
+
+        self.usage_tracker.check()?;
+        let call = self.language_model.infer(std::slice::from_ref(&prompt), &kwargs);
+        let results = self.stop.call(self.request_timeout, call).await?;
+        let output = results
+            .into_iter()
+            .next()
+            .and_then(|batch| batch.into_iter().next())
+            .ok_or_else(|| LangExtractError::inference_simple("Verifier returned no output"))?;
+
+        // Record token usage, estimating it when the provider reports none
+        let usage = self.usage_tracker.record_call(self.language_model.model_id(), &prompt, &output)?;
+
+        Ok((parse_verdicts(output.text())?, usage))
+    }
+
+    fn build_prompt<'a>(&self, text: &str, extractions: impl Iterator<Item = &'a Extraction>) -> String {
+        let mut prompt = String::from(
+            "Check each extracted item below against the source text it was taken from.\n\
+             For each item, decide whether the source supports its class and attributes:\n\
+             - \"confirmed\": the class and attributes are right\n\
+             - \"corrected\": the item is in the source but its class or attributes are wrong\n\
+             - \"rejected\": the source does not support the item\n",
+        );
+        if !self.classes.is_empty() {
+            prompt.push_str(&format!("\nAllowed classes: {}\n", self.classes.join(", ")));
+        }
+        prompt.push_str(
+            "\nReply with only a JSON array holding one object per item:\n\
+             {\"id\": 0, \"verdict\": \"corrected\", \"extraction_class\": \"...\", \"attributes\": {}, \"reason\": \"...\"}\n\
+             Give extraction_class and attributes only for corrected items. A null source means \
+             the item could not be found in the document.\n\nItems:\n",
+        );
+
+        for (id, extraction) in extractions.enumerate() {
+            let attributes: Map<String, Value> = extraction
+                .attributes
+                .iter()
+                .flatten()
+                .filter(|(key, _)| !INTERNAL_ATTRIBUTES.contains(&key.as_str()))
+                .map(|(key, value)| (key.clone(), value.clone()))
+                .collect();
+            let item = json!({
+                "id": id,
+                "extraction_class": extraction.extraction_class,
+                "extraction_text": extraction.extraction_text,
+                "attributes": attributes,
+                "source": self.source_window(text, extraction),
+            });
+            prompt.push_str(&item.to_string());
+            prompt.push('\n');
+        }
+        prompt
+    }
+
+    /// The extraction's span with `context_chars` of text on each side, or
+    /// around the first occurrence of its text when it has no span
+    fn source_window(&self, text: &str, extraction: &Extraction) -> Option<String> {
+        let (start, end) = match extraction.char_interval.as_ref().map(|c| (c.start_pos, c.end_pos)) {
+            Some((Some(start), Some(end))) if start <= end && end <= text.len() => (start, end),
+            _ => {
+                let start = text.find(&extraction.extraction_text)?;
+                (start, start + extraction.extraction_text.len())
+            }
+        };
+
+        let mut window_start = start.saturating_sub(self.config.context_chars);
+        while !text.is_char_boundary(window_start) {
+            window_start -= 1;
+        }
+        let mut window_end = (end + self.config.context_chars).min(text.len());
+        while !text.is_char_boundary(window_end) {
+            window_end += 1;
+        }
+        Some(text[window_start..window_end].to_string())
+    }
+
+    /// Record the verdict on the extraction, or `None` when it is dropped
+    fn apply_verdict(
+        &self,
+        mut extraction: Extraction,
+        item: Option<ItemVerdict>,
+        summary: &mut VerificationSummary,
+    ) -> Option<Extraction> {
+        let verdict = item
+            .as_ref()
+            .and_then(|item| Verdict::parse(&item.verdict))
+            .unwrap_or(Verdict::Unverified);
+        summary.count(verdict);
+        if verdict == Verdict::Rejected && self.config.on_reject == RejectAction::Drop {
+            return None;
+        }
+
+        let (corrected_class, corrected_attributes, reason) = match item {
+            Some(item) => (item.extraction_class, item.attributes, item.reason),
+            None => (None, None, None),
+        };
+        let mut attributes = extraction.attributes.take().unwrap_or_default();
+
+        if verdict == Verdict::Corrected {
+            let allowed = |class: &String| self.classes.is_empty() || self.classes.contains(class);
+            if let Some(class) = corrected_class.filter(|c| *c != extraction.extraction_class && allowed(c)) {
+                let original = std::mem::replace(&mut extraction.extraction_class, class);
+                attributes.insert(ORIGINAL_CLASS_ATTRIBUTE.to_string(), json!(original));
+            }
+            for (key, value) in corrected_attributes.into_iter().flatten() {
+                if !INTERNAL_ATTRIBUTES.contains(&key.as_str()) {
+                    attributes.insert(key, value);
+                }
+            }
+        }
+
+        attributes.insert(VERDICT_ATTRIBUTE.to_string(), json!(verdict.as_str()));
+        if let Some(reason) = reason.filter(|r| !r.trim().is_empty()) {
+            attributes.insert(VERDICT_REASON_ATTRIBUTE.to_string(), json!(reason));
+        }
+        extraction.attributes = Some(attributes);
+        Some(extraction)
+    }
+}
+
+/// Parse the verifier's reply: a JSON array of verdicts, possibly fenced or
+/// wrapped in an object
+fn parse_verdicts(output: &str) -> LangExtractResult<Vec<ItemVerdict>> {
+    let start = output.find(['[', '{']);
+    let end = output.rfind([']', '}']);
+    let json = match (start, end) {
+        (Some(start), Some(end)) if start < end => &output[start..=end],
+        _ => return Err(LangExtractError::parsing("Verifier reply holds no JSON")),
+    };
+
+    let items = match serde_json::from_str::<Value>(json)
+        .map_err(|e| LangExtractError::parsing(format!("Verifier reply is not valid JSON: {}", e)))?
+    {
+        Value::Array(items) => items,
+        Value::Object(mut object) => match object.remove("verdicts").or_else(|| object.remove("items")) {
+            Some(Value::Array(items)) => items,
+            _ => vec![Value::Object(object)],
+        },
+        _ => return Err(LangExtractError::parsing("Verifier reply is not a list of verdicts")),
+    };
+
+    // Skip malformed items; their extractions stay unverified
+    Ok(items.into_iter().filter_map(|item| serde_json::from_value(item).ok()).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::CharInterval;
+    use crate::inference::ScoredOutput;
+    use async_trait::async_trait;
+    use std::sync::Mutex;
+
+    /// Replies with a fixed verdict list and keeps the prompts it was sent
+    struct VerdictModel {
+        reply: &'static str,
+        prompts: Arc<Mutex<Vec<String>>>,
+    }
+
+    #[async_trait]
+    impl BaseLanguageModel for VerdictModel {
+        async fn infer(
+            &self,
+            batch_prompts: &[String],
+            _kwargs: &HashMap<String, Value>,
+        ) -> LangExtractResult<Vec<Vec<ScoredOutput>>> {
+            self.prompts.lock().unwrap().extend(batch_prompts.iter().cloned());
+            Ok(batch_prompts.iter().map(|_| vec![ScoredOutput::from_text(self.reply.to_string())]).collect())
+        }
+
+        fn model_id(&self) -> &str {
+            "verdicts"
+        }
+
+        fn provider_name(&self) -> &str {
+            "test"
+        }
+    }
+
+    fn spanned(class: &str, text: &str, source: &str) -> Extraction {
+        let start = source.find(text).unwrap();
+        let mut extraction = Extraction::new(class.to_string(), text.to_string());
+        extraction.char_interval = Some(CharInterval::new(Some(start), Some(start + text.len())));
+        extraction
+    }
+
+    fn document() -> AnnotatedDocument {
+        let text = "Take aspirin 100 mg daily. Dr. Ada Lovelace prescribed it in London.";
+        AnnotatedDocument::with_extractions(
+            vec![
+                spanned("medication", "aspirin", text),
+                spanned("dosage", "daily", text),
+                spanned("medication", "London", text),
+            ],
+            text.to_string(),
+        )
+    }
+
+    const REPLY: &str = r#"```json
+[
+  {"id": 0, "verdict": "confirmed", "reason": "named as taken"},
+  {"id": 1, "verdict": "corrected", "extraction_class": "frequency", "attributes": {"unit": "day"}},
+  {"id": 2, "verdict": "rejected", "reason": "a place, not a medication"}
+]
+```"#;
+
+    #[tokio::test]
+    async fn test_verify_records_verdicts_and_drops_rejected() {
+        let model = Box::new(VerdictModel { reply: REPLY, prompts: Arc::default() });
+        let classes = vec!["medication".to_string(), "dosage".to_string(), "frequency".to_string()];
+        let verifier = Verifier::new(model, VerificationConfig::default(), classes);
+
+        let mut document = document();
+        let summary = verifier.verify(&mut document).await.unwrap();
+        assert_eq!(summary, VerificationSummary { confirmed: 1, corrected: 1, rejected: 1, unverified: 0 });
+
+        let extractions = document.extractions.unwrap();
+        assert_eq!(extractions.len(), 2);
+        let attributes = extractions[0].attributes.as_ref().unwrap();
+        assert_eq!(attributes[VERDICT_ATTRIBUTE], "confirmed");
+        assert_eq!(attributes[VERDICT_REASON_ATTRIBUTE], "named as taken");
+
+        assert_eq!(extractions[1].extraction_class, "frequency");
+        let attributes = extractions[1].attributes.as_ref().unwrap();
+        assert_eq!(attributes[VERDICT_ATTRIBUTE], "corrected");
+        assert_eq!(attributes[ORIGINAL_CLASS_ATTRIBUTE], "dosage");
+        assert_eq!(attributes["unit"], "day");
+        assert_eq!(document.usage.unwrap().calls, 1);
+    }
+
+    #[tokio::test]
+    async fn test_verify_batches_with_source_windows_and_flags_rejected() {
+        let prompts = Arc::new(Mutex::new(Vec::new()));
+        let model = Box::new(VerdictModel {
+            reply: r#"[{"id": 0, "verdict": "rejected"}, {"id": 1, "verdict": "confirmed"}]"#,
+            prompts: prompts.clone(),
+        });
+        let config = VerificationConfig::default()
+            .with_batch_size(2)
+            .with_context_chars(5)
+            .with_on_reject(RejectAction::Flag);
+        let verifier = Verifier::new(model, config, Vec::new());
+
+        let mut document = document();
+        let summary = verifier.verify(&mut document).await.unwrap();
+        assert_eq!(summary, VerificationSummary { confirmed: 1, corrected: 0, rejected: 2, unverified: 0 });
+
+        let prompts = prompts.lock().unwrap();
+        assert_eq!(prompts.len(), 2);
+        assert!(prompts[1].contains(r#""source":"t in London.""#));
+
+        let verdicts: Vec<Value> = document
+            .extractions
+            .unwrap()
+            .iter()
+            .map(|e| e.attributes.as_ref().unwrap()[VERDICT_ATTRIBUTE].clone())
+            .collect();
+        assert_eq!(verdicts, vec![json!("rejected"), json!("confirmed"), json!("rejected")]);
+    }
+
+    #[test]
+    fn test_source_window_respects_context_and_char_boundaries() {
+        let verifier = Verifier::new(
+            Box::new(VerdictModel { reply: "[]", prompts: Arc::default() }),
+            VerificationConfig::default().with_context_chars(2),
+            Vec::new(),
+        );
+        let text = "café aspirin daily";
+        let extraction = spanned("medication", "aspirin", text);
+        assert_eq!(verifier.source_window(text, &extraction).unwrap(), "é aspirin d");
+
+        let unaligned = Extraction::new("medication".to_string(), "ibuprofen".to_string());
+        assert!(verifier.source_window(text, &unaligned).is_none());
+
+        let prompt = verifier.build_prompt(text, [&extraction, &unaligned].into_iter());
+        assert!(prompt.contains(r#""source":null"#));
+        assert!(!prompt.contains("Allowed classes"));
+    }
+
+    /// Extracts on the first call and verifies on the second
+    struct ExtractThenVerifyModel;
+
+    #[async_trait]
+    impl BaseLanguageModel for ExtractThenVerifyModel {
+        async fn infer(
+            &self,
+            batch_prompts: &[String],
+            _kwargs: &HashMap<String, Value>,
+        ) -> LangExtractResult<Vec<Vec<ScoredOutput>>> {
+            let reply = if batch_prompts[0].starts_with("Check each extracted item") {
+                r#"[{"id": 0, "verdict": "confirmed"}, {"id": 1, "verdict": "corrected", "extraction_class": "person"}]"#
+            } else {
+                r#"{"person": "Ada Lovelace", "place": "Charles Babbage"}"#
+            };
+            Ok(batch_prompts.iter().map(|_| vec![ScoredOutput::from_text(reply.to_string())]).collect())
+        }
+
+        fn model_id(&self) -> &str {
+            "extract-then-verify"
+        }
+
+        fn provider_name(&self) -> &str {
+            "test"
+        }
+    }
+
+    #[tokio::test]
+    async fn test_extract_verifies_after_aggregation() {
+        let mut prompt_template = crate::prompting::PromptTemplateStructured::new(Some("Extract people and places"));
+        prompt_template.examples.push(crate::data::ExampleData::new(
+            "Alan Turing lived in Manchester.".to_string(),
+            vec![
+                Extraction::new("person".to_string(), "Alan Turing".to_string()),
+                Extraction::new("place".to_string(), "Manchester".to_string()),
+            ],
+        ));
+        let config = crate::ExtractConfig::default().with_verification(VerificationConfig::default());
+
+        let result = crate::annotate_with_model(
+            "Ada Lovelace met Charles Babbage in London.",
+            prompt_template,
+            Box::new(ExtractThenVerifyModel),
+            &config,
+        )
+        .await
+        .unwrap();
+
+        let extractions = result.extractions.unwrap();
+        let classes: Vec<&str> = extractions.iter().map(|e| e.extraction_class.as_str()).collect();
+        assert_eq!(classes, vec!["person", "person"]);
+        let attributes = extractions[1].attributes.as_ref().unwrap();
+        assert_eq!(attributes[VERDICT_ATTRIBUTE], "corrected");
+        assert_eq!(attributes[ORIGINAL_CLASS_ATTRIBUTE], "place");
+        assert_eq!(result.usage.unwrap().calls, 2);
+    }
+
+    #[tokio::test]
+    async fn test_verifier_drops_extraction_schema_across_documents() {
+        use crate::test_support::{ollama, ollama_config, person_examples};
+        use futures::StreamExt;
+        use wiremock::matchers::{body_string_contains, method, path};
+        use wiremock::{Mock, ResponseTemplate};
+
+        let server = ollama().await;
+        Mock::given(method("POST"))
+            .and(path("/api/generate"))
+            .and(body_string_contains("Check each extracted item"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "response": r#"[{"id": 0, "verdict": "confirmed"}]"#
+            })))
+            .with_priority(1)
+            .mount(&server)
+            .await;
+
+        // Extraction calls are constrained to the extraction schema, which
+        // has no room for verdicts
+        let config = ollama_config(&server).with_verification(VerificationConfig::default());
+        assert!(config.use_schema_constraints);
+        let documents = ["Ada Lovelace wrote notes.", "Ada Lovelace met Charles Babbage."]
+            .map(|text| crate::data::Document::new(text.to_string()));
+        let results: Vec<_> = crate::extract_documents(futures::stream::iter(documents), None, &person_examples(), config)
+            .await
+            .unwrap()
+            .collect()
+            .await;
+        for result in results {
+            let extractions = result.unwrap().extractions.unwrap();
+            assert_eq!(extractions[0].attributes.as_ref().unwrap()[VERDICT_ATTRIBUTE], "confirmed");
+        }
+
+        let requests = server.received_requests().await.unwrap();
+        assert_eq!(requests.len(), 4);
+        for request in requests {
+            let body: Value = serde_json::from_slice(&request.body).unwrap();
+            if body["prompt"].as_str().unwrap().starts_with("Check each extracted item") {
+                assert_eq!(body["format"], "json");
+            } else {
+                assert!(body["format"].is_object());
+            }
+        }
+    }
+}
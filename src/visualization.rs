@@ -1,6 +1,7 @@
 //! Visualization utilities for annotated documents.
 
 use crate::{data::AnnotatedDocument, exceptions::LangExtractResult};
+use crate::diff::{RunDiff, SectionDiff};
 use crate::pipeline::PipelineResult;
 use serde_json::{json, Value};
 use std::collections::HashMap;
@@ -282,6 +283,195 @@ pub fn export_pipeline_flattened_json(
     Ok(serde_json::to_string_pretty(&Value::Object(root))?)
 }
 
+/// Export a comparison of two runs as side-by-side HTML
+///
+/// Each document or pipeline step shows the first run on the left and the
+/// second on the right, with extractions highlighted as removed, added,
+/// changed or unchanged, followed by a table of the differences.
+pub fn export_diff_html(diff: &RunDiff, config: &ExportConfig) -> LangExtractResult<String> {
+    let title = html_escape(config.title.as_deref().unwrap_or("LangExtract Run Comparison"));
+    let (added, removed, changed, unchanged) = diff.counts();
+
+    let mut html = String::new();
+    html.push_str("<!DOCTYPE html>\n");
+    html.push_str("<html lang=\"en\">\n");
+    html.push_str("<head>\n");
+    html.push_str("    <meta charset=\"UTF-8\">\n");
+    html.push_str("    <meta name=\"viewport\" content=\"width=device-width, initial-scale=1.0\">\n");
+    html.push_str(&format!("    <title>{}</title>\n", title));
+    html.push_str("    <style>\n");
+    html.push_str("        body { font-family: -apple-system, BlinkMacSystemFont, 'Segoe UI', Roboto, sans-serif; max-width: 1400px; margin: 0 auto; padding: 20px; background: #f8fafc; color: #334155; }\n");
+    html.push_str("        .container { background: white; border-radius: 12px; box-shadow: 0 4px 6px -1px rgba(0, 0, 0, 0.1); overflow: hidden; }\n");
+    html.push_str("        .header { background: linear-gradient(135deg, #0ea5e9 0%, #6366f1 100%); color: white; padding: 30px; text-align: center; }\n");
+    html.push_str("        .header h1 { margin: 0; font-size: 2.2em; font-weight: 400; }\n");
+    html.push_str("        .summary { margin-top: 12px; display: flex; gap: 12px; justify-content: center; }\n");
+    html.push_str("        .summary span { background: white; color: #334155; border-radius: 6px; padding: 4px 10px; }\n");
+    html.push_str("        .content { padding: 30px; }\n");
+    html.push_str("        .section { margin-bottom: 32px; }\n");
+    html.push_str("        .section h2 { color: #1e293b; border-bottom: 2px solid #e2e8f0; padding-bottom: 10px; margin-bottom: 16px; }\n");
+    html.push_str("        .side-by-side { display: grid; grid-template-columns: 1fr 1fr; gap: 16px; }\n");
+    html.push_str("        .side h3 { margin: 0 0 8px 0; color: #64748b; font-weight: 500; }\n");
+    html.push_str("        .document-text { background: #f1f5f9; border-radius: 8px; padding: 16px; font-family: 'Monaco', 'Menlo', monospace; line-height: 1.6; white-space: pre-wrap; }\n");
+    html.push_str("        .extraction-list { background: #f1f5f9; border-radius: 8px; padding: 16px 16px 16px 32px; margin: 0; }\n");
+    html.push_str("        .extraction-highlight { border-radius: 3px; padding: 1px 2px; }\n");
+    html.push_str("        .diff-added { background: rgba(16, 185, 129, 0.25); border: 1px solid rgba(16, 185, 129, 0.5); }\n");
+    html.push_str("        .diff-removed { background: rgba(244, 63, 94, 0.2); border: 1px solid rgba(244, 63, 94, 0.5); text-decoration: line-through; }\n");
+    html.push_str("        .diff-changed { background: rgba(234, 179, 8, 0.25); border: 1px solid rgba(234, 179, 8, 0.6); }\n");
+    html.push_str("        .diff-unchanged { background: rgba(148, 163, 184, 0.15); border: 1px solid rgba(148, 163, 184, 0.4); }\n");
+    html.push_str("        table { width: 100%; border-collapse: collapse; margin-top: 16px; }\n");
+    html.push_str("        th, td { text-align: left; padding: 6px 10px; border-bottom: 1px solid #e2e8f0; vertical-align: top; }\n");
+    html.push_str("    </style>\n");
+    html.push_str("</head>\n");
+    html.push_str("<body>\n");
+    html.push_str("    <div class=\"container\">\n");
+    html.push_str("        <div class=\"header\">\n");
+    html.push_str(&format!("            <h1>{}</h1>\n", title));
+    html.push_str(&format!(
+        "            <div class=\"summary\"><span>{} added</span><span>{} removed</span><span>{} changed</span><span>{} unchanged</span></div>\n",
+        added, removed, changed, unchanged
+    ));
+    html.push_str("        </div>\n");
+    html.push_str("        <div class=\"content\">\n");
+    for section in &diff.sections {
+        html.push_str("            <div class=\"section\">\n");
+        html.push_str(&format!("                <h2>{}</h2>\n", html_escape(&section.name)));
+        html.push_str("                <div class=\"side-by-side\">\n");
+        for (label, items) in [("Before", diff_side(section, true)), ("After", diff_side(section, false))] {
+            html.push_str(&format!("                    <div class=\"side\"><h3>{}</h3>", label));
+            html.push_str(&diff_side_html(section.text.as_deref(), &items));
+            html.push_str("</div>\n");
+        }
+        html.push_str("                </div>\n");
+        if section.has_changes() {
+            html.push_str(&diff_table_html(section));
+        }
+        html.push_str("            </div>\n");
+    }
+    html.push_str("        </div>\n");
+    html.push_str("    </div>\n");
+    html.push_str("</body>\n");
+    html.push_str("</html>\n");
+
+    Ok(html)
+}
+
+/// Extractions of one run in a section, with their diff status
+fn diff_side(section: &SectionDiff, before: bool) -> Vec<(&Extraction, &'static str)> {
+    let (only_here, only_label) = if before {
+        (&section.removed, "removed")
+    } else {
+        (&section.added, "added")
+    };
+    let mut items: Vec<(&Extraction, &'static str)> = only_here.iter().map(|e| (e, only_label)).collect();
+    items.extend(
+        section
+            .changed
+            .iter()
+            .map(|c| (if before { &c.before } else { &c.after }, "changed")),
+    );
+    items.extend(section.unchanged.iter().map(|e| (e, "unchanged")));
+    items.sort_by_key(|(e, _)| e.char_interval.as_ref().and_then(|i| i.start_pos).unwrap_or(usize::MAX));
+    items
+}
+
+/// Document text with the extractions highlighted, or a list of the
+/// extractions when there is no text
+fn diff_side_html(text: Option<&str>, items: &[(&Extraction, &'static str)]) -> String {
+    let Some(text) = text else {
+        let mut list = String::from("<ul class=\"extraction-list\">");
+        for (extraction, status) in items {
+            list.push_str(&format!(
+                "<li><span class=\"extraction-highlight diff-{}\">{}</span>: {}</li>",
+                status,
+                html_escape(&extraction.extraction_class),
+                html_escape(&extraction.extraction_text)
+            ));
+        }
+        list.push_str("</ul>");
+        return list;
+    };
+
+    let mut result = String::from("<div class=\"document-text\">");
+    let mut last_pos = 0;
+    for (extraction, status) in items {
+        let Some((start, end)) = extraction
+            .char_interval
+            .as_ref()
+            .and_then(|i| Some((i.start_pos?, i.end_pos?)))
+        else {
+            continue;
+        };
+        let start = find_char_boundary(text, start);
+        let end = find_char_boundary(text, end);
+        // Overlapping spans keep the first one
+        if start < last_pos || start >= end {
+            continue;
+        }
+        result.push_str(&html_escape(&text[last_pos..start]));
+        result.push_str(&format!(
+            "<span class=\"extraction-highlight diff-{}\" title=\"{}\">{}</span>",
+            status,
+            html_escape(&extraction.extraction_class),
+            html_escape(&text[start..end])
+        ));
+        last_pos = end;
+    }
+    result.push_str(&html_escape(&text[last_pos..]));
+    result.push_str("</div>");
+    result
+}
+
+fn diff_table_html(section: &SectionDiff) -> String {
+    let span = |e: &Extraction| match e.char_interval.as_ref().map(|i| (i.start_pos, i.end_pos)) {
+        Some((Some(start), Some(end))) => format!("{}..{}", start, end),
+        _ => "-".to_string(),
+    };
+    let mut rows = Vec::new();
+    for e in &section.removed {
+        rows.push(("removed", &e.extraction_class, html_escape(&e.extraction_text), String::new(), span(e)));
+    }
+    for e in &section.added {
+        rows.push(("added", &e.extraction_class, String::new(), html_escape(&e.extraction_text), span(e)));
+    }
+    for c in &section.changed {
+        let mut details = Vec::new();
+        if c.span_changed {
+            details.push(format!("{} &rarr; {}", span(&c.before), span(&c.after)));
+        }
+        for delta in &c.attributes {
+            let value = |v: &Option<Value>| v.as_ref().map(|v| v.to_string()).unwrap_or_else(|| "-".to_string());
+            details.push(format!(
+                "{}: {} &rarr; {}",
+                html_escape(&delta.key),
+                html_escape(&value(&delta.before)),
+                html_escape(&value(&delta.after))
+            ));
+        }
+        rows.push((
+            "changed",
+            &c.after.extraction_class,
+            html_escape(&c.before.extraction_text),
+            html_escape(&c.after.extraction_text),
+            details.join("<br>"),
+        ));
+    }
+
+    let mut table = String::from("                <table>\n                    <tr><th>Change</th><th>Class</th><th>Before</th><th>After</th><th>Details</th></tr>\n");
+    for (status, class, before, after, details) in rows {
+        table.push_str(&format!(
+            "                    <tr><td><span class=\"extraction-highlight diff-{}\">{}</span></td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+            status,
+            status,
+            html_escape(class),
+            before,
+            after,
+            details
+        ));
+    }
+    table.push_str("                </table>\n");
+    table
+}
+
 #[derive(Debug, Clone)]
 struct LayeredSpan {
     start: usize,
@@ -1310,6 +1500,9 @@ mod tests {
             document_id: Some("test_doc".to_string()),
             text: Some(text.to_string()),
             extractions: Some(extractions),
+            usage: None,
+            metadata: Default::default(),
+            coverage: None,
         }
     }
 
@@ -1505,6 +1698,9 @@ mod tests {
             document_id: Some("empty".to_string()),
             text: Some("".to_string()),
             extractions: None,
+            usage: None,
+            metadata: Default::default(),
+            coverage: None,
         };
 
         let config = ExportConfig::default();
@@ -1519,6 +1715,9 @@ mod tests {
             document_id: Some("no_text".to_string()),
             text: None,
             extractions: None,
+            usage: None,
+            metadata: Default::default(),
+            coverage: None,
         };
 
         let config = ExportConfig::default();
@@ -1616,6 +1815,7 @@ mod tests {
                 output_field: "requirements".to_string(),
                 filter: None,
                 depends_on: vec![],
+                verification: None,
             },
             PipelineStep {
                 id: "s2".to_string(),
@@ -1626,6 +1826,7 @@ mod tests {
                 output_field: "values".to_string(),
                 filter: None,
                 depends_on: vec!["s1".to_string()],
+                verification: None,
             },
         ];
 
@@ -1636,6 +1837,8 @@ mod tests {
             steps: steps.clone(),
             global_config: LibExtractConfig::default(),
             enable_parallel_execution: false,
+            timeout_ms: None,
+            cancellation: None,
         };
 
         // Compute positions
@@ -1662,6 +1865,7 @@ mod tests {
             input_count: 1,
             success: true,
             error_message: None,
+            usage: Default::default(),
         };
 
         let step2_res = StepResult {
@@ -1695,6 +1899,7 @@ mod tests {
             input_count: 1,
             success: true,
             error_message: None,
+            usage: Default::default(),
         };
 
         let pr = PipelineResult {
@@ -1704,6 +1909,7 @@ mod tests {
             total_time_ms: 2,
             success: true,
             error_message: None,
+            usage: Default::default(),
         };
 
         let config = ExportConfig { format: ExportFormat::Html, ..Default::default() };
@@ -1719,10 +1925,10 @@ mod tests {
         let text = "System uptime must be 99.9% for availability.";
 
         let steps = vec![
-            PipelineStep { id: "s1".to_string(), name: "Req".to_string(), description: "".to_string(), examples: vec![], prompt: "".to_string(), output_field: "req".to_string(), filter: None, depends_on: vec![] },
-            PipelineStep { id: "s2".to_string(), name: "Vals".to_string(), description: "".to_string(), examples: vec![], prompt: "".to_string(), output_field: "vals".to_string(), filter: None, depends_on: vec!["s1".to_string()] },
+            PipelineStep { id: "s1".to_string(), name: "Req".to_string(), description: "".to_string(), examples: vec![], prompt: "".to_string(), output_field: "req".to_string(), filter: None, depends_on: vec![], verification: None },
+            PipelineStep { id: "s2".to_string(), name: "Vals".to_string(), description: "".to_string(), examples: vec![], prompt: "".to_string(), output_field: "vals".to_string(), filter: None, depends_on: vec!["s1".to_string()], verification: None },
         ];
-        let cfg = PipelineConfig { name: "T".to_string(), description: "".to_string(), version: "0".to_string(), steps, global_config: LibExtractConfig::default(), enable_parallel_execution: false };
+        let cfg = PipelineConfig { name: "T".to_string(), description: "".to_string(), version: "0".to_string(), steps, global_config: LibExtractConfig::default(), enable_parallel_execution: false, timeout_ms: None, cancellation: None };
 
         let step1_res = StepResult {
             step_id: "s1".to_string(),
@@ -1742,6 +1948,7 @@ mod tests {
             input_count: 1,
             success: true,
             error_message: None,
+            usage: Default::default(),
         };
 
         let step2_res = StepResult {
@@ -1762,9 +1969,10 @@ mod tests {
             input_count: 1,
             success: true,
             error_message: None,
+            usage: Default::default(),
         };
 
-        let pr = PipelineResult { config: cfg, step_results: vec![step1_res, step2_res], nested_output: serde_json::json!({}), total_time_ms: 2, success: true, error_message: None };
+        let pr = PipelineResult { config: cfg, step_results: vec![step1_res, step2_res], nested_output: serde_json::json!({}), total_time_ms: 2, success: true, error_message: None, usage: Default::default() };
 
         let config = ExportConfig { format: ExportFormat::Html, ..Default::default() };
         let html = export_pipeline_html(&pr, text, &config).unwrap();
@@ -1777,10 +1985,10 @@ mod tests {
         let text = "The system shall support 10 users concurrently.";
 
         let steps = vec![
-            PipelineStep { id: "s1".to_string(), name: "Req".to_string(), description: "".to_string(), examples: vec![], prompt: "".to_string(), output_field: "req".to_string(), filter: None, depends_on: vec![] },
-            PipelineStep { id: "s2".to_string(), name: "Vals".to_string(), description: "".to_string(), examples: vec![], prompt: "".to_string(), output_field: "vals".to_string(), filter: None, depends_on: vec!["s1".to_string()] },
+            PipelineStep { id: "s1".to_string(), name: "Req".to_string(), description: "".to_string(), examples: vec![], prompt: "".to_string(), output_field: "req".to_string(), filter: None, depends_on: vec![], verification: None },
+            PipelineStep { id: "s2".to_string(), name: "Vals".to_string(), description: "".to_string(), examples: vec![], prompt: "".to_string(), output_field: "vals".to_string(), filter: None, depends_on: vec!["s1".to_string()], verification: None },
         ];
-        let cfg = PipelineConfig { name: "T".to_string(), description: "".to_string(), version: "0".to_string(), steps, global_config: LibExtractConfig::default(), enable_parallel_execution: false };
+        let cfg = PipelineConfig { name: "T".to_string(), description: "".to_string(), version: "0".to_string(), steps, global_config: LibExtractConfig::default(), enable_parallel_execution: false, timeout_ms: None, cancellation: None };
 
         let parent_start = 0usize;
         let parent_end = text.len();
@@ -1805,6 +2013,7 @@ mod tests {
             input_count: 1,
             success: true,
             error_message: None,
+            usage: Default::default(),
         };
 
         let step2_res = StepResult {
@@ -1838,9 +2047,10 @@ mod tests {
             input_count: 1,
             success: true,
             error_message: None,
+            usage: Default::default(),
         };
 
-        let pr = PipelineResult { config: cfg, step_results: vec![step1_res, step2_res], nested_output: serde_json::json!({}), total_time_ms: 2, success: true, error_message: None };
+        let pr = PipelineResult { config: cfg, step_results: vec![step1_res, step2_res], nested_output: serde_json::json!({}), total_time_ms: 2, success: true, error_message: None, usage: Default::default() };
 
         let mut config = ExportConfig { format: ExportFormat::Html, ..Default::default() };
         config.allow_overlapping_highlights = true;
@@ -1849,4 +2059,25 @@ mod tests {
         assert!(html.contains("10"));
         assert!(html.contains("10 users"));
     }
+
+    #[test]
+    fn test_export_diff_html_side_by_side() {
+        let text = "John Smith works at TechCorp.";
+        let person = Extraction::with_char_interval("person".to_string(), "John Smith".to_string(), CharInterval::new(Some(0), Some(10)));
+        let company = Extraction::with_char_interval("company".to_string(), "TechCorp".to_string(), CharInterval::new(Some(20), Some(28)));
+        let mut renamed = person.clone();
+        renamed.set_attribute("role".to_string(), serde_json::json!("engineer"));
+
+        let diff = crate::diff::diff_documents(
+            &[AnnotatedDocument::with_extractions(vec![person, company], text.to_string())],
+            &[AnnotatedDocument::with_extractions(vec![renamed], text.to_string())],
+        );
+        let html = export_diff_html(&diff, &ExportConfig::default()).unwrap();
+
+        assert!(html.contains("1 removed"));
+        assert!(html.contains("<h3>Before</h3>"));
+        assert!(html.contains(r#"<span class="extraction-highlight diff-removed" title="company">TechCorp</span>"#));
+        assert!(html.contains(r#"<span class="extraction-highlight diff-changed" title="person">John Smith</span>"#));
+        assert!(html.contains("role: - &rarr; &quot;engineer&quot;"));
+    }
 }